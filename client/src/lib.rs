@@ -0,0 +1,417 @@
+//! Typed access to the http api of a tanktactics server.
+//!
+//! Every endpoint is a method of `Client`, taking and returning the types of `tanktacticsgame`. Requests that need a
+//! signature take it already made, so each caller signs in its own way: the browser with a passkey, a bot with the key
+//! in its config. The requests are sent with reqwest behind the `native` feature and with the fetch api of the browser
+//! behind the `wasm` feature.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use tanktacticsgame::{decode_tokens, DataBaseGame, MoveLine, User, VoteTally};
+
+#[cfg(feature = "native")]
+mod native;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+#[cfg(all(feature = "native", not(feature = "wasm")))]
+use native::send;
+#[cfg(feature = "wasm")]
+use wasm::send;
+
+#[cfg(not(any(feature = "native", feature = "wasm")))]
+compile_error!("tanktactics-client needs the `native` or the `wasm` feature to send requests.");
+
+/// Why a request failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The server could not be reached, or the connection broke before it answered.
+    Unreachable,
+    /// The server answered with a status other than 2xx, and `body` as the reason.
+    Status { status: u16, body: String },
+    /// The server answered with something that isn't what the endpoint returns.
+    Malformed,
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreachable => write!(f, "The server could not be reached."),
+            Self::Status { body, .. } if !body.is_empty() => write!(f, "{body}"),
+            Self::Status { status, .. } => write!(f, "The server answered with status {status}."),
+            Self::Malformed => write!(f, "The server answered with something unexpected."),
+        }
+    }
+}
+impl std::error::Error for Error {}
+
+/// The answer of the server to a request, whatever its status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    /// The headers the server sent.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+impl Response {
+    /// The value of the header `name`, if the server sent it.
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+    /// Whether the status is 2xx.
+    #[must_use]
+    pub fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+    /// The response itself, or `Error::Status` if it isn't `ok`.
+    /// # Errors
+    /// If the status isn't 2xx.
+    pub fn checked(self) -> Result<Self, Error> {
+        if self.ok() {
+            Ok(self)
+        } else {
+            Err(Error::Status {
+                status: self.status,
+                body: self.body,
+            })
+        }
+    }
+    /// The body parsed as JSON.
+    /// # Errors
+    /// If the body isn't a `T`.
+    pub fn json<T: for<'de> Deserialize<'de>>(&self) -> Result<T, Error> {
+        serde_json::from_str(&self.body).map_err(|_| Error::Malformed)
+    }
+}
+
+/// The Elo rating of a player, see `/rating`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Rating {
+    pub user: i32,
+    pub rating: i32,
+    /// The finished games the rating is based on.
+    pub games: u32,
+}
+/// The average rating of the players of a game, see `/game_ratings`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct GameRating {
+    pub game: i32,
+    pub rating: i32,
+    pub players: u32,
+}
+/// Where the player stands in the matchmaking queue, see `/queue/status`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct QueueStatus {
+    /// The preset the player queued for, none if they aren't queued.
+    pub preset: Option<String>,
+    pub waiting: u32,
+    /// The players a match is made for.
+    pub size: u32,
+    /// The game the player was matched into.
+    pub game: Option<i32>,
+}
+/// The build of the frontend the server published, see `/client_info`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ClientInfo {
+    /// The sha-256 of the wasm bundle, in hex.
+    pub hash: String,
+    pub public_key: String,
+    /// The signature of `client|{hash}` by `public_key`.
+    pub signature: String,
+}
+/// A player made by `/quick_play`, with the casual game they joined.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct QuickPlayer {
+    pub user: i32,
+    pub game: i32,
+    pub private_key: String,
+}
+/// A request about the data of `user`, signed as `action|time`. (see `SignedRequest::data`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignedRequest {
+    pub user: i32,
+    /// The unix time in seconds the request was made at.
+    pub time: u64,
+    pub signature: String,
+}
+impl SignedRequest {
+    /// The text `user` signs for `action` at `time`.
+    #[must_use]
+    pub fn data(action: &str, time: u64) -> String {
+        format!("{action}|{time}")
+    }
+}
+/// The notifications a player wants by email, see `Client::set_email`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EmailPreferences {
+    pub shot: bool,
+    pub points: bool,
+    pub rounds: bool,
+}
+impl EmailPreferences {
+    /// The text `user` signs to set `address` with these preferences.
+    #[must_use]
+    pub fn data(self, user: i32, address: &str) -> String {
+        format!(
+            "{user}|{address}|{}|{}|{}",
+            self.shot, self.points, self.rounds
+        )
+    }
+}
+
+const JSON: (&str, &str) = ("Content-Type", "application/json");
+
+/// A tanktactics server, see the crate documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Client {
+    base: String,
+}
+impl Client {
+    /// The server at `base`, like `http://127.0.0.1:3000`.
+    #[must_use]
+    pub fn new(base: impl Into<String>) -> Self {
+        Self { base: base.into() }
+    }
+    /// Sends a request to `path` of the server, whatever the status of the answer.
+    /// # Errors
+    /// If the server could not be reached.
+    pub async fn request(
+        &self,
+        method: &'static str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<String>,
+    ) -> Result<Response, Error> {
+        send(method, format!("{}{path}", self.base), headers, body).await
+    }
+    async fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, Error> {
+        self.request("GET", path, &[], None)
+            .await?
+            .checked()?
+            .json()
+    }
+    async fn post(&self, path: &str, body: Option<String>) -> Result<Response, Error> {
+        let headers: &[_] = if body.is_some() { &[JSON] } else { &[] };
+        self.request("POST", path, headers, body).await?.checked()
+    }
+    fn body<T: Serialize>(value: &T) -> Option<String> {
+        serde_json::to_string(value).ok()
+    }
+
+    pub async fn games(&self) -> Result<Vec<DataBaseGame>, Error> {
+        self.get("/games").await
+    }
+    /// The games `user` made a move in.
+    pub async fn player_games(&self, user: i32) -> Result<Vec<i32>, Error> {
+        self.get(&format!("/player_games?user={user}")).await
+    }
+    pub async fn rating(&self, user: i32) -> Result<Rating, Error> {
+        self.get(&format!("/rating?user={user}")).await
+    }
+    /// The highest rated players, best first.
+    pub async fn leaderboard(&self) -> Result<Vec<Rating>, Error> {
+        self.get("/leaderboard").await
+    }
+    pub async fn game_ratings(&self) -> Result<Vec<GameRating>, Error> {
+        self.get("/game_ratings").await
+    }
+    /// The build of the frontend the server published, `None` if it publishes none.
+    pub async fn client_info(&self) -> Result<Option<ClientInfo>, Error> {
+        let response = self.request("GET", "/client_info", &[], None).await?;
+        if response.ok() {
+            response.json().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+    /// Plays without an account, see `/quick_play`.
+    pub async fn quick_play(&self) -> Result<QuickPlayer, Error> {
+        self.post("/quick_play", None).await?.json()
+    }
+    /// The id of the public demo game, see `/demo`.
+    pub async fn demo(&self) -> Result<i32, Error> {
+        self.get("/demo").await
+    }
+    /// The polls of `game` hidden by its moderators.
+    pub async fn hidden_polls(&self, game: i32) -> Result<Vec<i32>, Error> {
+        self.get(&format!("/moderation/hidden_polls?game={game}"))
+            .await
+    }
+    /// The signature of the last move of `game`, that the next move signs.
+    pub async fn head(&self, game: i32) -> Result<String, Error> {
+        let response = self
+            .request("GET", &format!("/head?game={game}"), &[], None)
+            .await?
+            .checked()?;
+        Ok(response.body.trim_matches('"').into())
+    }
+    /// The query parameters of a read as `viewer`, a `view_branch` request signed by them.
+    fn viewer_query(viewer: Option<&SignedRequest>) -> String {
+        viewer
+            .map(|v| format!("&user={}&time={}&signature={}", v.user, v.time, v.signature))
+            .unwrap_or_default()
+    }
+    /// The players of `game`, with the moves `viewer` may see. (see `Client::tokens`)
+    pub async fn users(
+        &self,
+        game: i32,
+        viewer: Option<&SignedRequest>,
+    ) -> Result<Vec<User>, Error> {
+        let viewer = Self::viewer_query(viewer);
+        self.get(&format!("/users?game={game}{viewer}")).await
+    }
+    /// The chain of `game` as `viewer` sees it, see `/tokens`. The viewer signs `view_branch` to see the moves they
+    /// made while quarantined.
+    pub async fn tokens(
+        &self,
+        game: i32,
+        viewer: Option<&SignedRequest>,
+    ) -> Result<Vec<MoveLine>, Error> {
+        self.tokens_after(game, viewer, None, None)
+            .await
+            .map(|(_, lines)| lines)
+    }
+    /// At most `limit` moves of the chain of `game` after the index `after_index`, with the index of the first one.
+    pub async fn tokens_after(
+        &self,
+        game: i32,
+        viewer: Option<&SignedRequest>,
+        after_index: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<(usize, Vec<MoveLine>), Error> {
+        let mut path = format!("/tokens?game={game}{}", Self::viewer_query(viewer));
+        if let Some(index) = after_index {
+            path.push_str(&format!("&after_index={index}"));
+        }
+        if let Some(limit) = limit {
+            path.push_str(&format!("&limit={limit}"));
+        }
+        let response = self
+            .request("GET", &path, &[("Accept", "text/plain")], None)
+            .await?
+            .checked()?;
+        // The index of the first move goes on the line before the moves.
+        let (index, lines) = response
+            .body
+            .split_once('\n')
+            .unwrap_or((&response.body, ""));
+        let index = index.parse().map_err(|_| Error::Malformed)?;
+        let lines = decode_tokens(lines).map_err(|_| Error::Malformed)?;
+        Ok((index, lines))
+    }
+    /// Appends the signed `line` to `game`. The server answers 409 with the user that got to a tile first, and 429
+    /// with the quota a join goes over.
+    pub async fn send_move(&self, game: i32, line: &MoveLine) -> Result<(), Error> {
+        self.post(&format!("/move?game={game}"), Self::body(line))
+            .await
+            .map(|_| ())
+    }
+    /// The tile `user` would join `game` at. The server answers 409 if they already joined and 403 if it is full.
+    pub async fn join_position(&self, game: i32, user: i32) -> Result<(u32, u32), Error> {
+        #[derive(Deserialize)]
+        struct Position {
+            x: u32,
+            y: u32,
+        }
+        let position: Position = self
+            .get(&format!("/join_position?game={game}&user={user}"))
+            .await?;
+        Ok((position.x, position.y))
+    }
+    /// The server signed statement `game|index|signature` of the move at `index`, with its signature.
+    pub async fn attest(&self, game: i32, index: usize) -> Result<(String, String), Error> {
+        #[derive(Deserialize)]
+        struct Attestation {
+            data: String,
+            signature: String,
+        }
+        let value: Attestation = self
+            .get(&format!("/attest?game={game}&index={index}"))
+            .await?;
+        Ok((value.data, value.signature))
+    }
+    /// The tally of the votes of `round` in `game`, see `/votes`.
+    pub async fn votes(&self, game: i32, round: u32) -> Result<VoteTally, Error> {
+        self.get(&format!("/votes?game={game}&round={round}")).await
+    }
+    /// Registers `public_key` as a new user, returning its id.
+    pub async fn register(&self, public_key: &str) -> Result<i32, Error> {
+        self.post("/regester", Self::body(&public_key))
+            .await?
+            .json()
+    }
+    /// The challenge a new passkey signs, see `/webauthn/challenge`.
+    pub async fn webauthn_challenge(&self) -> Result<String, Error> {
+        self.get("/webauthn/challenge").await
+    }
+    /// Registers the passkey of `registration`, the JSON the browser made of the credential, returning the user id.
+    pub async fn webauthn_register(&self, registration: String) -> Result<i32, Error> {
+        self.post("/webauthn/register", Some(registration))
+            .await?
+            .json()
+    }
+    /// Sets the email address and notification preferences of `user`, with the signature of
+    /// `EmailPreferences::data`. Returns whether a verification code was sent.
+    pub async fn set_email(
+        &self,
+        user: i32,
+        address: &str,
+        preferences: EmailPreferences,
+        signature: &str,
+    ) -> Result<bool, Error> {
+        let body = serde_json::json!({
+            "user": user,
+            "address": address,
+            "shot": preferences.shot,
+            "points": preferences.points,
+            "rounds": preferences.rounds,
+            "signature": signature,
+        });
+        self.post("/email", Some(body.to_string())).await?.json()
+    }
+    pub async fn verify_email(&self, user: i32, code: &str) -> Result<(), Error> {
+        self.post(&format!("/email/verify?user={user}&code={code}"), None)
+            .await
+            .map(|_| ())
+    }
+    /// Everything the server keeps about the user, as JSON text. Signs `export_my_data`.
+    pub async fn export_my_data(&self, request: &SignedRequest) -> Result<String, Error> {
+        Ok(self
+            .post("/export_my_data", Self::body(request))
+            .await?
+            .body)
+    }
+    /// Asks the server to forget what it keeps about the user beyond their moves. Signs `delete_my_data`.
+    pub async fn delete_my_data(&self, request: &SignedRequest) -> Result<(), Error> {
+        self.post("/delete_my_data", Self::body(request))
+            .await
+            .map(|_| ())
+    }
+    /// Queues for a match of `preset` with the signed `JoinRandom` `intent`, appended once the match is made.
+    pub async fn join_queue(&self, preset: &str, intent: &MoveLine) -> Result<(), Error> {
+        let body = serde_json::json!({ "preset": preset, "intent": intent });
+        self.post("/queue", Some(body.to_string()))
+            .await
+            .map(|_| ())
+    }
+    /// Takes the user out of the matchmaking queue. Signs `queue_leave`.
+    pub async fn leave_queue(&self, request: &SignedRequest) -> Result<(), Error> {
+        self.post("/queue/leave", Self::body(request))
+            .await
+            .map(|_| ())
+    }
+    pub async fn queue_status(&self, user: i32) -> Result<QueueStatus, Error> {
+        self.get(&format!("/queue/status?user={user}")).await
+    }
+    /// Creates a game with the settings of `game` and a fresh seed, returning its id. Signs `clone_game|{game}`, only
+    /// moderators of `game` may.
+    pub async fn clone_game(&self, game: i32, request: &SignedRequest) -> Result<i32, Error> {
+        self.post(&format!("/clone_game?game={game}"), Self::body(request))
+            .await?
+            .json()
+    }
+}
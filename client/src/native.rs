@@ -0,0 +1,32 @@
+//! Sends requests with reqwest, for bots and tools running outside the browser.
+
+use crate::{Error, Response};
+
+pub(crate) async fn send(
+    method: &'static str,
+    url: String,
+    headers: &[(&str, &str)],
+    body: Option<String>,
+) -> Result<Response, Error> {
+    let method = reqwest::Method::from_bytes(method.as_bytes()).map_err(|_| Error::Unreachable)?;
+    let mut request = reqwest::Client::new().request(method, url);
+    for (key, value) in headers {
+        request = request.header(*key, *value);
+    }
+    if let Some(body) = body {
+        request = request.body(body);
+    }
+    let response = request.send().await.map_err(|_| Error::Unreachable)?;
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(key, value)| Some((key.as_str().into(), value.to_str().ok()?.into())))
+        .collect();
+    let body = response.text().await.map_err(|_| Error::Unreachable)?;
+    Ok(Response {
+        status,
+        headers,
+        body,
+    })
+}
@@ -0,0 +1,55 @@
+//! Sends requests with the fetch api of the browser.
+
+use js_sys::wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Request, RequestInit, RequestMode};
+
+use crate::{Error, Response};
+
+pub(crate) async fn send(
+    method: &'static str,
+    url: String,
+    headers: &[(&str, &str)],
+    body: Option<String>,
+) -> Result<Response, Error> {
+    let opts = RequestInit::new();
+    opts.set_method(method);
+    opts.set_mode(RequestMode::Cors);
+    if let Some(body) = body {
+        opts.set_body(&body.into());
+    }
+    let request = Request::new_with_str_and_init(&url, &opts).map_err(|_| Error::Unreachable)?;
+    for (key, value) in headers {
+        request
+            .headers()
+            .set(key, value)
+            .map_err(|_| Error::Unreachable)?;
+    }
+    let window = web_sys::window().ok_or(Error::Unreachable)?;
+    let response = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(|_| Error::Unreachable)?
+        .dyn_into::<web_sys::Response>()
+        .map_err(|_| Error::Unreachable)?;
+    let headers = js_sys::try_iter(&response.headers())
+        .ok()
+        .flatten()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = js_sys::Array::from(&entry.ok()?);
+            Some((entry.get(0).as_string()?, entry.get(1).as_string()?))
+        })
+        .collect();
+    let text = response.text().map_err(|_| Error::Unreachable)?;
+    let body = JsFuture::from(text)
+        .await
+        .map_err(|_| Error::Unreachable)?
+        .as_string()
+        .ok_or(Error::Unreachable)?;
+    Ok(Response {
+        status: response.status(),
+        headers,
+        body,
+    })
+}
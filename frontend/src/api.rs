@@ -1,56 +1,304 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt::Display;
 
-use frontend::{get_json, get_text, request};
+use crate::router::{navigate, Route};
+use crate::{client, keystore};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use js_sys::{eval, Promise};
 use sycamore::reactive::{use_context, Scope, Signal};
-use tanktacticsgame::{get_key, DataBaseGame, Game, MoveLine, User};
-use web_sys::{Response, Storage};
+use tanktactics_client::{EmailPreferences, Error, SignedRequest};
+use tanktacticsgame::{
+    get_key, passkey_challenge, passkey_signature, sign_data, verify_data, DataBaseGame, Game,
+    MoveLine, PublicKey, RULES_VERSION,
+};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{window, Storage};
+
+pub use tanktactics_client::{GameRating, QueueStatus, Rating};
+
+/// Javascript helpers converting between array buffers and url safe base 64.
+const BASE64_JS: &str = "const b64 = (buf) => btoa(String.fromCharCode(...new Uint8Array(buf))).replace(/\\+/g, '-').replace(/\\//g, '_');
+const unb64 = (s) => Uint8Array.from(atob(s.replace(/-/g, '+').replace(/_/g, '/')), (c) => c.charCodeAt(0));";
+
+/// Runs `body` as an async javascript function and returns the string it resolves to.
+pub(crate) async fn run_js(body: &str) -> Result<String, ()> {
+    let promise = eval(&format!("(async () => {{ {BASE64_JS}\n{body} }})()")).map_err(|_| ())?;
+    JsFuture::from(Promise::resolve(&promise))
+        .await
+        .map_err(|_| ())?
+        .as_string()
+        .ok_or(())
+}
+/// Creates a passkey and registers it as a new user, returning the user id and the stored private key.
+/// The private key is `passkey:` followed by the credential id, so `sign` knows to ask the passkey.
+pub async fn register_passkey() -> Result<(i32, String), ()> {
+    let challenge = client().webauthn_challenge().await.map_err(|_| ())?;
+    let registration = run_js(&format!(
+        "const credential = await navigator.credentials.create({{ publicKey: {{
+            challenge: unb64('{challenge}'),
+            rp: {{ name: 'TankTactics' }},
+            user: {{ id: crypto.getRandomValues(new Uint8Array(16)), name: 'TankTactics', displayName: 'TankTactics' }},
+            pubKeyCredParams: [{{ type: 'public-key', alg: -7 }}],
+        }} }});
+        return JSON.stringify({{
+            credential_id: b64(credential.rawId),
+            public_key: b64(credential.response.getPublicKey()),
+            client_data_json: b64(credential.response.clientDataJSON),
+            authenticator_data: b64(credential.response.getAuthenticatorData()),
+        }});"
+    ))
+    .await?;
+    let credential_id = serde_json::from_str::<serde_json::Value>(&registration).map_err(|_| ())?
+        ["credential_id"]
+        .as_str()
+        .ok_or(())?
+        .to_string();
+    let user = client()
+        .webauthn_register(registration)
+        .await
+        .map_err(|_| ())?;
+    Ok((user, format!("passkey:{credential_id}")))
+}
+/// Signs `data` with `private_key`, asking the passkey for keys made by `register_passkey` and the passphrase for
+/// keys sealed by `keystore::protect`.
+pub async fn sign(private_key: String, data: &str) -> Result<String, ()> {
+    let private_key = keystore::unlock(private_key).await?;
+    let Some(credential) = private_key.strip_prefix("passkey:") else {
+        return sign_data(data, private_key).map_err(|_| ());
+    };
+    let challenge = URL_SAFE_NO_PAD.encode(passkey_challenge(data));
+    let assertion = run_js(&format!(
+        "const assertion = await navigator.credentials.get({{ publicKey: {{
+            challenge: unb64('{challenge}'),
+            allowCredentials: [{{ type: 'public-key', id: unb64('{credential}') }}],
+        }} }});
+        return JSON.stringify([
+            b64(assertion.response.authenticatorData),
+            b64(assertion.response.clientDataJSON),
+            b64(assertion.response.signature),
+        ]);"
+    ))
+    .await?;
+    let [authenticator_data, client_data_json, signature]: [String; 3] =
+        serde_json::from_str(&assertion).map_err(|_| ())?;
+    Ok(passkey_signature(
+        &authenticator_data,
+        &client_data_json,
+        &signature,
+    ))
+}
 
 pub async fn get_games() -> Result<Vec<DataBaseGame>, ()> {
-    let response = request("GET", "/games".into(), HashMap::new(), None).await?;
-    let value: Vec<DataBaseGame> = get_json(response).await?;
-    Ok(value)
-}
-pub async fn send_move(private_key: String, game: i32, mut line: MoveLine) -> Result<Response, ()> {
-    let head = request("GET", format!("/head?game={game}"), HashMap::new(), None).await?;
-    let head = get_text(head).await?;
-    let head = head.trim_matches('"');
-    line.sign(Some(head), private_key).unwrap();
-    let mut headers = HashMap::new();
-    headers.insert("Content-Type".into(), "application/json".into());
-    request(
-        "POST",
-        format!("/move?game={game}"),
-        headers,
-        Some(serde_json::to_string(&line).unwrap()),
+    client().games().await.map_err(|_| ())
+}
+/// The games `user` made a move in.
+pub async fn get_player_games(user: i32) -> Result<Vec<i32>, ()> {
+    client().player_games(user).await.map_err(|_| ())
+}
+pub async fn get_rating(user: i32) -> Result<Rating, ()> {
+    client().rating(user).await.map_err(|_| ())
+}
+/// The highest rated players, best first.
+pub async fn get_leaderboard() -> Result<Vec<Rating>, ()> {
+    client().leaderboard().await.map_err(|_| ())
+}
+pub async fn get_game_ratings() -> Result<Vec<GameRating>, ()> {
+    client().game_ratings().await.map_err(|_| ())
+}
+/// Checks the bundle this page was served against the build the server published, see `/client_info`. The server key
+/// is pinned the first time it is seen. Returns a warning for the user if something is off.
+pub async fn check_client(storage: &Storage) -> Result<(), String> {
+    let info = match client().client_info().await {
+        // The server publishes no build.
+        Ok(None) | Err(Error::Unreachable) => return Ok(()),
+        Ok(Some(info)) => info,
+        Err(_) => return Err("The client statement of the server is malformed.".into()),
+    };
+    let (hash, public_key) = (&info.hash, &info.public_key);
+    let valid = get_key(public_key.clone())
+        .is_some_and(|key| verify_data(&key, &format!("client|{hash}"), &info.signature));
+    if !valid {
+        return Err("The client statement of the server has an invalid signature.".into());
+    }
+    match storage.get_item("server_key").ok().flatten() {
+        Some(pinned) if pinned != *public_key => {
+            return Err("The key of the server changed since you first played here.".into())
+        }
+        Some(_) => {}
+        None => {
+            let _ = storage.set_item("server_key", public_key);
+        }
+    }
+    // Hashes the wasm bundle the page loaded, empty if it can't be found.
+    let bundle = run_js(
+        "const entry = performance.getEntriesByType('resource').find((e) => e.name.endsWith('.wasm'));
+        if (!entry) return '';
+        const digest = await crypto.subtle.digest('SHA-256', await (await fetch(entry.name)).arrayBuffer());
+        return Array.from(new Uint8Array(digest), (b) => b.toString(16).padStart(2, '0')).join('');",
     )
     .await
+    .unwrap_or_default();
+    if bundle.is_empty() || bundle == *hash {
+        Ok(())
+    } else {
+        Err(
+            "This client is not the build published by the server, it may have been tampered with."
+                .into(),
+        )
+    }
 }
-pub async fn get_game(game: DataBaseGame) -> Result<(Game, Vec<MoveLine>), ()> {
-    let users = request(
-        "GET",
-        format!("/users?game={}", game.id),
-        HashMap::new(),
-        None,
-    )
-    .await?;
-    let users = get_json::<Vec<User>>(users)
+/// Plays without an account, see `/quick_play`. Returns the new user, the casual game they joined and their key.
+pub async fn quick_play() -> Result<(i32, i32, String), ()> {
+    let player = client().quick_play().await.map_err(|_| ())?;
+    Ok((player.user, player.game, player.private_key))
+}
+/// The public demo game, see `/demo`, `None` if the server runs none.
+pub async fn get_demo() -> Result<Option<(Game, Vec<MoveLine>)>, ()> {
+    let id = client().demo().await.map_err(|_| ())?;
+    let Some(game) = get_games().await?.into_iter().find(|game| game.id == id) else {
+        return Ok(None);
+    };
+    get_game(game).await.map(Some)
+}
+/// Resolves after `ms` milliseconds.
+pub async fn sleep(ms: i32) -> Result<(), ()> {
+    let promise = Promise::new(&mut |resolve, _| {
+        let _ = window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms);
+    });
+    JsFuture::from(promise).await.map(|_| ()).map_err(|_| ())
+}
+/// The polls of `game` hidden by its moderators.
+pub async fn get_hidden_polls(game: i32) -> Result<Vec<i32>, ()> {
+    client().hidden_polls(game).await.map_err(|_| ())
+}
+/// Loads the game with id `game`, see `get_game`.
+pub async fn reload_game(game: i32) -> Result<(Game, Vec<MoveLine>), ()> {
+    let game = get_games()
         .await?
         .into_iter()
-        .map(|x| (x.id, get_key(x.public_key).unwrap()))
-        .collect::<HashMap<_, _>>();
+        .find(|x| x.id == game)
+        .ok_or(())?;
+    get_game(game).await
+}
+/// Signs `line` onto the head of `game` and sends it, see `Client::send_move`.
+pub async fn send_move(private_key: String, game: i32, mut line: MoveLine) -> Result<(), Error> {
+    let head = client().head(game).await?;
+    line.rules_version.get_or_insert(RULES_VERSION);
+    // A move that can't be signed is as good as one that didn't reach the server.
+    line.signature = sign(private_key, &line.signing_data(Some(&head)))
+        .await
+        .map_err(|()| Error::Unreachable)?;
+    client().send_move(game, &line).await
+}
+/// The user the chain is read for, see `/tokens`.
+/// How long a signed `view_branch` request is used before signing a new one, in seconds. The server accepts them for
+/// a day.
+const VIEW_RENEWAL: u64 = 23 * 60 * 60;
 
-    let tokens = request(
-        "GET",
-        format!("/tokens?game={}", game.id),
-        HashMap::new(),
-        None,
-    )
-    .await?;
-    let tokens = get_json::<Vec<MoveLine>>(tokens).await?;
+thread_local! {
+    /// The last `view_branch` request signed by the user.
+    static VIEWER: RefCell<Option<SignedRequest>> = const { RefCell::new(None) };
+}
+
+/// The signed `view_branch` request of the user, so reads show the moves they made while quarantined. Signed again
+/// once it gets old. Reads are made as a guest without an account or if the user doesn't sign it.
+async fn viewer() -> Option<SignedRequest> {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let time = (js_sys::Date::now() / 1000.0) as u64;
+    let storage = window()?.local_storage().ok().flatten()?;
+    let user: i32 = storage.get_item("user").ok().flatten()?.parse().ok()?;
+    let cached = VIEWER.with_borrow(|viewer| {
+        viewer
+            .clone()
+            .filter(|v| v.user == user && time.saturating_sub(v.time) < VIEW_RENEWAL)
+    });
+    if cached.is_some() {
+        return cached;
+    }
+    let private_key = storage.get_item("private_key").ok().flatten()?;
+    let request = data_request(private_key, user, "view_branch").await.ok()?;
+    VIEWER.set(Some(request.clone()));
+    Some(request)
+}
+/// The keys of the players of `game`.
+async fn get_users(
+    game: i32,
+    viewer: Option<&SignedRequest>,
+) -> Result<HashMap<i32, PublicKey>, ()> {
+    Ok(client()
+        .users(game, viewer)
+        .await
+        .map_err(|_| ())?
+        .into_iter()
+        .map(|x| (x.id, get_key(x.public_key).unwrap()))
+        .collect())
+}
+pub async fn get_game(game: DataBaseGame) -> Result<(Game, Vec<MoveLine>), ()> {
+    // The chain as the user sees it, see `/tokens`.
+    let viewer = viewer().await;
+    let users = get_users(game.id, viewer.as_ref()).await?;
+    let tokens = client()
+        .tokens(game.id, viewer.as_ref())
+        .await
+        .map_err(|_| ())?;
     let game = game.as_game(tokens.clone(), &users).map_err(|_| ())?;
     Ok((game, tokens))
 }
-pub async fn join_game(cx: Scope<'_>, game: DataBaseGame) -> Result<(), ()> {
+/// Downloads the moves appended to `game` since `known` was loaded and applies them to it, instead of loading the whole
+/// chain again.
+/// # Errors
+/// If `known` holds moves that aren't signed yet, or the moves don't continue it. The game has to be loaded whole then.
+pub async fn update_game(
+    game: i32,
+    known: &(Game, Vec<MoveLine>),
+) -> Result<(Game, Vec<MoveLine>), ()> {
+    if known.1.iter().any(|line| line.signature.is_empty()) {
+        return Err(());
+    }
+    let viewer = viewer().await;
+    let users = get_users(game, viewer.as_ref()).await?;
+    let (index, lines) = client()
+        .tokens_after(game, viewer.as_ref(), known.1.len().checked_sub(1), None)
+        .await
+        .map_err(|_| ())?;
+    if index != known.1.len() {
+        return Err(());
+    }
+    let (mut current, mut tokens) = known.clone();
+    for line in lines {
+        current.load(line.clone(), &users).map_err(|_| ())?;
+        tokens.push(line);
+    }
+    current.drain_events();
+    Ok((current, tokens))
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    AlreadyJoined,
+    GameFull,
+    /// The user has a tank in as many running games as the server allows.
+    TooManyGames(u32),
+    Failed,
+}
+impl Display for JoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AlreadyJoined => write!(f, "You already joined this game."),
+            Self::GameFull => write!(f, "This game is full."),
+            Self::TooManyGames(limit) => write!(
+                f,
+                "You already play in {limit} games, the most this server allows."
+            ),
+            Self::Failed => write!(f, "Could not join the game, try again."),
+        }
+    }
+}
+/// Joins `game` at the position the server picks, without downloading the chain.
+/// If the user already joined the game it is selected instead.
+pub async fn join_game(cx: Scope<'_>, game: i32) -> Result<(), JoinError> {
     let storage = use_context::<Signal<Storage>>(cx);
     let private_key = storage.get().get_item("private_key").unwrap().unwrap(); // JS function doesnt panic | join game only called when regestered
     let user = storage
@@ -61,24 +309,203 @@ pub async fn join_game(cx: Scope<'_>, game: DataBaseGame) -> Result<(), ()> {
         .parse::<i32>()
         .unwrap(); // JS function doesnt panic | join game only called when regestered | user is always a number
 
-    let game = get_game(game).await?.0;
-
-    let (x, y) = game.get_pos();
+    let (x, y) = match client().join_position(game, user).await {
+        Ok(position) => position,
+        Err(Error::Status { status: 409, .. }) => {
+            storage.get().set_item("game", &game.to_string()).unwrap();
+            navigate(Route::Game(game));
+            return Ok(());
+        }
+        Err(Error::Status { status: 403, .. }) => return Err(JoinError::GameFull),
+        Err(_) => return Err(JoinError::Failed),
+    };
 
     let m = MoveLine {
         move_type: tanktacticsgame::MoveLineType::Join,
         x: Some(x),
         y: Some(y),
         target: None,
+        amount: None,
+        text: None,
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: None,
         authorizer: user,
         signature: String::new(),
     };
 
-    send_move(private_key, game.id, m).await?;
+    match send_move(private_key, game, m).await {
+        Ok(()) => {}
+        Err(Error::Status { status: 429, body }) => {
+            let quota: serde_json::Value =
+                serde_json::from_str(&body).map_err(|_| JoinError::Failed)?;
+            let limit = quota["limit"].as_u64().and_then(|x| u32::try_from(x).ok());
+            return Err(limit.map_or(JoinError::Failed, JoinError::TooManyGames));
+        }
+        Err(_) => return Err(JoinError::Failed),
+    }
 
+    storage.get().set_item("game", &game.to_string()).unwrap();
+    navigate(Route::Game(game));
+    Ok(())
+}
+/// Why a chain served for a game conflicts with the one verified before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainError {
+    /// The server served less moves than were verified before.
+    Shorter { known: usize, served: usize },
+    /// The move at this index has a different signature than before.
+    Diverged(usize),
+}
+impl Display for ChainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Shorter { known, served } => write!(
+                f,
+                "The server served {served} moves, but {known} were verified before."
+            ),
+            Self::Diverged(index) => write!(
+                f,
+                "The server served a different history for move {index} than before."
+            ),
+        }
+    }
+}
+fn load_item<T: for<'de> serde::Deserialize<'de>>(storage: &Storage, key: &str) -> Option<T> {
     storage
-        .get()
-        .set_item("game", &game.id.to_string())
+        .get_item(key)
+        .unwrap()
+        .and_then(|x| serde_json::from_str(&x).ok())
+}
+/// Checks `tokens` of `game` against the signatures and the `(index, head_signature)` stored by `accept_chain`.
+/// # Errors
+/// If the server rolled the chain back or serves a history that diverges from the verified one.
+pub fn verify_chain(storage: &Storage, game: i32, tokens: &[MoveLine]) -> Result<(), ChainError> {
+    let seen: Vec<String> = load_item(storage, &format!("seen_{game}")).unwrap_or_default();
+    if let Some(index) = seen
+        .iter()
+        .zip(tokens)
+        .position(|(seen, line)| *seen != line.signature)
+    {
+        return Err(ChainError::Diverged(index));
+    }
+    let Some((index, head)) = load_item::<(usize, String)>(storage, &format!("head_{game}")) else {
+        return Ok(());
+    };
+    match tokens.get(index) {
+        None => Err(ChainError::Shorter {
+            known: index + 1,
+            served: tokens.len(),
+        }),
+        Some(line) if line.signature != head => Err(ChainError::Diverged(index)),
+        Some(_) => Ok(()),
+    }
+}
+/// Stores `tokens` as the verified chain of `game`.
+pub fn accept_chain(storage: &Storage, game: i32, tokens: &[MoveLine]) {
+    let seen: Vec<&String> = tokens.iter().map(|x| &x.signature).collect();
+    storage
+        .set_item(
+            &format!("seen_{game}"),
+            &serde_json::to_string(&seen).unwrap(),
+        )
         .unwrap();
-    Ok(())
+    let head = tokens
+        .len()
+        .checked_sub(1)
+        .map(|i| (i, &tokens[i].signature));
+    match head {
+        Some(head) => storage
+            .set_item(
+                &format!("head_{game}"),
+                &serde_json::to_string(&head).unwrap(),
+            )
+            .unwrap(),
+        None => storage.remove_item(&format!("head_{game}")).unwrap(),
+    }
+}
+/// Gets the server signed statement `game|index|signature` of the move at `index`.
+pub async fn attest(game: i32, index: usize) -> Result<(String, String), ()> {
+    client().attest(game, index).await.map_err(|_| ())
+}
+/// The tally of the votes of `round` in `game`, see `/votes`.
+pub async fn get_votes(game: i32, round: u32) -> Result<tanktacticsgame::VoteTally, ()> {
+    client().votes(game, round).await.map_err(|_| ())
+}
+/// Sets the email address and notification preferences of `user`, returning whether a verification code was sent.
+pub async fn set_email(
+    private_key: String,
+    user: i32,
+    address: String,
+    (shot, points, rounds): (bool, bool, bool),
+) -> Result<bool, ()> {
+    let preferences = EmailPreferences {
+        shot,
+        points,
+        rounds,
+    };
+    let signature = sign(private_key, &preferences.data(user, &address)).await?;
+    client()
+        .set_email(user, &address, preferences, &signature)
+        .await
+        .map_err(|_| ())
+}
+pub async fn verify_email(user: i32, code: &str) -> Result<(), ()> {
+    client().verify_email(user, code).await.map_err(|_| ())
+}
+/// A request about the data of `user`, signed as `action|time`.
+async fn data_request(private_key: String, user: i32, action: &str) -> Result<SignedRequest, ()> {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let time = (js_sys::Date::now() / 1000.0) as u64;
+    let signature = sign(private_key, &SignedRequest::data(action, time)).await?;
+    Ok(SignedRequest {
+        user,
+        time,
+        signature,
+    })
+}
+/// Everything the server keeps about `user`, as the JSON text of `/export_my_data`.
+pub async fn export_my_data(private_key: String, user: i32) -> Result<String, ()> {
+    let request = data_request(private_key, user, "export_my_data").await?;
+    client().export_my_data(&request).await.map_err(|_| ())
+}
+/// Asks the server to forget what it keeps about `user` beyond their moves, see `/delete_my_data`.
+pub async fn delete_my_data(private_key: String, user: i32) -> Result<(), ()> {
+    let request = data_request(private_key, user, "delete_my_data").await?;
+    client().delete_my_data(&request).await.map_err(|_| ())
+}
+/// Queues `user` for a match of `preset` with a `JoinRandom` move the server appends once the match is made.
+pub async fn join_queue(private_key: String, user: i32, preset: &str) -> Result<(), ()> {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let time = (js_sys::Date::now() / 1000.0) as u64;
+    let mut intent = MoveLine {
+        move_type: tanktacticsgame::MoveLineType::JoinRandom,
+        x: None,
+        y: None,
+        target: None,
+        amount: None,
+        text: Some(format!("queue|{preset}|{time}")),
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: Some(RULES_VERSION),
+        authorizer: user,
+        signature: String::new(),
+    };
+    intent.signature = sign(private_key, &intent.signing_data(None)).await?;
+    client().join_queue(preset, &intent).await.map_err(|_| ())
+}
+/// Creates a new game with the settings of `game` and a fresh seed, returning its id. Only moderators of `game` may.
+pub async fn clone_game(private_key: String, user: i32, game: i32) -> Result<i32, ()> {
+    let request = data_request(private_key, user, &format!("clone_game|{game}")).await?;
+    client().clone_game(game, &request).await.map_err(|_| ())
+}
+/// Takes `user` out of the matchmaking queue.
+pub async fn leave_queue(private_key: String, user: i32) -> Result<(), ()> {
+    let request = data_request(private_key, user, "queue_leave").await?;
+    client().leave_queue(&request).await.map_err(|_| ())
+}
+pub async fn get_queue_status(user: i32) -> Result<QueueStatus, ()> {
+    client().queue_status(user).await.map_err(|_| ())
 }
@@ -0,0 +1,87 @@
+#![warn(clippy::all, clippy::pedantic)]
+//! Read-only board viewer that can be embedded in other pages.
+//!
+//! Renders into the element with id `tanktactics-widget`, showing the game given by its `data-game` attribute:
+//! ```html
+//! <div id="tanktactics-widget" data-game="12"></div>
+//! <script type="module">import init from "https://example.com/widget/widget.js"; init();</script>
+//! ```
+
+use frontend::{
+    api::{get_game, get_games},
+    sleep,
+};
+use sycamore::futures::spawn_local_scoped;
+use sycamore::prelude::*;
+use tanktacticsgame::Game;
+use web_sys::window;
+
+/// Time between board refreshes in milliseconds.
+const REFRESH_MS: i32 = 10_000;
+/// Styles shipped with the widget, as the host page won't have them.
+const STYLE: &str = "
+.tt-board { position: relative; font-family: sans-serif; font-size: 12px; }
+.tt-tile { position: absolute; width: 30px; height: 30px; line-height: 30px; text-align: center; border: 1px solid #343a40; background-color: #495057; color: white; }
+.tt-player { background-color: blue; }
+";
+
+async fn load(id: i32) -> Option<Game> {
+    let game = get_games().await.ok()?.into_iter().find(|x| x.id == id)?;
+    get_game(game).await.ok().map(|x| x.0)
+}
+#[component]
+async fn Widget<G: Html>(cx: Scope<'_>, id: i32) -> View<G> {
+    let Some(game) = load(id).await else {
+        return view!(cx, "Could not load game.");
+    };
+    let width = game.settings.width;
+    let height = game.settings.height;
+    let board = create_signal(cx, game.board);
+    spawn_local_scoped(cx, async move {
+        loop {
+            sleep(REFRESH_MS).await;
+            if let Some(game) = load(id).await {
+                board.set(game.board);
+            }
+        }
+    });
+    let count = create_signal(cx, (0..(width * height)).collect::<Vec<_>>());
+    view!(cx,
+        style { (STYLE) }
+        div(class="tt-board", style={format!("width:{}px;height:{}px", width * 30, height * 30)}) {
+            Keyed(
+                iterable=count,
+                view=move |cx, i| view! { cx,
+                    div(class={
+                        if board.get().contains_key(&(i % width, i / width)) {
+                            "tt-tile tt-player"
+                        } else {
+                            "tt-tile"
+                        }
+                    }, style={format!("left:{}px;top:{}px", (i % width) * 30, (i / width) * 30)}) {
+                        ({
+                            board.get().get(&(i % width, i / width)).map_or(String::default(), std::string::ToString::to_string)
+                        })
+                    }
+                },
+                key=|x| *x,
+            )
+        }
+    )
+}
+fn main() {
+    let Some(root) = window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.get_element_by_id("tanktactics-widget"))
+    else {
+        return;
+    };
+    let Some(id) = root
+        .get_attribute("data-game")
+        .and_then(|x| x.parse::<i32>().ok())
+    else {
+        root.set_text_content(Some("Missing data-game attribute."));
+        return;
+    };
+    sycamore::render_to(|cx| view!(cx, Widget(id)), &root);
+}
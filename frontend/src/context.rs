@@ -0,0 +1,66 @@
+//! The game on the game page, shared with its components through the sycamore context.
+//!
+//! `GameContext::state` holds the game and its moves, and is the only signal moves are written to. The settings,
+//! players and board follow it, each only notifying its subscribers when it changed, so components read what they show
+//! from these signals when they render instead of copying it out of the game when they are made.
+
+use std::collections::HashMap;
+
+use sycamore::reactive::{
+    create_effect, create_memo, create_rc_signal, provide_context, use_context, RcSignal,
+    ReadSignal, Scope,
+};
+use tanktacticsgame::{Game, MoveLine, Player, Settings};
+
+#[derive(Clone)]
+pub struct GameContext {
+    /// The game and its moves.
+    pub state: RcSignal<(Game, Vec<MoveLine>)>,
+    pub settings: RcSignal<Settings>,
+    pub players: RcSignal<HashMap<i32, Player>>,
+    /// The tank on every taken tile.
+    pub board: RcSignal<HashMap<(u32, u32), i32>>,
+}
+impl GameContext {
+    /// Provides the context for `game` to the components of `cx`, and keeps the derived signals following the state.
+    pub fn provide(cx: Scope<'_>, game: (Game, Vec<MoveLine>)) -> &GameContext {
+        let context = GameContext {
+            settings: create_rc_signal(game.0.settings.clone()),
+            players: create_rc_signal(game.0.players.clone()),
+            board: create_rc_signal(game.0.board.clone()),
+            state: create_rc_signal(game),
+        };
+        let derived = context.clone();
+        create_effect(cx, move || {
+            let state = derived.state.get();
+            let game = &state.0;
+            if *derived.settings.get_untracked() != game.settings {
+                derived.settings.set(game.settings.clone());
+            }
+            if *derived.players.get_untracked() != game.players {
+                derived.players.set(game.players.clone());
+            }
+            if *derived.board.get_untracked() != game.board {
+                derived.board.set(game.board.clone());
+            }
+        });
+        provide_context(cx, context)
+    }
+    /// Every tile of the board, row by row, following the size in the settings.
+    pub fn tiles<'a>(&'a self, cx: Scope<'a>) -> &'a ReadSignal<Vec<(u32, u32)>> {
+        create_memo(cx, move || {
+            let settings = self.settings.get();
+            (0..settings.height)
+                .flat_map(|y| (0..settings.width).map(move |x| (x, y)))
+                .collect()
+        })
+    }
+    /// The context provided by `GameContext::provide`.
+    ///
+    /// # Panics
+    /// Outside of the game page.
+    #[must_use]
+    pub fn get(cx: Scope<'_>) -> &GameContext {
+        use_context::<GameContext>(cx)
+    }
+}
@@ -0,0 +1,164 @@
+//! Keeps the private key in local storage encrypted with a passphrase, see `SealedKey`.
+//!
+//! The passphrase is stretched with argon2id, which Web Crypto doesn't have, and the key encrypted with AES-GCM by Web
+//! Crypto. Keys sealed before argon2id are stretched with PBKDF2 by Web Crypto and stay sealed that way. A sealed key is
+//! opened the first time something is signed, asking for the passphrase, and kept open in memory until the page is
+//! closed or `lock` is called. Keys stored before passphrases existed keep working as they are, until the player
+//! protects them with `protect`.
+
+use std::cell::RefCell;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine;
+use tanktacticsgame::{SealedKey, BASE64};
+use web_sys::{window, Storage};
+
+use crate::api::run_js;
+
+thread_local! {
+    /// The last sealed key that was opened, and the key it holds.
+    static OPENED: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+/// A javascript string literal of `text`.
+fn literal(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_default()
+}
+
+/// Stretches `passphrase` over `salt` with argon2id into an AES-GCM key, returned as url safe base 64.
+fn stretch(passphrase: &str, salt: &[u8], iterations: u32) -> Result<String, ()> {
+    let params = Params::new(SealedKey::MEMORY, iterations, 1, Some(32)).map_err(|_| ())?;
+    let mut key = [0; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|_| ())?;
+    Ok(BASE64.encode(key))
+}
+
+/// Encrypts `private_key` with `passphrase`, returning the stored form of the sealed key.
+pub async fn seal(private_key: &str, passphrase: &str) -> Result<String, ()> {
+    let mut salt = [0; 16];
+    getrandom::getrandom(&mut salt).map_err(|_| ())?;
+    let key = stretch(passphrase, &salt, SealedKey::ITERATIONS)?;
+    let sealed = run_js(&format!(
+        "const key = await crypto.subtle.importKey('raw', unb64('{key}'), 'AES-GCM', false, ['encrypt']);
+        const iv = crypto.getRandomValues(new Uint8Array(12));
+        const sealed = await crypto.subtle.encrypt({{ name: 'AES-GCM', iv }}, key, new TextEncoder().encode({private_key}));
+        return JSON.stringify([b64(iv), b64(sealed)]);",
+        private_key = literal(private_key),
+    ))
+    .await?;
+    let [nonce, ciphertext]: [String; 2] = serde_json::from_str(&sealed).map_err(|_| ())?;
+    Ok(SealedKey {
+        kdf: SealedKey::KDF.into(),
+        iterations: SealedKey::ITERATIONS,
+        salt: BASE64.encode(salt),
+        nonce,
+        ciphertext,
+    }
+    .to_string())
+}
+
+/// Decrypts `sealed` with `passphrase`, failing if the passphrase is wrong.
+async fn open(sealed: &SealedKey, passphrase: &str) -> Result<String, ()> {
+    let key = if sealed.kdf == SealedKey::PBKDF2 {
+        format!(
+            "await crypto.subtle.deriveKey(
+                {{ name: 'PBKDF2', salt: unb64('{salt}'), iterations: {iterations}, hash: 'SHA-256' }},
+                await crypto.subtle.importKey('raw', new TextEncoder().encode({passphrase}), 'PBKDF2', false, ['deriveKey']),
+                {{ name: 'AES-GCM', length: 256 }}, false, ['decrypt'],
+            )",
+            passphrase = literal(passphrase),
+            salt = sealed.salt,
+            iterations = sealed.iterations,
+        )
+    } else {
+        let salt = BASE64.decode(&sealed.salt).map_err(|_| ())?;
+        format!(
+            "await crypto.subtle.importKey('raw', unb64('{}'), 'AES-GCM', false, ['decrypt'])",
+            stretch(passphrase, &salt, sealed.iterations)?
+        )
+    };
+    run_js(&format!(
+        "const key = {key};
+        const opened = await crypto.subtle.decrypt({{ name: 'AES-GCM', iv: unb64('{nonce}') }}, key, unb64('{ciphertext}'));
+        return new TextDecoder().decode(opened);",
+        nonce = sealed.nonce,
+        ciphertext = sealed.ciphertext,
+    ))
+    .await
+}
+
+/// The key in `stored`, asking for the passphrase if it is sealed and wasn't opened yet.
+/// Fails if the player cancels, or the stored key is sealed in a way this version can't open.
+pub async fn unlock(stored: String) -> Result<String, ()> {
+    if !SealedKey::is_sealed(&stored) {
+        return Ok(stored);
+    }
+    let opened = OPENED.with_borrow(|opened| {
+        opened
+            .as_ref()
+            .filter(|(sealed, _)| *sealed == stored)
+            .map(|(_, key)| key.clone())
+    });
+    if let Some(key) = opened {
+        return Ok(key);
+    }
+    let sealed = SealedKey::parse(&stored).ok_or(())?;
+    let mut message = "Enter the passphrase of your key.";
+    loop {
+        let passphrase = window()
+            .ok_or(())?
+            .prompt_with_message(message)
+            .map_err(|_| ())?
+            .ok_or(())?;
+        if let Ok(key) = open(&sealed, &passphrase).await {
+            OPENED.set(Some((stored, key.clone())));
+            return Ok(key);
+        }
+        message = "Wrong passphrase, try again.";
+    }
+}
+
+/// Forgets the opened key, so the passphrase is asked for again on the next signature.
+pub fn lock() {
+    OPENED.set(None);
+}
+
+/// Asks for a new passphrase twice and seals the plain key in `storage` with it.
+/// Returns whether the key was sealed, which it isn't if the player cancels.
+pub async fn protect(storage: &Storage) -> Result<bool, ()> {
+    let window = window().ok_or(())?;
+    let Some(private_key) = storage.get_item("private_key").map_err(|_| ())? else {
+        return Ok(false);
+    };
+    let Some(passphrase) = window
+        .prompt_with_message("Choose a passphrase for your key.")
+        .map_err(|_| ())?
+        .filter(|passphrase| !passphrase.is_empty())
+    else {
+        return Ok(false);
+    };
+    let repeated = window
+        .prompt_with_message("Enter the passphrase again.")
+        .map_err(|_| ())?;
+    if repeated.as_ref() != Some(&passphrase) {
+        window
+            .alert_with_message("The passphrases differ, your key was left as it was.")
+            .map_err(|_| ())?;
+        return Ok(false);
+    }
+    let sealed = seal(&private_key, &passphrase).await?;
+    storage.set_item("private_key", &sealed).map_err(|_| ())?;
+    OPENED.set(Some((sealed, private_key)));
+    Ok(true)
+}
+
+/// Replaces the sealed key in `storage` with the plain key, asking for the passphrase if needed.
+pub async fn unprotect(storage: &Storage) -> Result<(), ()> {
+    let stored = storage.get_item("private_key").map_err(|_| ())?.ok_or(())?;
+    let key = unlock(stored).await?;
+    storage.set_item("private_key", &key).map_err(|_| ())?;
+    lock();
+    Ok(())
+}
@@ -1,42 +1,131 @@
 #![warn(clippy::all, clippy::pedantic)]
 
-use frontend::{get_text, request};
+use frontend::api::{
+    accept_chain, attest, check_client, clone_game, delete_my_data, export_my_data, get_demo,
+    get_game, get_game_ratings, get_games, get_hidden_polls, get_leaderboard, get_player_games,
+    get_queue_status, get_rating, get_votes, join_game, join_queue, leave_queue, quick_play,
+    register_passkey, reload_game, send_move, set_email, sleep, update_game, verify_chain,
+    verify_email, ChainError, QueueStatus,
+};
+use frontend::context::GameContext;
+use frontend::keystore;
+use frontend::router::{self, navigate, Route};
+use frontend::sandbox::{self, Sandbox, MAX_BOTS, MIN_BOTS};
+use frontend::tutorial::{Tutorial, YOU};
+use frontend::{client, log};
+use frontend::{presence, sync};
 use js_sys::eval;
-use std::collections::HashMap;
+use js_sys::wasm_bindgen::{JsCast, JsValue};
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
-use sycamore::futures::spawn_local_scoped;
+use sycamore::futures::{spawn_local, spawn_local_scoped};
 use sycamore::prelude::*;
-use tanktacticsgame::{get_random_keys, Game, MoveLine};
-use web_sys::{window, Storage, WebSocket};
+use tanktactics_client::Error;
+use tanktacticsgame::{
+    get_random_keys, DataBaseGame, Game, GameStatus, Item, MoveLine, MoveLineType, Mutator, Preset,
+    SealedKey, Step, SyncFrame, Topology, Transfer, TransferKind, VoteTally, RULES_VERSION,
+    RULES_VERSIONS,
+};
+use web_sys::{window, HtmlElement, PointerEvent, Storage, WebSocket};
 
-use crate::api::{get_game, get_games, join_game, send_move};
-
-mod api;
-
-#[derive(Prop)]
-struct WorldProps<'a> {
-    user: i32,
-    game: &'a ReadSignal<(Game, Vec<MoveLine>)>,
+/// Tiles the enemy tank `enemy` could drive to or shoot at next turn.
+fn enemy_reach(game: &Game, enemy: i32, user: i32) -> HashMap<(u32, u32), &'static str> {
+    let mut tiles = HashMap::new();
+    let Some(enemy) = game
+        .players
+        .get(&enemy)
+        .filter(|p| p.user != user && p.is_alive(true).is_ok())
+    else {
+        return tiles;
+    };
+    let board = game.settings.board();
+    for tile in board.neighbors(enemy.x, enemy.y, game.player_range(enemy), Topology::Flat) {
+        tiles.insert(tile, "shootable");
+    }
+    tiles.insert((enemy.x, enemy.y), "shootable");
+    for tile in board.neighbors(enemy.x, enemy.y, 1, Topology::Flat) {
+        if !game.board.contains_key(&tile) {
+            tiles.insert(tile, "drivable");
+        }
+    }
+    tiles
 }
-#[component]
-fn World<'a, G: Html>(cx: Scope<'a>, WorldProps { game, user }: WorldProps<'a>) -> View<G> {
-    let x = create_signal(cx, 0);
-    let y = create_signal(cx, 0);
-    let target = create_signal(cx, 0);
-    let shoot = create_signal(cx, false);
-    let drive = create_signal(cx, false);
-    let vote = create_signal(cx, false);
-
-    let width = game.get().0.settings.width;
-    let height = game.get().0.settings.height;
+/// Hover text for the tank of `id`, listing its stats and who damaged it most recently.
+fn tank_info(game: &Game, id: i32) -> String {
+    let Some(player) = game.players.get(&id) else {
+        return String::new();
+    };
+    let mut health = HashMap::new();
+    let mut kills = 0;
+    let mut damaged_by = Vec::new();
+    for line in game
+        .lines
+        .iter()
+        .filter(|l| l.move_type == MoveLineType::Shoot)
+    {
+        let Some(target) = line.target else {
+            continue;
+        };
+        let hp = health.entry(target).or_insert(game.settings.health);
+        *hp = hp.saturating_sub(1);
+        if *hp == 0 && line.authorizer == id {
+            kills += 1;
+        }
+        if target == id {
+            damaged_by.push(line.authorizer.to_string());
+        }
+    }
+    let recent = damaged_by.iter().rev().take(3).cloned().collect::<Vec<_>>();
+    let actions = game
+        .actions_left(id)
+        .map(|left| format!("\nActions left this round: {left}"))
+        .unwrap_or_default();
+    let list = |items: &[Item]| {
+        items
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let disabled = player
+        .disabled_round
+        .map(|round| {
+            format!(
+                "\nDisabled in round {round}, destroyed when round {} ends unless repaired",
+                round + 1
+            )
+        })
+        .unwrap_or_default();
+    let items = if player.items.is_empty() && player.effects.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\nItems: {}\nActive: {}",
+            list(&player.items),
+            list(&player.effects)
+        )
+    };
+    format!(
+        "Player {id}\nKey: {}\nHealth: {}{disabled}\nLevel: {}\nPoints: {}{actions}{items}\nKills: {kills}\nRecently damaged by: {}",
+        game.fingerprints.get(&player.user).map_or("unknown", String::as_str),
+        player.health,
+        player.level,
+        player.points,
+        if recent.is_empty() {
+            "nobody".into()
+        } else {
+            recent.join(", ")
+        }
+    )
+}
+#[component(inline_props)]
+fn TokenList<'a, G: Html>(cx: Scope<'a>, game: &'a ReadSignal<(Game, Vec<MoveLine>)>) -> View<G> {
     let tokens = game.map(cx, |x| {
         x.1.iter()
             .map(MoveLine::clone)
             .enumerate()
             .collect::<Vec<_>>()
     });
-    let board = game.map(cx, |game| game.0.board.clone());
-    let count = create_signal(cx, (0..(width * height)).collect::<Vec<_>>());
     let token_string = tokens
         .get()
         .iter()
@@ -63,53 +152,1027 @@ fn World<'a, G: Html>(cx: Scope<'a>, WorldProps { game, user }: WorldProps<'a>)
                 "Copy"
             }
         }
-        div(id="world", style={format!("width:{}px;height:{}px", width * 50, height * 50)}) {
+    )
+}
+/// Whether `user` can drive to `pos` after passing `path`, and whether `pos` can be added to the path instead.
+fn drivable(game: &Game, user: i32, path: &[Step], pos: (u32, u32)) -> (bool, bool) {
+    let Some(player) = game.players.get(&user) else {
+        return (false, false);
+    };
+    let from = path.last().map_or((player.x, player.y), |s| (s.x, s.y));
+    let length = u32::try_from(path.len()).unwrap() + 1;
+    let drive = player.is_alive(true).is_ok()
+        && !game.board.contains_key(&pos)
+        && !path.iter().any(|s| (s.x, s.y) == pos)
+        && game.settings.board().chebyshev(from, pos, Topology::Flat) <= 1
+        && player.points.get() >= length;
+    (drive, drive && length < game.settings.drive_length)
+}
+/// The cheapest drive of `user` to `pos` they can afford, as the tiles passed before `pos`. Only for drives longer
+/// than one tile, shorter ones need no path.
+fn suggest_path(game: &Game, user: i32, pos: (u32, u32)) -> Option<Vec<Step>> {
+    let player = game.players.get(&user)?;
+    let tiles = game.drive_path(user, pos)?;
+    let cost = u32::try_from(tiles.len())
+        .ok()?
+        .saturating_mul(game.settings.costs.drive);
+    if tiles.len() < 2 || player.is_alive(true).is_err() || player.points.get() < cost {
+        return None;
+    }
+    Some(
+        tiles[..tiles.len() - 1]
+            .iter()
+            .map(|&(x, y)| Step { x, y })
+            .collect(),
+    )
+}
+/// Whether `user` can drive to the selected tile `pos` and whether it can be added to the path, like `drivable`.
+/// Replaces a suggested path, and suggests one when `pos` is out of reach of a single step.
+fn select_drive(
+    game: &Game,
+    user: i32,
+    path: &Signal<Vec<Step>>,
+    suggested: &Signal<bool>,
+    pos: (u32, u32),
+) -> (bool, bool) {
+    if *suggested.get() {
+        path.set(Vec::new());
+        suggested.set(false);
+    }
+    let (drive, extend) = drivable(game, user, &path.get(), pos);
+    if drive || !path.get().is_empty() {
+        return (drive, extend);
+    }
+    match suggest_path(game, user, pos) {
+        Some(steps) => {
+            path.set(steps);
+            suggested.set(true);
+            (true, false)
+        }
+        None => (false, false),
+    }
+}
+/// Pixels per point in the flow chart.
+const FLOW_UNIT: u32 = 8;
+/// A node of the flow chart: a player, the pixel offset of its top and its height.
+type FlowNode = (i32, u32, u32);
+/// Lays `flows` out as a Sankey chart, givers on the left and receivers on the right.
+/// Returns both node columns and, per flow, the y of its band center on either side.
+fn sankey(flows: &[Transfer]) -> (Vec<FlowNode>, Vec<FlowNode>, Vec<(u32, u32)>) {
+    let column = |side: fn(&Transfer) -> i32| {
+        let mut totals = std::collections::BTreeMap::<i32, u32>::new();
+        for flow in flows {
+            *totals.entry(side(flow)).or_default() += flow.amount * FLOW_UNIT;
+        }
+        let mut top = 0;
+        totals
+            .into_iter()
+            .map(|(player, height)| {
+                top += height + FLOW_UNIT;
+                (player, top - height - FLOW_UNIT, height)
+            })
+            .collect::<Vec<_>>()
+    };
+    let (givers, receivers) = (column(|f| f.from), column(|f| f.to));
+    let mut used = HashMap::<(bool, i32), u32>::new();
+    let mut band = |nodes: &[FlowNode], right: bool, player: i32, height: u32| {
+        let node = nodes.iter().find(|n| n.0 == player).unwrap();
+        let used = used.entry((right, player)).or_default();
+        *used += height;
+        node.1 + *used - height / 2
+    };
+    let bands = flows
+        .iter()
+        .map(|f| {
+            let height = f.amount * FLOW_UNIT;
+            (
+                band(&givers, false, f.from, height),
+                band(&receivers, true, f.to, height),
+            )
+        })
+        .collect();
+    (givers, receivers, bands)
+}
+/// Shows every gift and loot of the game as a Sankey chart.
+#[component(inline_props)]
+fn Flows<'a, G: Html>(cx: Scope<'a>, game: &'a ReadSignal<(Game, Vec<MoveLine>)>) -> View<G> {
+    let shown = create_signal(cx, false);
+    view!(cx,
+        button(on:click=move |_| shown.set(!*shown.get())) {
+            (if *shown.get() { "Hide point flows" } else { "Show point flows" })
+        }
+        (if *shown.get() {
+            let flows = game.get().0.flows();
+            let (givers, receivers, bands) = sankey(&flows);
+            let height = givers.iter().chain(&receivers).map(|n| n.1 + n.2).max().unwrap_or_default();
+            let nodes = View::new_fragment(
+                givers.iter().map(|n| (0, n)).chain(receivers.iter().map(|n| (290, n)))
+                    .map(|(x, &(player, top, height))| view! { cx,
+                        rect(class="flow-node", x=x.to_string(), y=top.to_string(), width="10", height=height.to_string())
+                        text(x=if x == 0 { "14" } else { "286" }, y=(top + height / 2).to_string(), text-anchor=if x == 0 { "start" } else { "end" }) { (player) }
+                    })
+                    .collect(),
+            );
+            let ribbons = View::new_fragment(
+                flows.iter().zip(bands)
+                    .map(|(flow, (from, to))| {
+                        let class = match flow.kind {
+                            TransferKind::Gift => "flow gift",
+                            TransferKind::Loot => "flow loot",
+                            TransferKind::Loan | TransferKind::Repayment => "flow loan",
+                        };
+                        let width = (flow.amount * FLOW_UNIT).to_string();
+                        let label = format!("{} -> {}: {} ({:?})", flow.from, flow.to, flow.amount, flow.kind);
+                        view! { cx,
+                            path(class=class, d=format!("M10 {from} C150 {from} 150 {to} 290 {to}"), stroke-width=width) {
+                                title { (label) }
+                            }
+                        }
+                    })
+                    .collect(),
+            );
+            view! { cx,
+                svg(id="flows", width="300", height=height.to_string()) { (ribbons) (nodes) }
+            }
+        } else {
+            view!(cx,)
+        })
+    )
+}
+/// Lists the loans that aren't paid back yet, see `MoveLineType::Lend`.
+#[component(inline_props)]
+fn Loans<'a, G: Html>(cx: Scope<'a>, game: &'a ReadSignal<(Game, Vec<MoveLine>)>) -> View<G> {
+    let loans = create_memo(cx, || game.get().0.loans.clone());
+    view!(
+        cx,
+        (if loans.get().is_empty() {
+            view!(cx,)
+        } else {
+            view! { cx,
+                h3 { "Loans" }
+                ul(id="loans") {
+                    Indexed(
+                        iterable=loans,
+                        view=|cx, loan| view! { cx,
+                            li(class=if loan.defaulted { "defaulted" } else { "" }) {
+                                (format!("{} owes {} {} points, due at the end of round {}{}", loan.borrower, loan.lender, loan.amount, loan.due_round, if loan.defaulted { " (defaulted)" } else { "" }))
+                            }
+                        }
+                    )
+                }
+            }
+        })
+    )
+}
+/// Offers moderators a rematch once the game is over, a new game with the same settings and a fresh seed.
+#[component(inline_props)]
+fn Rematch<'a, G: Html>(
+    cx: Scope<'a>,
+    game: &'a ReadSignal<(Game, Vec<MoveLine>)>,
+    user: i32,
+) -> View<G> {
+    let over = create_memo(cx, || game.get().0.status().is_over());
+    let rematch = move |_| {
+        let private_key = use_context::<Signal<Storage>>(cx)
+            .get()
+            .get_item("private_key")
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let id = game.get().0.id;
+        let toast = use_context::<Toast>(cx).0.clone();
+        spawn_local_scoped(cx, async move {
+            match clone_game(private_key, user, id).await {
+                Ok(game) => navigate(Route::Game(game)),
+                Err(()) => {
+                    toast.set("Only the moderators of this game can start a rematch.".into());
+                }
+            }
+        });
+    };
+    view!(
+        cx,
+        (if *over.get() {
+            view! { cx, div(id="rematch") { "This game is over. " button(on:click=rematch) { "Rematch" } } }
+        } else {
+            view!(cx,)
+        })
+    )
+}
+/// A banner while the game is paused, see `MoveLineType::Pause`.
+#[component(inline_props)]
+fn PausedBanner<'a, G: Html>(
+    cx: Scope<'a>,
+    game: &'a ReadSignal<(Game, Vec<MoveLine>)>,
+) -> View<G> {
+    view!(
+        cx,
+        (match game.get().0.paused {
+            Some(since) => {
+                #[allow(clippy::cast_precision_loss)]
+                let since: String = js_sys::Date::new(&JsValue::from_f64(since as f64 * 1000.0))
+                    .to_locale_string("default", &JsValue::UNDEFINED)
+                    .into();
+                view! { cx,
+                    div(id="paused") {
+                        "This game is paused since " (since.clone())
+                        ". No votes are handled and only polls are open until it is resumed."
+                    }
+                }
+            }
+            None => view! { cx, },
+        })
+    )
+}
+/// The mutator of this round and the ones before it.
+#[component(inline_props)]
+fn RoundEvents<'a, G: Html>(cx: Scope<'a>, game: &'a ReadSignal<(Game, Vec<MoveLine>)>) -> View<G> {
+    let history = View::new_fragment(
+        game.get()
+            .0
+            .mutator_events
+            .iter()
+            .rev()
+            .map(
+                |&(round, mutator)| view! { cx, li { "Round " (round) ": " (mutator.describe()) } },
+            )
+            .collect(),
+    );
+    view!(cx,
+        p { (game.get().0.mutator.map_or("No mutator this round.", Mutator::describe)) }
+        ul(id="round-events") { (history) }
+    )
+}
+/// Every round that ended, newest first, each folded into a summary line.
+#[component(inline_props)]
+fn RoundTimeline<'a, G: Html>(
+    cx: Scope<'a>,
+    game: &'a ReadSignal<(Game, Vec<MoveLine>)>,
+    tally: &'a Signal<Option<u32>>,
+) -> View<G> {
+    let rounds = game.map(cx, |game| {
+        game.0.rounds.iter().rev().cloned().collect::<Vec<_>>()
+    });
+    view!(cx,
+        div(id="round-timeline") {
             Keyed(
-                iterable=count,
-                view=move |cx, i| view! { cx,
+                iterable=rounds,
+                view=move |cx, round| {
+                    let number = round.round;
+                    let votes = round
+                        .votes
+                        .iter()
+                        .map(|v| format!("{} for {}", v.voter, v.target))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let list = |players: &[i32]| {
+                        players.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+                    };
+                    let standings = View::new_fragment(
+                        round
+                            .standings
+                            .iter()
+                            .map(|s| {
+                                let text = format!(
+                                    "Player {}: {}, {} health, {} points, level {}",
+                                    s.user,
+                                    if s.alive { "alive" } else { "dead" },
+                                    s.health,
+                                    s.points,
+                                    s.level
+                                );
+                                view! { cx, li { (text) } }
+                            })
+                            .collect(),
+                    );
+                    let summary = format!(
+                        "Round {}: {} votes, {} killed",
+                        round.round,
+                        round.votes.len(),
+                        round.kills.len()
+                    );
+                    let details = [
+                        format!("Votes: {}", if votes.is_empty() { "none".into() } else { votes }),
+                        format!("Vote point for: {}", if round.elected.is_empty() { "nobody".into() } else { list(&round.elected) }),
+                        format!("Everyone got {} points.", round.points),
+                        format!("Killed: {}", if round.kills.is_empty() { "nobody".into() } else { list(&round.kills) }),
+                    ];
+                    let details = View::new_fragment(
+                        details.into_iter().map(|text| view! { cx, p { (text) } }).collect(),
+                    );
+                    view! { cx,
+                        details {
+                            summary { (summary) }
+                            (details)
+                            button(on:click=move |_| tally.set(Some(number))) {"Show tally"}
+                            ol { (standings) }
+                        }
+                    }
+                },
+                key=|round| round.round,
+            )
+        }
+    )
+}
+/// The tally of the votes of a round, opened when the votes are handled or from `RoundTimeline`. Shows who voted for
+/// whom and why a tank did or didn't get the point.
+#[component(inline_props)]
+fn VoteReport<'a, G: Html>(
+    cx: Scope<'a>,
+    game: &'a ReadSignal<(Game, Vec<MoveLine>)>,
+    round: &'a Signal<Option<u32>>,
+) -> View<G> {
+    let id = game.get_untracked().0.id;
+    let seen = create_signal(cx, game.get_untracked().0.round);
+    create_effect(cx, move || {
+        let current = game.get().0.round;
+        if current > *seen.get_untracked() {
+            seen.set(current);
+            round.set(Some(current - 1));
+        }
+    });
+    let tally = create_signal(cx, None::<VoteTally>);
+    create_effect(cx, move || {
+        let Some(number) = *round.get() else {
+            tally.set(None);
+            return;
+        };
+        spawn_local_scoped(cx, async move {
+            tally.set(get_votes(id, number).await.ok());
+        });
+    });
+    view!(
+        cx,
+        (if let Some(tally) = tally.get().as_ref().clone() {
+            let empty = tally.candidates.is_empty();
+            let candidates = View::new_fragment(
+                tally
+                    .candidates
+                    .into_iter()
+                    .map(|c| {
+                        let voters = c
+                            .voters
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        let outcome = match (c.reached, c.elected) {
+                            (_, true) => "got the point",
+                            (true, false) => "reached the threshold, but was dead",
+                            (false, _) => "fell short of the threshold",
+                        };
+                        let text = format!(
+                            "Player {}: {} votes from {voters}, {outcome}.",
+                            c.target, c.weight
+                        );
+                        view! { cx, li { (text) } }
+                    })
+                    .collect(),
+            );
+            view!(cx,
+                div(id="votes") {
+                    b { (format!("Votes of round {}", tally.round)) }
+                    p { (format!("A point needed {} votes, from a jury of {}.", tally.threshold, tally.jury)) }
+                    (if empty {
+                        view!(cx, p { "Nobody got votes." })
+                    } else {
+                        view!(cx, ul { (candidates) })
+                    })
+                    button(on:click=move |_| round.set(None)) {"Close"}
+                }
+            )
+        } else {
+            view!(cx,)
+        })
+    )
+}
+/// Lists the key fingerprint of every player, so they can be compared with what the players say outside of the game.
+/// Verified fingerprints are remembered, a player whose key changed since is flagged.
+#[component(inline_props)]
+fn VerifyPlayers<'a, G: Html>(
+    cx: Scope<'a>,
+    game: &'a ReadSignal<(Game, Vec<MoveLine>)>,
+) -> View<G> {
+    let storage = use_context::<Signal<Storage>>(cx);
+    let online = try_use_context::<RcSignal<HashSet<i32>>>(cx).cloned();
+    let shown = create_signal(cx, false);
+    view!(cx,
+        button(on:click=move |_| shown.set(!*shown.get())) {
+            (if *shown.get() { "Hide keys" } else { "Verify players" })
+        }
+        (if *shown.get() {
+            let mut fingerprints = game.get().0.fingerprints.clone().into_iter().collect::<Vec<_>>();
+            fingerprints.sort();
+            let rows = View::new_fragment(
+                fingerprints.into_iter()
+                    .map(|(user, fingerprint)| {
+                        let item = format!("verified_{user}");
+                        let status = match storage.get().get_item(&item).unwrap() {
+                            Some(verified) if verified == fingerprint => view!(cx, " verified"),
+                            Some(_) => view!(cx, b { " key changed since you verified it!" }),
+                            None => {
+                                let fingerprint = fingerprint.clone();
+                                view!(cx, button(on:click=move |_| {
+                                    storage.get().set_item(&item, &fingerprint).unwrap();
+                                    storage.trigger_subscribers();
+                                }) { "Mark verified" })
+                            }
+                        };
+                        let dot = match &online {
+                            Some(online) if online.get().contains(&user) => "presence online",
+                            Some(_) => "presence offline",
+                            None => "presence",
+                        };
+                        view! { cx, li {
+                            span(class=dot)
+                            a(href=Route::Player(user).to_string()) { (format!("Player {user}")) }
+                            (format!(": {fingerprint}"))
+                            (status)
+                        } }
+                    })
+                    .collect(),
+            );
+            view! { cx, ul(id="fingerprints") { (rows) } }
+        } else {
+            view!(cx,)
+        })
+    )
+}
+/// Signs and sends `line` in `game`.
+/// The last problem with a move, shown until the player dismisses it.
+#[derive(Clone)]
+struct Toast(RcSignal<String>);
+/// Sends `line` and shows it on the board right away. Once the server accepted it the game is reloaded to pick up the
+/// moves of others; when the server refused it the move is rolled back and the reason shown in the `Toast`. In a
+/// `Sandbox` the move is played locally instead.
+fn send_line(cx: Scope<'_>, game: i32, mut line: MoveLine) {
+    if let Some(sandbox) = try_use_context::<Sandbox>(cx) {
+        let state = GameContext::get(cx).state.clone();
+        let mut next = state.get().as_ref().clone();
+        match sandbox.play(&mut next, line) {
+            Ok(()) => state.set(next),
+            Err(e) => use_context::<Toast>(cx).0.set(e.to_string()),
+        }
+        return;
+    }
+    let storage = use_context::<Signal<Storage>>(cx).get().as_ref().clone();
+    let state = GameContext::get(cx).state.clone();
+    let toast = use_context::<Toast>(cx).0.clone();
+    let private_key = storage.get_item("private_key").unwrap().unwrap();
+    line.rules_version.get_or_insert(RULES_VERSION);
+    let before = state.get();
+    let mut optimistic = before.as_ref().clone();
+    if let Err(e) = optimistic.0.apply(line.clone()) {
+        toast.set(e.to_string());
+        return;
+    }
+    optimistic.1.push(line.clone());
+    state.set(optimistic);
+    // Not scoped, the menu that sent the move may be gone before the server answers.
+    spawn_local(async move {
+        let refused = match send_move(private_key, game, line).await {
+            Ok(()) => None,
+            Err(Error::Status {
+                status: 409,
+                body: user,
+            }) => {
+                // Someone else got to the tile first, the board is out of date.
+                state.set(before.as_ref().clone());
+                toast.set(format!(
+                    "Player {user} got to that tile first, the board has been refreshed."
+                ));
+                None
+            }
+            Err(Error::Status { body, .. }) => Some(body),
+            Err(e) => Some(e.to_string()),
+        };
+        if let Some(reason) = refused {
+            state.set(before.as_ref().clone());
+            toast.set(reason);
+            return;
+        }
+        refresh(&storage, game, &state, &toast).await;
+    });
+}
+/// Loads the new moves of `game`, or the whole game when they don't continue the current state. Keeps the current
+/// state if the server changed its history.
+async fn refresh(
+    storage: &Storage,
+    game: i32,
+    state: &RcSignal<(Game, Vec<MoveLine>)>,
+    toast: &RcSignal<String>,
+) {
+    let fresh = match update_game(game, &state.get()).await {
+        Ok(fresh) => Ok(fresh),
+        Err(()) => reload_game(game).await,
+    };
+    let Ok(fresh) = fresh else {
+        return;
+    };
+    if verify_chain(storage, game, &fresh.1).is_ok() {
+        accept_chain(storage, game, &fresh.1);
+        state.set(fresh);
+    } else {
+        toast.set("The server changed the history of this game, reload to check it.".into());
+    }
+}
+/// Follows the moves of `game` as the server appends them. Moves that continue the chain are applied right away,
+/// anything else, like a move that replaced one of ours, makes the game load again.
+fn follow(cx: Scope<'_>, game: i32) {
+    let storage = use_context::<Signal<Storage>>(cx).get().as_ref().clone();
+    let state = GameContext::get(cx).state.clone();
+    let toast = use_context::<Toast>(cx).0.clone();
+    sync::connect(game, None, move |frame| {
+        let mut current = state.get().as_ref().clone();
+        match frame {
+            SyncFrame::Move { index, line } if index == current.1.len() => {
+                if current.0.apply(line.clone()).is_ok() {
+                    current.0.drain_events();
+                    current.1.push(line);
+                    state.set(current);
+                    return;
+                }
+            }
+            SyncFrame::Move { index, line } => {
+                // Our own moves are shown before they are signed.
+                let ours = current.1.get(index).is_some_and(|known| {
+                    *known == line
+                        || known.signature.is_empty()
+                            && *known
+                                == MoveLine {
+                                    signature: String::new(),
+                                    ..line.clone()
+                                }
+                });
+                if ours {
+                    current.1[index] = line;
+                    state.set(current);
+                    return;
+                }
+            }
+            SyncFrame::State { moves } if moves == current.1 => return,
+            SyncFrame::State { .. } => {}
+            // Only sent for a viewport.
+            SyncFrame::View { .. } | SyncFrame::Update { .. } | SyncFrame::Minimap(_) => return,
+        }
+        let (storage, state, toast) = (storage.clone(), state.clone(), toast.clone());
+        spawn_local(async move { refresh(&storage, game, &state, &toast).await });
+    });
+}
+#[component]
+fn ToastView<G: Html>(cx: Scope) -> View<G> {
+    let toast = create_ref(cx, use_context::<Toast>(cx).0.clone());
+    view!(
+        cx,
+        (if toast.get().is_empty() {
+            view!(cx,)
+        } else {
+            view!(cx, div(class="toast") {
+                (toast.get())
+                button(on:click=move |_| toast.set(String::new())) { "Dismiss" }
+            })
+        })
+    )
+}
+/// A move of `user` without any arguments.
+fn empty_line(user: i32, move_type: MoveLineType) -> MoveLine {
+    MoveLine {
+        move_type,
+        x: None,
+        y: None,
+        target: None,
+        amount: None,
+        text: None,
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: None,
+        authorizer: user,
+        signature: String::new(),
+    }
+}
+/// The items of `user`, with a button to use each and to buy the items of the game.
+#[component(inline_props)]
+fn Inventory<'a, G: Html>(
+    cx: Scope<'a>,
+    game: &'a ReadSignal<(Game, Vec<MoveLine>)>,
+    user: i32,
+) -> View<G> {
+    let id = game.get().0.id;
+    let send = move |move_type: MoveLineType, item: Item| {
+        let mut line = empty_line(user, move_type);
+        line.text = Some(item.to_string());
+        send_line(cx, id, line);
+    };
+    let context = GameContext::get(cx);
+    let inventory = create_memo(cx, move || {
+        let players = context.players.get();
+        let Some(player) = players.get(&user).filter(|p| p.is_alive(true).is_ok()) else {
+            return Vec::new();
+        };
+        let active = player.effects.clone();
+        let mut items = player
+            .items
+            .iter()
+            .map(|item| (*item, active.contains(item)))
+            .collect::<Vec<_>>();
+        items.extend(active.into_iter().map(|item| (item, true)));
+        items
+    });
+    let shop = create_memo(cx, move || {
+        let alive = context
+            .players
+            .get()
+            .get(&user)
+            .is_some_and(|p| p.is_alive(true).is_ok());
+        if alive {
+            context.settings.get().items.clone()
+        } else {
+            Vec::new()
+        }
+    });
+    let cost = create_selector(cx, || context.settings.get().costs.item);
+    view!(cx,
+        div(id="inventory") {
+            Indexed(
+                iterable=inventory,
+                view=move |cx, (item, active)| view! { cx,
+                    div(class="item", title=item.describe()) {
+                        (item.to_string())
+                        (if active {
+                            view!(cx, i { " (active)" })
+                        } else {
+                            view!(cx, button(on:click=move |_| send(MoveLineType::UseItem, item)) {"Use"})
+                        })
+                    }
+                },
+            )
+            Indexed(
+                iterable=shop,
+                view=move |cx, item| view! { cx,
+                    button(title=item.describe(), on:click=move |_| send(MoveLineType::BuyItem, item)) {
+                        (format!("Buy {item} ({} points)", cost.get()))
+                    }
+                },
+            )
+        }
+    )
+}
+#[component(inline_props)]
+fn Polls<'a, G: Html>(
+    cx: Scope<'a>,
+    game: &'a ReadSignal<(Game, Vec<MoveLine>)>,
+    user: i32,
+) -> View<G> {
+    let id = game.get().0.id;
+    let alive = game
+        .get()
+        .0
+        .players
+        .get(&user)
+        .is_some_and(|p| p.is_alive(true).is_ok());
+    let polls = game.map(cx, |x| {
+        x.0.polls.iter().cloned().enumerate().collect::<Vec<_>>()
+    });
+    let hidden = create_signal(cx, Vec::<i32>::new());
+    spawn_local_scoped(cx, async move {
+        if let Ok(polls) = get_hidden_polls(id).await {
+            hidden.set(polls);
+        }
+    });
+    let question = create_signal(cx, String::new());
+    let options = create_signal(cx, String::new());
+    let create = move |_| {
+        let mut line = empty_line(user, MoveLineType::Poll);
+        line.text = Some(format!("{}\n{}", question.get(), options.get()));
+        send_line(cx, id, line);
+    };
+    view!(cx,
+        div(id="polls") {
+            Keyed(
+                iterable=polls,
+                view=move |cx, (index, poll)| {
+                    let target = i32::try_from(index).ok();
+                    let choices = View::new_fragment(
+                        poll.options
+                            .iter()
+                            .cloned()
+                            .zip(poll.tally())
+                            .zip(0..)
+                            .map(|((option, votes), option_index)| {
+                                let vote = move |_| {
+                                    let mut line = empty_line(user, MoveLineType::PollVote);
+                                    line.target = target;
+                                    line.amount = Some(option_index);
+                                    send_line(cx, id, line);
+                                };
+                                let chosen = poll.votes.get(&user) == Some(&option_index);
+                                let can_vote = alive && poll.open;
+                                view! { cx,
+                                    div(class="poll-option") {
+                                        (format!("{option}: {votes}{}", if chosen { " (your vote)" } else { "" }))
+                                        (if can_vote { view!(cx, button(on:click=vote) {"Vote"}) } else { view!(cx,) })
+                                    }
+                                }
+                            })
+                            .collect(),
+                    );
+                    let close = move |_| {
+                        let mut line = empty_line(user, MoveLineType::ClosePoll);
+                        line.target = target;
+                        send_line(cx, id, line);
+                    };
+                    let closable = alive && poll.open && poll.creator == user;
+                    let title = format!("{} ({})", poll.question, if poll.open { "open" } else { "closed" });
+                    let shown = view! { cx, b { (title) } (choices) };
+                    view! { cx,
+                        div(class="poll") {
+                            (if target.is_some_and(|target| hidden.get().contains(&target)) {
+                                view!(cx, i { "Removed by a moderator." })
+                            } else {
+                                shown.clone()
+                            })
+                            (if closable { view!(cx, button(on:click=close) {"Close poll"}) } else { view!(cx,) })
+                        }
+                    }
+                },
+                key=|x| (x.0, x.1.open, x.1.votes.len(), x.1.tally()),
+            )
+            (if alive { view!(cx,
+                input(placeholder="Question", bind:value=question)
+                textarea(placeholder="One option per line", bind:value=options)
+                button(on:click=create) {"Create poll"}
+            )} else { view!(cx,) })
+        }
+    )
+}
+#[derive(Prop)]
+struct WorldProps<'a> {
+    user: i32,
+    game: &'a ReadSignal<(Game, Vec<MoveLine>)>,
+}
+/// The classes of the tile at `pos`, tanks are marked online or offline when presence is known.
+fn tile_class(
+    pos: (u32, u32),
+    user: i32,
+    board: &HashMap<(u32, u32), i32>,
+    bounties: &HashMap<(u32, u32), u32>,
+    path: &[Step],
+    reach: &HashMap<(u32, u32), &str>,
+    online: Option<&HashSet<i32>>,
+) -> String {
+    let mut class = String::from("tile");
+    if let Some(p) = board.get(&pos) {
+        class.push_str(if p == &user { " user" } else { " player" });
+        if let Some(online) = online {
+            class.push_str(if online.contains(p) {
+                " online"
+            } else {
+                " offline"
+            });
+        }
+    } else if bounties.contains_key(&pos) {
+        class.push_str(" bounty");
+    }
+    if path.iter().any(|s| (s.x, s.y) == pos) {
+        class.push_str(" path");
+    }
+    if let Some(r) = reach.get(&pos) {
+        class.push(' ');
+        class.push_str(r);
+    }
+    class
+}
+/// Above this many health or levels, a tank shows a number instead of pips or chevrons.
+const MAX_PIPS: u32 = 5;
+/// The profile color of `user`, spread around the color wheel so neighbouring ids differ.
+fn user_hue(user: i32) -> u32 {
+    user.unsigned_abs().wrapping_mul(137) % 360
+}
+/// Draws the tank of `user` on its tile: its id in its profile color, health pips, level chevrons and a skull once it
+/// died.
+fn tank_view<G: Html>(cx: Scope<'_>, game: &Game, user: i32) -> View<G> {
+    let Some(player) = game.players.get(&user) else {
+        return view! { cx, (user.to_string()) };
+    };
+    let health = player.health.get();
+    let max = game.settings.health.max(health);
+    let pips = if max > MAX_PIPS {
+        format!("{health}/{max}")
+    } else {
+        "\u{25cf}".repeat(health as usize) + &"\u{25cb}".repeat((max - health) as usize)
+    };
+    let level = if player.level > MAX_PIPS {
+        format!("\u{25b2}{}", player.level)
+    } else {
+        "\u{25b2}".repeat(player.level as usize)
+    };
+    let dead = player.died_round.is_some();
+    let class = if dead { "tank dead" } else { "tank" };
+    let style = format!("border-color:hsl({},70%,50%)", user_hue(user));
+    view! { cx,
+        div(class=class, style=style) {
+            span(class="initials") { (user.to_string()) }
+            span(class="pips") { (pips) }
+            span(class="level") { (level) }
+            (if dead { view! { cx, span(class="skull") { "\u{2620}" } } } else { view! { cx, } })
+        }
+    }
+}
+/// The tank on the tile at `pos`, or the bounty or pickup lying there.
+fn tile_view<G: Html>(
+    cx: Scope<'_>,
+    game: &Game,
+    board: &HashMap<(u32, u32), i32>,
+    bounties: &HashMap<(u32, u32), u32>,
+    pos: (u32, u32),
+) -> View<G> {
+    if let Some(id) = board.get(&pos) {
+        return tank_view(cx, game, *id);
+    }
+    let text = bounties
+        .get(&pos)
+        .map(|b| format!("+{b}"))
+        .or_else(|| game.pickups.contains_key(&pos).then(|| "?".into()))
+        .unwrap_or_default();
+    view! { cx, (text) }
+}
+/// How far, in pixels, the pointer has to move over the board before it pans instead of clicking a tile.
+const DRAG_THRESHOLD: i32 = 5;
+#[derive(Prop)]
+struct BoardProps<'a, G: Html> {
+    width: &'a ReadSignal<u32>,
+    height: &'a ReadSignal<u32>,
+    /// Set while the board is being panned, so the tiles can ignore the click that ends the drag.
+    dragged: &'a Signal<bool>,
+    children: Children<'a, G>,
+}
+/// The tiles of a board with row and column labels, a zoom slider and drag-to-pan.
+#[component]
+fn Board<'a, G: Html>(
+    cx: Scope<'a>,
+    BoardProps {
+        width,
+        height,
+        dragged,
+        children,
+    }: BoardProps<'a, G>,
+) -> View<G> {
+    let zoom = create_signal(cx, String::from("100"));
+    let pan = create_signal(cx, (0, 0));
+    // The last pointer position while the board is held.
+    let held = create_signal(cx, None::<(i32, i32)>);
+    let tiles = children.call(cx);
+    view!(cx,
+        label {
+            "Zoom "
+            input(type="range", min="25", max="200", step="25", bind:value=zoom)
+        }
+        button(on:click=move |_| {
+            zoom.set("100".into());
+            pan.set((0, 0));
+        }) {"Reset view"}
+        div(id="viewport", on:pointerdown=move |e: web_sys::Event| {
+            let e = e.unchecked_into::<PointerEvent>();
+            held.set(Some((e.client_x(), e.client_y())));
+            dragged.set(false);
+        }, on:pointermove=move |e: web_sys::Event| {
+            let e = e.unchecked_into::<PointerEvent>();
+            let Some((last_x, last_y)) = *held.get() else {
+                return;
+            };
+            let (dx, dy) = (e.client_x() - last_x, e.client_y() - last_y);
+            if *dragged.get() || dx.abs() + dy.abs() > DRAG_THRESHOLD {
+                dragged.set(true);
+                let (pan_x, pan_y) = *pan.get();
+                pan.set((pan_x + dx, pan_y + dy));
+                held.set(Some((e.client_x(), e.client_y())));
+            }
+        }, on:pointerup=move |_| held.set(None), on:pointerleave=move |_| held.set(None)) {
+            div(id="world", style={format!("width:{}px;height:{}px;transform:translate({}px,{}px) scale({})", *width.get() * 50, *height.get() * 50, pan.get().0, pan.get().1, zoom.get().parse::<f64>().unwrap_or(100.0) / 100.0)}) {
+                (View::new_fragment((0..*width.get()).map(|column| view! { cx,
+                    div(class="label", style={format!("left:{}px;top:-25px", column * 50)}) { (column) }
+                }).collect()))
+                (View::new_fragment((0..*height.get()).map(|row| view! { cx,
+                    div(class="label", style={format!("left:-50px;top:{}px", row * 50)}) { (row) }
+                }).collect()))
+                (tiles.clone())
+            }
+        }
+    )
+}
+#[component]
+fn World<'a, G: Html>(cx: Scope<'a>, WorldProps { game, user }: WorldProps<'a>) -> View<G> {
+    let x = create_signal(cx, 0);
+    let y = create_signal(cx, 0);
+    let target = create_signal(cx, 0);
+    let shoot = create_signal(cx, false);
+    let drive = create_signal(cx, false);
+    let vote = create_signal(cx, false);
+    let bounty = create_signal(cx, false);
+    let repair = create_signal(cx, false);
+    let extend = create_signal(cx, false);
+    let path = create_signal(cx, Vec::<Step>::new());
+    let ghost = create_signal(cx, false);
+    let threats = create_signal(cx, false);
+    let online = try_use_context::<RcSignal<HashSet<i32>>>(cx);
+    let dragged = create_signal(cx, false);
+    // Whether the path was suggested for the selected tile, rather than made by hand.
+    let suggested = create_signal(cx, false);
+
+    let context = GameContext::get(cx);
+    let width = create_selector(cx, || context.settings.get().width);
+    let height = create_selector(cx, || context.settings.get().height);
+    let board = &*context.board;
+    let bounties = game.map(cx, |game| game.0.bounties.clone());
+    let tiles = context.tiles(cx);
+    let reach = create_memo(cx, move || {
+        if *ghost.get() {
+            enemy_reach(&game.get().0, *target.get(), user)
+        } else if *threats.get() {
+            game.get()
+                .0
+                .threat_map(user)
+                .into_keys()
+                .map(|pos| (pos, "threatened"))
+                .collect()
+        } else {
+            HashMap::new()
+        }
+    });
+    view!(cx,
+        TokenList(game=game)
+        Polls(game=game, user=user)
+        Inventory(game=game, user=user)
+        button(on:click=move |_| ghost.set(!*ghost.get())) {
+            (if *ghost.get() { "Hide enemy reach" } else { "Show enemy reach" })
+        }
+        button(on:click=move |_| threats.set(!*threats.get())) {
+            (if *threats.get() { "Hide threats" } else { "Show threats" })
+        }
+        (if game.get().0.players.get(&user).is_some_and(|p| p.is_alive(true).is_ok()) {
+            view!(cx, button(on:click=move |_| {
+                if window().unwrap().confirm_with_message("Surrender? Your tank is destroyed and you join the jury.").unwrap() {
+                    send_line(cx, game.get().0.id, empty_line(user, MoveLineType::Surrender));
+                }
+            }) {"Surrender"})
+        } else {
+            view!(cx,)
+        })
+        Board(width=width, height=height, dragged=dragged) {
+            Keyed(
+                iterable=tiles,
+                view=move |cx, pos| view! { cx,
                     div(on:click=move |_| {
-                        x.set(i % width);
-                        y.set(i / width);
+                        if *dragged.get() {
+                            return;
+                        }
+                        x.set(pos.0);
+                        y.set(pos.1);
 
                         let game = game.get();
                         let player = game.0.players.get(&user).unwrap();
-                        let is_tank = game.0.board.get(&(i % width, i / width));
+                        let is_tank = game.0.board.get(&pos);
                         if let Some(id) = is_tank {
                             target.set(*id);
                         }
                         let target = is_tank.map(|id| game.0.players.get(id).unwrap());
-                        let target_alive = target.map_or(false, |x| x.is_alive(true).is_ok());
+                        let target_alive = target.is_some_and(|x| x.is_alive(true).is_ok());
 
-                        shoot.set(player.is_alive(true).is_ok() && target_alive && player.in_range(i % width, i / width, game.0.settings.range.get_range(player.level)).is_ok());
-                        drive.set(player.is_alive(true).is_ok() && is_tank.is_none() && player.in_range(i % width, i / width, 1).is_ok());
+                        let hidden = target.is_some_and(|x| x.effects.contains(&Item::Smoke));
+                        shoot.set(player.is_alive(true).is_ok() && target_alive && !hidden && player.in_range(pos.0, pos.1, game.0.player_range(player)).is_ok());
+                        let (can_drive, can_extend) = select_drive(&game.0, user, path, suggested, pos);
+                        drive.set(can_drive);
+                        extend.set(can_extend);
                         vote.set(player.is_alive(false).is_ok() && target_alive);
+                        repair.set(player.is_alive(true).is_ok() && target.is_some_and(|x| x.disabled_round.is_some()) && player.in_range(pos.0, pos.1, game.0.player_range(player)).is_ok());
+                        bounty.set(player.is_alive(true).is_ok() && is_tank.is_none() && player.has_points().is_ok());
                     }, class={
-                        if let Some(p) = board.get().get(&(i % width, i / width)) {
-                            if p == &user {
-                                "tile user"
-                            } else {
-                                "tile player"
-                            }
-                        } else {
-                            "tile"
-                        }
-                    }, style={format!("left:{}px;top:{}px", (i % width) * 50, (i / width) * 50)}) {
-                        ({
-                            board.get().get(&(i % width, i / width)).map_or(String::default(), std::string::ToString::to_string)
-                        })
+                        let online = online.as_ref().map(|o| o.get());
+                        tile_class(pos, user, &board.get(), &bounties.get(), &path.get(), &reach.get(), online.as_deref())
+                    }, title={
+                        board.get().get(&pos).map_or(String::new(), |id| tank_info(&game.get().0, *id))
+                    }, style={format!("left:{}px;top:{}px", pos.0 * 50, pos.1 * 50)}) {
+                        (tile_view(cx, &game.get().0, &board.get(), &bounties.get(), pos))
                     }
                 },
-                key=|x| *x,
+                key=|pos| *pos,
             )
-            ContextMenu(shoot=shoot, vote=vote, drive=drive, x=x, y=y, target=target, user=user, game=game.get().0.id)
+            ContextMenu(shoot=shoot, repair=repair, vote=vote, drive=drive, extend=extend, path=path, bounty=bounty, x=x, y=y, target=target, user=user, game=game.get().0.id)
         }
     )
 }
 #[derive(Prop)]
 struct ContextMenuProps<'a> {
     shoot: &'a Signal<bool>,
+    repair: &'a Signal<bool>,
     vote: &'a Signal<bool>,
     drive: &'a Signal<bool>,
+    /// Whether the selected tile can be added to the path instead of ending the drive there.
+    extend: &'a Signal<bool>,
+    /// The tiles the next drive passes before the selected tile.
+    path: &'a Signal<Vec<Step>>,
+    bounty: &'a Signal<bool>,
     x: &'a Signal<u32>,
     y: &'a Signal<u32>,
     target: &'a Signal<i32>,
@@ -121,8 +1184,12 @@ fn ContextMenu<'a, G: Html>(
     cx: Scope<'a>,
     ContextMenuProps {
         shoot,
+        repair,
         vote,
         drive,
+        extend,
+        path,
+        bounty,
         user,
         game,
         x,
@@ -130,77 +1197,525 @@ fn ContextMenu<'a, G: Html>(
         target,
     }: ContextMenuProps<'a>,
 ) -> View<G> {
-    let storage = use_context::<Signal<Storage>>(cx);
     view!(cx,
         div(id="modal", style={format!("left:{}px;top:{}px",25+<u32 as TryInto<i32>>::try_into(*x.get()).unwrap()*50i32,<u32 as TryInto<i32>>::try_into(*y.get()).unwrap()*50i32-25)}) {
+            p(class="coordinates") { (format!("({}, {})", x.get(), y.get())) }
             (if *shoot.get() {view!(cx,
                 button(style="display:block", on:click=move |_| {
-                    let private_key = storage.get().get_item("private_key").unwrap().unwrap();
-                    let line = MoveLine {authorizer: user, move_type: tanktacticsgame::MoveLineType::Shoot, signature: String::new(), target: Some(*target.get()), x: None, y: None};
-                    spawn_local_scoped(cx, async move {send_move(private_key, game, line).await.unwrap();storage.trigger_subscribers();});
+                    let line = MoveLine {authorizer: user, move_type: tanktacticsgame::MoveLineType::Shoot, signature: String::new(), target: Some(*target.get()), amount: None, text: None, time: None, clock: None, path: None, rules_version: None, x: None, y: None};
+                    send_line(cx, game, line);
                 }) {"Shoot"}
+                button(style="display:block", on:click=move |_| {
+                    let line = MoveLine {authorizer: user, move_type: tanktacticsgame::MoveLineType::Lend, signature: String::new(), target: Some(*target.get()), amount: Some(1), text: None, time: None, clock: None, path: None, rules_version: None, x: Some(1), y: None};
+                    send_line(cx, game, line);
+                }) {"Lend a point until the round ends"}
+            )} else {view!(cx,)})
+            (if *repair.get() {view!(cx,
+                button(style="display:block", on:click=move |_| {
+                    let line = MoveLine {authorizer: user, move_type: tanktacticsgame::MoveLineType::Repair, signature: String::new(), target: Some(*target.get()), amount: None, text: None, time: None, clock: None, path: None, rules_version: None, x: None, y: None};
+                    send_line(cx, game, line);
+                }) {"Repair"}
             )} else {view!(cx,)})
             (if *drive.get() {view!(cx,
                 button(style="display:block", on:click=move |_| {
-                    let private_key = storage.get().get_item("private_key").unwrap().unwrap();
-                    let line = MoveLine {authorizer: user, move_type: tanktacticsgame::MoveLineType::Drive, signature: String::new(), target: None, x: Some(*x.get()), y: Some(*y.get())};
-                    spawn_local_scoped(cx, async move {send_move(private_key, game, line).await.unwrap();storage.trigger_subscribers();});
+                    let steps = path.get().as_ref().clone();
+                    let line = MoveLine {authorizer: user, move_type: tanktacticsgame::MoveLineType::Drive, signature: String::new(), target: None, amount: None, text: None, time: None, clock: None, path: (!steps.is_empty()).then_some(steps), rules_version: None, x: Some(*x.get()), y: Some(*y.get())};
+                    path.set(Vec::new());
+                    send_line(cx, game, line);
                 }) {"Move"}
             )} else {view!(cx,)})
+            (if *extend.get() {view!(cx,
+                button(style="display:block", on:click=move |_| {
+                    path.modify().push(Step { x: *x.get(), y: *y.get() });
+                    drive.set(false);
+                    extend.set(false);
+                }) {"Add to path"}
+            )} else {view!(cx,)})
+            (if path.get().is_empty() {view!(cx,)} else {view!(cx,
+                button(style="display:block", on:click=move |_| path.set(Vec::new())) {"Clear path"}
+            )})
             (if *vote.get() {view!(cx,
                 button(style="display:block", on:click=move |_| {
-                    let private_key = storage.get().get_item("private_key").unwrap().unwrap();
-                    let line = MoveLine {authorizer: user, move_type: tanktacticsgame::MoveLineType::Vote, signature: String::new(), target: Some(*target.get()), x: None, y: None};
-                    spawn_local_scoped(cx, async move {send_move(private_key, game, line).await.unwrap();storage.trigger_subscribers();});
+                    let line = MoveLine {authorizer: user, move_type: tanktacticsgame::MoveLineType::Vote, signature: String::new(), target: Some(*target.get()), amount: None, text: None, time: None, clock: None, path: None, rules_version: None, x: None, y: None};
+                    send_line(cx, game, line);
                 }) {"Vote"}
             )} else {view!(cx,)})
+            (if *bounty.get() {view!(cx,
+                button(style="display:block", on:click=move |_| {
+                    let line = MoveLine {authorizer: user, move_type: tanktacticsgame::MoveLineType::Bounty, signature: String::new(), target: None, amount: Some(1), text: None, time: None, clock: None, path: None, rules_version: None, x: Some(*x.get()), y: Some(*y.get())};
+                    send_line(cx, game, line);
+                }) {"Place bounty"}
+            )} else {view!(cx,)})
+        }
+    )
+}
+#[component]
+fn EmailForm<G: Html>(cx: Scope) -> View<G> {
+    let storage = use_context::<Signal<Storage>>(cx);
+    let address = create_signal(cx, String::new());
+    let code = create_signal(cx, String::new());
+    let shot = create_signal(cx, true);
+    let points = create_signal(cx, true);
+    let rounds = create_signal(cx, true);
+    let status = create_signal(cx, String::new());
+    let save = move |_| {
+        let private_key = storage.get().get_item("private_key").unwrap().unwrap();
+        let user = storage
+            .get()
+            .get_item("user")
+            .unwrap()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let preferences = (*shot.get(), *points.get(), *rounds.get());
+        spawn_local_scoped(cx, async move {
+            status.set(
+                match set_email(private_key, user, address.get().to_string(), preferences).await {
+                    Ok(true) => "Verification code sent.",
+                    Ok(false) => "Preferences saved.",
+                    Err(()) => "Could not save email.",
+                }
+                .into(),
+            );
+        });
+    };
+    let verify = move |_| {
+        let user = storage
+            .get()
+            .get_item("user")
+            .unwrap()
+            .unwrap()
+            .parse()
+            .unwrap();
+        spawn_local_scoped(cx, async move {
+            status.set(
+                match verify_email(user, &code.get()).await {
+                    Ok(()) => "Email verified.",
+                    Err(()) => "Wrong verification code.",
+                }
+                .into(),
+            );
+        });
+    };
+    view!(cx,
+        div(id="email") {
+            input(type="email", placeholder="Email", bind:value=address)
+            label { input(type="checkbox", bind:checked=shot) "Shot" }
+            label { input(type="checkbox", bind:checked=points) "Points" }
+            label { input(type="checkbox", bind:checked=rounds) "Rounds" }
+            button(on:click=save) {"Save email"}
+            input(placeholder="Code", bind:value=code)
+            button(on:click=verify) {"Verify"}
+            (status.get())
         }
     )
 }
+/// Saves `text` as a file named `name`.
+fn download(name: &str, text: &str) -> Result<(), JsValue> {
+    let document = window()
+        .ok_or(JsValue::NULL)?
+        .document()
+        .ok_or(JsValue::NULL)?;
+    let link = document.create_element("a")?;
+    link.set_attribute(
+        "href",
+        &format!(
+            "data:application/json;charset=utf-8,{}",
+            js_sys::encode_uri_component(text)
+        ),
+    )?;
+    link.set_attribute("download", name)?;
+    link.unchecked_into::<HtmlElement>().click();
+    Ok(())
+}
+/// Downloads everything the server keeps about the user, or asks it to forget what the games don't need.
 #[component]
-fn Hud<G: Html>(cx: Scope, height: u32) -> View<G> {
+fn MyData<G: Html>(cx: Scope) -> View<G> {
     let storage = use_context::<Signal<Storage>>(cx);
+    let status = create_signal(cx, String::new());
+    let account = move || {
+        let storage = storage.get();
+        let private_key = storage.get_item("private_key").ok()??;
+        let user = storage.get_item("user").ok()??.parse().ok()?;
+        Some((private_key, user))
+    };
+    let export = move |_| {
+        let Some((private_key, user)) = account() else {
+            return;
+        };
+        spawn_local_scoped(cx, async move {
+            let result = export_my_data(private_key, user).await;
+            status.set(
+                match result.map(|data| download(&format!("tanktactics-{user}.json"), &data)) {
+                    Ok(Ok(())) => String::new(),
+                    _ => "Could not export your data.".into(),
+                },
+            );
+        });
+    };
+    let delete = move |_| {
+        let Some((private_key, user)) = account() else {
+            return;
+        };
+        if !window().unwrap().confirm_with_message("Delete your email address, passkey and scheduled moves, and hide your polls? Your moves stay part of their games.").unwrap() {
+            return;
+        }
+        spawn_local_scoped(cx, async move {
+            status.set(
+                match delete_my_data(private_key, user).await {
+                    Ok(()) => "Your data was deleted.",
+                    Err(()) => "Could not delete your data.",
+                }
+                .into(),
+            );
+        });
+    };
+    view!(cx,
+        div(id="my-data") {
+            button(on:click=export) {"Export my data"}
+            button(on:click=delete) {"Delete my data"}
+            (status.get())
+        }
+    )
+}
+/// Seals the private key with a passphrase, or turns a sealed key back into a plain one. (see `keystore`)
+#[component]
+fn KeyProtection<G: Html>(cx: Scope) -> View<G> {
+    let storage = use_context::<Signal<Storage>>(cx);
+    let status = create_signal(cx, String::new());
+    let stored = create_memo(cx, || storage.get().get_item("private_key").ok().flatten());
+    let protect = move |_| {
+        spawn_local_scoped(cx, async move {
+            match keystore::protect(&storage.get()).await {
+                Ok(true) => status.set("Your key is protected by the passphrase.".into()),
+                Ok(false) => {}
+                Err(()) => status.set("Could not protect your key.".into()),
+            }
+            storage.trigger_subscribers();
+        });
+    };
+    let unprotect = move |_| {
+        spawn_local_scoped(cx, async move {
+            if keystore::unprotect(&storage.get()).await.is_err() {
+                status.set("Could not remove the passphrase.".into());
+            } else {
+                status.set(String::new());
+            }
+            storage.trigger_subscribers();
+        });
+    };
+    let lock = move |_| {
+        keystore::lock();
+        status.set("The passphrase will be asked for again.".into());
+    };
+    view!(cx,
+        div(id="key-protection") {
+            (match stored.get().as_deref() {
+                Some(key) if SealedKey::is_sealed(key) => view! { cx,
+                    button(on:click=lock) {"Lock key"}
+                    button(on:click=unprotect) {"Remove passphrase"}
+                },
+                Some(key) if !key.starts_with("passkey:") => view! { cx,
+                    button(on:click=protect) {"Protect key with a passphrase"}
+                },
+                _ => view! { cx, },
+            })
+            (status.get())
+        }
+    )
+}
+/// The account controls, with the points, health, level and range of the player next to what moves cost.
+#[component(inline_props)]
+fn Hud<'a, G: Html>(
+    cx: Scope<'a>,
+    game: &'a ReadSignal<(Game, Vec<MoveLine>)>,
+    user: i32,
+) -> View<G> {
+    let storage = use_context::<Signal<Storage>>(cx);
+    let settings = &GameContext::get(cx).settings;
+    let budget = create_memo(cx, move || {
+        let game = &game.get().0;
+        let Some(player) = game.players.get(&user) else {
+            return Vec::new();
+        };
+        let mut rows = vec![
+            ("Points".to_string(), player.points.to_string()),
+            ("Health".to_string(), player.health.to_string()),
+            ("Level".to_string(), player.level.to_string()),
+            ("Range".to_string(), game.player_range(player).to_string()),
+        ];
+        rows.extend(
+            game.settings
+                .costs
+                .describe()
+                .into_iter()
+                .map(|(action, cost)| (format!("{action} costs"), cost)),
+        );
+        rows
+    });
     let delete_keys = |_| {
         storage.get().remove_item("game").unwrap(); // JS function doesnt panic
         storage.get().remove_item("public_key").unwrap(); // JS function doesnt panic
         storage.get().remove_item("private_key").unwrap(); // JS function doesnt panic
         storage.trigger_subscribers();
+        navigate(Route::Games);
     };
     view!(cx,
-        div(id="hud",style={format!("height:{}px", height * 50)}) {
+        div(id="hud",style={format!("height:{}px", settings.get().height * 50)}) {
+            table(class="budget") {
+                Indexed(
+                    iterable=budget,
+                    view=|cx, (name, value)| view! { cx, tr { th { (name) } td { (value) } } },
+                )
+            }
+            a(href=Route::Games.to_string()) {"All games"}
+            " "
+            a(href=Route::Player(user).to_string()) {"My games"}
+            " "
+            a(href=Route::Leaderboard.to_string()) {"Leaderboard"}
+            " "
+            a(href=Route::Replay(game.get().0.id, game.get().1.len()).to_string()) {"Replay"}
+            br {}
             button(on:click=delete_keys) {"Delete Account from device."}
+            EmailForm()
+            KeyProtection()
+            MyData()
+        }
+    )
+}
+/// Summarizes the rules of `game` before joining it, highlighting where they differ from the Classic preset.
+#[component(inline_props)]
+fn RulesModal<'a, G: Html>(
+    cx: Scope<'a>,
+    game: DataBaseGame,
+    selected: &'a Signal<Option<DataBaseGame>>,
+    message: &'a Signal<String>,
+) -> View<G> {
+    let id = game.id;
+    let supported = RULES_VERSIONS.contains(&game.rules_version);
+    // Written by the creator in the language of the game.
+    let description = (!game.description.is_empty()).then(|| {
+        let (locale, text) = (game.locale.clone(), game.description.clone());
+        view! { cx, p(class="description", lang=locale) { (text) } }
+    });
+    let rules = View::new_fragment(
+        game.settings()
+            .map(|x| x.describe())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|rule| {
+                let class = if rule.deviates {
+                    "rule deviates"
+                } else {
+                    "rule"
+                };
+                view! { cx, li(class=class) { (rule.text) } }
+            })
+            .collect(),
+    );
+    let join = move |_| {
+        selected.set(None);
+        spawn_local_scoped(cx, async move {
+            match join_game(cx, id).await {
+                Ok(()) => use_context::<Signal<Storage>>(cx).trigger_subscribers(),
+                Err(e) => message.set(e.to_string()),
+            }
+        });
+    };
+    view!(cx,
+        div(id="rules") {
+            b { (format!("Rules of game {id}")) }
+            (description.clone().unwrap_or_default())
+            ul { (rules) }
+            "Highlighted rules differ from the Classic preset."
+            br {}
+            (if supported {
+                view!(cx, button(on:click=join) {"Join"})
+            } else {
+                view!(cx, p { "This game uses rules this client can't play, update the client to join." })
+            })
+            button(on:click=move |_| selected.set(None)) {"Cancel"}
+        }
+    )
+}
+/// The average rating of the players of every game, and the rating of the player if they have an account.
+async fn ratings(storage: &Storage) -> (HashMap<i32, i32>, Option<i32>) {
+    let ratings = get_game_ratings()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|r| (r.game, r.rating))
+        .collect();
+    let user = storage
+        .get_item("user")
+        .ok()
+        .flatten()
+        .and_then(|user| user.parse().ok());
+    let mine = match user {
+        Some(user) => get_rating(user).await.ok().map(|r| r.rating),
+        None => None,
+    };
+    (ratings, mine)
+}
+/// How often the queue status is checked while the player waits for a match, in milliseconds.
+const QUEUE_REFRESH: i32 = 5000;
+/// Queues the player for a match of a preset, and opens the game once the match is made. (see `/queue`)
+#[component]
+fn Matchmaking<G: Html>(cx: Scope) -> View<G> {
+    let storage = use_context::<Signal<Storage>>(cx);
+    let status = create_signal(cx, None::<QueueStatus>);
+    let message = create_signal(cx, String::new());
+    let account = move || {
+        let storage = storage.get();
+        let private_key = storage.get_item("private_key").ok()??;
+        let user = storage.get_item("user").ok()??.parse().ok()?;
+        Some((private_key, user))
+    };
+    // Polls until the player is matched or leaves the queue.
+    let poll = move |user: i32| {
+        spawn_local_scoped(cx, async move {
+            loop {
+                let Ok(current) = get_queue_status(user).await else {
+                    break;
+                };
+                let waiting = current.preset.is_some() && current.game.is_none();
+                if let Some(game) = current.game {
+                    storage.get().set_item("game", &game.to_string()).unwrap();
+                    navigate(Route::Game(game));
+                }
+                status.set(Some(current));
+                if !waiting || sleep(QUEUE_REFRESH).await.is_err() {
+                    break;
+                }
+            }
+        });
+    };
+    if let Some((_, user)) = account() {
+        poll(user);
+    }
+    let queue = move |preset: Preset| {
+        let Some((private_key, user)) = account() else {
+            message.set("Make an account to queue for a match.".into());
+            return;
+        };
+        spawn_local_scoped(cx, async move {
+            if join_queue(private_key, user, preset.name()).await.is_ok() {
+                message.set(String::new());
+                poll(user);
+            } else {
+                message.set("Could not queue for a match.".into());
+            }
+        });
+    };
+    let leave = move |_| {
+        let Some((private_key, user)) = account() else {
+            return;
+        };
+        spawn_local_scoped(cx, async move {
+            if leave_queue(private_key, user).await.is_ok() {
+                status.set(None);
+            } else {
+                message.set("Could not leave the queue.".into());
+            }
+        });
+    };
+    view!(cx,
+        div(id="matchmaking") {
+            (match status.get().as_ref() {
+                Some(QueueStatus { preset: Some(preset), waiting, size, game: None }) => {
+                    let text = format!("Waiting for a {preset} match, {waiting} of {size} players.");
+                    view! { cx, (text) " " button(on:click=leave) {"Leave queue"} }
+                }
+                _ => View::new_fragment(
+                    Preset::ALL
+                        .into_iter()
+                        .map(|preset| view! { cx,
+                            button(on:click=move |_| queue(preset)) { (format!("Find a {} match", preset.name())) }
+                        })
+                        .collect(),
+                ),
+            })
+            " "
+            (message.get())
+        }
+    )
+}
+/// The heading of the columns of `GameList`.
+#[component]
+fn GameListHeader<G: Html>(cx: Scope) -> View<G> {
+    view!(cx,
+        tr {
+            th {"Join"}
+            th {"Id"}
+            th {"Seed"}
+            th {"Width"}
+            th {"Height"}
+            th {"Health"}
+            th {"Max Level"}
+            th {"Max Players"}
+            th {"Vote Threshold"}
+            th {"Range"}
+            th {"Last Vote"}
+            th {"Point Decay"}
+            th {"Jury Weight"}
+            th {"Loot Mode"}
+            th {"Drive Length"}
+            th {"Rules"}
+            th {"Mutators"}
+            th {"Threshold Mode"}
+            th {"Win Condition"}
+            th {"Starting Points"}
+            th {"Starting Level"}
+            th {"Actions per Round"}
+            th {"Items"}
+            th {"Two-phase Kills"}
+            th {"Symmetric Spawns"}
+            th {"Gift Cooldown"}
+            th {"Vote Interval"}
+            th {"Language"}
+            th {"Rating"}
         }
     )
 }
 #[component]
 async fn GameList<G: Html>(cx: Scope<'_>) -> View<G> {
+    let storage = use_context::<Signal<Storage>>(cx);
     let games = create_signal(cx, get_games().await.unwrap_or_default());
+    let message = create_signal(cx, String::new());
+    let selected = create_signal(cx, None);
+    let (ratings, mine) = ratings(&storage.get()).await;
+    let ratings = create_ref(cx, ratings);
+    // Games closest to the rating of the player first, games nobody joined yet last.
+    let sort_by_rating = move |_| {
+        let Some(mine) = mine else {
+            return;
+        };
+        let mut list = games.get().as_ref().clone();
+        list.sort_by_key(|game| ratings.get(&game.id).map_or(u32::MAX, |r| r.abs_diff(mine)));
+        games.set(list);
+    };
     view!(cx,
+        p { (message.get()) }
+        (if mine.is_some() {
+            view!(cx, button(on:click=sort_by_rating) {"Sort by my rating"})
+        } else {
+            view!(cx,)
+        })
+        (if let Some(game) = selected.get().as_ref().clone() {
+            view!(cx, RulesModal(game=game, selected=selected, message=message))
+        } else {
+            view!(cx,)
+        })
         table {
-            tr {
-                th {"Join"}
-                th {"Id"}
-                th {"Seed"}
-                th {"Width"}
-                th {"Height"}
-                th {"Health"}
-                th {"Max Level"}
-                th {"Max Players"}
-                th {"Vote Threshold"}
-                th {"Range"}
-                th {"Last Vote"}
-            }
+            GameListHeader {}
             Keyed(
                 iterable=games,
-                view=|cx, x| {
+                view=move |cx, x| {
                     let cloned_x = x.clone();
                     view! { cx,
                         tr {
-                            td { button(on:click=move |_| {
-                                let cloned_x = cloned_x.clone();
-                                spawn_local_scoped(cx, async move {join_game(cx, cloned_x.clone()).await.unwrap();use_context::<Signal<Storage>>(cx).trigger_subscribers()});
-                            }) {"join"} }
-                            td { (x.id) }
+                            td { button(on:click=move |_| selected.set(Some(cloned_x.clone()))) {"join"} }
+                            td { a(href=Route::Game(x.id).to_string()) { (x.id) } }
                             td { (x.seed) }
                             td { (x.width) }
                             td { (x.height) }
@@ -210,6 +1725,24 @@ async fn GameList<G: Html>(cx: Scope<'_>) -> View<G> {
                             td { (x.vote_threshold) }
                             td { (x.range) }
                             td { (x.last_vote) }
+                            td { (x.point_decay) }
+                            td { (x.jury_weight) }
+                            td { (x.loot_mode) }
+                            td { (x.drive_length) }
+                            td { (x.rules_version) }
+                            td { (x.mutators) }
+                            td { (x.threshold_mode) }
+                            td { (x.win_condition) }
+                            td { (x.starting_points) }
+                            td { (x.starting_level) }
+                            td { (x.actions_per_round) }
+                            td { (x.items) }
+                            td { (x.two_phase_kills) }
+                            td { (x.symmetric_spawns) }
+                            td { (x.gift_cooldown) }
+                            td { (x.vote_interval) }
+                            td { (x.locale) }
+                            td { (ratings.get(&x.id).map_or_else(String::new, ToString::to_string)) }
                         }
                     }
                 },
@@ -223,25 +1756,235 @@ async fn GameList<G: Html>(cx: Scope<'_>) -> View<G> {
     )
 }
 async fn regester(cx: Scope<'_>, key: String) -> Result<(), ()> {
-    let mut headers = HashMap::new();
-    headers.insert("Content-Type".into(), "application/json".into());
-    let response = request(
-        "POST",
-        "/regester".into(),
-        headers,
-        Some(format!("\"{key}\"")),
-    )
-    .await?;
-    let response = get_text(response).await?;
+    let user = client().register(&key).await.map_err(|_| ())?;
 
     let storage = use_context::<Signal<Storage>>(cx);
-    storage.get().set_item("user", &response).unwrap();
+    storage.get().set_item("user", &user.to_string()).unwrap();
     Ok(())
 }
+/// Teaches the rules with the scripted local game of `Tutorial`.
+#[component]
+fn TutorialGame<G: Html>(cx: Scope) -> View<G> {
+    let tutorial = create_signal(cx, Tutorial::default());
+    let message = create_signal(cx, String::new());
+    let width = Tutorial::settings().width;
+    let height = Tutorial::settings().height;
+    let count = create_signal(cx, (0..(width * height)).collect::<Vec<_>>());
+    view!(cx,
+        p { (tutorial.get().lesson().text) }
+        p { (message.get()) }
+        div(id="world", style={format!("width:{}px;height:{}px", width * 50, height * 50)}) {
+            Keyed(
+                iterable=count,
+                view=move |cx, i| view! { cx,
+                    div(on:click=move |_| {
+                        let result = tutorial.modify().play((i % width, i / width));
+                        message.set(result.err().map(|e| e.to_string()).unwrap_or_default());
+                    }, class={
+                        match tutorial.get().game.board.get(&(i % width, i / width)) {
+                            Some(&YOU) => "tile user",
+                            Some(_) => "tile player",
+                            None => "tile",
+                        }
+                    }, title={
+                        let game = &tutorial.get().game;
+                        game.board.get(&(i % width, i / width)).map_or(String::new(), |id| tank_info(game, *id))
+                    }, style={format!("left:{}px;top:{}px", (i % width) * 50, (i / width) * 50)}) {
+                        ({
+                            let game = &tutorial.get().game;
+                            game.board.get(&(i % width, i / width)).map_or_else(View::empty, |id| tank_view(cx, game, *id))
+                        })
+                    }
+                },
+                key=|x| *x,
+            )
+        }
+    )
+}
+/// Practice against bots on the device, see `Sandbox`.
+#[component]
+fn SandboxGame<G: Html>(cx: Scope) -> View<G> {
+    let bots = create_signal(cx, String::from("3"));
+    let started = create_signal(cx, None::<u32>);
+    view!(cx,
+        p { "Practice against bots on your own device, nothing is sent to the server." }
+        label {
+            "Bots "
+            input(type="number", min=MIN_BOTS.to_string(), max=MAX_BOTS.to_string(), bind:value=bots)
+        }
+        button(on:click=move |_| started.set(bots.get().parse().ok())) {
+            (if started.get().is_some() { "Restart" } else { "Start" })
+        }
+        (match *started.get() {
+            Some(bots) => view! { cx, SandboxBoard(bots=bots) },
+            None => view! { cx, },
+        })
+    )
+}
+/// The board of a sandbox game against `bots` bots, played through the components of the game page.
+#[component(inline_props)]
+fn SandboxBoard<G: Html>(cx: Scope, bots: u32) -> View<G> {
+    let (sandbox, game) = Sandbox::start(bots);
+    provide_context(cx, sandbox);
+    provide_context(cx, Toast(create_rc_signal(String::new())));
+    let game = &GameContext::provide(cx, game).state;
+    view!(cx,
+        ToastView()
+        p {
+            (match game.get().0.status() {
+                GameStatus::Won(winners) if winners.contains(&sandbox::YOU) => "You won!",
+                GameStatus::Won(_) => "The bots won, restart to try again.",
+                GameStatus::Draw => "Every tank was destroyed, restart to try again.",
+                GameStatus::Waiting | GameStatus::Running => "",
+            })
+        }
+        World(user=sandbox::YOU, game=game)
+    )
+}
+/// How often the demo game is reloaded, in milliseconds.
+const DEMO_REFRESH: i32 = 5000;
+/// Watches the public demo game played by the server's bots.
+#[component]
+fn DemoGame<G: Html>(cx: Scope) -> View<G> {
+    let demo = create_signal(cx, None::<(Game, Vec<MoveLine>)>);
+    let loaded = create_signal(cx, false);
+    spawn_local_scoped(cx, async move {
+        loop {
+            if let Ok(game) = get_demo().await {
+                demo.set(game);
+                loaded.set(true);
+            }
+            if sleep(DEMO_REFRESH).await.is_err() {
+                break;
+            }
+        }
+    });
+    view!(
+        cx,
+        (if let Some(game) = (*demo.get()).as_ref().map(|game| game.0.clone()) {
+            let online = try_use_context::<RcSignal<HashSet<i32>>>(cx);
+            let width = game.settings.width;
+            let count = create_signal(cx, (0..(width * game.settings.height)).collect::<Vec<_>>());
+            let game = create_ref(cx, game);
+            view! { cx,
+                p { (format!("Round {}, {} tanks left.", game.round, game.players.values().filter(|p| p.is_alive(true).is_ok()).count())) }
+                div(id="world", style={format!("width:{}px;height:{}px", width * 50, game.settings.height * 50)}) {
+                    Keyed(
+                        iterable=count,
+                        view=move |cx, i| view! { cx,
+                            div(class={
+                                let online = online.map(|online| online.get());
+                                tile_class((i % width, i / width), 0, &game.board, &game.bounties, &[], &HashMap::new(), online.as_deref())
+                            }, title={
+                                game.board.get(&(i % width, i / width)).map_or(String::new(), |id| tank_info(game, *id))
+                            }, style={format!("left:{}px;top:{}px", (i % width) * 50, (i / width) * 50)}) {
+                                (game.board.get(&(i % width, i / width)).map_or_else(View::empty, |id| tank_view(cx, game, *id)))
+                            }
+                        },
+                        key=|x| *x,
+                    )
+                }
+            }
+        } else if *loaded.get() {
+            view! { cx, "The server runs no demo game." }
+        } else {
+            view! { cx, "Loading the demo game." }
+        })
+    )
+}
+/// A game as it was after its first `index` moves, read-only, with a scrubber over its moves. The url follows the
+/// scrubber, so the link shares the move being looked at.
+#[component]
+async fn ReplayPage<G: Html>(cx: Scope<'_>, route: (i32, usize)) -> View<G> {
+    let (game, index) = route;
+    let Some(record) = get_games()
+        .await
+        .ok()
+        .and_then(|games| games.into_iter().find(|x| x.id == game))
+    else {
+        return view!(cx, a(href=Route::Games.to_string()) {"All games"} p { (format!("Game {game} does not exist.")) });
+    };
+    let Ok((game, lines)) = get_game(record).await else {
+        return view!(cx, p { (format!("Game {game} could not be loaded.")) });
+    };
+    let (id, total) = (game.id, lines.len());
+    let settings = game.settings.clone();
+    let index = create_signal(cx, index.min(total).to_string());
+    let replayed = create_memo(cx, move || {
+        let shown = index.get().parse::<usize>().unwrap_or(total).min(total);
+        let mut game = Game::new(id, settings.clone());
+        for line in &lines[..shown] {
+            if game.apply(line.clone()).is_err() {
+                break;
+            }
+        }
+        game
+    });
+    create_effect(cx, move || {
+        router::replace(Route::Replay(id, replayed.get().lines.len()));
+    });
+    let width = create_signal(cx, game.settings.width);
+    let height = create_signal(cx, game.settings.height);
+    let dragged = create_signal(cx, false);
+    let tiles = create_signal(
+        cx,
+        (0..(game.settings.width * game.settings.height)).collect::<Vec<_>>(),
+    );
+    let share = move |_| {
+        let link = format!(
+            "{}{}",
+            window()
+                .unwrap()
+                .location()
+                .href()
+                .unwrap_or_default()
+                .split('#')
+                .next()
+                .unwrap_or_default(),
+            Route::Replay(id, replayed.get().lines.len())
+        );
+        let _ = eval(&format!("window.navigator.clipboard.writeText(\"{link}\")"));
+    };
+    view!(cx,
+        a(href=Route::Game(id).to_string()) {"Back to the game"}
+        p { (format!("Move {} of {}, round {}.", replayed.get().lines.len(), total, replayed.get().round)) }
+        label {
+            "Move "
+            input(type="range", min="0", max=total.to_string(), bind:value=index)
+        }
+        button(on:click=share) {"Copy link to this move"}
+        Board(width=width, height=height, dragged=dragged) {
+            Keyed(
+                iterable=tiles,
+                view=move |cx, i| {
+                    let pos = (i % *width.get(), i / *width.get());
+                    view! { cx,
+                        div(class={
+                            let game = replayed.get();
+                            tile_class(pos, 0, &game.board, &game.bounties, &[], &HashMap::new(), None)
+                        }, title={
+                            let game = replayed.get();
+                            game.board.get(&pos).map_or(String::new(), |id| tank_info(&game, *id))
+                        }, style={format!("left:{}px;top:{}px", pos.0 * 50, pos.1 * 50)}) {
+                            ({
+                                let game = replayed.get();
+                                game.board.get(&pos).map_or_else(View::empty, |id| tank_view(cx, &game, *id))
+                            })
+                        }
+                    }
+                },
+                key=|x| *x,
+            )
+        }
+    )
+}
 #[component]
 fn Login<G: Html>(cx: Scope) -> View<G> {
     let storage = use_context::<Signal<Storage>>(cx);
     let public_key = create_memo(cx, || storage.get().get_item("public_key").unwrap());
+    let tutorial = create_signal(cx, false);
+    let practice = create_signal(cx, false);
+    let demo = create_signal(cx, false);
 
     let deleteKeys = |_| {
         storage.get().remove_item("game").unwrap();
@@ -251,24 +1994,119 @@ fn Login<G: Html>(cx: Scope) -> View<G> {
     };
     view!(
         cx,
-        (if public_key.get().is_some() {
+        button(on:click=move |_| tutorial.set(!*tutorial.get())) {
+            (if *tutorial.get() { "Leave tutorial" } else { "Tutorial" })
+        }
+        button(on:click=move |_| practice.set(!*practice.get())) {
+            (if *practice.get() { "Stop practicing" } else { "Practice" })
+        }
+        button(on:click=move |_| demo.set(!*demo.get())) {
+            (if *demo.get() { "Stop watching" } else { "Watch demo" })
+        }
+        (if *tutorial.get() {
+            view! { cx, TutorialGame() }
+        } else if *practice.get() {
+            view! { cx, SandboxGame() }
+        } else if *demo.get() {
+            view! { cx, DemoGame() }
+        } else if public_key.get().is_some() {
             view! { cx,
                 button(on:click=deleteKeys) { "Delete Account from device." }
                 br()
                 "Please join a game."
+                Matchmaking()
                 GameList()
             }
         } else {
-            view! { cx, button(on:click= move |_| spawn_local_scoped(cx, async move {
-                let (private, public) = get_random_keys();
-                storage.get().set_item("public_key", &public).unwrap();
-                storage.get().set_item("private_key", &private).unwrap();
-                regester(cx, public).await.unwrap();
-                storage.trigger_subscribers();
-            })) { "Generate new Account" } }
+            view! { cx,
+                button(on:click= move |_| spawn_local_scoped(cx, async move {
+                    let (private, public) = get_random_keys();
+                    storage.get().set_item("public_key", &public).unwrap();
+                    storage.get().set_item("private_key", &private).unwrap();
+                    regester(cx, public).await.unwrap();
+                    storage.trigger_subscribers();
+                })) { "Generate new Account" }
+                button(on:click= move |_| spawn_local_scoped(cx, async move {
+                    let Ok((user, private)) = register_passkey().await else {
+                        log("Could not create a passkey.".into());
+                        return;
+                    };
+                    storage.get().set_item("user", &user.to_string()).unwrap();
+                    storage.get().set_item("public_key", "passkey").unwrap();
+                    storage.get().set_item("private_key", &private).unwrap();
+                    storage.trigger_subscribers();
+                })) { "Use a passkey" }
+                button(on:click= move |_| spawn_local_scoped(cx, async move {
+                    let Ok((user, game, private)) = quick_play().await else {
+                        log("Could not start quick play.".into());
+                        return;
+                    };
+                    storage.get().set_item("user", &user.to_string()).unwrap();
+                    storage.get().set_item("public_key", "quick play").unwrap();
+                    storage.get().set_item("private_key", &private).unwrap();
+                    storage.get().set_item("game", &game.to_string()).unwrap();
+                    storage.trigger_subscribers();
+                    navigate(Route::Game(game));
+                })) { "Quick play" }
+            }
         })
     )
 }
+/// The games `user` made a move in, linking to each of them.
+#[component]
+async fn PlayerPage<G: Html>(cx: Scope<'_>, user: i32) -> View<G> {
+    let games = View::new_fragment(
+        get_player_games(user)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|game| view! { cx, li { a(href=Route::Game(game).to_string()) { (format!("Game {game}")) } } })
+            .collect(),
+    );
+    let rating = match get_rating(user).await {
+        Ok(r) if r.games > 0 => format!("Rated {} after {} finished games.", r.rating, r.games),
+        Ok(r) => format!("Rated {}, no finished games yet.", r.rating),
+        Err(()) => String::new(),
+    };
+    view!(cx,
+        a(href=Route::Games.to_string()) {"All games"}
+        " "
+        a(href=Route::Leaderboard.to_string()) {"Leaderboard"}
+        h2 { (format!("Player {user}")) }
+        p { (rating) }
+        ul { (games) }
+    )
+}
+/// The highest rated players, linking to each of them.
+#[component]
+async fn LeaderboardPage<G: Html>(cx: Scope<'_>) -> View<G> {
+    let rows = View::new_fragment(
+        get_leaderboard()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .zip(1..)
+            .map(|(r, place)| {
+                view! { cx,
+                    tr {
+                        td { (place) }
+                        td { a(href=Route::Player(r.user).to_string()) { (format!("Player {}", r.user)) } }
+                        td { (r.rating) }
+                        td { (r.games) }
+                    }
+                }
+            })
+            .collect(),
+    );
+    view!(cx,
+        a(href=Route::Games.to_string()) {"All games"}
+        h2 { "Leaderboard" }
+        table {
+            tr { th {"#"} th {"Player"} th {"Rating"} th {"Games"} }
+            (rows)
+        }
+    )
+}
 #[component]
 async fn Game<G: Html>(cx: Scope<'_>, game: i32) -> View<G> {
     let storage = use_context::<Signal<Storage>>(cx);
@@ -280,18 +2118,54 @@ async fn Game<G: Html>(cx: Scope<'_>, game: i32) -> View<G> {
         .parse()
         .unwrap();
 
-    let game = get_games()
+    let Some(game) = get_games()
         .await
         .unwrap()
         .into_iter()
         .find(|x| x.id == game)
-        .unwrap();
+    else {
+        return view!(cx, a(href=Route::Games.to_string()) {"All games"} p { (format!("Game {game} does not exist.")) });
+    };
     let game = get_game(game).await.unwrap();
-    let game = create_signal(cx, game);
+    if let Err(error) = verify_chain(&storage.get(), game.0.id, &game.1) {
+        if let ChainError::Diverged(index) = error {
+            // Keep the servers statement of the conflicting move as evidence.
+            if let Ok((data, signature)) = attest(game.0.id, index).await {
+                storage
+                    .get()
+                    .set_item(
+                        &format!("fork_{}", game.0.id),
+                        &format!("{data}|{signature}"),
+                    )
+                    .unwrap();
+            }
+        }
+        let accept = window()
+            .unwrap()
+            .confirm_with_message(&format!("{error} Accept this history anyway?"))
+            .unwrap();
+        if !accept {
+            return view!(cx, "The history served by the server was refused.");
+        }
+    }
+    accept_chain(&storage.get(), game.0.id, &game.1);
+    let game = &GameContext::provide(cx, game).state;
+    provide_context(cx, Toast(create_rc_signal(String::new())));
+    follow(cx, game.get().0.id);
+    let tally = create_signal(cx, None);
 
     view!(cx,
+        ToastView()
+        PausedBanner(game=game)
+        Rematch(game=game, user=user)
         World(user=user, game=game)
-        Hud(game.get().0.settings.height)
+        Hud(game=game, user=user)
+        RoundEvents(game=game)
+        VoteReport(game=game, round=tally)
+        RoundTimeline(game=game, tally=tally)
+        VerifyPlayers(game=game)
+        Flows(game=game)
+        Loans(game=game)
     )
 }
 fn main() {
@@ -305,26 +2179,40 @@ fn main() {
     sycamore::render(|cx| {
         let storage = create_signal(cx, storage);
         provide_context_ref(cx, storage);
+        spawn_local_scoped(cx, async move {
+            if let Err(warning) = check_client(&storage.get()).await {
+                let _ = window().unwrap().alert_with_message(&warning);
+            }
+        });
         if let Some(user) = user {
             let socket =
                 WebSocket::new_with_str(&format!("ws://127.0.0.1:3000/ws/{user}"), "tanktacktics")
                     .unwrap();
             let socket = create_signal(cx, socket);
             provide_context_ref(cx, socket);
+            provide_context(cx, presence::connect(user));
         }
+        // Without a route in the url, the game last played on this device is opened.
+        let last_game = storage
+            .get()
+            .get_item("game")
+            .unwrap()
+            .as_deref()
+            .map(<i32 as FromStr>::from_str)
+            .and_then(Result::ok);
+        let route = create_ref(
+            cx,
+            router::connect(last_game.map_or(Route::Games, Route::Game)),
+        );
         view!(
             cx,
-            (if let Some(game) = storage
-                .get()
-                .get_item("game")
-                .unwrap()
-                .as_deref()
-                .map(<i32 as FromStr>::from_str)
-                .and_then(Result::ok)
-            {
-                Game(cx, game)
-            } else {
-                Login(cx)
+            (match *route.get() {
+                Route::Replay(game, index) => ReplayPage(cx, (game, index)),
+                Route::Game(game) if storage.get().get_item("user").unwrap().is_some() =>
+                    Game(cx, game),
+                Route::Player(user) => PlayerPage(cx, user),
+                Route::Leaderboard => LeaderboardPage(cx),
+                Route::Games | Route::Game(_) => Login(cx),
             })
         )
     });
@@ -0,0 +1,44 @@
+//! Which users have the game open, kept up to date over the presence socket of the server.
+
+use std::collections::HashSet;
+
+use js_sys::wasm_bindgen::{closure::Closure, JsCast};
+use serde::Deserialize;
+use sycamore::reactive::{create_rc_signal, RcSignal};
+use web_sys::{MessageEvent, WebSocket};
+
+#[derive(Deserialize)]
+struct Change {
+    user: i32,
+    online: bool,
+}
+
+/// Opens the presence socket of `user`, returning the users that are online.
+#[must_use]
+pub fn connect(user: i32) -> RcSignal<HashSet<i32>> {
+    let online = create_rc_signal(HashSet::new());
+    let Ok(socket) = WebSocket::new(&format!("ws://127.0.0.1:3000/ws/presence/{user}")) else {
+        return online;
+    };
+    let signal = online.clone();
+    let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+        let Some(change) = event
+            .data()
+            .as_string()
+            .and_then(|text| serde_json::from_str::<Change>(&text).ok())
+        else {
+            return;
+        };
+        let mut users = signal.get().as_ref().clone();
+        if change.online {
+            users.insert(change.user);
+        } else {
+            users.remove(&change.user);
+        }
+        signal.set(users);
+    });
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    // The socket lives as long as the page, and with it the handler.
+    on_message.forget();
+    online
+}
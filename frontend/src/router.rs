@@ -0,0 +1,91 @@
+//! The page shown, kept in the hash of the url so a game or player can be shared as a link.
+//!
+//! `#/games` lists the games, `#/game/12` opens game 12, `#/game/12@137` replays game 12 up to its first 137 moves,
+//! `#/player/5` shows the games player 5 is in and `#/leaderboard` the highest rated players.
+
+use std::fmt::Display;
+
+use js_sys::wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use sycamore::reactive::{create_rc_signal, RcSignal};
+use web_sys::window;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    Games,
+    Game(i32),
+    /// A game as it was after the given number of moves, read-only.
+    Replay(i32, usize),
+    Player(i32),
+    Leaderboard,
+}
+impl Route {
+    /// Reads a hash like `#/game/12`, `None` for an empty or unknown one.
+    #[must_use]
+    pub fn parse(hash: &str) -> Option<Route> {
+        let path = hash.trim_start_matches('#').trim_matches('/');
+        let mut parts = path.split('/');
+        let route = match (parts.next()?, parts.next()) {
+            ("games", None) => Route::Games,
+            ("leaderboard", None) => Route::Leaderboard,
+            ("game", Some(id)) => match id.split_once('@') {
+                Some((id, index)) => Route::Replay(id.parse().ok()?, index.parse().ok()?),
+                None => Route::Game(id.parse().ok()?),
+            },
+            ("player", Some(id)) => Route::Player(id.parse().ok()?),
+            _ => return None,
+        };
+        parts.next().is_none().then_some(route)
+    }
+}
+impl Display for Route {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Route::Games => write!(f, "#/games"),
+            Route::Game(id) => write!(f, "#/game/{id}"),
+            Route::Replay(id, index) => write!(f, "#/game/{id}@{index}"),
+            Route::Player(id) => write!(f, "#/player/{id}"),
+            Route::Leaderboard => write!(f, "#/leaderboard"),
+        }
+    }
+}
+
+fn current() -> Option<Route> {
+    Route::parse(&window()?.location().hash().ok()?)
+}
+
+/// Shows `route`, adding it to the history of the browser.
+pub fn navigate(route: Route) {
+    if let Some(window) = window() {
+        let _ = window.location().set_hash(&route.to_string());
+    }
+}
+
+/// Shows `route` in the url without adding it to the history of the browser or leaving the page, so it can be shared.
+pub fn replace(route: Route) {
+    if let Some(history) = window().and_then(|window| window.history().ok()) {
+        let _ = history.replace_state_with_url(&JsValue::NULL, "", Some(&route.to_string()));
+    }
+}
+
+/// Follows the hash of the url, starting at `default` when the url has no route.
+#[must_use]
+pub fn connect(default: Route) -> RcSignal<Route> {
+    let route = create_rc_signal(current().unwrap_or(default));
+    let Some(window) = window() else {
+        return route;
+    };
+    if current().is_none() {
+        // Replaced rather than navigated to, so going back leaves the page instead of landing on an empty hash.
+        let _ = window.location().replace(&route.get().to_string());
+    }
+    let signal = route.clone();
+    let on_change = Closure::<dyn FnMut()>::new(move || {
+        if let Some(route) = current() {
+            signal.set(route);
+        }
+    });
+    window.set_onhashchange(Some(on_change.as_ref().unchecked_ref()));
+    // The page keeps following the hash for as long as it is open.
+    on_change.forget();
+    route
+}
@@ -0,0 +1,99 @@
+//! A local game against bots for practicing, played with the real engine and without a server or an account.
+//!
+//! The components of the game page are reused: the sandbox page provides a `Sandbox` as context, and moves sent while
+//! it is there are applied to the local game, after which every bot answers with a move of its `Strategy`.
+
+use std::{cell::RefCell, rc::Rc};
+
+use tanktacticsgame::{
+    Error, Game, Hunter, MoveLine, MoveLineType, Settings, Strategy, ThresholdMode,
+};
+
+/// The tank of the player.
+pub const YOU: i32 = 1;
+/// The fewest bots a sandbox can be played against.
+pub const MIN_BOTS: u32 = 2;
+/// The most bots a sandbox can be played against.
+pub const MAX_BOTS: u32 = 5;
+
+/// A bot and the strategy it plays.
+type Bot = (i32, Box<dyn Strategy>);
+
+#[derive(Clone)]
+pub struct Sandbox {
+    /// Every tank in the game but the one of the player.
+    bots: Rc<RefCell<Vec<Bot>>>,
+}
+impl Sandbox {
+    /// Starts a game of the player against `bots` `Hunter`s, clamped to `MIN_BOTS..=MAX_BOTS`, with every tank joined.
+    #[must_use]
+    pub fn start(bots: u32) -> (Sandbox, (Game, Vec<MoveLine>)) {
+        let bots = bots.clamp(MIN_BOTS, MAX_BOTS);
+        let mut game = (Game::new(0, Self::settings(bots)), Vec::new());
+        let users = (YOU..).take(bots as usize + 1).collect::<Vec<_>>();
+        for &user in &users {
+            let (x, y) = game.0.get_pos();
+            let join = MoveLine {
+                move_type: MoveLineType::Join,
+                x: Some(x),
+                y: Some(y),
+                ..Hunter.next_move(&game.0, user)
+            };
+            // An empty board has room for every tank.
+            let _ = Self::apply(&mut game, join);
+        }
+        let bots = users[1..]
+            .iter()
+            .map(|&user| -> Bot { (user, Box::new(Hunter)) })
+            .collect();
+        (
+            Sandbox {
+                bots: Rc::new(RefCell::new(bots)),
+            },
+            game,
+        )
+    }
+    /// A board that grows with the number of tanks.
+    #[must_use]
+    pub fn settings(bots: u32) -> Settings {
+        let size = 4 + 2 * bots;
+        Settings {
+            width: size,
+            height: size,
+            max_players: i32::try_from(bots + 1).unwrap_or(i32::MAX),
+            threshold_mode: ThresholdMode::Fixed(1),
+            ..Settings::default()
+        }
+    }
+    fn apply(game: &mut (Game, Vec<MoveLine>), line: MoveLine) -> Result<(), Error> {
+        game.0.apply(line.clone())?;
+        game.0.drain_events();
+        game.1.push(line);
+        Ok(())
+    }
+    /// Makes the move of the player, then lets every living bot answer. The votes are handled at most once per answer.
+    /// # Errors
+    /// If the engine refuses the move of the player, the game is left as it was then.
+    pub fn play(&self, game: &mut (Game, Vec<MoveLine>), line: MoveLine) -> Result<(), Error> {
+        Self::apply(game, line)?;
+        let mut handled = false;
+        for (bot, strategy) in self.bots.borrow_mut().iter_mut() {
+            if game.0.status().is_over() {
+                break;
+            }
+            if !game.0.players.get(bot).is_some_and(|p| p.alive()) {
+                continue;
+            }
+            let line = strategy.next_move(&game.0, *bot);
+            if line.move_type == MoveLineType::HandleVotes {
+                if handled {
+                    continue;
+                }
+                handled = true;
+            }
+            // A bot that can't move skips its turn.
+            let _ = Self::apply(game, line);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+//! Moves of the open game as they are made, over the game sync socket of the server.
+//!
+//! Frames are asked for as deflated CBOR, the smallest the server offers.
+
+use std::io::Read;
+
+use flate2::read::DeflateDecoder;
+use js_sys::{
+    wasm_bindgen::{closure::Closure, JsCast},
+    ArrayBuffer, Uint8Array,
+};
+use tanktacticsgame::{Region, SyncCompression, SyncFormat, SyncFrame, SyncHello};
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+fn decode(data: &[u8], hello: &SyncHello) -> Option<SyncFrame> {
+    let data = match hello.compression.first() {
+        Some(SyncCompression::Deflate) => {
+            let mut inflated = Vec::new();
+            DeflateDecoder::new(data).read_to_end(&mut inflated).ok()?;
+            inflated
+        }
+        _ => data.to_vec(),
+    };
+    match hello.formats.first() {
+        Some(SyncFormat::Cbor) => ciborium::from_reader(data.as_slice()).ok(),
+        _ => serde_json::from_slice(&data).ok(),
+    }
+}
+
+/// Opens the sync socket of `game`, calling `on_frame` with every frame the server sends.
+///
+/// With a `viewport` the server sends views of it and a minimap instead of the moves, for boards too large to follow
+/// whole.
+pub fn connect(game: i32, viewport: Option<Region>, mut on_frame: impl FnMut(SyncFrame) + 'static) {
+    let Ok(socket) = WebSocket::new(&format!("ws://127.0.0.1:3000/ws/game/{game}")) else {
+        return;
+    };
+    socket.set_binary_type(BinaryType::Arraybuffer);
+    let hello = SyncHello {
+        formats: vec![SyncFormat::Cbor, SyncFormat::Json],
+        compression: vec![SyncCompression::Deflate, SyncCompression::None],
+        viewport,
+    };
+    let sender = socket.clone();
+    let on_open = Closure::<dyn FnMut()>::new(move || {
+        let _ = sender.send_with_str(&serde_json::to_string(&hello).unwrap());
+    });
+    // The answer to the hello, the format the server picked.
+    let mut picked: Option<SyncHello> = None;
+    let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+        let data = event.data();
+        let frame = match (&picked, data.as_string()) {
+            (None, Some(text)) => {
+                picked = serde_json::from_str(&text).ok();
+                return;
+            }
+            (None, None) => return,
+            (Some(_), Some(text)) => serde_json::from_str(&text).ok(),
+            (Some(hello), None) => data
+                .dyn_into::<ArrayBuffer>()
+                .ok()
+                .and_then(|buffer| decode(&Uint8Array::new(&buffer).to_vec(), hello)),
+        };
+        if let Some(frame) = frame {
+            on_frame(frame);
+        }
+    });
+    socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    // The socket lives as long as the page, and with it the handlers.
+    on_open.forget();
+    on_message.forget();
+}
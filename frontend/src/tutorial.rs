@@ -0,0 +1,189 @@
+//! A scripted local game teaching the rules, played with the real engine and without a server.
+
+use std::collections::HashMap;
+
+use tanktacticsgame::{
+    get_key, get_random_keys, Error, Game, LevelRangeMap, MoveLine, MoveLineType, PublicKey,
+    Settings, ThresholdMode,
+};
+
+/// The tank of the player.
+pub const YOU: i32 = 1;
+/// The scripted tank that gets shot and fires back.
+pub const RED: i32 = 2;
+/// The scripted tank that receives a gift.
+pub const BLUE: i32 = 3;
+
+/// A step of the tutorial, finished by making a move of `move_type`.
+pub struct Lesson {
+    pub text: &'static str,
+    /// The move the player has to make, `None` once the tutorial is over.
+    pub move_type: Option<MoveLineType>,
+}
+
+pub const LESSONS: [Lesson; 6] = [
+    Lesson {
+        text: "Welcome! Click any tile to join the game, your tank spawns on a random free tile.",
+        move_type: Some(MoveLineType::Join),
+    },
+    Lesson {
+        text: "Red (2) and Blue (3) joined too. Every tank starts with a point. Driving costs a point: click a free tile next to your tank to drive there.",
+        move_type: Some(MoveLineType::Drive),
+    },
+    Lesson {
+        text: "The round ended and every tank got a point. Shooting costs a point and takes a health: click Red to shoot it.",
+        move_type: Some(MoveLineType::Shoot),
+    },
+    Lesson {
+        text: "Another round passed. Points can also be given away: click Blue to gift it a point.",
+        move_type: Some(MoveLineType::Gift),
+    },
+    Lesson {
+        text: "Red and Blue shot back and destroyed your tank, Red took your points. Dead tanks join the jury: click a living tank to vote for it, it gets an extra point when the round ends.",
+        move_type: Some(MoveLineType::Vote),
+    },
+    Lesson {
+        text: "The round ended and your vote gave a point. That's all, now join a real game!",
+        move_type: None,
+    },
+];
+
+#[derive(Clone)]
+pub struct Tutorial {
+    pub game: Game,
+    /// Index into `LESSONS`.
+    pub lesson: usize,
+    private_keys: HashMap<i32, String>,
+    users: HashMap<i32, PublicKey>,
+}
+impl Default for Tutorial {
+    fn default() -> Self {
+        let mut private_keys = HashMap::new();
+        let mut users = HashMap::new();
+        for user in [YOU, RED, BLUE] {
+            let (private, public) = get_random_keys();
+            private_keys.insert(user, private);
+            users.insert(user, get_key(public).unwrap()); // freshly generated keys are always valid
+        }
+        Tutorial {
+            game: Game::new(0, Self::settings()),
+            lesson: 0,
+            private_keys,
+            users,
+        }
+    }
+}
+impl Tutorial {
+    /// A small board where every tank can reach every tile and two shots kill.
+    #[must_use]
+    pub fn settings() -> Settings {
+        Settings {
+            width: 3,
+            height: 3,
+            health: 2,
+            max_players: 3,
+            threshold_mode: ThresholdMode::Fixed(1),
+            range: LevelRangeMap::Affine { base: 2, slope: 0 },
+            ..Settings::default()
+        }
+    }
+    #[must_use]
+    pub fn lesson(&self) -> &'static Lesson {
+        &LESSONS[self.lesson]
+    }
+    /// Makes the move of the current lesson on the tile at `pos`, then lets the other tanks answer.
+    /// # Errors
+    /// If the engine refuses the move, the lesson is not finished then.
+    pub fn play(&mut self, pos: (u32, u32)) -> Result<(), Error> {
+        let mut next = self.clone();
+        next.play_lesson(pos)?;
+        *self = next;
+        Ok(())
+    }
+    fn play_lesson(&mut self, pos: (u32, u32)) -> Result<(), Error> {
+        let Some(move_type) = self.lesson().move_type.clone() else {
+            return Ok(());
+        };
+        let mut line = MoveLine {
+            move_type: move_type.clone(),
+            x: None,
+            y: None,
+            target: None,
+            amount: None,
+            text: None,
+            time: None,
+            clock: None,
+            path: None,
+            rules_version: None,
+            authorizer: YOU,
+            signature: String::new(),
+        };
+        match move_type {
+            MoveLineType::Join => {
+                let (x, y) = self.game.get_pos();
+                (line.x, line.y) = (Some(x), Some(y));
+            }
+            MoveLineType::Drive => (line.x, line.y) = (Some(pos.0), Some(pos.1)),
+            _ => {
+                line.target = Some(
+                    *self
+                        .game
+                        .board
+                        .get(&pos)
+                        .ok_or_else(|| Error::NotFound("tank on this tile".into()))?,
+                );
+            }
+        }
+        self.load(line)?;
+        self.answer()?;
+        self.lesson += 1;
+        Ok(())
+    }
+    /// The scripted moves that follow the current lesson.
+    fn answer(&mut self) -> Result<(), Error> {
+        match self.lesson().move_type {
+            Some(MoveLineType::Join) => {
+                self.play_as(RED, MoveLineType::Join, None)?;
+                self.play_as(BLUE, MoveLineType::Join, None)
+            }
+            Some(MoveLineType::Drive | MoveLineType::Shoot | MoveLineType::Vote) => {
+                self.play_as(RED, MoveLineType::HandleVotes, None)
+            }
+            Some(MoveLineType::Gift) => {
+                self.play_as(BLUE, MoveLineType::Shoot, Some(YOU))?;
+                self.play_as(RED, MoveLineType::Shoot, Some(YOU))
+            }
+            _ => Ok(()),
+        }
+    }
+    fn play_as(
+        &mut self,
+        user: i32,
+        move_type: MoveLineType,
+        target: Option<i32>,
+    ) -> Result<(), Error> {
+        let pos = (move_type == MoveLineType::Join).then(|| self.game.get_pos());
+        self.load(MoveLine {
+            move_type,
+            x: pos.map(|p| p.0),
+            y: pos.map(|p| p.1),
+            target,
+            amount: None,
+            text: None,
+            time: None,
+            clock: None,
+            path: None,
+            rules_version: None,
+            authorizer: user,
+            signature: String::new(),
+        })
+    }
+    /// Signs `line` with the key of its authorizer and loads it into the game.
+    fn load(&mut self, mut line: MoveLine) -> Result<(), Error> {
+        line.sign(
+            self.game.lines.last().map(|x| x.signature.as_str()),
+            self.private_keys[&line.authorizer].clone(),
+        )?;
+        self.game.load(line, &self.users)
+    }
+}
@@ -0,0 +1,27 @@
+-- The tables of servers from before migrations, which already have them, so they are only created when missing. What
+-- was added to them since is added by the migrations after this one.
+CREATE TABLE IF NOT EXISTS users (
+    id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    public_key TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS games (
+    id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    seed BIGINT UNSIGNED NOT NULL,
+    last_vote BIGINT UNSIGNED NOT NULL,
+    width INT UNSIGNED NOT NULL,
+    height INT UNSIGNED NOT NULL,
+    health INT UNSIGNED NOT NULL,
+    max_level INT NOT NULL,
+    max_players INT NOT NULL,
+    vote_threshold INT UNSIGNED NOT NULL,
+    `range` VARCHAR(255) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS moves (
+    user INT NOT NULL,
+    game INT NOT NULL,
+    `index` INT NOT NULL,
+    token TEXT NOT NULL,
+    PRIMARY KEY (game, `index`)
+);
@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN drive_length INT UNSIGNED NOT NULL DEFAULT 1;
@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN rules_version INT UNSIGNED NOT NULL DEFAULT 1;
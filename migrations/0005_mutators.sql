@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN mutators VARCHAR(255) NOT NULL DEFAULT '';
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS pending_moves (
+    id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user INT NOT NULL,
+    game INT NOT NULL,
+    token TEXT NOT NULL,
+    at BIGINT UNSIGNED NOT NULL
+);
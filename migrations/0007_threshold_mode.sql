@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN threshold_mode VARCHAR(255) NOT NULL DEFAULT '';
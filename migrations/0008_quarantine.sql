@@ -0,0 +1,10 @@
+ALTER TABLE users ADD COLUMN quarantined BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Moves of quarantined users are not canonical and may share an index with a canonical move,
+-- so the index is only unique among canonical moves.
+ALTER TABLE moves
+    ADD COLUMN canonical BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN canonical_index INT AS (IF(canonical, `index`, NULL)) STORED,
+    DROP PRIMARY KEY,
+    ADD UNIQUE KEY canonical_moves (game, canonical_index),
+    ADD KEY game_moves (game, `index`);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS demo_games (
+    game INT NOT NULL PRIMARY KEY
+);
+
+CREATE TABLE IF NOT EXISTS demo_bots (
+    user INT NOT NULL PRIMARY KEY,
+    private_key TEXT NOT NULL
+);
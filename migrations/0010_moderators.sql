@@ -0,0 +1,18 @@
+-- Kept apart from `games`, whose rows map onto `DataBaseGame`.
+CREATE TABLE IF NOT EXISTS game_moderation (
+    game INT NOT NULL PRIMARY KEY,
+    creator INT NULL,
+    paused BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS moderators (
+    game INT NOT NULL,
+    user INT NOT NULL,
+    PRIMARY KEY (game, user)
+);
+
+CREATE TABLE IF NOT EXISTS hidden_polls (
+    game INT NOT NULL,
+    poll INT NOT NULL,
+    PRIMARY KEY (game, poll)
+);
@@ -0,0 +1,7 @@
+-- A summary of every round, see `RoundSummary`. Rounds that ended before this migration have none.
+CREATE TABLE IF NOT EXISTS rounds (
+    game INT NOT NULL,
+    round INT UNSIGNED NOT NULL,
+    summary TEXT NOT NULL,
+    PRIMARY KEY (game, round)
+);
@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN win_condition VARCHAR(255) NOT NULL DEFAULT '';
@@ -0,0 +1,10 @@
+-- Append-only: rows are never updated or deleted.
+CREATE TABLE IF NOT EXISTS admin_log (
+    id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    user INT NOT NULL,
+    game INT NULL,
+    action TEXT NOT NULL,
+    time BIGINT UNSIGNED NOT NULL,
+    signature TEXT NOT NULL,
+    KEY game_log (game)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE games
+    ADD COLUMN starting_points INT UNSIGNED NOT NULL DEFAULT 1,
+    ADD COLUMN starting_level INT UNSIGNED NOT NULL DEFAULT 0;
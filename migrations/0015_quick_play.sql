@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS casual_games (
+    game INT NOT NULL PRIMARY KEY
+);
+
+CREATE TABLE IF NOT EXISTS quick_play (
+    user INT NOT NULL PRIMARY KEY,
+    game INT NOT NULL,
+    private_key TEXT NOT NULL,
+    last_active BIGINT UNSIGNED NOT NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE games
+    ADD COLUMN actions_per_round INT UNSIGNED NOT NULL DEFAULT 0;
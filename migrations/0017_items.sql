@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN items VARCHAR(255) NOT NULL DEFAULT '';
@@ -0,0 +1 @@
+ALTER TABLE game_moderation ADD COLUMN created BIGINT UNSIGNED NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+-- Everyone that made a move in a game, so the players of a game are found without going through all of its moves.
+-- `canonical` is unset for users that only made moves while quarantined.
+CREATE TABLE IF NOT EXISTS game_players (
+    game INT NOT NULL,
+    user INT NOT NULL,
+    canonical BOOLEAN NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (game, user),
+    KEY player_games (user)
+);
+
+INSERT IGNORE INTO game_players (game, user, canonical)
+    SELECT game, user, MAX(canonical) FROM moves GROUP BY game, user;
@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN two_phase_kills BOOLEAN NOT NULL DEFAULT FALSE;
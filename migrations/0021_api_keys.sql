@@ -0,0 +1,15 @@
+-- Keys for community tools reading the API, with a limit of their own instead of the one for anonymous reads.
+-- Only the sha256 of a key is stored, the key itself is shown once when it is issued.
+-- `requests` and `limited` count the reads made and refused, added to every minute.
+CREATE TABLE IF NOT EXISTS api_keys (
+    id INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    name TEXT NOT NULL,
+    key_hash CHAR(64) NOT NULL,
+    per_minute INT UNSIGNED NOT NULL,
+    created BIGINT UNSIGNED NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    requests BIGINT UNSIGNED NOT NULL DEFAULT 0,
+    limited BIGINT UNSIGNED NOT NULL DEFAULT 0,
+    last_used BIGINT UNSIGNED NULL,
+    UNIQUE KEY api_key_hash (key_hash)
+);
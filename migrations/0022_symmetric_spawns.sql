@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN symmetric_spawns BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,14 @@
+-- The Elo rating of every user that finished a game, see `ratings`. Users without a row have the starting rating.
+CREATE TABLE IF NOT EXISTS ratings (
+    user INT NOT NULL PRIMARY KEY,
+    rating INT NOT NULL,
+    games INT UNSIGNED NOT NULL,
+    updated BIGINT UNSIGNED NOT NULL,
+    KEY leaderboard (rating)
+);
+
+-- The finished games the ratings were updated for, so a game is only counted once.
+CREATE TABLE IF NOT EXISTS rated_games (
+    game INT NOT NULL PRIMARY KEY,
+    rated BIGINT UNSIGNED NOT NULL
+);
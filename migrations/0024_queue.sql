@@ -0,0 +1,10 @@
+-- The users waiting for a match of a preset, see `queue`. `intent` is their signed `JoinRandom` move, `game` the game
+-- they were matched into, NULL while they wait.
+CREATE TABLE IF NOT EXISTS queue (
+    user INT NOT NULL PRIMARY KEY,
+    preset VARCHAR(32) NOT NULL,
+    intent TEXT NOT NULL,
+    queued BIGINT UNSIGNED NOT NULL,
+    game INT NULL,
+    KEY waiting (preset, game, queued)
+);
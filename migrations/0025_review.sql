@@ -0,0 +1,10 @@
+ALTER TABLE users ADD COLUMN flagged BOOLEAN NOT NULL DEFAULT FALSE;
+-- Moves of flagged users awaiting a moderator, see `review`. A held move keeps the next `index` of its game until it
+-- is approved, rejected or `expires`, so a game holds at most one.
+CREATE TABLE IF NOT EXISTS review_moves (
+    game INT NOT NULL PRIMARY KEY,
+    user INT NOT NULL,
+    `index` INT NOT NULL,
+    token TEXT NOT NULL,
+    expires BIGINT UNSIGNED NOT NULL
+);
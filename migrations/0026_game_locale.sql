@@ -0,0 +1,3 @@
+-- The language a game is hosted in, as a BCP 47 tag, and the description its creator wrote in it. Empty if not given.
+ALTER TABLE games ADD COLUMN locale VARCHAR(35) NOT NULL DEFAULT '';
+ALTER TABLE games ADD COLUMN description VARCHAR(2000) NOT NULL DEFAULT '';
@@ -0,0 +1,2 @@
+-- Whether the jurors that haven't voted were reminded of a scheduled handling of the votes.
+ALTER TABLE pending_moves ADD COLUMN reminded BOOL NOT NULL DEFAULT FALSE;
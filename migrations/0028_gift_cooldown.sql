@@ -0,0 +1,2 @@
+-- The rounds a player waits after a gift before gifting the same player again, see `Settings::gift_cooldown`.
+ALTER TABLE games ADD COLUMN gift_cooldown INT UNSIGNED NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- The seconds after the last handling of the votes at which the server handles them by itself, never if 0.
+ALTER TABLE games ADD COLUMN vote_interval BIGINT UNSIGNED NOT NULL DEFAULT 0;
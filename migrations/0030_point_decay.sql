@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN point_decay BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,10 @@
+-- The address each user gets notified at, see `email`.
+CREATE TABLE IF NOT EXISTS emails (
+    user INT NOT NULL PRIMARY KEY,
+    address VARCHAR(320) NOT NULL,
+    verified BOOLEAN NOT NULL,
+    code VARCHAR(6) NOT NULL,
+    notify_shot BOOLEAN NOT NULL,
+    notify_points BOOLEAN NOT NULL,
+    notify_rounds BOOLEAN NOT NULL
+);
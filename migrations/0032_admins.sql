@@ -0,0 +1,3 @@
+CREATE TABLE IF NOT EXISTS admins (
+    user INT NOT NULL PRIMARY KEY
+);
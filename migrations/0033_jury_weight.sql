@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN jury_weight INT NOT NULL DEFAULT 0;
@@ -0,0 +1,10 @@
+-- The challenges handed out for passkey registrations, and the credential of every passkey, see `passkey`.
+CREATE TABLE IF NOT EXISTS webauthn_challenges (
+    challenge VARCHAR(64) NOT NULL PRIMARY KEY,
+    created BIGINT UNSIGNED NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS passkeys (
+    user INT NOT NULL PRIMARY KEY,
+    credential_id VARCHAR(1024) NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN loot_mode VARCHAR(16) NOT NULL DEFAULT 'killer';
@@ -0,0 +1,5 @@
+-- When the verification code of an address was mailed and the wrong codes tried since, see `EmailAPI::verify_email`.
+-- The code is cleared once the address is verified.
+ALTER TABLE emails MODIFY code VARCHAR(6) NULL,
+    ADD COLUMN code_sent BIGINT UNSIGNED NOT NULL DEFAULT 0,
+    ADD COLUMN attempts INT UNSIGNED NOT NULL DEFAULT 0;
@@ -0,0 +1,7 @@
+-- The signed result of every finished game, issued once for the moves up to the one that ended it, see
+-- `GameAPI::get_certificate`. `data` is `Game::result_data` of the game.
+CREATE TABLE IF NOT EXISTS certificates (
+    game INT NOT NULL PRIMARY KEY,
+    data TEXT NOT NULL,
+    signature VARCHAR(128) NOT NULL
+);
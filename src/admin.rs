@@ -0,0 +1,297 @@
+//! Administrative endpoints.
+//!
+//! Admins are the users listed in the `admins` table. Every admin request carries an `AdminAuth` signed with the
+//! admin's key over the action and a recent unix time, so a captured request can't be replayed later.
+//!
+//! Every authorized admin request, and every moderator action on a game, is appended to the `admin_log` table with
+//! its signature, so anyone can check what interventions happened and who signed them. (see `/admin/log`)
+//!
+//! Quarantined users keep playing as usual, but their moves are stored with `canonical` unset: they are left out of
+//! the game for everyone else and trigger no notifications, while the user still sees them on their own branch.
+//! Flagged users are trusted less than that: their moves wait for a moderator of the game. (see `review`)
+
+use poem::web::Data;
+use poem_openapi::{
+    param::Query,
+    payload::Json,
+    types::{ParseFromJSON, ToJSON, Type},
+    Object, OpenApi,
+};
+use sqlx::{mysql::MySqlPool, query};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tanktacticsgame::{get_key, verify_data, MoveLine};
+
+use crate::{
+    backup::{self, BackupTarget},
+    storage::read_move,
+    table::CustomResponse,
+};
+
+/// How far the time of an admin request may be from the server time, in seconds.
+const MAX_CLOCK_SKEW: u64 = 300;
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[derive(Object)]
+pub struct AdminAuth {
+    /// The admin making the request.
+    pub admin: i32,
+    /// The unix time at which the request was signed.
+    pub time: u64,
+    /// `action|time` signed by the admin.
+    pub signature: String,
+}
+impl AdminAuth {
+    /// Checks that the request for `action` was recently signed by an admin.
+    pub(crate) async fn check<T: Type + ToJSON>(
+        &self,
+        pool: &MySqlPool,
+        action: &str,
+    ) -> Result<(), CustomResponse<T>> {
+        if now().abs_diff(self.time) > MAX_CLOCK_SKEW {
+            return Err(CustomResponse::error("Request expired.", false));
+        }
+        let key = query!(
+            "SELECT users.public_key FROM admins, users WHERE admins.user = ? AND users.id = admins.user",
+            self.admin
+        )
+        .fetch_one(pool)
+        .await
+        .ok()
+        .and_then(|r| get_key(r.public_key))
+        .ok_or(CustomResponse::error("Not an admin.", false))?;
+        if !verify_data(&key, &format!("{action}|{}", self.time), &self.signature) {
+            return Err(CustomResponse::error("Invalid signature.", false));
+        }
+        record(pool, self.admin, None, action, self.time, &self.signature).await
+    }
+}
+
+/// An admin or moderator action, see `/admin/log`.
+#[derive(Object)]
+pub(crate) struct LogEntry {
+    id: i32,
+    /// The admin or moderator that signed the action.
+    user: i32,
+    /// The game the action was taken in, if any.
+    game: Option<i32>,
+    action: String,
+    time: u64,
+    /// `action|time` signed by `user`.
+    signature: String,
+}
+
+/// Appends an authorized action to the admin log. The action is refused if it can't be logged.
+pub(crate) async fn record<T: Type + ToJSON>(
+    pool: &MySqlPool,
+    user: i32,
+    game: Option<i32>,
+    action: &str,
+    time: u64,
+    signature: &str,
+) -> Result<(), CustomResponse<T>> {
+    query!(
+        "INSERT INTO admin_log (user, game, action, time, signature) VALUES (?, ?, ?, ?, ?);",
+        user,
+        game,
+        action,
+        time,
+        signature
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+    Ok(())
+}
+
+/// The admin log, oldest first, of a single game if `game` is given.
+pub(crate) async fn read_log(
+    pool: &MySqlPool,
+    game: Option<i32>,
+) -> Result<Vec<LogEntry>, sqlx::Error> {
+    sqlx::query_as!(
+        LogEntry,
+        "SELECT id, user, game, action, time, signature FROM admin_log WHERE ? IS NULL OR game = ? ORDER BY id",
+        game,
+        game
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The admin and moderator actions signed by `user`, oldest first.
+pub(crate) async fn signed_by(pool: &MySqlPool, user: i32) -> Result<Vec<LogEntry>, sqlx::Error> {
+    sqlx::query_as!(
+        LogEntry,
+        "SELECT id, user, game, action, time, signature FROM admin_log WHERE user = ? ORDER BY id",
+        user
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Why a mutating request was refused.
+#[derive(Object)]
+pub struct Unavailable {
+    pub message: String,
+    /// The unix time at which maintenance mode was enabled.
+    pub since: u64,
+}
+
+/// A move made by a quarantined user.
+#[derive(Object)]
+struct ShadowMove {
+    game: i32,
+    /// The index of the move on the branch of the user.
+    index: i32,
+    line: MoveLine,
+}
+
+/// Whether the server is in maintenance mode, refusing every request that changes the database.
+#[derive(Clone, Default)]
+pub struct Maintenance(Arc<AtomicU64>);
+impl Maintenance {
+    /// Refuses the request with a 503 while maintenance mode is enabled.
+    pub(crate) fn check<T: Type + ToJSON>(&self) -> Result<(), CustomResponse<T>> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => Ok(()),
+            since => Err(CustomResponse::Unavailable(Json(Unavailable {
+                message: "The server is in maintenance mode, only reads are allowed.".into(),
+                since,
+            }))),
+        }
+    }
+    fn set(&self, enabled: bool) {
+        self.0
+            .store(if enabled { now().max(1) } else { 0 }, Ordering::Relaxed);
+    }
+}
+
+pub struct AdminAPI;
+
+#[OpenApi]
+impl AdminAPI {
+    /// Returns every admin and moderator action, or only those taken in `game`, oldest first.
+    #[oai(path = "/admin/log", method = "get")]
+    async fn log(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<Option<i32>>,
+    ) -> CustomResponse<Vec<LogEntry>> {
+        read_log(pool.0, game)
+            .await
+            .map(|entries| CustomResponse::Ok(Json(entries)))
+            .unwrap_or_else(|e| CustomResponse::error(&format!("SQL error: {e}."), true))
+    }
+    /// Writes a backup of all users, games and moves to the configured backup target. Returns where the backup was written.
+    #[oai(path = "/admin/backup", method = "post")]
+    async fn backup(
+        &self,
+        pool: Data<&MySqlPool>,
+        Json(auth): Json<AdminAuth>,
+    ) -> CustomResponse<String> {
+        auth.check(pool.0, "backup").await?;
+        let target =
+            BackupTarget::from_env().map_err(|e| CustomResponse::error(&format!("{e}"), true))?;
+        backup::run(pool.0, &target)
+            .await
+            .map(|location| CustomResponse::Ok(Json(location)))
+            .unwrap_or_else(|e| CustomResponse::error(&format!("{e}"), true))
+    }
+    /// Enables or disables maintenance mode, in which moves, new games and new users are refused. (admin action `maintenance|enabled`)
+    #[oai(path = "/admin/maintenance", method = "post")]
+    async fn maintenance(
+        &self,
+        pool: Data<&MySqlPool>,
+        maintenance: Data<&Maintenance>,
+        Query(enabled): Query<bool>,
+        Json(auth): Json<AdminAuth>,
+    ) -> CustomResponse<bool> {
+        auth.check(pool.0, &format!("maintenance|{enabled}"))
+            .await?;
+        maintenance.0.set(enabled);
+        CustomResponse::Ok(Json(enabled))
+    }
+    /// Quarantines a user or lifts their quarantine. Moves made during the quarantine stay off the games. (admin action `quarantine|user|enabled`)
+    /// From their first move in quarantine on, the user no longer sees the moves of others. (see `GameAPI::read_branch`)
+    #[oai(path = "/admin/quarantine", method = "post")]
+    async fn quarantine(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(user): Query<i32>,
+        Query(enabled): Query<bool>,
+        Json(auth): Json<AdminAuth>,
+    ) -> CustomResponse<bool> {
+        auth.check(pool.0, &format!("quarantine|{user}|{enabled}"))
+            .await?;
+        let result = query!(
+            "UPDATE users SET quarantined = ? WHERE id = ?;",
+            enabled,
+            user
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        if result.rows_affected() == 0 {
+            return CustomResponse::error("User does not exist.", false);
+        }
+        CustomResponse::Ok(Json(enabled))
+    }
+    /// Flags a user or lifts their flag. Moves of flagged users wait for a moderator of the game, see `review`. (admin action `flag|user|enabled`)
+    #[oai(path = "/admin/flag", method = "post")]
+    async fn flag(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(user): Query<i32>,
+        Query(enabled): Query<bool>,
+        Json(auth): Json<AdminAuth>,
+    ) -> CustomResponse<bool> {
+        auth.check(pool.0, &format!("flag|{user}|{enabled}"))
+            .await?;
+        let result = query!("UPDATE users SET flagged = ? WHERE id = ?;", enabled, user)
+            .execute(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        if result.rows_affected() == 0 {
+            return CustomResponse::error("User does not exist.", false);
+        }
+        CustomResponse::Ok(Json(enabled))
+    }
+    /// Returns the moves a user made while quarantined, for review. (admin action `shadow_moves|user`)
+    #[oai(path = "/admin/shadow_moves", method = "post")]
+    async fn shadow_moves(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(user): Query<i32>,
+        Json(auth): Json<AdminAuth>,
+    ) -> CustomResponse<Vec<ShadowMove>> {
+        auth.check(pool.0, &format!("shadow_moves|{user}")).await?;
+        let rows = query!(
+            "SELECT game, `index`, token FROM moves WHERE user = ? AND NOT canonical ORDER BY game, `index`;",
+            user
+        )
+        .fetch_all(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        let moves = rows
+            .into_iter()
+            .map(|r| {
+                Some(ShadowMove {
+                    game: r.game,
+                    index: r.index,
+                    line: read_move(&r.token).ok()?,
+                })
+            })
+            .collect::<Option<_>>()
+            .ok_or(CustomResponse::error("Corrupted move.", true))?;
+        CustomResponse::Ok(Json(moves))
+    }
+}
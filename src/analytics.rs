@@ -0,0 +1,98 @@
+//! Outcome analytics per game setting.
+//!
+//! Every game is replayed from its moves and grouped by its settings, so presets can be tuned on how real games went.
+//! A game is won once at least two players joined and at most one of them is alive.
+
+use poem::web::Data;
+use poem_openapi::{payload::Json, Object, OpenApi};
+use sqlx::{mysql::MySqlPool, query};
+use std::collections::BTreeMap;
+
+use crate::table::{CustomResponse, GameAPI};
+
+#[derive(Object)]
+struct BoardSizeOutcome {
+    width: u32,
+    height: u32,
+    /// The number of games with this board size.
+    games: u32,
+    /// The share of those games that have a winner.
+    win_rate: f64,
+}
+#[derive(Object)]
+struct VoteThresholdOutcome {
+    /// The threshold mode, in the form stored with games. (see `ThresholdMode`)
+    vote_threshold: String,
+    /// The number of won games with this threshold.
+    won: u32,
+    /// The average number of rounds the won games took.
+    average_rounds: f64,
+}
+#[derive(Object)]
+struct SettingsAnalytics {
+    board_sizes: Vec<BoardSizeOutcome>,
+    vote_thresholds: Vec<VoteThresholdOutcome>,
+}
+
+pub struct AnalyticsAPI;
+
+#[OpenApi]
+impl AnalyticsAPI {
+    /// Returns how games ended, grouped by board size and by vote threshold.
+    #[oai(path = "/analytics/settings", method = "get")]
+    async fn settings(&self, pool: Data<&MySqlPool>) -> CustomResponse<SettingsAnalytics> {
+        let ids = query!("SELECT id FROM games")
+            .fetch_all(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        // (games, won) per board size and (won, rounds) per vote threshold.
+        let mut sizes = BTreeMap::<(u32, u32), (u32, u32)>::new();
+        let mut thresholds = BTreeMap::<String, (u32, u32)>::new();
+        for id in ids {
+            let Ok((game, _)) = GameAPI
+                .load_game::<SettingsAnalytics>(pool.0, id.id, 0)
+                .await
+            else {
+                tracing::warn!("Skipping corrupted game ({}) in analytics.", id.id);
+                continue;
+            };
+            let alive = game
+                .players
+                .values()
+                .filter(|p| p.is_alive(true).is_ok())
+                .count();
+            let won = game.players.len() >= 2 && alive <= 1;
+            let size = sizes
+                .entry((game.settings.width, game.settings.height))
+                .or_default();
+            size.0 += 1;
+            if won {
+                size.1 += 1;
+                let threshold = thresholds
+                    .entry(game.settings.threshold_mode.to_string())
+                    .or_default();
+                threshold.0 += 1;
+                threshold.1 += game.round;
+            }
+        }
+        CustomResponse::Ok(Json(SettingsAnalytics {
+            board_sizes: sizes
+                .into_iter()
+                .map(|((width, height), (games, won))| BoardSizeOutcome {
+                    width,
+                    height,
+                    games,
+                    win_rate: f64::from(won) / f64::from(games),
+                })
+                .collect(),
+            vote_thresholds: thresholds
+                .into_iter()
+                .map(|(vote_threshold, (won, rounds))| VoteThresholdOutcome {
+                    vote_threshold,
+                    won,
+                    average_rounds: f64::from(rounds) / f64::from(won),
+                })
+                .collect(),
+        }))
+    }
+}
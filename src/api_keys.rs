@@ -0,0 +1,323 @@
+//! Limits on how often the API can be read, and keys that come with a limit of their own.
+//!
+//! Anonymous reads (every `GET` on the API, and `/verify_chain`, which replays a whole chain) are limited to `--reads-per-minute` per address. Community tools such as
+//! stats sites and bots can be issued a key by an admin, sent as the `X-Api-Key` header, which gets its own limit per
+//! minute instead. A key only changes how often the API can be read: every other request is handled as usual.
+//!
+//! A read over the limit gets a `429` with a `Retry-After` header, a read with an unknown or revoked key a `401`.
+//! Every allowed read carries its remaining budget in `X-RateLimit-Remaining`. The reads made and refused per key are
+//! added to `api_keys` every minute, see `/admin/api_keys/list`.
+
+use poem::{
+    http::{Method, StatusCode},
+    web::Data,
+    Endpoint, IntoResponse, Request, Response,
+};
+use poem_openapi::{param::Query, payload::Json, Object, OpenApi};
+use rand_chacha::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use sqlx::{mysql::MySqlPool, query};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use crate::{
+    admin::{now, AdminAuth},
+    table::CustomResponse,
+};
+
+/// The header a key is sent in.
+const HEADER: &str = "X-Api-Key";
+/// The length of a rate limit window, in seconds.
+const WINDOW: u64 = 60;
+/// The requests other than `GET`s that count as reads, as they are as costly as one.
+const COSTLY_POSTS: [&str; 1] = ["/verify_chain"];
+
+#[derive(Clone, Copy)]
+struct Key {
+    id: i32,
+    per_minute: u32,
+}
+
+/// Who a read is counted for.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Client {
+    Key(i32),
+    Address(IpAddr),
+}
+
+#[derive(Default)]
+struct Usage {
+    requests: u64,
+    limited: u64,
+    last_used: u64,
+}
+
+/// Why a read was refused.
+enum Refusal {
+    UnknownKey,
+    /// Over the limit until the given unix time.
+    Limited(u64),
+}
+
+/// The issued keys and the reads counted against them this minute.
+pub struct ApiKeys {
+    /// The reads per minute per address without a key, unlimited if `None`.
+    anonymous: Option<u32>,
+    /// The keys that are not revoked, by the sha256 of the key.
+    keys: Mutex<HashMap<String, Key>>,
+    /// The start of the current window, and the reads counted in it.
+    windows: Mutex<(u64, HashMap<Client, u32>)>,
+    /// The reads per key since the last flush.
+    usage: Mutex<HashMap<i32, Usage>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn hash(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+impl ApiKeys {
+    /// Reads the keys that are not revoked.
+    pub async fn load(pool: &MySqlPool, anonymous: Option<u32>) -> Result<Self, sqlx::Error> {
+        let keys = query!("SELECT id, key_hash, per_minute FROM api_keys WHERE NOT revoked")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|r| {
+                let key = Key {
+                    id: r.id,
+                    per_minute: r.per_minute,
+                };
+                (r.key_hash, key)
+            })
+            .collect();
+        Ok(ApiKeys {
+            anonymous,
+            keys: Mutex::new(keys),
+            windows: Mutex::default(),
+            usage: Mutex::default(),
+        })
+    }
+    /// Counts a read with `key` or from `address`, returning how many reads are left this minute if it is limited.
+    fn count(&self, key: Option<&str>, address: Option<IpAddr>) -> Result<Option<u32>, Refusal> {
+        let (client, limit) = match key {
+            Some(key) => {
+                let key = *lock(&self.keys)
+                    .get(&hash(key))
+                    .ok_or(Refusal::UnknownKey)?;
+                (Client::Key(key.id), key.per_minute)
+            }
+            None => match (address, self.anonymous) {
+                (Some(address), Some(limit)) => (Client::Address(address), limit),
+                _ => return Ok(None),
+            },
+        };
+        let time = now();
+        let (allowed, left, reset) = {
+            let mut windows = lock(&self.windows);
+            let (start, counts) = &mut *windows;
+            if time >= *start + WINDOW {
+                *start = time - time % WINDOW;
+                counts.clear();
+            }
+            let count = counts.entry(client).or_default();
+            let allowed = *count < limit;
+            if allowed {
+                *count += 1;
+            }
+            (allowed, limit - *count, *start + WINDOW)
+        };
+        if let Client::Key(id) = client {
+            let mut usage = lock(&self.usage);
+            let usage = usage.entry(id).or_default();
+            usage.last_used = time;
+            if allowed {
+                usage.requests += 1;
+            } else {
+                usage.limited += 1;
+            }
+        }
+        if allowed {
+            Ok(Some(left))
+        } else {
+            Err(Refusal::Limited(reset))
+        }
+    }
+    /// Adds the reads counted since the last flush to `api_keys`.
+    async fn flush(&self, pool: &MySqlPool) -> Result<(), sqlx::Error> {
+        let usage = std::mem::take(&mut *lock(&self.usage));
+        for (id, usage) in usage {
+            query!(
+                "UPDATE api_keys SET requests = requests + ?, limited = limited + ?, last_used = ? WHERE id = ?;",
+                usage.requests,
+                usage.limited,
+                usage.last_used,
+                id
+            )
+            .execute(pool)
+            .await?;
+        }
+        Ok(())
+    }
+    /// Keeps adding the reads of every key to `api_keys`, every minute.
+    pub async fn run(self: Arc<Self>, pool: MySqlPool) {
+        loop {
+            tokio::time::sleep(Duration::from_secs(WINDOW)).await;
+            if let Err(e) = self.flush(&pool).await {
+                tracing::warn!("Could not record the API key usage: {e}");
+            }
+        }
+    }
+}
+
+/// Limits the reads of `endpoint`, see the module docs.
+pub async fn limit<E: Endpoint>(
+    endpoint: Arc<E>,
+    request: Request,
+    keys: Arc<ApiKeys>,
+) -> poem::Result<Response> {
+    let read = request.method() == Method::GET
+        || (request.method() == Method::POST && COSTLY_POSTS.contains(&request.uri().path()));
+    if !read {
+        return Ok(endpoint.call(request).await?.into_response());
+    }
+    let key = request.header(HEADER).map(str::to_string);
+    let address = request.remote_addr().as_socket_addr().map(|a| a.ip());
+    match keys.count(key.as_deref(), address) {
+        Ok(left) => {
+            let mut response = endpoint.call(request).await?.into_response();
+            if let Some(left) = left {
+                response
+                    .headers_mut()
+                    .insert("X-RateLimit-Remaining", left.into());
+            }
+            Ok(response)
+        }
+        Err(Refusal::UnknownKey) => Ok(Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body("Unknown or revoked API key.")),
+        Err(Refusal::Limited(until)) => Ok(Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header("Retry-After", until.saturating_sub(now()).max(1))
+            .body("Too many reads, try again later.")),
+    }
+}
+
+#[derive(Object)]
+struct NewApiKey {
+    auth: AdminAuth,
+    /// Who the key is for, such as the name of the tool.
+    name: String,
+    per_minute: u32,
+}
+
+/// A newly issued key. The key itself is not stored, so it can't be shown again.
+#[derive(Object)]
+struct IssuedApiKey {
+    id: i32,
+    key: String,
+}
+
+/// An issued key and how much it was used.
+#[derive(Object)]
+struct ApiKeyUsage {
+    id: i32,
+    name: String,
+    per_minute: u32,
+    created: u64,
+    revoked: bool,
+    /// The reads made with the key.
+    requests: u64,
+    /// The reads refused for going over `per_minute`.
+    limited: u64,
+    last_used: Option<u64>,
+}
+
+pub struct ApiKeyAPI;
+
+#[OpenApi]
+impl ApiKeyAPI {
+    /// Issues a key for reading the API with its own limit per minute. (admin action `api_key|name|per_minute`)
+    #[oai(path = "/admin/api_keys", method = "post")]
+    async fn issue(
+        &self,
+        pool: Data<&MySqlPool>,
+        keys: Data<&Arc<ApiKeys>>,
+        Json(new): Json<NewApiKey>,
+    ) -> CustomResponse<IssuedApiKey> {
+        new.auth
+            .check(pool.0, &format!("api_key|{}|{}", new.name, new.per_minute))
+            .await?;
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let key = hex::encode(bytes);
+        let key_hash = hash(&key);
+        let id = query!(
+            "INSERT INTO api_keys (name, key_hash, per_minute, created) VALUES (?, ?, ?, ?);",
+            new.name,
+            key_hash,
+            new.per_minute,
+            now()
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+        .last_insert_id();
+        let id = i32::try_from(id).map_err(|_| CustomResponse::error("Too many keys.", true))?;
+        lock(&keys.keys).insert(
+            key_hash,
+            Key {
+                id,
+                per_minute: new.per_minute,
+            },
+        );
+        CustomResponse::Ok(Json(IssuedApiKey { id, key }))
+    }
+    /// Revokes a key, after which reads with it are refused. (admin action `api_key_revoke|id`)
+    #[oai(path = "/admin/api_keys/revoke", method = "post")]
+    async fn revoke(
+        &self,
+        pool: Data<&MySqlPool>,
+        keys: Data<&Arc<ApiKeys>>,
+        Query(id): Query<i32>,
+        Json(auth): Json<AdminAuth>,
+    ) -> CustomResponse<bool> {
+        auth.check(pool.0, &format!("api_key_revoke|{id}")).await?;
+        let result = query!("UPDATE api_keys SET revoked = TRUE WHERE id = ?;", id)
+            .execute(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        lock(&keys.keys).retain(|_, key| key.id != id);
+        CustomResponse::Ok(Json(result.rows_affected() > 0))
+    }
+    /// Returns every issued key with its usage, including the reads of the current minute. (admin action `api_keys`)
+    #[oai(path = "/admin/api_keys/list", method = "post")]
+    async fn list(
+        &self,
+        pool: Data<&MySqlPool>,
+        keys: Data<&Arc<ApiKeys>>,
+        Json(auth): Json<AdminAuth>,
+    ) -> CustomResponse<Vec<ApiKeyUsage>> {
+        auth.check(pool.0, "api_keys").await?;
+        keys.flush(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        let rows = sqlx::query_as!(
+            ApiKeyUsage,
+            "SELECT id, name, per_minute, created, revoked AS `revoked: bool`, requests, limited, last_used FROM api_keys ORDER BY id"
+        )
+        .fetch_all(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(rows))
+    }
+}
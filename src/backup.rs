@@ -0,0 +1,126 @@
+//! Consistent dumps of the whole database.
+//!
+//! Backups are written to `TANKTACTICS_BACKUP_DIR`, or to the bucket `TANKTACTICS_BACKUP_S3_BUCKET` on the
+//! S3-compatible endpoint `TANKTACTICS_BACKUP_S3_ENDPOINT` (using `TANKTACTICS_BACKUP_S3_REGION`,
+//! `TANKTACTICS_BACKUP_S3_ACCESS_KEY` and `TANKTACTICS_BACKUP_S3_SECRET_KEY`).
+
+use s3::{creds::Credentials, Bucket, Region};
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::MySqlPool;
+use std::path::PathBuf;
+use tanktacticsgame::{DataBaseGame, User};
+use thiserror::Error;
+
+use crate::admin::now;
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("Backups are not configured.")]
+    NotConfigured,
+    #[error("SQL error: {0}.")]
+    Sql(#[from] sqlx::Error),
+    #[error("IO error: {0}.")]
+    Io(#[from] std::io::Error),
+    #[error("S3 error: {0}.")]
+    S3(#[from] s3::error::S3Error),
+    #[error("Credentials error: {0}.")]
+    Credentials(#[from] s3::creds::error::CredentialsError),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MoveRow {
+    pub user: i32,
+    pub game: i32,
+    pub index: i32,
+    pub token: String,
+    /// Unset for moves made while the user was quarantined.
+    pub canonical: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Backup {
+    /// The unix time the backup was made.
+    pub created: u64,
+    pub users: Vec<User>,
+    pub games: Vec<DataBaseGame>,
+    pub moves: Vec<MoveRow>,
+}
+impl Backup {
+    /// Reads all tables within a single transaction, so the dump is a consistent snapshot.
+    pub async fn read(pool: &MySqlPool) -> Result<Self, BackupError> {
+        let mut tx = pool.begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .execute(&mut *tx)
+            .await?;
+        let users = sqlx::query_as!(User, "SELECT id, public_key FROM users")
+            .fetch_all(&mut *tx)
+            .await?;
+        let games = sqlx::query_as!(DataBaseGame, "SELECT * FROM games")
+            .fetch_all(&mut *tx)
+            .await?;
+        let moves = sqlx::query_as!(
+            MoveRow,
+            "SELECT user, game, `index`, token, canonical AS `canonical: bool` FROM moves ORDER BY game, `index`"
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(Backup {
+            created: now(),
+            users,
+            games,
+            moves,
+        })
+    }
+}
+
+pub enum BackupTarget {
+    Directory(PathBuf),
+    S3(Bucket),
+}
+impl BackupTarget {
+    pub fn from_env() -> Result<Self, BackupError> {
+        let var = |name: &str| std::env::var(name).ok();
+        if let Some(dir) = var("TANKTACTICS_BACKUP_DIR") {
+            return Ok(BackupTarget::Directory(dir.into()));
+        }
+        let (Some(bucket), Some(endpoint)) = (
+            var("TANKTACTICS_BACKUP_S3_BUCKET"),
+            var("TANKTACTICS_BACKUP_S3_ENDPOINT"),
+        ) else {
+            return Err(BackupError::NotConfigured);
+        };
+        let region = Region::Custom {
+            region: var("TANKTACTICS_BACKUP_S3_REGION").unwrap_or_default(),
+            endpoint,
+        };
+        let credentials = Credentials::new(
+            var("TANKTACTICS_BACKUP_S3_ACCESS_KEY").as_deref(),
+            var("TANKTACTICS_BACKUP_S3_SECRET_KEY").as_deref(),
+            None,
+            None,
+            None,
+        )?;
+        Ok(BackupTarget::S3(
+            Bucket::new(&bucket, region, credentials)?.with_path_style(),
+        ))
+    }
+}
+
+/// Writes a backup to `target`, returning where it was written.
+pub async fn run(pool: &MySqlPool, target: &BackupTarget) -> Result<String, BackupError> {
+    let backup = Backup::read(pool).await?;
+    let name = format!("backup-{}.json", backup.created);
+    let data = serde_json::to_vec(&backup).map_err(std::io::Error::from)?;
+    match target {
+        BackupTarget::Directory(dir) => {
+            let path = dir.join(name);
+            tokio::fs::write(&path, data).await?;
+            Ok(path.display().to_string())
+        }
+        BackupTarget::S3(bucket) => {
+            bucket.put_object(&name, &data).await?;
+            Ok(format!("s3://{}/{name}", bucket.name()))
+        }
+    }
+}
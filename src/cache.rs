@@ -0,0 +1,82 @@
+//! The states of the games played recently, so a move doesn't replay and verify the whole chain again.
+//!
+//! A cached state is only used while the database still ends with the same move, as the chain can be changed from
+//! outside the server (see `chain`) or by another server on the same database. Otherwise the game is replayed and the
+//! new state replaces the old one, checking only the signatures that were not verified before. (see `VerifiedCache`)
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+use tanktacticsgame::{Game, VerifiedCache};
+
+/// The most games kept in memory, the least recently used one is dropped for a new one.
+const CAPACITY: usize = 256;
+/// The most verified signatures kept in memory, about 100 bytes each.
+const VERIFIED: usize = 200_000;
+
+pub struct GameCache {
+    games: Mutex<Games>,
+    /// The signatures of the moves replayed so far.
+    pub verified: VerifiedCache,
+}
+impl Default for GameCache {
+    fn default() -> Self {
+        GameCache {
+            games: Mutex::default(),
+            verified: VerifiedCache::new(VERIFIED),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Games {
+    /// The uses of the cache so far.
+    uses: u64,
+    /// The states by game, with the use they were last used at.
+    states: HashMap<i32, (u64, Game)>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+impl GameCache {
+    /// A copy of the state of `game`, if it is cached with `moves` moves of which the last is signed with `signature`.
+    pub fn get(&self, game: i32, moves: usize, signature: &str) -> Option<Game> {
+        let mut games = lock(&self.games);
+        games.uses += 1;
+        let at = games.uses;
+        let (used, state) = games.states.get_mut(&game)?;
+        let last = state
+            .lines
+            .last()
+            .map_or("", |line| line.signature.as_str());
+        if state.lines.len() != moves || last != signature {
+            games.states.remove(&game);
+            return None;
+        }
+        *used = at;
+        Some(state.clone())
+    }
+    /// Caches `state` as the state of `game`, without the events of getting there.
+    pub fn insert(&self, game: i32, mut state: Game) {
+        state.drain_events();
+        let mut games = lock(&self.games);
+        games.uses += 1;
+        let at = games.uses;
+        if games.states.len() >= CAPACITY && !games.states.contains_key(&game) {
+            if let Some(oldest) = games
+                .states
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(id, _)| *id)
+            {
+                games.states.remove(&oldest);
+            }
+        }
+        games.states.insert(game, (at, state));
+    }
+}
@@ -0,0 +1,276 @@
+//! Offline repair of the move chain of a game, for operators recovering from corruption.
+//!
+//! `inspect` lists every canonical move and where the replay first fails, `verify` only reports the failure.
+//! `truncate` drops every move from an index on and `rebuild` recomputes what is derived from the moves: the `rounds`
+//! summaries, the `game_players` of the game and `games.last_vote`. Both change the database in a single transaction
+//! and append an `admin_log` entry signed with the key of an admin, just like an admin request would.
+
+use clap::Subcommand;
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use poem_openapi::types::ToJSON;
+use sqlx::{mysql::MySqlPool, query, MySql, Transaction};
+use std::{collections::HashMap, path::PathBuf};
+use tanktacticsgame::{get_key, sign_data, verify_data, DataBaseGame, Game, MoveLine, PublicKey};
+
+use crate::{admin::now, storage::read_move};
+
+#[derive(Subcommand)]
+pub enum ChainCommand {
+    /// List the canonical moves of a game, marking where the replay fails.
+    Inspect { game: i32 },
+    /// Replay a game, reporting the first move that doesn't verify.
+    Verify { game: i32 },
+    /// Drop every move of a game from `index` on, then rebuild what is derived from the moves.
+    Truncate {
+        game: i32,
+        index: i32,
+        #[command(flatten)]
+        signer: Signer,
+    },
+    /// Recompute the round summaries, players and last vote of a game from its moves.
+    Rebuild {
+        game: i32,
+        #[command(flatten)]
+        signer: Signer,
+    },
+}
+
+/// The admin signing the `admin_log` entry of a change.
+#[derive(clap::Args)]
+pub struct Signer {
+    /// The admin taking responsibility for the change.
+    #[arg(long)]
+    admin: i32,
+    /// A file holding the base 64 private key of the admin.
+    #[arg(long)]
+    key_file: PathBuf,
+}
+impl Signer {
+    /// Signs `action|time` with the key of the admin, checking it is the key the server knows for them.
+    async fn sign(&self, pool: &MySqlPool, action: &str, time: u64) -> Result<String> {
+        let private = std::fs::read_to_string(&self.key_file)
+            .wrap_err_with(|| format!("Could not read {}.", self.key_file.display()))?;
+        let data = format!("{action}|{time}");
+        let signature = sign_data(&data, private.trim().to_string())
+            .map_err(|e| eyre!("Could not sign with the key: {e}"))?;
+        let key = query!(
+            "SELECT users.public_key FROM admins, users WHERE admins.user = ? AND users.id = admins.user",
+            self.admin
+        )
+        .fetch_optional(pool)
+        .await?
+        .and_then(|r| get_key(r.public_key))
+        .ok_or_else(|| eyre!("User {} is not an admin.", self.admin))?;
+        if !verify_data(&key, &data, &signature) {
+            bail!("The key does not belong to admin {}.", self.admin);
+        }
+        Ok(signature)
+    }
+}
+
+/// A game replayed from its stored moves.
+struct Replay {
+    game: Game,
+    /// Every canonical move as stored, by index.
+    tokens: Vec<(i32, String)>,
+    /// The position in `tokens` of the first move that doesn't verify, and why.
+    failure: Option<(usize, String)>,
+}
+
+async fn replay(pool: &MySqlPool, game_id: i32) -> Result<Replay> {
+    let record = sqlx::query_as!(DataBaseGame, "SELECT * FROM games WHERE id = ?", game_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| eyre!("Game {game_id} does not exist."))?;
+    let tokens = query!(
+        "SELECT `index`, token FROM moves WHERE game = ? AND canonical ORDER BY `index`",
+        game_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| (r.index, r.token))
+    .collect::<Vec<_>>();
+    let users = query!(
+        "SELECT DISTINCT users.id, users.public_key FROM moves, users WHERE moves.game = ? AND moves.user = users.id",
+        game_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .filter_map(|r| get_key(r.public_key).map(|key| (r.id, key)))
+    .collect::<HashMap<i32, PublicKey>>();
+    let mut game = record
+        .as_game(Vec::new(), &users)
+        .map_err(|e| eyre!("Game {game_id} has malformed settings: {e}"))?;
+    let mut failure = None;
+    for (position, ((index, token), expected)) in tokens.iter().zip(0..).enumerate() {
+        let result = if *index == expected {
+            read_move(token)
+                .map_err(|e| e.to_string())
+                .and_then(|line| game.load(line, &users).map_err(|e| e.to_string()))
+        } else {
+            Err(format!("Expected move {expected} here."))
+        };
+        if let Err(e) = result {
+            failure = Some((position, e));
+            break;
+        }
+    }
+    Ok(Replay {
+        game,
+        tokens,
+        failure,
+    })
+}
+
+/// Recomputes the round summaries, players and last vote of `game` from its moves.
+async fn rebuild(
+    transaction: &mut Transaction<'_, MySql>,
+    game_id: i32,
+    game: &Game,
+) -> Result<()> {
+    query!("DELETE FROM rounds WHERE game = ?;", game_id)
+        .execute(&mut **transaction)
+        .await?;
+    for summary in &game.rounds {
+        query!(
+            "INSERT INTO rounds (game, round, summary) VALUES (?, ?, ?);",
+            game_id,
+            summary.round,
+            summary.to_json_string()
+        )
+        .execute(&mut **transaction)
+        .await?;
+    }
+    query!("DELETE FROM game_players WHERE game = ?;", game_id)
+        .execute(&mut **transaction)
+        .await?;
+    query!(
+        "INSERT INTO game_players (game, user, canonical) SELECT game, user, MAX(canonical) FROM moves WHERE game = ? GROUP BY game, user;",
+        game_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    query!(
+        "UPDATE games SET last_vote = ? WHERE id = ?;",
+        game.last_vote,
+        game_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Runs a chain command, printing what it found or did.
+pub async fn run(pool: &MySqlPool, command: ChainCommand) -> Result<()> {
+    match command {
+        ChainCommand::Inspect { game } => {
+            let replay = replay(pool, game).await?;
+            for (position, (index, token)) in replay.tokens.iter().enumerate() {
+                let line = read_move(token).map_or_else(|_| token.clone(), |line| line.to_string());
+                match &replay.failure {
+                    Some((failed, e)) if *failed == position => println!("{index}\t{line}\t<- {e}"),
+                    _ => println!("{index}\t{line}"),
+                }
+            }
+            report(game, &replay);
+        }
+        ChainCommand::Verify { game } => report(game, &replay(pool, game).await?),
+        ChainCommand::Truncate {
+            game,
+            index,
+            signer,
+        } => {
+            let time = now();
+            let action = format!("chain_truncate|{game}|{index}");
+            let signature = signer.sign(pool, &action, time).await?;
+            let mut transaction = pool.begin().await?;
+            let removed = query!(
+                "DELETE FROM moves WHERE game = ? AND `index` >= ?;",
+                game,
+                index
+            )
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+            log(
+                &mut transaction,
+                signer.admin,
+                game,
+                &action,
+                time,
+                &signature,
+            )
+            .await?;
+            transaction.commit().await?;
+            println!("Removed {removed} moves of game {game}.");
+            rebuild_game(pool, game, &signer).await?;
+        }
+        ChainCommand::Rebuild { game, signer } => rebuild_game(pool, game, &signer).await?,
+    }
+    Ok(())
+}
+
+/// Rebuilds `game` if its moves verify.
+async fn rebuild_game(pool: &MySqlPool, game: i32, signer: &Signer) -> Result<()> {
+    let replay = replay(pool, game).await?;
+    if let Some((position, e)) = &replay.failure {
+        bail!(
+            "Move {} of game {game} does not verify: {e} Truncate it first.",
+            replay.tokens[*position].0
+        );
+    }
+    let time = now();
+    let action = format!("chain_rebuild|{game}");
+    let signature = signer.sign(pool, &action, time).await?;
+    let mut transaction = pool.begin().await?;
+    rebuild(&mut transaction, game, &replay.game).await?;
+    log(
+        &mut transaction,
+        signer.admin,
+        game,
+        &action,
+        time,
+        &signature,
+    )
+    .await?;
+    transaction.commit().await?;
+    println!(
+        "Rebuilt game {game}: {} moves, {} rounds.",
+        replay.tokens.len(),
+        replay.game.rounds.len()
+    );
+    Ok(())
+}
+
+fn report(game: i32, replay: &Replay) {
+    match &replay.failure {
+        Some((position, e)) => println!(
+            "Game {game} fails at move {}: {e}",
+            replay.tokens[*position].0
+        ),
+        None => println!("All {} moves of game {game} verify.", replay.tokens.len()),
+    }
+}
+
+async fn log(
+    transaction: &mut Transaction<'_, MySql>,
+    admin: i32,
+    game: i32,
+    action: &str,
+    time: u64,
+    signature: &str,
+) -> Result<()> {
+    query!(
+        "INSERT INTO admin_log (user, game, action, time, signature) VALUES (?, ?, ?, ?, ?);",
+        admin,
+        game,
+        action,
+        time,
+        signature
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! The published client build.
+//!
+//! With `--client-bundle` the server hashes the frontend wasm bundle it is deployed with and publishes the hash,
+//! signed with the server key, on `/client_info`. The frontend hashes the bundle it was served and warns the user when
+//! the two differ, or when the server key changed since it was first seen.
+
+use color_eyre::eyre::{Result, WrapErr};
+use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+use poem::web::Data;
+use poem_openapi::{payload::Json, Object, OpenApi};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::table::CustomResponse;
+
+/// The hex SHA-256 of the client bundle, if one was given.
+#[derive(Clone)]
+pub struct ClientBuild(Option<String>);
+impl ClientBuild {
+    pub fn read(bundle: Option<&Path>) -> Result<Self> {
+        let Some(bundle) = bundle else {
+            return Ok(ClientBuild(None));
+        };
+        let data = std::fs::read(bundle)
+            .wrap_err_with(|| format!("Could not read {}.", bundle.display()))?;
+        Ok(ClientBuild(Some(hex::encode(Sha256::digest(data)))))
+    }
+}
+
+#[derive(Object)]
+struct ClientInfo {
+    /// The hex SHA-256 of the wasm bundle of the frontend.
+    hash: String,
+    /// The public key of the server.
+    public_key: String,
+    /// `client|hash` signed by the server.
+    signature: String,
+}
+
+pub struct ClientAPI;
+
+#[OpenApi]
+impl ClientAPI {
+    /// Returns the hash of the frontend build this server publishes, signed by the server.
+    #[oai(path = "/client_info", method = "get")]
+    async fn client_info(
+        &self,
+        keys: Data<&(SigningKey, String)>,
+        build: Data<&ClientBuild>,
+    ) -> CustomResponse<ClientInfo> {
+        let Some(hash) = build.0 .0.clone() else {
+            return CustomResponse::error("No client build is published.", false);
+        };
+        let signature: Signature = keys.0 .0.sign(format!("client|{hash}").as_bytes());
+        CustomResponse::Ok(Json(ClientInfo {
+            hash,
+            public_key: keys.0 .1.clone(),
+            signature: signature.to_string(),
+        }))
+    }
+}
@@ -0,0 +1,109 @@
+//! The settings the server starts with.
+//!
+//! The database, listen address, secret file and allowed origins can be given on the command line, in their environment
+//! variable, or in the TOML file given with `--config` (`TANKTACTICS_CONFIG`), for example:
+//!
+//! ```toml
+//! database_url = "mysql://tanktactics@localhost/tanktactics"
+//! bind = "0.0.0.0:3000"
+//! secret_file = "/etc/tanktactics/secret.txt"
+//! allowed_origins = ["https://tanktactics.example"]
+//! ```
+//!
+//! The command line and environment take precedence over the file. Without any allowed origins, every origin may call
+//! the API.
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use poem::{http::HeaderValue, middleware::Cors};
+use std::path::{Path, PathBuf};
+
+/// The address the server listens on when none is configured.
+pub const DEFAULT_BIND: &str = "127.0.0.1:3000";
+/// The secret file the server reads when none is configured.
+pub const DEFAULT_SECRET_FILE: &str = "secret.txt";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// The MySQL database to use, only the mirror and playtest servers run without one.
+    pub database_url: Option<String>,
+    pub bind: String,
+    /// The file holding the base 64 server signing key, and the public key on the next line.
+    pub secret_file: PathBuf,
+    /// The origins browsers may call the API from, any origin if empty.
+    pub allowed_origins: Vec<String>,
+}
+impl Config {
+    /// Fills the settings not given on the command line or in the environment from the file at `path`, and the rest
+    /// from the defaults.
+    pub fn load(path: Option<&Path>, given: Config) -> Result<Config> {
+        let mut config = match path {
+            Some(path) => read(path)?,
+            None => Config::default(),
+        };
+        if given.database_url.is_some() {
+            config.database_url = given.database_url;
+        }
+        if !given.bind.is_empty() {
+            config.bind = given.bind;
+        }
+        if !given.secret_file.as_os_str().is_empty() {
+            config.secret_file = given.secret_file;
+        }
+        if !given.allowed_origins.is_empty() {
+            config.allowed_origins = given.allowed_origins;
+        }
+        if config.bind.is_empty() {
+            config.bind = DEFAULT_BIND.into();
+        }
+        if config.secret_file.as_os_str().is_empty() {
+            config.secret_file = DEFAULT_SECRET_FILE.into();
+        }
+        if let Some(origin) = config
+            .allowed_origins
+            .iter()
+            .find(|origin| HeaderValue::from_str(origin).is_err())
+        {
+            return Err(eyre!("{origin} is not a valid origin."));
+        }
+        Ok(config)
+    }
+    /// The CORS middleware allowing the configured origins.
+    pub fn cors(&self) -> Cors {
+        Cors::new().allow_origins(self.allowed_origins.iter().map(String::as_str))
+    }
+}
+
+/// Reads the settings in the TOML file at `path`.
+fn read(path: &Path) -> Result<Config> {
+    let text = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Could not read {}.", path.display()))?;
+    let document = text
+        .parse::<toml_edit::Document>()
+        .wrap_err_with(|| format!("{} is not valid TOML.", path.display()))?;
+    let mut config = Config::default();
+    for (key, item) in document.iter() {
+        let text = || {
+            item.as_str()
+                .map(String::from)
+                .ok_or_else(|| eyre!("{key} should be a string."))
+        };
+        match key {
+            "database_url" => config.database_url = Some(text()?),
+            "bind" => config.bind = text()?,
+            "secret_file" => config.secret_file = text()?.into(),
+            "allowed_origins" => {
+                config.allowed_origins = item
+                    .as_array()
+                    .and_then(|origins| {
+                        origins
+                            .iter()
+                            .map(|origin| origin.as_str().map(String::from))
+                            .collect()
+                    })
+                    .ok_or_else(|| eyre!("{key} should be a list of strings."))?;
+            }
+            _ => return Err(eyre!("{key} is not a setting.")),
+        }
+    }
+    Ok(config)
+}
@@ -0,0 +1,223 @@
+//! The public demo game.
+//!
+//! With `--demo` the server keeps a game running that anyone can watch, see `/demo`. Bots fill all but one of its slots,
+//! so a visitor can always join, and together make a single move every `INTERVAL`, which keeps the game slow enough to
+//! follow. Once the game is over a new one is made. Demo games are listed in `demo_games` (`game`), the bots are
+//! ordinary users whose keys are kept in `demo_bots` (`user`, `private_key`), and moderate the game so they can handle
+//! the votes whenever they are out of moves.
+
+use poem::web::Data;
+use poem_openapi::{payload::Json, OpenApi};
+use sqlx::{mysql::MySqlPool, query};
+use std::{sync::Arc, time::Duration};
+use tanktacticsgame::{
+    get_random_keys, DataBaseGame, Game, Hunter, MoveLine, MoveLineType, Strategy, RULES_VERSION,
+};
+
+use crate::{
+    admin::{now, Maintenance},
+    email::Mailer,
+    moderation::set_moderators,
+    sync::GameSync,
+    table::{CustomResponse, GameAPI},
+};
+
+/// How often a bot makes a move.
+const INTERVAL: Duration = Duration::from_secs(20);
+
+fn settings() -> DataBaseGame {
+    DataBaseGame {
+        id: 0,
+        seed: now(),
+        last_vote: 0,
+        width: 8,
+        height: 8,
+        health: 3,
+        max_level: 2,
+        max_players: 5,
+        vote_threshold: 2,
+        range: "L".into(),
+        point_decay: false,
+        jury_weight: 0,
+        loot_mode: "killer".into(),
+        drive_length: 1,
+        rules_version: RULES_VERSION,
+        mutators: String::new(),
+        threshold_mode: String::new(),
+        win_condition: String::new(),
+        starting_points: 1,
+        starting_level: 0,
+        actions_per_round: 0,
+        items: String::new(),
+        two_phase_kills: false,
+        symmetric_spawns: false,
+        gift_cooldown: 0,
+        vote_interval: 0,
+        locale: String::new(),
+        description: String::new(),
+    }
+}
+
+/// The current demo game, if there is one.
+async fn current(pool: &MySqlPool) -> Option<i32> {
+    query!("SELECT MAX(game) AS game FROM demo_games")
+        .fetch_one(pool)
+        .await
+        .ok()?
+        .game
+}
+
+/// Makes a new demo game.
+async fn reset(pool: &MySqlPool) {
+    let CustomResponse::Ok(Json(game)) = GameAPI.create_game(pool, &settings()).await else {
+        tracing::warn!("Could not create a demo game.");
+        return;
+    };
+    if let Err(e) = query!("INSERT INTO demo_games (game) VALUES (?);", game)
+        .execute(pool)
+        .await
+    {
+        tracing::warn!("Could not create a demo game: {e}");
+    }
+}
+
+/// The bots and their private keys, registering new bots until there are `count`.
+async fn bots(pool: &MySqlPool, count: usize) -> Result<Vec<(i32, String)>, sqlx::Error> {
+    let mut bots = query!("SELECT user, private_key FROM demo_bots ORDER BY user")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| (r.user, r.private_key))
+        .collect::<Vec<_>>();
+    while bots.len() < count {
+        let (private_key, public_key) = get_random_keys();
+        let user = query!("INSERT INTO users (public_key) VALUES (?);", public_key)
+            .execute(pool)
+            .await?
+            .last_insert_id()
+            .try_into()
+            .unwrap();
+        query!(
+            "INSERT INTO demo_bots (user, private_key) VALUES (?, ?);",
+            user,
+            private_key
+        )
+        .execute(pool)
+        .await?;
+        bots.push((user, private_key));
+    }
+    Ok(bots)
+}
+
+/// The move of `bot`, played by a `Hunter`. Votes are handled with the current time.
+fn bot_move(game: &Game, bot: i32) -> MoveLine {
+    let mut line = Hunter.next_move(game, bot);
+    if line.move_type == MoveLineType::HandleVotes {
+        line.time = Some(now());
+    }
+    line
+}
+
+/// Makes the next bot move in the demo game, or a new game if it is over.
+async fn tick(
+    pool: &MySqlPool,
+    client: &reqwest::Client,
+    mailer: &Option<Arc<Mailer>>,
+    sync: &GameSync,
+    turn: usize,
+) -> Result<(), sqlx::Error> {
+    let Some(game_id) = current(pool).await else {
+        reset(pool).await;
+        return Ok(());
+    };
+    let Ok((game, _)) = GameAPI.load_game::<i32>(pool, game_id, 0).await else {
+        tracing::warn!("The demo game ({game_id}) is corrupted, starting a new one.");
+        reset(pool).await;
+        return Ok(());
+    };
+    if game.status().is_over() {
+        reset(pool).await;
+        return Ok(());
+    }
+    let seats = usize::try_from(game.settings.max_players - 1).unwrap_or_default();
+    let bots = bots(pool, seats).await?;
+    if game.lines.is_empty() {
+        // Bots handle the votes whenever they are out of moves.
+        let users = bots.iter().map(|(bot, _)| *bot).collect::<Vec<_>>();
+        set_moderators(pool, game_id, &users).await?;
+    }
+    let joined = game.players.len() < seats;
+    let Some((bot, private_key)) = (if joined {
+        bots.iter().find(|(bot, _)| !game.players.contains_key(bot))
+    } else {
+        let playing = bots
+            .iter()
+            .filter(|(bot, _)| {
+                game.players
+                    .get(bot)
+                    .is_some_and(|p| p.is_alive(true).is_ok())
+            })
+            .collect::<Vec<_>>();
+        playing.get(turn % playing.len().max(1)).copied()
+    }) else {
+        return Ok(());
+    };
+    let mut line = if joined {
+        let (x, y) = game.get_pos();
+        MoveLine {
+            move_type: MoveLineType::Join,
+            x: Some(x),
+            y: Some(y),
+            time: None,
+            ..bot_move(&game, *bot)
+        }
+    } else {
+        bot_move(&game, *bot)
+    };
+    if line
+        .sign(
+            game.lines.last().map(|l| l.signature.as_str()),
+            private_key.clone(),
+        )
+        .is_err()
+    {
+        tracing::warn!("Demo bot ({bot}) has a malformed key.");
+        return Ok(());
+    }
+    if let CustomResponse::UserError(e) | CustomResponse::ServerError(e) = GameAPI
+        .append(pool, mailer.clone(), client, sync, game_id, line)
+        .await
+    {
+        tracing::warn!("Demo bot ({bot}) made an invalid move: {}", e.0);
+    }
+    Ok(())
+}
+
+/// Keeps the demo game going. Bots wait while maintenance mode is enabled.
+pub async fn run(
+    pool: MySqlPool,
+    client: reqwest::Client,
+    mailer: Option<Arc<Mailer>>,
+    sync: Arc<GameSync>,
+    maintenance: Maintenance,
+) {
+    for turn in 0.. {
+        if maintenance.check::<i32>().is_ok() {
+            if let Err(e) = tick(&pool, &client, &mailer, &sync, turn).await {
+                tracing::warn!("Demo game: SQL error: {e}.");
+            }
+        }
+        tokio::time::sleep(INTERVAL).await;
+    }
+}
+
+pub struct DemoAPI;
+
+#[OpenApi]
+impl DemoAPI {
+    /// Returns the id of the public demo game, 0 if the server has none.
+    #[oai(path = "/demo", method = "get")]
+    async fn demo(&self, pool: Data<&MySqlPool>) -> Json<i32> {
+        Json(current(pool.0).await.unwrap_or_default())
+    }
+}
@@ -0,0 +1,254 @@
+//! Optional email notifications.
+//!
+//! Enabled when `TANKTACTICS_SMTP_HOST` is set. Players register an address with a signed request, confirm it with
+//! the code mailed to it and choose which events they want to hear about. Addresses are kept in the `emails` table
+//! (`user`, `address`, `verified`, `code`, `code_sent`, `attempts`, `notify_shot`, `notify_points`, `notify_rounds`).
+
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, Enum, Object, OpenApi};
+use rand_chacha::rand_core::{OsRng, RngCore};
+use sqlx::{mysql::MySqlPool, query};
+use std::sync::Arc;
+use tanktacticsgame::{get_key, verify_data};
+
+use crate::{admin::now, table::CustomResponse};
+
+/// How long a verification code can be used after it was mailed, in seconds.
+const CODE_LIFETIME: u64 = 15 * 60;
+/// The most codes that can be tried for an address, before a new code has to be requested.
+const MAX_CODE_ATTEMPTS: u32 = 5;
+
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+impl Mailer {
+    /// Creates a mailer from the `TANKTACTICS_SMTP_*` environment variables, if configured.
+    pub fn from_env() -> Option<Self> {
+        let host = std::env::var("TANKTACTICS_SMTP_HOST").ok()?;
+        let user = std::env::var("TANKTACTICS_SMTP_USER").unwrap_or_default();
+        let password = std::env::var("TANKTACTICS_SMTP_PASSWORD").unwrap_or_default();
+        let from = std::env::var("TANKTACTICS_SMTP_FROM").ok()?.parse().ok()?;
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&host)
+            .ok()?
+            .credentials(Credentials::new(user, password))
+            .build();
+        Some(Mailer { transport, from })
+    }
+    async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), String> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse().map_err(|_| "Malformed address.".to_string())?)
+            .subject(subject)
+            .body(body)
+            .map_err(|e| e.to_string())?;
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Something that happened to a player in a game.
+#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+pub enum NotifyEvent {
+    /// The player was shot.
+    Shot,
+    /// The player received points.
+    Points,
+    /// The votes of a round were handled.
+    VoteRound,
+    /// The player was matched into a game from the queue.
+    MatchStarted,
+    /// The votes are handled soon and the player, a juror, hasn't voted yet.
+    VoteReminder,
+}
+impl NotifyEvent {
+    fn describe(self, game: i32) -> String {
+        match self {
+            NotifyEvent::Shot => format!("Your tank was shot in game {game}."),
+            NotifyEvent::Points => format!("You received a point in game {game}."),
+            NotifyEvent::VoteRound => format!("A new round started in game {game}."),
+            NotifyEvent::MatchStarted => format!("Your match started in game {game}."),
+            NotifyEvent::VoteReminder => {
+                format!("The votes of game {game} are handled soon and you haven't voted yet.")
+            }
+        }
+    }
+}
+
+/// Mails every player that wants to know about their event.
+pub async fn notify(
+    pool: MySqlPool,
+    mailer: Arc<Mailer>,
+    game: i32,
+    events: Vec<(i32, NotifyEvent)>,
+) {
+    for (user, event) in events {
+        let Ok(record) = query!(
+            "SELECT address, notify_shot, notify_points, notify_rounds FROM emails WHERE user = ? AND verified",
+            user
+        )
+        .fetch_one(&pool)
+        .await
+        else {
+            continue;
+        };
+        let wanted = match event {
+            NotifyEvent::Shot => record.notify_shot,
+            NotifyEvent::Points => record.notify_points,
+            NotifyEvent::VoteRound | NotifyEvent::VoteReminder => record.notify_rounds,
+            // The player asked for the match by queueing.
+            NotifyEvent::MatchStarted => true,
+        };
+        if wanted {
+            if let Err(e) = mailer
+                .send(&record.address, "TankTactics", event.describe(game))
+                .await
+            {
+                tracing::warn!("Could not mail user ({user}): {e}");
+            }
+        }
+    }
+}
+
+#[derive(Object)]
+struct EmailSettings {
+    /// The user the settings are for.
+    user: i32,
+    /// The address to send notifications to.
+    address: String,
+    /// Notify when shot.
+    shot: bool,
+    /// Notify when receiving points.
+    points: bool,
+    /// Notify when a vote round passes.
+    rounds: bool,
+    /// The settings signed by the user. (see `EmailSettings::signed_data`)
+    signature: String,
+}
+impl EmailSettings {
+    /// The text the user signs: `user|address|shot|points|rounds`.
+    fn signed_data(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.user, self.address, self.shot, self.points, self.rounds
+        )
+    }
+}
+
+pub struct EmailAPI;
+
+#[OpenApi]
+impl EmailAPI {
+    /// Sets the email address and notification preferences of a user. A changed address has to be verified again using the code mailed to it.
+    /// A new code is mailed for addresses that aren't verified yet. Returns whether a code was mailed.
+    #[oai(path = "/email", method = "post")]
+    async fn set_email(
+        &self,
+        pool: Data<&MySqlPool>,
+        mailer: Data<&Option<Arc<Mailer>>>,
+        Json(settings): Json<EmailSettings>,
+    ) -> CustomResponse<bool> {
+        let mailer = mailer
+            .0
+            .clone()
+            .ok_or(CustomResponse::error("Email is not enabled.", false))?;
+        let key = query!("SELECT public_key FROM users WHERE id = ?", settings.user)
+            .fetch_one(pool.0)
+            .await
+            .ok()
+            .and_then(|r| get_key(r.public_key))
+            .ok_or(CustomResponse::error("User not availible.", false))?;
+        if !verify_data(&key, &settings.signed_data(), &settings.signature) {
+            return CustomResponse::error("Invalid signature.", false);
+        }
+        let current = query!(
+            "SELECT address, verified AS `verified: bool` FROM emails WHERE user = ?",
+            settings.user
+        )
+        .fetch_one(pool.0)
+        .await
+        .ok();
+        let changed = current
+            .as_ref()
+            .map_or(true, |r| r.address != settings.address);
+        // An address that isn't verified yet gets a new code, for when the last one expired or too many were tried.
+        let mail = changed || current.is_some_and(|r| !r.verified);
+        let code = format!("{:06}", OsRng.next_u32() % 1_000_000);
+        query!(
+            "INSERT INTO emails (user, address, verified, code, code_sent, attempts, notify_shot, notify_points, notify_rounds) VALUES (?, ?, FALSE, ?, ?, 0, ?, ?, ?) ON DUPLICATE KEY UPDATE address = VALUES(address), verified = verified AND NOT ?, code = IF(?, VALUES(code), code), code_sent = IF(?, VALUES(code_sent), code_sent), attempts = IF(?, 0, attempts), notify_shot = VALUES(notify_shot), notify_points = VALUES(notify_points), notify_rounds = VALUES(notify_rounds);",
+            settings.user,
+            settings.address,
+            code,
+            now(),
+            settings.shot,
+            settings.points,
+            settings.rounds,
+            changed,
+            mail,
+            mail,
+            mail
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        if mail {
+            mailer
+                .send(
+                    &settings.address,
+                    "TankTactics verification",
+                    format!("Your verification code is {code}."),
+                )
+                .await
+                .map_err(|e| {
+                    CustomResponse::error(&format!("Could not send email: {e}."), false)
+                })?;
+        }
+        CustomResponse::Ok(Json(mail))
+    }
+    /// Verifies the email address of a user with the code that was mailed to it, at most `CODE_LIFETIME` ago. After
+    /// `MAX_CODE_ATTEMPTS` tries a new code has to be requested with `/email`.
+    #[oai(path = "/email/verify", method = "post")]
+    async fn verify_email(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(user): Query<i32>,
+        Query(code): Query<String>,
+    ) -> CustomResponse<bool> {
+        // The attempt is counted first, so tries made at the same time can't go over the limit.
+        let counted = query!(
+            "UPDATE emails SET attempts = attempts + 1 WHERE user = ? AND NOT verified AND attempts < ?;",
+            user,
+            MAX_CODE_ATTEMPTS
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        if counted.rows_affected() == 0 {
+            return CustomResponse::error(
+                "No address to verify, or too many wrong codes. Request a new code.",
+                false,
+            );
+        }
+        let result = query!(
+            "UPDATE emails SET verified = TRUE, code = NULL, attempts = 0 WHERE user = ? AND code = ? AND code_sent >= ?;",
+            user,
+            code,
+            now().saturating_sub(CODE_LIFETIME)
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        if result.rows_affected() == 0 {
+            CustomResponse::error("Wrong or expired verification code.", false)
+        } else {
+            CustomResponse::Ok(Json(true))
+        }
+    }
+}
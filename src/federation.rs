@@ -0,0 +1,234 @@
+//! Read-only mirroring of a primary server.
+//!
+//! A mirror follows the primary by pulling every game's users and new moves, verifies each chain itself and serves
+//! the read endpoints from that copy. A game whose chain the primary rewrote keeps the copy the mirror had. Moves posted
+//! to a mirror are forwarded to the primary.
+
+use poem::web::Data;
+use poem_openapi::{
+    param::{Header, Query},
+    payload::{Json, PlainText},
+    OpenApi,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tanktacticsgame::{get_key, DataBaseGame, Game, MoveLine, User};
+use tokio::sync::RwLock;
+
+use crate::table::{CustomResponse, TokensResponse};
+
+/// A page of `/tokens` of the primary.
+#[derive(Deserialize)]
+struct TokensPage {
+    index: usize,
+    moves: Vec<MoveLine>,
+}
+
+#[derive(Clone)]
+struct MirroredGame {
+    game: DataBaseGame,
+    users: Vec<User>,
+    tokens: Vec<MoveLine>,
+    /// The game after `tokens`, so a sync only verifies the moves that are new.
+    state: Game,
+}
+
+pub struct Mirror {
+    primary: String,
+    client: reqwest::Client,
+    games: RwLock<HashMap<i32, MirroredGame>>,
+}
+impl Mirror {
+    pub fn new(primary: String) -> Self {
+        Mirror {
+            primary,
+            client: reqwest::Client::new(),
+            games: RwLock::new(HashMap::new()),
+        }
+    }
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> reqwest::Result<T> {
+        self.client
+            .get(format!("{}{path}", self.primary))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+    /// Downloads the moves of a game from the primary after those of `known`, its last verified copy, and verifies
+    /// them on top of it. The new moves are signed on top of the last known one, so the chain of the primary has to
+    /// start with the known moves for them to verify.
+    async fn fetch(
+        &self,
+        game: DataBaseGame,
+        known: Option<MirroredGame>,
+    ) -> Result<MirroredGame, String> {
+        let users: Vec<User> = self
+            .get(&format!("/users?game={}", game.id))
+            .await
+            .map_err(|e| e.to_string())?;
+        let keys = users
+            .iter()
+            .map(|user| get_key(user.public_key.clone()).map(|key| (user.id, key)))
+            .collect::<Option<HashMap<_, _>>>()
+            .ok_or("Corrupted user key.")?;
+        let settings = game
+            .settings()
+            .map_err(|e| format!("Invalid settings: {e}"))?;
+        let (mut state, mut tokens) = match known {
+            Some(known) if known.state.settings == settings => (known.state, known.tokens),
+            Some(_) => return Err("The settings of the game changed.".into()),
+            None => (
+                game.clone()
+                    .as_game(Vec::new(), &keys)
+                    .map_err(|e| format!("Invalid chain: {e}"))?,
+                Vec::new(),
+            ),
+        };
+        let after = tokens
+            .len()
+            .checked_sub(1)
+            .map(|index| format!("&after_index={index}"))
+            .unwrap_or_default();
+        let page: TokensPage = self
+            .get(&format!("/tokens?game={}{after}", game.id))
+            .await
+            .map_err(|e| e.to_string())?;
+        if page.index != tokens.len() {
+            return Err("The chain no longer has the moves mirrored before.".into());
+        }
+        for line in page.moves {
+            state
+                .load(line.clone(), &keys)
+                .map_err(|e| format!("Invalid chain: {e}"))?;
+            tokens.push(line);
+        }
+        state.drain_events();
+        Ok(MirroredGame {
+            game,
+            users,
+            tokens,
+            state,
+        })
+    }
+    /// Pulls all games from the primary, keeping the previous copy of any game that fails verification.
+    pub async fn sync(&self) {
+        let games: Vec<DataBaseGame> = match self.get("/games").await {
+            Ok(games) => games,
+            Err(e) => {
+                tracing::warn!("Could not reach primary: {e}");
+                return;
+            }
+        };
+        for game in games {
+            let id = game.id;
+            let known = self.games.read().await.get(&id).cloned();
+            match self.fetch(game, known).await {
+                Ok(mirrored) => {
+                    self.games.write().await.insert(id, mirrored);
+                }
+                Err(e) => tracing::warn!("Rejected game ({id}) from primary: {e}"),
+            }
+        }
+    }
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        loop {
+            self.sync().await;
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+pub struct MirrorAPI;
+
+#[OpenApi]
+impl MirrorAPI {
+    /// Returns the last token from a game specified by the `game` query.
+    #[oai(path = "/head", method = "get")]
+    async fn get_head(
+        &self,
+        mirror: Data<&Arc<Mirror>>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<String> {
+        let games = mirror.games.read().await;
+        let head = games
+            .get(&game)
+            .and_then(|g| g.tokens.last())
+            .map(|line| line.signature.clone())
+            .unwrap_or_default();
+        CustomResponse::Ok(Json(head))
+    }
+    /// Returns all mirrored games and their settings.
+    #[oai(path = "/games", method = "get")]
+    async fn get_games(&self, mirror: Data<&Arc<Mirror>>) -> Json<Vec<DataBaseGame>> {
+        let games = mirror.games.read().await;
+        Json(games.values().map(|g| g.game.clone()).collect())
+    }
+    /// Gets all signed moves for a specific game, in the compact text form when requested with `Accept: text/plain`.
+    /// With `after_index` only the moves after that index are sent, at most `limit` of them. (see `TokensResponse::page`)
+    #[oai(path = "/tokens", method = "get")]
+    async fn get_tokens(
+        &self,
+        mirror: Data<&Arc<Mirror>>,
+        Query(game): Query<i32>,
+        Query(after_index): Query<Option<usize>>,
+        Query(limit): Query<Option<usize>>,
+        #[oai(name = "Accept")] Header(accept): Header<Option<String>>,
+    ) -> TokensResponse {
+        let games = mirror.games.read().await;
+        TokensResponse::page(
+            games
+                .get(&game)
+                .map(|g| g.tokens.clone())
+                .unwrap_or_default(),
+            after_index,
+            limit,
+            accept.as_deref(),
+        )
+    }
+    /// Gets the public key of all players in a specific game.
+    #[oai(path = "/users", method = "get")]
+    async fn get_users(
+        &self,
+        mirror: Data<&Arc<Mirror>>,
+        Query(game): Query<i32>,
+    ) -> Json<Vec<User>> {
+        let games = mirror.games.read().await;
+        Json(
+            games
+                .get(&game)
+                .map(|g| g.users.clone())
+                .unwrap_or_default(),
+        )
+    }
+    /// Make a move. The move is forwarded to the primary server.
+    #[oai(path = "/move", method = "post")]
+    async fn make_move(
+        &self,
+        mirror: Data<&Arc<Mirror>>,
+        Query(game): Query<i32>,
+        Json(token): Json<MoveLine>,
+    ) -> CustomResponse<i32> {
+        let response = mirror
+            .client
+            .post(format!("{}/move?game={game}", mirror.primary))
+            .json(&token)
+            .send()
+            .await
+            .map_err(|e| CustomResponse::error(&format!("Primary unreachable: {e}."), true))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| CustomResponse::error(&format!("Primary unreachable: {e}."), true))?;
+        if status.is_success() {
+            text.parse()
+                .map(|id| CustomResponse::Ok(Json(id)))
+                .unwrap_or_else(|_| CustomResponse::error("Malformed primary response.", true))
+        } else if status.is_client_error() {
+            CustomResponse::UserError(PlainText(text))
+        } else {
+            CustomResponse::ServerError(PlainText(text))
+        }
+    }
+}
@@ -0,0 +1,382 @@
+//! The game endpoints of `GameAPI` served from a `LocalStorage`, for servers without MySQL.
+//!
+//! `serve` answers with `LocalAPI` when its database is `memory:` or a `sqlite:` connection string instead of MySQL, for
+//! demos, CI and self-hosting. Users register, create games, move and read them at the same paths and with the same
+//! schemas as on MySQL, answered by the same helpers of `GameAPI`. What needs the tables only MySQL has is not served:
+//! moderation, quarantine, email, ratings, webhooks, API keys, certificates, scheduled moves and the quotas. Since
+//! nobody is quarantined, `/tokens` and `/users` ignore `user`.
+//!
+//! Every game is replayed once when the server starts and then kept in memory, so reads don't replay it.
+
+use base64::Engine;
+use k256::ecdsa::SigningKey;
+use poem::web::Data;
+use poem_openapi::{
+    param::{Header, Query},
+    payload::{Json, PlainText},
+    types::{ToJSON, Type},
+    OpenApi,
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
+use tanktacticsgame::{
+    get_key, DataBaseGame, Game, MoveLine, MoveLineType, PublicKey, RoundSummary, Transfer, User,
+    VoteTally, BASE64,
+};
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+use crate::{
+    admin::now,
+    storage::{LocalStorage, Storage, StorageError},
+    table::{
+        CustomResponse, GameAPI, GameState, JoinResponse, PlayerState, SignedTime, TileState,
+        TokensResponse,
+    },
+};
+
+struct State {
+    /// The key of every user.
+    users: HashMap<i32, PublicKey>,
+    /// The settings and state of every game.
+    games: BTreeMap<i32, (DataBaseGame, Game)>,
+}
+
+/// The games of a `LocalStorage`, kept in memory.
+pub struct Local {
+    storage: LocalStorage,
+    /// The server signing key and public key, to sign the time with.
+    keys: (SigningKey, String),
+    state: RwLock<State>,
+}
+impl Local {
+    /// Replays every game in `storage`.
+    pub async fn open(
+        storage: LocalStorage,
+        keys: (SigningKey, String),
+    ) -> Result<Self, StorageError> {
+        let mut users = HashMap::new();
+        for user in storage.users().await? {
+            if let Some(key) = get_key(user.public_key) {
+                users.insert(user.id, key);
+            }
+        }
+        let mut games = BTreeMap::new();
+        for settings in storage.games().await? {
+            let lines = storage.tokens(settings.id).await?;
+            let mut game = settings.clone().as_game(lines, &users).map_err(|e| {
+                StorageError::Refused(format!("Game {} is corrupted: {e}", settings.id))
+            })?;
+            game.drain_events();
+            games.insert(settings.id, (settings, game));
+        }
+        Ok(Local {
+            storage,
+            keys,
+            state: RwLock::new(State { users, games }),
+        })
+    }
+    /// The storage the games are kept in.
+    pub fn storage(&self) -> &LocalStorage {
+        &self.storage
+    }
+    /// Stores a new game with the settings `rules`, returning its id.
+    pub async fn create_game(&self, rules: DataBaseGame) -> Result<i32, StorageError> {
+        let mut state = self.state.write().await;
+        let id = self.storage.create_game(rules.clone()).await?;
+        let settings = DataBaseGame { id, ..rules };
+        let game = settings
+            .clone()
+            .as_game(Vec::new(), &state.users)
+            .map_err(|e| StorageError::Refused(e.to_string()))?;
+        state.games.insert(id, (settings, game));
+        Ok(id)
+    }
+    /// The settings and state of `game`.
+    async fn game<T: Type + ToJSON>(
+        &self,
+        game: i32,
+    ) -> Result<RwLockReadGuard<'_, (DataBaseGame, Game)>, CustomResponse<T>> {
+        RwLockReadGuard::try_map(self.state.read().await, |state| state.games.get(&game))
+            .map_err(|_| CustomResponse::error("Game does not exist.", false))
+    }
+}
+
+pub struct LocalAPI;
+
+#[OpenApi]
+impl LocalAPI {
+    /// Returns the last token from a game specified by the `game` query.
+    #[oai(path = "/head", method = "get")]
+    async fn get_head(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<String> {
+        let head = match local.game::<String>(game).await {
+            Ok(game) => game.1.lines.last().map(|line| line.signature.clone()),
+            Err(_) => None,
+        };
+        CustomResponse::Ok(Json(head.unwrap_or_default()))
+    }
+    /// Returns the current time of the server, signed with the server key. Votes can only be handled with a time from
+    /// here, so rounds don't depend on the clock of the client.
+    #[oai(path = "/time", method = "get")]
+    async fn get_time(&self, local: Data<&Arc<Local>>) -> Json<SignedTime> {
+        Json(GameAPI::sign_time(&local.keys))
+    }
+    /// Returns all games and their settings.
+    #[oai(path = "/games", method = "get")]
+    async fn get_games(&self, local: Data<&Arc<Local>>) -> Json<Vec<DataBaseGame>> {
+        let state = local.state.read().await;
+        let games = state.games.values().map(|(settings, game)| DataBaseGame {
+            last_vote: game.last_vote,
+            ..settings.clone()
+        });
+        Json(games.collect())
+    }
+    /// Gets all signed moves for a specific game, in the compact text form when requested with `Accept: text/plain`.
+    /// With `after_index` only the moves after that index are sent, at most `limit` of them. (see `TokensResponse::page`)
+    /// Nobody is quarantined here, so `user`, `time` and `signature` are ignored.
+    #[oai(path = "/tokens", method = "get")]
+    #[allow(clippy::too_many_arguments)]
+    async fn get_tokens(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+        Query(user): Query<Option<i32>>,
+        Query(time): Query<Option<u64>>,
+        Query(signature): Query<Option<String>>,
+        Query(after_index): Query<Option<usize>>,
+        Query(limit): Query<Option<usize>>,
+        #[oai(name = "Accept")] Header(accept): Header<Option<String>>,
+    ) -> TokensResponse {
+        let lines = match local.game::<i32>(game).await {
+            Ok(game) => game.1.lines.clone(),
+            Err(_) => Vec::new(),
+        };
+        TokensResponse::page(lines, after_index, limit, accept.as_deref())
+    }
+    /// Gets the public key of all players in a specific game. Nobody is quarantined here, so `user`, `time` and
+    /// `signature` are ignored.
+    #[oai(path = "/users", method = "get")]
+    async fn get_users(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+        Query(user): Query<Option<i32>>,
+        Query(time): Query<Option<u64>>,
+        Query(signature): Query<Option<String>>,
+    ) -> CustomResponse<Vec<User>> {
+        let players = match local.game::<i32>(game).await {
+            Ok(game) => game.1.lines.iter().map(|line| line.authorizer).collect(),
+            Err(_) => HashSet::new(),
+        };
+        let users = local
+            .storage
+            .users()
+            .await
+            .map_err(|e| CustomResponse::error(&e.to_string(), true))?;
+        let users = users.into_iter().filter(|u| players.contains(&u.id));
+        CustomResponse::Ok(Json(users.collect()))
+    }
+    /// Gets the games `user` made a move in.
+    #[oai(path = "/player_games", method = "get")]
+    async fn player_games(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(user): Query<i32>,
+    ) -> Json<Vec<i32>> {
+        let state = local.state.read().await;
+        let games = state
+            .games
+            .iter()
+            .filter(|(_, (_, game))| game.lines.iter().any(|line| line.authorizer == user))
+            .map(|(id, _)| *id);
+        Json(games.collect())
+    }
+    /// Make a move and return its index. Moves handling the votes need a `time` and `clock` from `/time`. Games can't
+    /// be paused and moves can't be scheduled without MySQL.
+    #[oai(path = "/move", method = "post")]
+    async fn make_move(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+        Query(schedule): Query<Option<u64>>,
+        Json(token): Json<MoveLine>,
+    ) -> CustomResponse<i32> {
+        if schedule.is_some() {
+            return CustomResponse::error("Moves can only be scheduled on a MySQL server.", false);
+        }
+        match token.move_type {
+            MoveLineType::HandleVotes => {
+                let key = get_key(local.keys.1.clone())
+                    .ok_or(CustomResponse::error("Malformed server key.", true))?;
+                token
+                    .check_clock(&key)
+                    .map_err(|e| CustomResponse::refused(&e))?;
+                if token.time.map_or(true, |time| now().abs_diff(time) > 300) {
+                    return CustomResponse::error(
+                        "Votes must be handled with the current time.",
+                        false,
+                    );
+                }
+            }
+            MoveLineType::Pause | MoveLineType::Resume => {
+                return CustomResponse::error("Only moderators may pause the game.", false);
+            }
+            _ => {}
+        }
+        let mut state = local.state.write().await;
+        let state = &mut *state;
+        let (_, current) = state
+            .games
+            .get_mut(&game)
+            .ok_or(CustomResponse::error("Game does not exist.", false))?;
+        let mut next = current.clone();
+        next.load(token.clone(), &state.users)
+            .map_err(|e| CustomResponse::refused(&e))?;
+        next.drain_events();
+        let index = next.lines.len() - 1;
+        local
+            .storage
+            .append(game, index, token)
+            .await
+            .map_err(|e| CustomResponse::error(&e.to_string(), true))?;
+        *current = next;
+        CustomResponse::Ok(Json(index.try_into().unwrap()))
+    }
+    /// Returns the current round of a game, when votes were last handled and how many moves and living players it has.
+    #[oai(path = "/state", method = "get")]
+    async fn get_state(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<GameState> {
+        let game = local.game(game).await?;
+        CustomResponse::Ok(Json(GameAPI::summarize(&game.1)))
+    }
+    /// Returns a summary of every round of a game that ended, oldest first.
+    #[oai(path = "/rounds", method = "get")]
+    async fn get_rounds(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<Vec<RoundSummary>> {
+        let game = local.game(game).await?;
+        CustomResponse::Ok(Json(game.1.rounds.clone()))
+    }
+    /// Returns the tally of the votes of `round` of a game: who voted for whom and who reached the threshold. For the
+    /// current round it holds the votes cast so far.
+    #[oai(path = "/votes", method = "get")]
+    async fn get_votes(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+        Query(round): Query<u32>,
+    ) -> CustomResponse<VoteTally> {
+        let game = local.game(game).await?;
+        GameAPI::tally(game.0.clone(), game.1.lines.clone(), round)
+    }
+    /// Returns the players and board of a game as url safe base 64 of `Game::pack_board`.
+    #[oai(path = "/state/packed", method = "get")]
+    async fn get_packed_state(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<String> {
+        let game = local.game(game).await?;
+        CustomResponse::Ok(Json(BASE64.encode(game.1.pack_board())))
+    }
+    /// Returns the state of a single player in a game.
+    #[oai(path = "/player_state", method = "get")]
+    async fn get_player_state(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+        Query(player): Query<i32>,
+    ) -> CustomResponse<PlayerState> {
+        let game = local.game(game).await?;
+        GameAPI::player_state(&game.1, player)
+    }
+    /// Returns who is on a single tile of a game and who can shoot it.
+    #[oai(path = "/tile", method = "get")]
+    async fn get_tile(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+        Query(x): Query<u32>,
+        Query(y): Query<u32>,
+    ) -> CustomResponse<TileState> {
+        let game = local.game(game).await?;
+        GameAPI::tile_state(&game.1, x, y)
+    }
+    /// Returns every gift and loot of a game, summed up per giver, receiver and kind.
+    #[oai(path = "/flows", method = "get")]
+    async fn get_flows(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<Vec<Transfer>> {
+        let game = local.game(game).await?;
+        CustomResponse::Ok(Json(game.1.flows()))
+    }
+    /// Returns the position `user` would spawn at when joining `game` now. Gives a conflict if the user already joined
+    /// and forbidden if the game is full.
+    #[oai(path = "/join_position", method = "get")]
+    async fn join_position(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(game): Query<i32>,
+        Query(user): Query<i32>,
+    ) -> JoinResponse {
+        match local.game::<i32>(game).await {
+            Ok(game) => GameAPI::spawn_position(&game.1, user),
+            Err(_) => JoinResponse::UserError(PlainText("Game does not exist.".into())),
+        }
+    }
+    /// Regester a new user with a public key. Returns the id of the new user.
+    #[oai(path = "/regester", method = "post")]
+    async fn regester(
+        &self,
+        local: Data<&Arc<Local>>,
+        Json(public_key): Json<String>,
+    ) -> CustomResponse<i32> {
+        let Some(key @ PublicKey::K256(_)) = get_key(public_key.clone()) else {
+            return CustomResponse::error("Malformed key given.", false);
+        };
+        let mut state = local.state.write().await;
+        let id = local
+            .storage
+            .register(public_key)
+            .await
+            .map_err(|e| CustomResponse::error(&e.to_string(), true))?;
+        state.users.insert(id, key);
+        CustomResponse::Ok(Json(id))
+    }
+    /// Create a new game with settings. Returns the id of the new game. Moderators can't be appointed without MySQL,
+    /// so `creator`, `time` and `signature` are ignored.
+    #[oai(path = "/make_game", method = "post")]
+    async fn make_game(
+        &self,
+        local: Data<&Arc<Local>>,
+        Query(creator): Query<Option<i32>>,
+        Query(moderators): Query<Option<Vec<i32>>>,
+        Query(time): Query<Option<u64>>,
+        Query(signature): Query<Option<String>>,
+        Json(game): Json<DataBaseGame>,
+    ) -> CustomResponse<i32> {
+        if moderators.is_some_and(|moderators| !moderators.is_empty()) {
+            return CustomResponse::error(
+                "Moderators can only be appointed on a MySQL server.",
+                false,
+            );
+        }
+        GameAPI::check_settings(&game)?;
+        let id = local.create_game(game).await.map_err(|e| {
+            CustomResponse::error(&e.to_string(), !matches!(e, StorageError::Refused(_)))
+        })?;
+        CustomResponse::Ok(Json(id))
+    }
+}
@@ -0,0 +1,341 @@
+//! Moderators of a single game.
+//!
+//! The user that creates a game may appoint moderators, listed in the `moderators` table (`game`, `user`). Creators
+//! and paused games are kept in `game_moderation` (`game`, `creator`, `paused`). The
+//! creator, moderators and admins can pause the game, hide spam polls and handle the votes before the round is over.
+//! Besides stopping every move with `/moderation/pause`, they can freeze the game for a holiday with a
+//! `MoveLineType::Pause` move, which only they may append. They can also clone a game for a rematch with
+//! `/clone_game`.
+//! Every request carries a `ModeratorAuth` signed like an `AdminAuth`, over the action and a recent unix time, and is
+//! logged like one.
+
+use poem::web::Data;
+use poem_openapi::{
+    param::Query,
+    payload::Json,
+    types::{ToJSON, Type},
+    Object, OpenApi,
+};
+use rand_chacha::rand_core::{OsRng, RngCore};
+use sqlx::{mysql::MySqlPool, query, query_as};
+use tanktacticsgame::{get_key, verify_data, DataBaseGame, RULES_VERSION};
+
+use crate::{
+    admin::{now, record, Maintenance},
+    quota::Quotas,
+    table::{CustomResponse, GameAPI},
+};
+
+/// How far the time of a moderator request may be from the server time, in seconds.
+const MAX_CLOCK_SKEW: u64 = 300;
+/// How long a vote round lasts, in seconds, in games without a `vote_interval`. Only moderators may handle the votes
+/// before that.
+pub const ROUND_LENGTH: u64 = 24 * 60 * 60;
+
+#[derive(Object)]
+pub struct ModeratorAuth {
+    /// The user making the request.
+    pub user: i32,
+    /// The unix time at which the request was signed.
+    pub time: u64,
+    /// `action|time` signed by the user.
+    pub signature: String,
+}
+impl ModeratorAuth {
+    /// Checks that the request for `action` was recently signed by `user`.
+    pub(crate) async fn check_signed<T: Type + ToJSON>(
+        pool: &MySqlPool,
+        user: i32,
+        action: &str,
+        time: u64,
+        signature: &str,
+    ) -> Result<(), CustomResponse<T>> {
+        Self::check_signed_within(pool, user, action, time, signature, MAX_CLOCK_SKEW).await
+    }
+    /// Checks that the request for `action` was signed by `user` at most `lifetime` seconds ago.
+    pub(crate) async fn check_signed_within<T: Type + ToJSON>(
+        pool: &MySqlPool,
+        user: i32,
+        action: &str,
+        time: u64,
+        signature: &str,
+        lifetime: u64,
+    ) -> Result<(), CustomResponse<T>> {
+        let now = now();
+        if time > now + MAX_CLOCK_SKEW || now.saturating_sub(time) > lifetime {
+            return Err(CustomResponse::error("Request expired.", false));
+        }
+        let key = query!("SELECT public_key FROM users WHERE id = ?", user)
+            .fetch_one(pool)
+            .await
+            .ok()
+            .and_then(|r| get_key(r.public_key))
+            .ok_or(CustomResponse::error("User does not exist.", false))?;
+        if verify_data(&key, &format!("{action}|{time}"), signature) {
+            Ok(())
+        } else {
+            Err(CustomResponse::error("Invalid signature.", false))
+        }
+    }
+    /// Checks that the request for `action` was recently signed by a moderator of `game`.
+    pub(crate) async fn check<T: Type + ToJSON>(
+        &self,
+        pool: &MySqlPool,
+        game: i32,
+        action: &str,
+    ) -> Result<(), CustomResponse<T>> {
+        Self::check_signed(pool, self.user, action, self.time, &self.signature).await?;
+        if !is_moderator(pool, game, self.user).await {
+            return Err(CustomResponse::error(
+                "Not a moderator of this game.",
+                false,
+            ));
+        }
+        record(
+            pool,
+            self.user,
+            Some(game),
+            action,
+            self.time,
+            &self.signature,
+        )
+        .await
+    }
+    /// Checks that the request for `action` was recently signed by the creator of `game`.
+    async fn check_creator<T: Type + ToJSON>(
+        &self,
+        pool: &MySqlPool,
+        game: i32,
+        action: &str,
+    ) -> Result<(), CustomResponse<T>> {
+        Self::check_signed(pool, self.user, action, self.time, &self.signature).await?;
+        let creator = query!("SELECT creator FROM game_moderation WHERE game = ?", game)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+            .and_then(|r| r.creator);
+        if creator != Some(self.user) {
+            return Err(CustomResponse::error(
+                "Not the creator of this game.",
+                false,
+            ));
+        }
+        record(
+            pool,
+            self.user,
+            Some(game),
+            action,
+            self.time,
+            &self.signature,
+        )
+        .await
+    }
+}
+
+/// How long a vote round of `game` lasts, in seconds: its `vote_interval`, or `ROUND_LENGTH` if it has none.
+pub(crate) async fn round_length(pool: &MySqlPool, game: i32) -> u64 {
+    query!("SELECT vote_interval FROM games WHERE id = ?", game)
+        .fetch_one(pool)
+        .await
+        .ok()
+        .map(|r| r.vote_interval)
+        .filter(|&interval| interval > 0)
+        .unwrap_or(ROUND_LENGTH)
+}
+
+/// Whether `user` is the creator, a moderator or an admin of `game`.
+pub(crate) async fn is_moderator(pool: &MySqlPool, game: i32, user: i32) -> bool {
+    query!(
+        "SELECT (EXISTS (SELECT 1 FROM game_moderation WHERE game = ? AND creator = ?)
+            OR EXISTS (SELECT 1 FROM moderators WHERE game = ? AND user = ?)
+            OR EXISTS (SELECT 1 FROM admins WHERE user = ?)) AS `moderator: bool`",
+        game,
+        user,
+        game,
+        user,
+        user
+    )
+    .fetch_one(pool)
+    .await
+    .is_ok_and(|r| r.moderator)
+}
+
+/// Replaces the moderators of `game`.
+pub(crate) async fn set_moderators<'c>(
+    pool: impl sqlx::Acquire<'c, Database = sqlx::MySql>,
+    game: i32,
+    moderators: &[i32],
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    query!("DELETE FROM moderators WHERE game = ?;", game)
+        .execute(&mut *transaction)
+        .await?;
+    for user in moderators {
+        query!(
+            "INSERT IGNORE INTO moderators (game, user) VALUES (?, ?);",
+            game,
+            user
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await
+}
+
+/// The action a list of moderators is signed as, `action|game|moderators`.
+pub(crate) fn moderators_action(action: &str, game: Option<i32>, moderators: &[i32]) -> String {
+    let moderators = moderators
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    match game {
+        Some(game) => format!("{action}|{game}|{moderators}"),
+        None => format!("{action}|{moderators}"),
+    }
+}
+
+pub struct ModerationAPI;
+
+#[OpenApi]
+impl ModerationAPI {
+    /// Returns the moderators appointed to a game, without its creator.
+    #[oai(path = "/moderators", method = "get")]
+    async fn moderators(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<Vec<i32>> {
+        query!(
+            "SELECT user FROM moderators WHERE game = ? ORDER BY user",
+            game
+        )
+        .fetch_all(pool.0)
+        .await
+        .map(|rows| CustomResponse::Ok(Json(rows.into_iter().map(|r| r.user).collect())))
+        .unwrap_or_else(|e| CustomResponse::error(&format!("SQL error: {e}."), true))
+    }
+    /// Replaces the moderators of a game. (creator action `moderators|game|user,user`)
+    #[oai(path = "/moderators", method = "post")]
+    async fn set_moderators(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+        Query(moderators): Query<Option<Vec<i32>>>,
+        Json(auth): Json<ModeratorAuth>,
+    ) -> CustomResponse<Vec<i32>> {
+        let moderators = moderators.unwrap_or_default();
+        auth.check_creator(
+            pool.0,
+            game,
+            &moderators_action("moderators", Some(game), &moderators),
+        )
+        .await?;
+        set_moderators(pool.0, game, &moderators)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(moderators))
+    }
+    /// Pauses or resumes a game. No moves are accepted while a game is paused. (moderator action `pause|game|paused`)
+    #[oai(path = "/moderation/pause", method = "post")]
+    async fn pause(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+        Query(paused): Query<bool>,
+        Json(auth): Json<ModeratorAuth>,
+    ) -> CustomResponse<bool> {
+        auth.check(pool.0, game, &format!("pause|{game}|{paused}"))
+            .await?;
+        query!(
+            "INSERT INTO game_moderation (game, paused) VALUES (?, ?) ON DUPLICATE KEY UPDATE paused = VALUES(paused);",
+            game,
+            paused
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(paused))
+    }
+    /// Hides the text of a spam poll. The poll stays part of the game. (moderator action `hide_poll|game|poll`)
+    #[oai(path = "/moderation/hide_poll", method = "post")]
+    async fn hide_poll(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+        #[oai(name = "poll")] Query(poll_id): Query<i32>,
+        Json(auth): Json<ModeratorAuth>,
+    ) -> CustomResponse<i32> {
+        auth.check(pool.0, game, &format!("hide_poll|{game}|{poll_id}"))
+            .await?;
+        query!(
+            "INSERT IGNORE INTO hidden_polls (game, poll) VALUES (?, ?);",
+            game,
+            poll_id
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(poll_id))
+    }
+    /// Creates a new game with the settings of a game and a fresh seed, for a rematch. Returns the id of the new game.
+    /// The user that asked for it creates the new game, and the moderators of the game moderate it too. The new game
+    /// is played with the current rules version. (moderator action `clone_game|game`)
+    #[oai(path = "/clone_game", method = "post")]
+    async fn clone_game(
+        &self,
+        pool: Data<&MySqlPool>,
+        maintenance: Data<&Maintenance>,
+        quotas: Data<&Quotas>,
+        Query(game): Query<i32>,
+        Json(auth): Json<ModeratorAuth>,
+    ) -> CustomResponse<i32> {
+        maintenance.check()?;
+        auth.check(pool.0, game, &format!("clone_game|{game}"))
+            .await?;
+        quotas.check_create(pool.0, Some(auth.user)).await?;
+        let mut settings = query_as!(DataBaseGame, "SELECT * FROM games WHERE id = ?", game)
+            .fetch_optional(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+            .ok_or(CustomResponse::error("Game does not exist.", false))?;
+        settings.seed = OsRng.next_u64();
+        settings.last_vote = 0;
+        settings.rules_version = RULES_VERSION;
+        let id = GameAPI.create_game(pool.0, &settings).await?;
+        query!(
+            "INSERT INTO game_moderation (game, creator, created) VALUES (?, ?, ?);",
+            id,
+            auth.user,
+            now()
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        query!(
+            "INSERT INTO moderators (game, user) SELECT ?, user FROM moderators WHERE game = ?;",
+            id,
+            game
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(id))
+    }
+    /// Returns the polls of a game hidden by its moderators.
+    #[oai(path = "/moderation/hidden_polls", method = "get")]
+    async fn hidden_polls(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<Vec<i32>> {
+        query!(
+            "SELECT poll FROM hidden_polls WHERE game = ? ORDER BY poll",
+            game
+        )
+        .fetch_all(pool.0)
+        .await
+        .map(|rows| CustomResponse::Ok(Json(rows.into_iter().map(|r| r.poll).collect())))
+        .unwrap_or_else(|e| CustomResponse::error(&format!("SQL error: {e}."), true))
+    }
+}
@@ -0,0 +1,150 @@
+//! Passkeys as signing identities.
+//!
+//! A client asks for a challenge, creates a P-256 passkey with it and registers the key here. Registration makes a
+//! new user whose moves are signed with `WebAuthn` assertions instead of a key kept by the frontend (see
+//! `tanktacticsgame::PublicKey`). The key is bound to the site it was created on, so assertions made for another site
+//! are refused. Open challenges live in the `webauthn_challenges` table (`challenge`, `created`)
+//! and registered credentials in the `passkeys` table (`user`, `credential_id`).
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, Object, OpenApi};
+use rand_chacha::rand_core::{OsRng, RngCore};
+use serde_json::Value;
+use sqlx::{mysql::MySqlPool, query};
+use tanktacticsgame::{passkey_rp_id, verify_passkey_registration, PublicKey, BASE64};
+
+use crate::{
+    admin::{now, Maintenance},
+    table::CustomResponse,
+};
+
+/// How long a challenge can be used, in seconds.
+const CHALLENGE_LIFETIME: u64 = 300;
+
+#[derive(Object)]
+struct PasskeyRegistration {
+    /// The credential id of the passkey. (url safe base 64)
+    credential_id: String,
+    /// The DER encoded public key of the passkey. (url safe base 64)
+    public_key: String,
+    /// The client data JSON of the registration. (url safe base 64)
+    client_data_json: String,
+    /// The authenticator data of the registration, with the attested credential. (url safe base 64)
+    authenticator_data: String,
+}
+
+pub struct PasskeyAPI;
+
+#[OpenApi]
+impl PasskeyAPI {
+    /// Returns a new challenge to create a passkey with, valid for 5 minutes.
+    #[oai(path = "/webauthn/challenge", method = "get")]
+    async fn challenge(&self, pool: Data<&MySqlPool>) -> CustomResponse<String> {
+        let mut bytes = [0; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let challenge = URL_SAFE_NO_PAD.encode(bytes);
+        query!(
+            "DELETE FROM webauthn_challenges WHERE created < ?;",
+            now().saturating_sub(CHALLENGE_LIFETIME)
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        query!(
+            "INSERT INTO webauthn_challenges (challenge, created) VALUES (?, ?);",
+            challenge,
+            now()
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(challenge))
+    }
+    /// Registers a passkey created with a challenge from `/webauthn/challenge` as a new user. Returns the id of the user.
+    #[oai(path = "/webauthn/register", method = "post")]
+    async fn register(
+        &self,
+        pool: Data<&MySqlPool>,
+        maintenance: Data<&Maintenance>,
+        Json(registration): Json<PasskeyRegistration>,
+    ) -> CustomResponse<i32> {
+        maintenance.check()?;
+        let client_data: Value = BASE64
+            .decode(&registration.client_data_json)
+            .ok()
+            .and_then(|x| serde_json::from_slice(&x).ok())
+            .ok_or(CustomResponse::error("Malformed client data.", false))?;
+        if client_data["type"] != "webauthn.create" {
+            return CustomResponse::error("Not a passkey registration.", false);
+        }
+        let rp_id = client_data["origin"]
+            .as_str()
+            .and_then(passkey_rp_id)
+            .ok_or(CustomResponse::error(
+                "Passkeys can only be made on a secure site.",
+                false,
+            ))?;
+        let challenge = client_data["challenge"].as_str().unwrap_or_default();
+        let used = query!(
+            "DELETE FROM webauthn_challenges WHERE challenge = ? AND created >= ?;",
+            challenge,
+            now().saturating_sub(CHALLENGE_LIFETIME)
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        if used.rows_affected() == 0 {
+            return CustomResponse::error("Unknown or expired challenge.", false);
+        }
+        let der = BASE64
+            .decode(&registration.public_key)
+            .map_err(|_| CustomResponse::error("Malformed public key.", false))?;
+        let public_key = PublicKey::passkey_from_der(&der, rp_id).ok_or(CustomResponse::error(
+            "Only P-256 passkeys are supported.",
+            false,
+        ))?;
+        let attested = match (
+            BASE64.decode(&registration.authenticator_data),
+            BASE64.decode(&registration.credential_id),
+        ) {
+            (Ok(data), Ok(id)) => verify_passkey_registration(&data, rp_id, &id, &der),
+            _ => false,
+        };
+        if !attested {
+            return CustomResponse::error(
+                "The authenticator data does not attest this passkey.",
+                false,
+            );
+        }
+        let id: i32 = query!("INSERT INTO users (public_key) VALUES (?);", public_key)
+            .execute(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+            .last_insert_id()
+            .try_into()
+            .unwrap();
+        query!(
+            "INSERT INTO passkeys (user, credential_id) VALUES (?, ?);",
+            id,
+            registration.credential_id
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(id))
+    }
+    /// Returns the credential id of the passkey of `user`, to sign in on another device.
+    #[oai(path = "/webauthn/credential", method = "get")]
+    async fn credential(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(user): Query<i32>,
+    ) -> CustomResponse<String> {
+        let record = query!("SELECT credential_id FROM passkeys WHERE user = ?;", user)
+            .fetch_one(pool.0)
+            .await
+            .map_err(|_| CustomResponse::error("User has no passkey.", false))?;
+        CustomResponse::Ok(Json(record.credential_id))
+    }
+}
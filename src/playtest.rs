@@ -0,0 +1,165 @@
+//! A local server for trying out rules, without a database.
+//!
+//! `playtest --rules rules.toml` serves a game with the settings in the file. The file only needs the keys of
+//! `DataBaseGame` that differ from `defaults`, for example `width = 12` or `mutators = "fog"`. Users register, move and
+//! read the game through `LocalAPI`, the game endpoints of a server without MySQL, on a `LocalStorage`. (see `local`)
+//! The time is signed with a key made for the playtest. The file is checked every `INTERVAL`: when it changes a new
+//! game starts with the new rules, keeping the registered users, and `/games` lists it last. Rules that don't parse are
+//! reported and leave the running game alone.
+//!
+//! With `--persist state.json` the storage is written to the file on shutdown, and the next playtest resumes the last
+//! game if its rules are unchanged. With the `sqlite` feature, `--persist sqlite:state.db` keeps it in a SQLite
+//! database instead, written as moves are made.
+
+use base64::Engine;
+use k256::ecdsa::SigningKey;
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tanktacticsgame::{get_random_keys, DataBaseGame, BASE64, RULES_VERSION};
+
+use crate::{
+    local::Local,
+    storage::{LocalStorage, Storage, StorageError},
+};
+
+/// How often the rules file is checked for changes.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// The rules a playtest starts from, before the rules file is applied.
+fn defaults() -> DataBaseGame {
+    DataBaseGame {
+        id: 0,
+        seed: 0,
+        last_vote: 0,
+        width: 8,
+        height: 8,
+        health: 3,
+        max_level: 2,
+        max_players: 4,
+        vote_threshold: 2,
+        range: "L".into(),
+        point_decay: false,
+        jury_weight: 0,
+        loot_mode: "killer".into(),
+        drive_length: 1,
+        rules_version: RULES_VERSION,
+        mutators: String::new(),
+        threshold_mode: String::new(),
+        win_condition: String::new(),
+        starting_points: 1,
+        starting_level: 0,
+        actions_per_round: 0,
+        items: String::new(),
+        two_phase_kills: false,
+        symmetric_spawns: false,
+        gift_cooldown: 0,
+        vote_interval: 0,
+        locale: String::new(),
+        description: String::new(),
+    }
+}
+
+/// Reads the rules in the TOML file at `path` on top of `defaults`.
+fn read_rules(path: &Path) -> Result<DataBaseGame, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let document = text
+        .parse::<toml_edit::Document>()
+        .map_err(|e| e.to_string())?;
+    let mut rules = serde_json::to_value(defaults()).map_err(|e| e.to_string())?;
+    for (key, item) in document.iter() {
+        let value = match item.as_value() {
+            Some(toml_edit::Value::String(s)) => Value::from(s.value().as_str()),
+            Some(toml_edit::Value::Integer(i)) => Value::from(*i.value()),
+            Some(toml_edit::Value::Boolean(b)) => Value::from(*b.value()),
+            _ => return Err(format!("{key} should be a string, integer or boolean.")),
+        };
+        match rules.get_mut(key) {
+            Some(slot) if key != "id" => *slot = value,
+            _ => return Err(format!("{key} is not a setting.")),
+        }
+    }
+    let rules: DataBaseGame = serde_json::from_value(rules).map_err(|e| e.to_string())?;
+    rules.settings().map_err(|e| e.to_string())?;
+    Ok(rules)
+}
+
+/// A key for the playtest to sign the time with, see `/time`.
+fn server_keys() -> Result<(SigningKey, String), String> {
+    let (private, public) = get_random_keys();
+    let private = BASE64.decode(private).map_err(|e| e.to_string())?;
+    let key = SigningKey::from_slice(&private).map_err(|e| e.to_string())?;
+    Ok((key, public))
+}
+
+pub struct Playtest {
+    path: PathBuf,
+    local: Arc<Local>,
+}
+impl Playtest {
+    /// Starts a playtest with the rules at `path`, resuming the last game in `persist` if it has the same rules.
+    pub async fn new(path: PathBuf, persist: Option<PathBuf>) -> Result<Self, String> {
+        let rules = read_rules(&path)?;
+        let storage = LocalStorage::open(persist)
+            .await
+            .map_err(|e| e.to_string())?;
+        let last = storage
+            .games()
+            .await
+            .map_err(|e| e.to_string())?
+            .pop()
+            .filter(|game| {
+                DataBaseGame {
+                    id: 0,
+                    ..game.clone()
+                } == rules
+            });
+        let local = Local::open(storage, server_keys()?)
+            .await
+            .map_err(|e| e.to_string())?;
+        if last.is_none() {
+            local.create_game(rules).await.map_err(|e| e.to_string())?;
+        }
+        Ok(Playtest {
+            path,
+            local: Arc::new(local),
+        })
+    }
+    /// Starts a new game with the rules in the file.
+    async fn reload(&self) -> Result<(), String> {
+        let rules = read_rules(&self.path)?;
+        self.local
+            .create_game(rules)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+    /// The games of the playtest, to serve with `LocalAPI`.
+    pub fn local(&self) -> Arc<Local> {
+        self.local.clone()
+    }
+    /// Writes the storage to the `persist` file, if one was given.
+    pub fn save(&self) -> Result<(), StorageError> {
+        self.local.storage().save()
+    }
+    /// Starts the game over whenever the rules file changes.
+    pub async fn run(self: Arc<Self>) {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut seen = modified(&self.path);
+        loop {
+            tokio::time::sleep(INTERVAL).await;
+            let current = modified(&self.path);
+            if current == seen {
+                continue;
+            }
+            seen = current;
+            match self.reload().await {
+                Ok(()) => tracing::info!("Rules changed, the game starts over."),
+                Err(e) => tracing::warn!("Could not read the rules, keeping the game: {e}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Which users have the game open.
+//!
+//! Every client keeps a socket open on `/ws/presence/:user`. It first receives a message for every user that is online,
+//! then one whenever a user comes online or goes offline, each as `{"user": id, "online": bool}`. A user stays online
+//! while at least one of their sockets is open.
+
+use futures::{SinkExt, StreamExt};
+use poem::{
+    handler,
+    web::{
+        websocket::{Message, WebSocket},
+        Data, Path,
+    },
+    IntoResponse,
+};
+use serde_json::json;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{broadcast, Mutex};
+
+/// How many changes a slow client may fall behind before it misses some.
+const BACKLOG: usize = 64;
+
+pub struct Presence {
+    /// The number of open sockets of every online user.
+    online: Mutex<HashMap<i32, usize>>,
+    changes: broadcast::Sender<(i32, bool)>,
+}
+impl Default for Presence {
+    fn default() -> Self {
+        Self {
+            online: Mutex::default(),
+            changes: broadcast::channel(BACKLOG).0,
+        }
+    }
+}
+impl Presence {
+    /// Counts a new socket of `user`, returning everyone that is online including `user`.
+    async fn connect(&self, user: i32) -> Vec<i32> {
+        let mut online = self.online.lock().await;
+        let sockets = online.entry(user).or_default();
+        *sockets += 1;
+        if *sockets == 1 {
+            let _ = self.changes.send((user, true));
+        }
+        online.keys().copied().collect()
+    }
+    async fn disconnect(&self, user: i32) {
+        let mut online = self.online.lock().await;
+        let Some(sockets) = online.get_mut(&user) else {
+            return;
+        };
+        *sockets -= 1;
+        if *sockets == 0 {
+            online.remove(&user);
+            let _ = self.changes.send((user, false));
+        }
+    }
+}
+
+fn change(user: i32, online: bool) -> Message {
+    Message::Text(json!({ "user": user, "online": online }).to_string())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[handler]
+pub fn presence(
+    Path(user): Path<i32>,
+    ws: WebSocket,
+    presence: Data<&Arc<Presence>>,
+) -> impl IntoResponse {
+    let presence = presence.0.clone();
+    ws.on_upgrade(move |socket| async move {
+        let (mut sink, mut stream) = socket.split();
+        let mut changes = presence.changes.subscribe();
+        for online in presence.connect(user).await {
+            if sink.send(change(online, true)).await.is_err() {
+                presence.disconnect(user).await;
+                return;
+            }
+        }
+        loop {
+            tokio::select! {
+                message = stream.next() => match message {
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+                update = changes.recv() => match update {
+                    Ok((other, online)) => {
+                        if sink.send(change(other, online)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+        presence.disconnect(user).await;
+    })
+}
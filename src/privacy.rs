@@ -0,0 +1,345 @@
+//! Self-service export and deletion of the data kept about a user.
+//!
+//! `/export_my_data` bundles everything the server keeps about the signing user into a single JSON document.
+//! `/delete_my_data` forgets the email address, passkey, scheduled moves and quick play key of the user and hides the
+//! text of their polls, like a moderator hiding a spam poll. Their moves and public key stay, as every later move of
+//! a game is signed on top of them and the chain would no longer verify without them.
+//!
+//! Hiding a poll only keeps its text out of the poll listings: the poll move itself, text included, is still served
+//! by `/tokens` and `/export`, as its signature covers the text. Nothing written in a poll is anonymized.
+
+use poem::web::Data;
+use poem_openapi::{
+    payload::Json,
+    types::{ParseFromJSON, ToJSON, Type},
+    Object, OpenApi,
+};
+use sqlx::{mysql::MySqlPool, query};
+use tanktacticsgame::{MoveLine, MoveLineType};
+
+use crate::{
+    admin::{signed_by, LogEntry, Maintenance},
+    moderation::ModeratorAuth,
+    storage::read_move,
+    table::{CustomResponse, GameAPI},
+};
+
+/// A request signed by the user whose data it is about.
+#[derive(Object)]
+pub struct DataRequest {
+    pub user: i32,
+    /// The unix time at which the request was signed.
+    pub time: u64,
+    /// `action|time` signed by the user.
+    pub signature: String,
+}
+
+/// A move authored by the user.
+#[derive(Object)]
+struct AuthoredMove {
+    game: i32,
+    index: i32,
+    /// Unset for moves made while quarantined, which only the user sees.
+    canonical: bool,
+    line: MoveLine,
+}
+
+/// A poll opened by the user.
+#[derive(Object)]
+struct PollText {
+    game: i32,
+    /// The index of the poll in its game.
+    poll: i32,
+    text: String,
+    /// Whether the text is hidden by a moderator or a deletion request.
+    hidden: bool,
+}
+
+/// The email address of the user and the notifications mailed to it.
+#[derive(Object)]
+struct EmailRecord {
+    address: String,
+    verified: bool,
+    notify_shot: bool,
+    notify_points: bool,
+    notify_rounds: bool,
+}
+
+/// A move the user scheduled but that was not made yet.
+#[derive(Object)]
+struct ScheduledMove {
+    game: i32,
+    /// The unix time the move is due.
+    at: u64,
+    line: MoveLine,
+}
+
+/// Everything the server keeps about a user.
+#[derive(Object)]
+struct DataExport {
+    user: i32,
+    public_key: String,
+    quarantined: bool,
+    moves: Vec<AuthoredMove>,
+    polls: Vec<PollText>,
+    email: Option<EmailRecord>,
+    /// The id of the passkey credential of the user.
+    passkey: Option<String>,
+    scheduled: Vec<ScheduledMove>,
+    /// The games the user created.
+    created: Vec<i32>,
+    /// The games the user was appointed a moderator of.
+    moderates: Vec<i32>,
+    /// The game the user was quick playing in, if the server still keeps their key.
+    quick_play: Option<i32>,
+    /// The admin and moderator actions the user signed.
+    interventions: Vec<LogEntry>,
+}
+
+/// What a deletion request removed, and what is kept.
+#[derive(Object)]
+struct DeletionReport {
+    /// The number of polls whose text was hidden.
+    hidden_polls: u64,
+    /// The number of moves that are kept in the chains of their games.
+    kept_moves: u64,
+}
+
+/// The polls opened by `user` in `game`, with their index and text.
+async fn polls<T: Type + ToJSON>(
+    pool: &MySqlPool,
+    game: i32,
+    user: i32,
+) -> Result<Vec<(i32, String)>, CustomResponse<T>> {
+    let (state, _) = GameAPI.load_game(pool, game, user).await?;
+    Ok(state
+        .lines
+        .iter()
+        .filter(|l| l.move_type == MoveLineType::Poll)
+        .zip(0..)
+        .filter(|(l, _)| l.authorizer == user)
+        .map(|(l, poll)| (poll, l.text.clone().unwrap_or_default()))
+        .collect())
+}
+
+/// The games `user` made a move in.
+async fn games(pool: &MySqlPool, user: i32) -> Result<Vec<i32>, sqlx::Error> {
+    Ok(query!(
+        "SELECT game FROM game_players WHERE user = ? ORDER BY game",
+        user
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| r.game)
+    .collect())
+}
+
+async fn export(
+    pool: &MySqlPool,
+    user: i32,
+    polls: Vec<PollText>,
+) -> Result<DataExport, sqlx::Error> {
+    let record = query!(
+        "SELECT public_key, quarantined AS `quarantined: bool` FROM users WHERE id = ?",
+        user
+    )
+    .fetch_one(pool)
+    .await?;
+    let moves = query!(
+        "SELECT game, `index`, token, canonical AS `canonical: bool` FROM moves WHERE user = ? ORDER BY game, `index`",
+        user
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .filter_map(|r| {
+        Some(AuthoredMove {
+            game: r.game,
+            index: r.index,
+            canonical: r.canonical,
+            line: read_move(&r.token).ok()?,
+        })
+    })
+    .collect();
+    let email = query!(
+        "SELECT address, verified AS `verified: bool`, notify_shot AS `notify_shot: bool`, notify_points AS `notify_points: bool`, notify_rounds AS `notify_rounds: bool` FROM emails WHERE user = ?",
+        user
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|r| EmailRecord {
+        address: r.address,
+        verified: r.verified,
+        notify_shot: r.notify_shot,
+        notify_points: r.notify_points,
+        notify_rounds: r.notify_rounds,
+    });
+    let passkey = query!("SELECT credential_id FROM passkeys WHERE user = ?", user)
+        .fetch_optional(pool)
+        .await?
+        .map(|r| r.credential_id);
+    let scheduled = query!(
+        "SELECT game, at, token FROM pending_moves WHERE user = ? ORDER BY at",
+        user
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .filter_map(|r| {
+        Some(ScheduledMove {
+            game: r.game,
+            at: r.at,
+            line: read_move(&r.token).ok()?,
+        })
+    })
+    .collect();
+    let created = query!(
+        "SELECT game FROM game_moderation WHERE creator = ? ORDER BY game",
+        user
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| r.game)
+    .collect();
+    let moderates = query!(
+        "SELECT game FROM moderators WHERE user = ? ORDER BY game",
+        user
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| r.game)
+    .collect();
+    let quick_play = query!("SELECT game FROM quick_play WHERE user = ?", user)
+        .fetch_optional(pool)
+        .await?
+        .map(|r| r.game);
+    let interventions = signed_by(pool, user).await?;
+    Ok(DataExport {
+        user,
+        public_key: record.public_key,
+        quarantined: record.quarantined,
+        moves,
+        polls,
+        email,
+        passkey,
+        scheduled,
+        created,
+        moderates,
+        quick_play,
+        interventions,
+    })
+}
+
+pub struct PrivacyAPI;
+
+#[OpenApi]
+impl PrivacyAPI {
+    /// Returns everything the server keeps about a user. (user action `export_my_data`)
+    #[oai(path = "/export_my_data", method = "post")]
+    async fn export_my_data(
+        &self,
+        pool: Data<&MySqlPool>,
+        Json(request): Json<DataRequest>,
+    ) -> CustomResponse<DataExport> {
+        ModeratorAuth::check_signed(
+            pool.0,
+            request.user,
+            "export_my_data",
+            request.time,
+            &request.signature,
+        )
+        .await?;
+        let sql = |e: sqlx::Error| CustomResponse::error(&format!("SQL error: {e}."), true);
+        let hidden = query!(
+            "SELECT game, poll FROM hidden_polls WHERE game IN (SELECT game FROM game_players WHERE user = ?)",
+            request.user
+        )
+        .fetch_all(pool.0)
+        .await
+        .map_err(sql)?
+        .into_iter()
+        .map(|r| (r.game, r.poll))
+        .collect::<Vec<_>>();
+        let mut texts = Vec::new();
+        for game in games(pool.0, request.user).await.map_err(sql)? {
+            let polls = polls(pool.0, game, request.user).await?;
+            texts.extend(polls.into_iter().map(|(poll, text)| PollText {
+                game,
+                poll,
+                text,
+                hidden: hidden.contains(&(game, poll)),
+            }));
+        }
+        let data = export(pool.0, request.user, texts).await.map_err(sql)?;
+        CustomResponse::Ok(Json(data))
+    }
+    /// Forgets the email address, passkey, scheduled moves and quick play key of a user and hides the text of their
+    /// polls. Moves are kept, as the chains of their games can't be verified without them, so the text of a hidden
+    /// poll is still part of the signed poll move served by `/tokens`. (user action `delete_my_data`)
+    #[oai(path = "/delete_my_data", method = "post")]
+    async fn delete_my_data(
+        &self,
+        pool: Data<&MySqlPool>,
+        maintenance: Data<&Maintenance>,
+        Json(request): Json<DataRequest>,
+    ) -> CustomResponse<DeletionReport> {
+        maintenance.check()?;
+        ModeratorAuth::check_signed(
+            pool.0,
+            request.user,
+            "delete_my_data",
+            request.time,
+            &request.signature,
+        )
+        .await?;
+        let sql = |e: sqlx::Error| CustomResponse::error(&format!("SQL error: {e}."), true);
+        let mut hidden_polls = Vec::new();
+        for game in games(pool.0, request.user).await.map_err(sql)? {
+            let polls = polls(pool.0, game, request.user).await?;
+            hidden_polls.extend(polls.into_iter().map(|(poll, _)| (game, poll)));
+        }
+        let mut transaction = pool.0.begin().await.map_err(sql)?;
+        for (game, poll) in &hidden_polls {
+            query!(
+                "INSERT IGNORE INTO hidden_polls (game, poll) VALUES (?, ?);",
+                game,
+                poll
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(sql)?;
+        }
+        query!("DELETE FROM emails WHERE user = ?;", request.user)
+            .execute(&mut *transaction)
+            .await
+            .map_err(sql)?;
+        query!("DELETE FROM passkeys WHERE user = ?;", request.user)
+            .execute(&mut *transaction)
+            .await
+            .map_err(sql)?;
+        query!("DELETE FROM pending_moves WHERE user = ?;", request.user)
+            .execute(&mut *transaction)
+            .await
+            .map_err(sql)?;
+        query!("DELETE FROM quick_play WHERE user = ?;", request.user)
+            .execute(&mut *transaction)
+            .await
+            .map_err(sql)?;
+        let kept_moves = query!(
+            "SELECT COUNT(*) AS count FROM moves WHERE user = ?",
+            request.user
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(sql)?
+        .count;
+        transaction.commit().await.map_err(sql)?;
+        CustomResponse::Ok(Json(DeletionReport {
+            hidden_polls: hidden_polls.len().try_into().unwrap_or_default(),
+            kept_moves: kept_moves.try_into().unwrap_or_default(),
+        }))
+    }
+}
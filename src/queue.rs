@@ -0,0 +1,293 @@
+//! A matchmaking queue for games with the rules of a preset.
+//!
+//! A user queues with `/queue`, sending a `MoveLineType::JoinRandom` move signed with the text `queue|preset|time`,
+//! the time being the current unix time. As such a move doesn't depend on the moves before it, the server can append
+//! it to whichever game it makes later. The `queue` table (`user`, `preset`, `intent`, `queued`, `game`) holds the
+//! users that wait; once `MATCH_SIZE` of them wait for the same preset, `run` makes a game with the rules of the
+//! preset, joins them in the order they queued and notifies them. The row of a matched user is kept, with its `game`,
+//! so `/queue/status` can tell them where to play, until they leave, queue again or a day passed.
+
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, Object, OpenApi};
+use sqlx::{mysql::MySqlPool, query};
+use std::{sync::Arc, time::Duration};
+use tanktacticsgame::{
+    get_key, verify_data, DataBaseGame, MoveLine, MoveLineType, Preset, RULES_VERSION,
+};
+
+use crate::{
+    admin::{now, Maintenance},
+    email::{notify, Mailer, NotifyEvent},
+    moderation::ModeratorAuth,
+    privacy::DataRequest,
+    quota::Quotas,
+    storage::read_move,
+    sync::GameSync,
+    table::{CustomResponse, GameAPI},
+};
+
+/// How many players a match is made for, at most the max players of the preset.
+const MATCH_SIZE: usize = 4;
+/// How often the queue is checked for matches.
+const INTERVAL: Duration = Duration::from_secs(10);
+/// How far the time of a queue request may be from the server time, in seconds.
+const MAX_CLOCK_SKEW: u64 = 300;
+/// How long a matched user is kept in the queue, in seconds.
+const MATCHED_FOR: u64 = 24 * 60 * 60;
+
+/// The game made for a match of `preset`.
+fn preset_game(preset: Preset) -> DataBaseGame {
+    match preset {
+        Preset::Classic => DataBaseGame {
+            id: 0,
+            seed: now(),
+            last_vote: 0,
+            width: 5,
+            height: 5,
+            health: 3,
+            max_level: 2,
+            max_players: 10,
+            vote_threshold: 3,
+            range: "L".into(),
+            point_decay: false,
+            jury_weight: 0,
+            loot_mode: "killer".into(),
+            drive_length: 1,
+            rules_version: RULES_VERSION,
+            mutators: String::new(),
+            threshold_mode: String::new(),
+            win_condition: String::new(),
+            starting_points: 1,
+            starting_level: 0,
+            actions_per_round: 0,
+            items: String::new(),
+            two_phase_kills: false,
+            symmetric_spawns: false,
+            gift_cooldown: 0,
+            vote_interval: 0,
+            locale: String::new(),
+            description: String::new(),
+        },
+    }
+}
+
+/// The preset called `name`.
+fn preset(name: &str) -> Option<Preset> {
+    Preset::ALL.into_iter().find(|preset| preset.name() == name)
+}
+
+/// Makes a game for the users that waited longest for `preset`, if enough wait.
+async fn match_preset(
+    pool: &MySqlPool,
+    client: &reqwest::Client,
+    mailer: &Option<Arc<Mailer>>,
+    sync: &GameSync,
+    preset: Preset,
+) -> Result<(), sqlx::Error> {
+    let settings = preset_game(preset);
+    let size = MATCH_SIZE.min(usize::try_from(settings.max_players).unwrap_or_default());
+    let waiting = query!(
+        "SELECT user, intent FROM queue WHERE preset = ? AND game IS NULL ORDER BY queued, user LIMIT ?",
+        preset.name(),
+        u64::try_from(size).unwrap_or_default()
+    )
+    .fetch_all(pool)
+    .await?;
+    if size == 0 || waiting.len() < size {
+        return Ok(());
+    }
+    let CustomResponse::Ok(Json(game)) = GameAPI.create_game(pool, &settings).await else {
+        tracing::warn!("Could not make a game for the {} queue.", preset.name());
+        return Ok(());
+    };
+    let mut joined = Vec::new();
+    for row in waiting {
+        let appended = match read_move(&row.intent) {
+            Ok(intent) => {
+                GameAPI
+                    .append(pool, mailer.clone(), client, sync, game, intent)
+                    .await
+            }
+            Err(_) => CustomResponse::error("Malformed intent.", true),
+        };
+        if let CustomResponse::UserError(e) | CustomResponse::ServerError(e) = appended {
+            // The user can't join anymore, so they leave the queue.
+            tracing::warn!("Queued user ({}) could not join: {}", row.user, e.0);
+            query!("DELETE FROM queue WHERE user = ?;", row.user)
+                .execute(pool)
+                .await?;
+            continue;
+        }
+        query!("UPDATE queue SET game = ? WHERE user = ?;", game, row.user)
+            .execute(pool)
+            .await?;
+        joined.push((row.user, NotifyEvent::MatchStarted));
+    }
+    if let Some(mailer) = mailer {
+        tokio::spawn(notify(pool.clone(), mailer.clone(), game, joined));
+    }
+    Ok(())
+}
+
+/// Keeps making matches for every preset. No matches are made while maintenance mode is enabled.
+pub async fn run(
+    pool: MySqlPool,
+    client: reqwest::Client,
+    mailer: Option<Arc<Mailer>>,
+    sync: Arc<GameSync>,
+    maintenance: Maintenance,
+) {
+    loop {
+        if maintenance.check::<i32>().is_ok() {
+            for preset in Preset::ALL {
+                if let Err(e) = match_preset(&pool, &client, &mailer, &sync, preset).await {
+                    tracing::warn!("Queue: SQL error: {e}.");
+                }
+            }
+            if let Err(e) = query!(
+                "DELETE FROM queue WHERE game IS NOT NULL AND queued < ?;",
+                now().saturating_sub(MATCHED_FOR)
+            )
+            .execute(&pool)
+            .await
+            {
+                tracing::warn!("Queue clean up: SQL error: {e}.");
+            }
+        }
+        tokio::time::sleep(INTERVAL).await;
+    }
+}
+
+/// A request to wait for a match.
+#[derive(Object)]
+struct QueueRequest {
+    /// The name of the preset, see `Preset::name`.
+    preset: String,
+    /// A `JoinRandom` move with the text `queue|preset|time`, signed by the user at the unix time `time`.
+    intent: MoveLine,
+}
+
+/// Where a user stands in the queue.
+#[derive(Object)]
+struct QueueStatus {
+    /// The preset the user queued for, none if they aren't queued.
+    preset: Option<String>,
+    /// How many users wait for the preset, the user included.
+    waiting: u32,
+    /// The players a match is made for.
+    size: u32,
+    /// The game the user was matched into.
+    game: Option<i32>,
+}
+
+pub struct QueueAPI;
+
+#[OpenApi]
+impl QueueAPI {
+    /// Queues the user for a match of a preset, replacing an earlier place in the queue.
+    #[oai(path = "/queue", method = "post")]
+    async fn queue(
+        &self,
+        pool: Data<&MySqlPool>,
+        maintenance: Data<&Maintenance>,
+        quotas: Data<&Quotas>,
+        sync: Data<&Arc<GameSync>>,
+        Json(request): Json<QueueRequest>,
+    ) -> CustomResponse<i32> {
+        maintenance.check()?;
+        let preset =
+            preset(&request.preset).ok_or(CustomResponse::error("Unknown preset.", false))?;
+        let intent = request.intent;
+        let user = intent.authorizer;
+        let time = intent
+            .text
+            .as_deref()
+            .and_then(|text| text.strip_prefix(&format!("queue|{}|", preset.name())))
+            .and_then(|time| time.parse::<u64>().ok());
+        let Some(time) = time.filter(|_| intent.move_type == MoveLineType::JoinRandom) else {
+            return CustomResponse::error("Malformed intent.", false);
+        };
+        if now().abs_diff(time) > MAX_CLOCK_SKEW {
+            return CustomResponse::error("Request expired.", false);
+        }
+        let key = query!("SELECT public_key FROM users WHERE id = ?", user)
+            .fetch_optional(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+            .and_then(|r| get_key(r.public_key))
+            .ok_or(CustomResponse::error("User does not exist.", false))?;
+        if !verify_data(&key, &intent.signing_data(None), &intent.signature) {
+            return CustomResponse::error("Invalid signature.", false);
+        }
+        quotas.check_join(pool.0, sync.0, user).await?;
+        query!(
+            "REPLACE INTO queue (user, preset, intent, queued, game) VALUES (?, ?, ?, ?, NULL);",
+            user,
+            preset.name(),
+            intent.to_canonical_json(),
+            now()
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(user))
+    }
+    /// Takes the user out of the queue. (action `queue_leave`)
+    #[oai(path = "/queue/leave", method = "post")]
+    async fn leave(
+        &self,
+        pool: Data<&MySqlPool>,
+        Json(request): Json<DataRequest>,
+    ) -> CustomResponse<i32> {
+        ModeratorAuth::check_signed(
+            pool.0,
+            request.user,
+            "queue_leave",
+            request.time,
+            &request.signature,
+        )
+        .await?;
+        query!("DELETE FROM queue WHERE user = ?;", request.user)
+            .execute(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(request.user))
+    }
+    /// Returns where `user` stands in the queue, and the game they were matched into once it is made.
+    #[oai(path = "/queue/status", method = "get")]
+    async fn status(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(user): Query<i32>,
+    ) -> CustomResponse<QueueStatus> {
+        let row = query!("SELECT preset, game FROM queue WHERE user = ?", user)
+            .fetch_optional(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        let Some(row) = row else {
+            return CustomResponse::Ok(Json(QueueStatus {
+                preset: None,
+                waiting: 0,
+                size: 0,
+                game: None,
+            }));
+        };
+        let waiting = query!(
+            "SELECT COUNT(*) AS `waiting!: i64` FROM queue WHERE preset = ? AND game IS NULL",
+            row.preset
+        )
+        .fetch_one(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+        .waiting;
+        let size = preset(&row.preset).map_or(0, |preset| {
+            MATCH_SIZE.min(usize::try_from(preset_game(preset).max_players).unwrap_or_default())
+        });
+        CustomResponse::Ok(Json(QueueStatus {
+            preset: Some(row.preset),
+            waiting: u32::try_from(waiting).unwrap_or(u32::MAX),
+            size: u32::try_from(size).unwrap_or(u32::MAX),
+            game: row.game,
+        }))
+    }
+}
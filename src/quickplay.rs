@@ -0,0 +1,275 @@
+//! Quick play without an account.
+//!
+//! `/quick_play` registers a user with a fresh key pair and joins them to a casual game, the newest game listed in
+//! `casual_games` (`game`) with room left, or a new one. The server keeps the private key in `quick_play` (`user`,
+//! `game`, `private_key`, `last_active`) so it can forfeit for the player: once the game is over, or the player made no
+//! move for `INACTIVITY`, a living tank surrenders and the key is forgotten. The user row is only removed when the user
+//! made no moves, as the chain of the game needs its public key.
+
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, Object, OpenApi};
+use sqlx::{mysql::MySqlPool, query};
+use std::{sync::Arc, time::Duration};
+use tanktacticsgame::{
+    get_random_keys, DataBaseGame, GameStatus, MoveLine, MoveLineType, RULES_VERSION,
+};
+
+use crate::{
+    admin::{now, AdminAuth, Maintenance},
+    email::Mailer,
+    sync::GameSync,
+    table::{CustomResponse, GameAPI},
+};
+
+/// How long a quick play user may make no move before they forfeit, in seconds.
+const INACTIVITY: u64 = 3 * 24 * 60 * 60;
+/// How often quick play users are cleaned up.
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How many of the newest casual games are tried before a new one is made.
+const CANDIDATES: i64 = 5;
+
+fn casual_settings() -> DataBaseGame {
+    DataBaseGame {
+        id: 0,
+        seed: now(),
+        last_vote: 0,
+        width: 12,
+        height: 12,
+        health: 3,
+        max_level: 2,
+        max_players: 8,
+        vote_threshold: 2,
+        range: "L".into(),
+        point_decay: false,
+        jury_weight: 0,
+        loot_mode: "killer".into(),
+        drive_length: 1,
+        rules_version: RULES_VERSION,
+        mutators: String::new(),
+        threshold_mode: String::new(),
+        win_condition: String::new(),
+        starting_points: 1,
+        starting_level: 0,
+        actions_per_round: 0,
+        items: String::new(),
+        two_phase_kills: false,
+        symmetric_spawns: false,
+        gift_cooldown: 0,
+        vote_interval: 0,
+        locale: String::new(),
+        description: String::new(),
+    }
+}
+
+fn line(move_type: MoveLineType, user: i32) -> MoveLine {
+    MoveLine {
+        move_type,
+        x: None,
+        y: None,
+        target: None,
+        amount: None,
+        text: None,
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: Some(RULES_VERSION),
+        authorizer: user,
+        signature: String::new(),
+    }
+}
+
+/// A casual game `user` can join, making a new one if none has room.
+async fn casual_game(pool: &MySqlPool, user: i32) -> CustomResponse<i32> {
+    let games = query!(
+        "SELECT game FROM casual_games ORDER BY game DESC LIMIT ?",
+        CANDIDATES
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+    for row in games {
+        if let Ok((game, _)) = GameAPI.load_game::<i32>(pool, row.game, user).await {
+            if !game.status().is_over() && game.can_join(user).is_ok() {
+                return CustomResponse::Ok(Json(row.game));
+            }
+        }
+    }
+    let game = GameAPI.create_game(pool, &casual_settings()).await?;
+    query!("INSERT INTO casual_games (game) VALUES (?);", game)
+        .execute(pool)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+    CustomResponse::Ok(Json(game))
+}
+
+/// Signs `line` on top of the head of `game` with `private_key` and appends it.
+async fn sign_and_append(
+    pool: &MySqlPool,
+    client: &reqwest::Client,
+    mailer: &Option<Arc<Mailer>>,
+    sync: &GameSync,
+    game: i32,
+    mut line: MoveLine,
+    private_key: &str,
+) -> CustomResponse<i32> {
+    let (state, _) = GameAPI.load_game(pool, game, line.authorizer).await?;
+    line.sign(
+        state.lines.last().map(|l| l.signature.as_str()),
+        private_key.into(),
+    )
+    .map_err(|_| CustomResponse::error("Malformed key.", true))?;
+    GameAPI
+        .append(pool, mailer.clone(), client, sync, game, line)
+        .await
+}
+
+/// Forfeits for quick play users whose game is over or who have been inactive, and forgets them.
+async fn clean_up(
+    pool: &MySqlPool,
+    client: &reqwest::Client,
+    mailer: &Option<Arc<Mailer>>,
+    sync: &GameSync,
+) -> Result<(), sqlx::Error> {
+    let players = query!("SELECT user, game, private_key, last_active FROM quick_play")
+        .fetch_all(pool)
+        .await?;
+    let now = now();
+    for player in players {
+        let game = GameAPI
+            .load_game::<i32>(pool, player.game, player.user)
+            .await
+            .ok()
+            .map(|(game, _)| game);
+        let over = game.as_ref().map_or(true, |game| game.status().is_over());
+        if !over && now.saturating_sub(player.last_active) < INACTIVITY {
+            continue;
+        }
+        let alive = game
+            .and_then(|game| game.players.get(&player.user).cloned())
+            .is_some_and(|p| p.is_alive(true).is_ok());
+        if alive {
+            let surrender = line(MoveLineType::Surrender, player.user);
+            if let CustomResponse::UserError(e) | CustomResponse::ServerError(e) = sign_and_append(
+                pool,
+                client,
+                mailer,
+                sync,
+                player.game,
+                surrender,
+                &player.private_key,
+            )
+            .await
+            {
+                tracing::warn!(
+                    "Quick play user ({}) could not forfeit: {}",
+                    player.user,
+                    e.0
+                );
+                continue;
+            }
+        }
+        query!("DELETE FROM quick_play WHERE user = ?;", player.user)
+            .execute(pool)
+            .await?;
+        query!(
+            "DELETE FROM users WHERE id = ? AND NOT EXISTS (SELECT 1 FROM moves WHERE moves.user = ?);",
+            player.user,
+            player.user
+        )
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Keeps cleaning up quick play users. Nothing is cleaned up while maintenance mode is enabled.
+pub async fn run(
+    pool: MySqlPool,
+    client: reqwest::Client,
+    mailer: Option<Arc<Mailer>>,
+    sync: Arc<GameSync>,
+    maintenance: Maintenance,
+) {
+    loop {
+        if maintenance.check::<i32>().is_ok() {
+            if let Err(e) = clean_up(&pool, &client, &mailer, &sync).await {
+                tracing::warn!("Quick play clean up: SQL error: {e}.");
+            }
+        }
+        tokio::time::sleep(INTERVAL).await;
+    }
+}
+
+/// A quick play user, already in a game.
+#[derive(Object)]
+struct QuickPlayer {
+    user: i32,
+    game: i32,
+    /// The key of the user, also kept by the server until the user is cleaned up.
+    private_key: String,
+}
+
+pub struct QuickPlayAPI;
+
+#[OpenApi]
+impl QuickPlayAPI {
+    /// Registers a user with a new key and joins them to a casual game. The user forfeits once they stop playing.
+    #[oai(path = "/quick_play", method = "post")]
+    async fn quick_play(
+        &self,
+        pool: Data<&MySqlPool>,
+        maintenance: Data<&Maintenance>,
+        mailer: Data<&Option<Arc<Mailer>>>,
+        client: Data<&reqwest::Client>,
+        sync: Data<&Arc<GameSync>>,
+    ) -> CustomResponse<QuickPlayer> {
+        maintenance.check()?;
+        let (private_key, public_key) = get_random_keys();
+        let user = query!("INSERT INTO users (public_key) VALUES (?);", public_key)
+            .execute(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+            .last_insert_id()
+            .try_into()
+            .unwrap();
+        let game = casual_game(pool.0, user).await?;
+        query!(
+            "INSERT INTO quick_play (user, game, private_key, last_active) VALUES (?, ?, ?, ?);",
+            user,
+            game,
+            private_key,
+            now()
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        let (state, _) = GameAPI.load_game(pool.0, game, user).await?;
+        let (x, y) = state.get_pos();
+        let join = MoveLine {
+            x: Some(x),
+            y: Some(y),
+            ..line(MoveLineType::Join, user)
+        };
+        sign_and_append(pool.0, client.0, mailer.0, sync.0, game, join, &private_key).await?;
+        CustomResponse::Ok(Json(QuickPlayer {
+            user,
+            game,
+            private_key,
+        }))
+    }
+    /// Lists a game for quick play. (admin action `casual_game|game`)
+    #[oai(path = "/admin/casual_game", method = "post")]
+    async fn casual_game(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+        Json(auth): Json<AdminAuth>,
+    ) -> CustomResponse<i32> {
+        auth.check(pool.0, &format!("casual_game|{game}")).await?;
+        query!("INSERT IGNORE INTO casual_games (game) VALUES (?);", game)
+            .execute(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(game))
+    }
+}
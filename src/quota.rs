@@ -0,0 +1,112 @@
+//! Limits on how much a single user can take part in, so one account can't flood the public game list.
+//!
+//! `--games-per-day` limits the games a user creates in 24 hours, which then need a signed `creator`.
+//! `--active-games` limits the running games a user has a living tank in, checked when they join another one.
+//! Both are unlimited when unset. A request over a limit gets a `429` with a `QuotaExceeded`.
+
+use poem_openapi::{payload::Json, types::ToJSON, types::Type, Object};
+use sqlx::{mysql::MySqlPool, query};
+use tanktacticsgame::GameStatus;
+
+use crate::{
+    admin::now,
+    sync::GameSync,
+    table::{CustomResponse, GameAPI},
+};
+
+/// The length of the window `Quotas::games_per_day` counts in, in seconds.
+const DAY: u64 = 24 * 60 * 60;
+
+/// Why a request was refused for going over a quota.
+#[derive(Object)]
+pub struct QuotaExceeded {
+    /// The quota, `games_per_day` or `active_games`.
+    pub quota: String,
+    pub limit: u32,
+    pub used: u32,
+    /// The unix time at which the request can be made again, if waiting is enough.
+    pub retry_after: Option<u64>,
+}
+
+/// The configured limits per user, `None` is unlimited.
+#[derive(Clone, Copy, Default)]
+pub struct Quotas {
+    pub games_per_day: Option<u32>,
+    pub active_games: Option<u32>,
+}
+impl Quotas {
+    /// Refuses a new game of `creator` if they created `games_per_day` games in the last day.
+    pub(crate) async fn check_create<T: Type + ToJSON>(
+        &self,
+        pool: &MySqlPool,
+        creator: Option<i32>,
+    ) -> Result<(), CustomResponse<T>> {
+        let Some(limit) = self.games_per_day else {
+            return Ok(());
+        };
+        let Some(creator) = creator else {
+            return Err(CustomResponse::error(
+                "Games need a signed creator while games per day are limited.",
+                false,
+            ));
+        };
+        let since = now().saturating_sub(DAY);
+        let created = query!(
+            "SELECT created FROM game_moderation WHERE creator = ? AND created > ? ORDER BY created",
+            creator,
+            since
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        let used = u32::try_from(created.len()).unwrap_or(u32::MAX);
+        if used < limit {
+            return Ok(());
+        }
+        Err(CustomResponse::QuotaExceeded(Json(QuotaExceeded {
+            quota: "games_per_day".into(),
+            limit,
+            used,
+            retry_after: created.first().map(|r| r.created + DAY),
+        })))
+    }
+    /// Refuses `user` joining another game if they have a living tank in `active_games` running games. The games are
+    /// taken from the cache of `sync`, so only the games that changed since are replayed.
+    pub(crate) async fn check_join<T: Type + ToJSON>(
+        &self,
+        pool: &MySqlPool,
+        sync: &GameSync,
+        user: i32,
+    ) -> Result<(), CustomResponse<T>> {
+        let Some(limit) = self.active_games else {
+            return Ok(());
+        };
+        let joined = query!(
+            "SELECT game FROM game_players WHERE user = ? AND canonical",
+            user
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        let mut active = 0;
+        for row in joined {
+            let (game, _) = GameAPI.load_cached::<T>(pool, sync, row.game, user).await?;
+            let alive = game
+                .players
+                .get(&user)
+                .is_some_and(|p| p.is_alive(true).is_ok());
+            if alive && !game.status().is_over() {
+                active += 1;
+            }
+        }
+        if active < limit {
+            return Ok(());
+        }
+        Err(CustomResponse::QuotaExceeded(Json(QuotaExceeded {
+            quota: "active_games".into(),
+            limit,
+            used: active,
+            retry_after: None,
+        })))
+    }
+}
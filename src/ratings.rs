@@ -0,0 +1,178 @@
+//! Elo ratings of players across finished games.
+//!
+//! When a game finishes its players are ranked by `Game::standings`: the winners first, then the tanks still alive,
+//! then the dead tanks, the last to die first. Every pair of players is scored as a game of its own, a win for the
+//! better ranked one or a draw for equal ranks, and a player's rating moves by `K` times their summed score over what
+//! their rating expected, divided by the number of opponents. So a game moves a rating by at most `K`, however many
+//! joined.
+//!
+//! Players start at `STARTING_RATING`. Only canonical players count, and each game only once (see `rated_games`).
+
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, Object, OpenApi};
+use sqlx::{mysql::MySqlPool, query};
+use std::collections::HashMap;
+use tanktacticsgame::Game;
+
+use crate::{admin::now, table::CustomResponse};
+
+/// The rating of a player that didn't finish a game yet.
+pub const STARTING_RATING: i32 = 1500;
+/// The most a single game moves a rating.
+const K: f64 = 32.0;
+/// The most players `/leaderboard` returns.
+const LEADERBOARD_LIMIT: u32 = 100;
+
+/// The new rating of every player, from their place and current rating.
+fn updated(players: &[(i32, u32, i32)]) -> Vec<(i32, i32)> {
+    let opponents = players.len().saturating_sub(1).max(1);
+    #[allow(clippy::cast_precision_loss)]
+    let k = K / opponents as f64;
+    players
+        .iter()
+        .map(|&(user, place, rating)| {
+            let change = players
+                .iter()
+                .filter(|&&(other, ..)| other != user)
+                .map(|&(_, other_place, other_rating)| {
+                    let expected =
+                        1.0 / (1.0 + 10f64.powf(f64::from(other_rating - rating) / 400.0));
+                    let score = match place.cmp(&other_place) {
+                        std::cmp::Ordering::Less => 1.0,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Greater => 0.0,
+                    };
+                    score - expected
+                })
+                .sum::<f64>();
+            #[allow(clippy::cast_possible_truncation)]
+            let change = (k * change).round() as i32;
+            (user, rating + change)
+        })
+        .collect()
+}
+
+/// Updates the ratings of the players of `game` if it finished and wasn't rated yet.
+pub(crate) async fn rate(pool: &MySqlPool, game_id: i32, game: &Game) -> Result<(), sqlx::Error> {
+    if !game.status().is_over() {
+        return Ok(());
+    }
+    let mut transaction = pool.begin().await?;
+    let first = query!(
+        "INSERT IGNORE INTO rated_games (game, rated) VALUES (?, ?);",
+        game_id,
+        now()
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected()
+        == 1;
+    if !first {
+        return Ok(());
+    }
+    let canonical = query!(
+        "SELECT game_players.user, ratings.rating AS `rating?` FROM game_players LEFT JOIN ratings ON ratings.user = game_players.user WHERE game_players.game = ? AND game_players.canonical",
+        game_id
+    )
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .map(|r| (r.user, r.rating.unwrap_or(STARTING_RATING)))
+    .collect::<HashMap<_, _>>();
+    let players = game
+        .standings()
+        .into_iter()
+        .filter_map(|(user, place)| Some((user, place, *canonical.get(&user)?)))
+        .collect::<Vec<_>>();
+    if players.len() >= 2 {
+        let time = now();
+        for (user, rating) in updated(&players) {
+            query!(
+                "INSERT INTO ratings (user, rating, games, updated) VALUES (?, ?, 1, ?) ON DUPLICATE KEY UPDATE rating = VALUES(rating), games = games + 1, updated = VALUES(updated);",
+                user,
+                rating,
+                time
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+    }
+    transaction.commit().await
+}
+
+#[derive(Object)]
+struct Rating {
+    user: i32,
+    rating: i32,
+    /// The finished games the rating is based on.
+    games: u32,
+}
+
+/// The average rating of the players of a game.
+#[derive(Object)]
+struct GameRating {
+    game: i32,
+    rating: i32,
+    players: u32,
+}
+
+pub struct RatingAPI;
+
+#[OpenApi]
+impl RatingAPI {
+    /// Returns the rating of `user`, the starting rating if they didn't finish a game yet.
+    #[oai(path = "/rating", method = "get")]
+    async fn rating(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(user): Query<i32>,
+    ) -> CustomResponse<Rating> {
+        let row = query!("SELECT rating, games FROM ratings WHERE user = ?", user)
+            .fetch_optional(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(Rating {
+            user,
+            rating: row.as_ref().map_or(STARTING_RATING, |r| r.rating),
+            games: row.map_or(0, |r| r.games),
+        }))
+    }
+    /// Returns the highest rated players, best first, at most 100.
+    #[oai(path = "/leaderboard", method = "get")]
+    async fn leaderboard(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(limit): Query<Option<u32>>,
+    ) -> CustomResponse<Vec<Rating>> {
+        let limit = limit.unwrap_or(LEADERBOARD_LIMIT).min(LEADERBOARD_LIMIT);
+        let rows = sqlx::query_as!(
+            Rating,
+            "SELECT user, rating, games FROM ratings ORDER BY rating DESC, user LIMIT ?",
+            limit
+        )
+        .fetch_all(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(rows))
+    }
+    /// Returns the average rating of the players of every game with players, for sorting games by rating.
+    #[oai(path = "/game_ratings", method = "get")]
+    async fn game_ratings(&self, pool: Data<&MySqlPool>) -> CustomResponse<Vec<GameRating>> {
+        let rows = query!(
+            "SELECT game_players.game, CAST(ROUND(AVG(COALESCE(ratings.rating, ?))) AS SIGNED) AS `rating!: i64`, COUNT(*) AS `players!: i64` FROM game_players LEFT JOIN ratings ON ratings.user = game_players.user WHERE game_players.canonical GROUP BY game_players.game",
+            STARTING_RATING
+        )
+        .fetch_all(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        let ratings = rows
+            .into_iter()
+            .map(|r| GameRating {
+                game: r.game,
+                rating: i32::try_from(r.rating).unwrap_or(STARTING_RATING),
+                players: u32::try_from(r.players).unwrap_or(u32::MAX),
+            })
+            .collect();
+        CustomResponse::Ok(Json(ratings))
+    }
+}
@@ -0,0 +1,177 @@
+//! Moves of flagged users held for review.
+//!
+//! Admins flag suspicious users with `/admin/flag`. A move of a flagged user is checked like any other, then kept in
+//! the `review_moves` table (`game`, `user`, `index`, `token`, `expires`) instead of being appended. Moves are signed on
+//! top of the head they were made for, so the held move keeps the next index of its game: no other move is accepted
+//! until a moderator of the game approves it, which appends it, or rejects it with `/moderation/review`. A move nobody
+//! reviewed within `EXPIRY` seconds is dropped, so the other players of the game are never held up for longer.
+
+use poem::web::Data;
+use poem_openapi::{
+    param::Query,
+    payload::Json,
+    types::{ToJSON, Type},
+    Object, OpenApi,
+};
+use sqlx::{mysql::MySqlPool, query};
+use std::sync::Arc;
+use tanktacticsgame::MoveLine;
+
+use crate::{
+    admin::now,
+    email::Mailer,
+    moderation::ModeratorAuth,
+    storage::read_move,
+    sync::GameSync,
+    table::{CustomResponse, GameAPI},
+};
+
+/// How long a move waits for a moderator before it is dropped, in seconds.
+const EXPIRY: u64 = 15 * 60;
+
+/// A move of a flagged user awaiting review.
+#[derive(Object)]
+struct HeldMove {
+    /// The index the move is appended at when it is approved.
+    index: i32,
+    line: MoveLine,
+    /// The unix time at which the move is dropped.
+    expires: u64,
+}
+
+/// Refuses moves to `game` while a move is held for review in it, dropping the held move once it expired.
+pub(crate) async fn check_held<T: Type + ToJSON>(
+    pool: &MySqlPool,
+    game: i32,
+) -> Result<(), CustomResponse<T>> {
+    let now = now();
+    query!(
+        "DELETE FROM review_moves WHERE game = ? AND expires <= ?;",
+        game,
+        now
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+    let held = query!("SELECT expires FROM review_moves WHERE game = ?", game)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+    match held {
+        Some(r) => Err(CustomResponse::error(
+            &format!(
+                "A move is awaiting review by a moderator, try again in {} seconds.",
+                r.expires.saturating_sub(now)
+            ),
+            false,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Whether the moves of `user` are held for review.
+pub(crate) async fn is_flagged<T: Type + ToJSON>(
+    pool: &MySqlPool,
+    user: i32,
+) -> Result<bool, CustomResponse<T>> {
+    query!(
+        "SELECT flagged AS `flagged: bool` FROM users WHERE id = ?",
+        user
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|r| r.is_some_and(|r| r.flagged))
+    .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))
+}
+
+/// Checks `token` of a flagged user and holds it for review. Returns 0, like a move of a quarantined user.
+pub(crate) async fn hold(pool: &MySqlPool, game_id: i32, token: MoveLine) -> CustomResponse<i32> {
+    let (mut game, users) = GameAPI.load_game(pool, game_id, token.authorizer).await?;
+    let index: i32 = game.lines.len().try_into().unwrap();
+    game.load(token.clone(), &users)
+        .map_err(|e| CustomResponse::refused(&e))?;
+    query!(
+        "INSERT INTO review_moves (game, user, `index`, token, expires) VALUES (?, ?, ?, ?, ?);",
+        game_id,
+        token.authorizer,
+        index,
+        token.to_canonical_json(),
+        now() + EXPIRY
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+    CustomResponse::Ok(Json(0))
+}
+
+pub struct ReviewAPI;
+
+#[OpenApi]
+impl ReviewAPI {
+    /// Returns the move held for review in a game, if any. (moderator action `held|game`)
+    #[oai(path = "/moderation/held", method = "post")]
+    async fn held(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+        Json(auth): Json<ModeratorAuth>,
+    ) -> CustomResponse<Option<HeldMove>> {
+        auth.check(pool.0, game, &format!("held|{game}")).await?;
+        let held = query!(
+            "SELECT `index`, token, expires FROM review_moves WHERE game = ? AND expires > ?",
+            game,
+            now()
+        )
+        .fetch_optional(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        let Some(r) = held else {
+            return CustomResponse::Ok(Json(None));
+        };
+        let line =
+            read_move(&r.token).map_err(|_| CustomResponse::error("Corrupted move.", true))?;
+        CustomResponse::Ok(Json(Some(HeldMove {
+            index: r.index,
+            line,
+            expires: r.expires,
+        })))
+    }
+    /// Approves the move held for review in a game, appending it like `/move` does, or rejects it. Returns what `/move`
+    /// returns for an approved move and 0 for a rejected one. (moderator action `review|game|approve`)
+    #[oai(path = "/moderation/review", method = "post")]
+    #[allow(clippy::too_many_arguments)]
+    async fn review(
+        &self,
+        pool: Data<&MySqlPool>,
+        mailer: Data<&Option<Arc<Mailer>>>,
+        client: Data<&reqwest::Client>,
+        sync: Data<&Arc<GameSync>>,
+        Query(game): Query<i32>,
+        Query(approve): Query<bool>,
+        Json(auth): Json<ModeratorAuth>,
+    ) -> CustomResponse<i32> {
+        auth.check(pool.0, game, &format!("review|{game}|{approve}"))
+            .await?;
+        let held = query!(
+            "SELECT token FROM review_moves WHERE game = ? AND expires > ?",
+            game,
+            now()
+        )
+        .fetch_optional(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+        .ok_or(CustomResponse::error("No move is awaiting review.", false))?;
+        query!("DELETE FROM review_moves WHERE game = ?;", game)
+            .execute(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        if !approve {
+            return CustomResponse::Ok(Json(0));
+        }
+        let token =
+            read_move(&held.token).map_err(|_| CustomResponse::error("Corrupted move.", true))?;
+        GameAPI
+            .append_canonical(pool.0, mailer.0.clone(), client.0, sync.0, game, token)
+            .await
+    }
+}
@@ -0,0 +1,307 @@
+//! Moves held until a scheduled time.
+//!
+//! A move posted with `schedule` is checked against the game as it is now and kept in the `pending_moves` table
+//! (`id`, `user`, `game`, `token`, `at`). Once `at` has passed the scheduler appends it like any other move. Moves are
+//! signed on top of the head they were made for, so a pending move is dropped when another move was appended to the
+//! game in the meantime, when it broke the rules by then, or when it is more than `EXPIRY` seconds late. Votes due while
+//! the game is paused (see `MoveLineType::Pause`) are skipped, so no points are handed out during the pause.
+//!
+//! `REMINDER` seconds before a scheduled `HandleVotes` the jurors that haven't voted yet are reminded, by email and
+//! with a `vote_reminder` webhook. The `reminded` column keeps them from being reminded twice.
+//!
+//! Games with a `vote_interval` have their votes handled by the server itself, like the daily point of Tank Turn
+//! Tactics: once the interval has passed since `last_vote`, the scheduler appends a `HandleVotes` at that time, signed
+//! with the server key by the user holding it (see `server_user`). Games that are paused, over or still waiting for
+//! players are skipped.
+
+use base64::Engine;
+use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+use poem_openapi::{
+    payload::Json,
+    types::{ParseFromJSON, ToJSON},
+};
+use sqlx::{mysql::MySqlPool, query};
+use std::{sync::Arc, time::Duration};
+use tanktacticsgame::{clock_data, GameStatus, MoveLine, MoveLineType, BASE64};
+
+use crate::{
+    admin::{now, Maintenance},
+    email::{notify, Mailer, NotifyEvent},
+    storage::read_move,
+    sync::GameSync,
+    table::{CustomResponse, GameAPI},
+    webhook::{deliver, Delivery},
+};
+
+/// How far ahead a move can be scheduled, in seconds.
+const MAX_DELAY: u64 = 7 * 24 * 60 * 60;
+/// How late a move may be appended, in seconds, for when the server was down at the scheduled time.
+const EXPIRY: u64 = 60 * 60;
+/// How long before scheduled votes are handled the jurors that haven't voted are reminded, in seconds.
+const REMINDER: u64 = 2 * 60 * 60;
+/// How far the time of votes the server handles may be from the server time, in seconds. (see `GameAPI::append`)
+const CLOCK_SKEW: u64 = 300;
+/// How often the scheduler looks for due moves.
+const INTERVAL: Duration = Duration::from_secs(10);
+
+/// Checks `token` and holds it until `at`. Returns the id of the pending move.
+pub(crate) async fn hold(
+    pool: &MySqlPool,
+    game_id: i32,
+    token: MoveLine,
+    at: u64,
+) -> CustomResponse<i32> {
+    let now = now();
+    if at <= now || at - now > MAX_DELAY {
+        return CustomResponse::error("Moves can be scheduled up to a week ahead.", false);
+    }
+    if token.move_type == MoveLineType::HandleVotes
+        && token.time.map_or(true, |time| at.abs_diff(time) > 300)
+    {
+        return CustomResponse::error("Votes must be handled with the scheduled time.", false);
+    }
+    let (mut game, users) = GameAPI.load_game(pool, game_id, token.authorizer).await?;
+    game.load(token.clone(), &users)
+        .map_err(|e| CustomResponse::refused(&e))?;
+    let id = query!(
+        "INSERT INTO pending_moves (user, game, token, at) VALUES (?, ?, ?, ?);",
+        token.authorizer,
+        game_id,
+        token.to_canonical_json(),
+        at
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+    .last_insert_id();
+    CustomResponse::Ok(Json(id.try_into().unwrap()))
+}
+
+/// Appends the pending moves that are due, oldest first. Pending moves wait while maintenance mode is enabled.
+async fn append_due(
+    pool: &MySqlPool,
+    client: &reqwest::Client,
+    mailer: &Option<Arc<Mailer>>,
+    sync: &GameSync,
+    maintenance: &Maintenance,
+) {
+    if maintenance.check::<i32>().is_err() {
+        return;
+    }
+    let now = now();
+    let Ok(due) = query!(
+        "SELECT id, game, token, at FROM pending_moves WHERE at <= ? ORDER BY at, id;",
+        now
+    )
+    .fetch_all(pool)
+    .await
+    else {
+        return;
+    };
+    for pending in due {
+        if query!("DELETE FROM pending_moves WHERE id = ?;", pending.id)
+            .execute(pool)
+            .await
+            .is_err()
+        {
+            continue;
+        }
+        if now - pending.at > EXPIRY {
+            tracing::info!("Pending move ({}) expired.", pending.id);
+            continue;
+        }
+        let Ok(token) = read_move(&pending.token) else {
+            tracing::warn!("Pending move ({}) is corrupted.", pending.id);
+            continue;
+        };
+        if token.move_type == MoveLineType::HandleVotes
+            && GameAPI
+                .load_game::<i32>(pool, pending.game, token.authorizer)
+                .await
+                .is_ok_and(|(game, _)| game.is_paused())
+        {
+            tracing::info!("Pending move ({}) skipped, the game is paused.", pending.id);
+            continue;
+        }
+        if let CustomResponse::Ok(_) = GameAPI
+            .append(pool, mailer.clone(), client, sync, pending.game, token)
+            .await
+        {
+            continue;
+        }
+        tracing::info!("Pending move ({}) is no longer valid.", pending.id);
+    }
+}
+
+/// Reminds the jurors that haven't voted of the votes that are handled within `REMINDER`.
+async fn remind_voters(pool: &MySqlPool, client: &reqwest::Client, mailer: &Option<Arc<Mailer>>) {
+    let Ok(due) = query!(
+        "SELECT id, game, token, at FROM pending_moves WHERE NOT reminded AND at <= ?;",
+        now() + REMINDER
+    )
+    .fetch_all(pool)
+    .await
+    else {
+        return;
+    };
+    for pending in due {
+        if query!(
+            "UPDATE pending_moves SET reminded = TRUE WHERE id = ?;",
+            pending.id
+        )
+        .execute(pool)
+        .await
+        .is_err()
+        {
+            continue;
+        }
+        let Ok(token) = read_move(&pending.token) else {
+            continue;
+        };
+        if token.move_type != MoveLineType::HandleVotes {
+            continue;
+        }
+        let Ok((game, _)) = GameAPI
+            .load_game::<i32>(pool, pending.game, token.authorizer)
+            .await
+        else {
+            continue;
+        };
+        let jurors = game.missing_voters();
+        if jurors.is_empty() {
+            continue;
+        }
+        let delivery = Delivery::vote_reminder(pending.game, pending.at, &jurors);
+        tokio::spawn(deliver(
+            pool.clone(),
+            client.clone(),
+            pending.game,
+            vec![delivery],
+        ));
+        if let Some(mailer) = mailer {
+            let events = jurors
+                .into_iter()
+                .map(|user| (user, NotifyEvent::VoteReminder))
+                .collect();
+            tokio::spawn(notify(pool.clone(), mailer.clone(), pending.game, events));
+        }
+    }
+}
+
+/// The user whose key is the server key, registered the first time the server makes a move of its own.
+async fn server_user(pool: &MySqlPool, public_key: &str) -> Result<i32, sqlx::Error> {
+    let user = query!(
+        "SELECT id FROM users WHERE public_key = ? ORDER BY id LIMIT 1;",
+        public_key
+    )
+    .fetch_optional(pool)
+    .await?;
+    match user {
+        Some(user) => Ok(user.id),
+        None => Ok(
+            query!("INSERT INTO users (public_key) VALUES (?);", public_key)
+                .execute(pool)
+                .await?
+                .last_insert_id()
+                .try_into()
+                .unwrap(),
+        ),
+    }
+}
+
+/// Handles the votes of the games whose `vote_interval` has passed, as the server user. The votes are handled at the
+/// time they were due, so the rounds don't drift, unless the server was down for longer than the clock may be off.
+async fn handle_rounds(
+    pool: &MySqlPool,
+    client: &reqwest::Client,
+    mailer: &Option<Arc<Mailer>>,
+    sync: &GameSync,
+    maintenance: &Maintenance,
+    keys: &(SigningKey, String),
+) {
+    if maintenance.check::<i32>().is_err() {
+        return;
+    }
+    let now = now();
+    let Ok(due) = query!(
+        "SELECT games.id, games.last_vote + games.vote_interval AS `due: u64` FROM games LEFT JOIN game_moderation ON game_moderation.game = games.id WHERE games.vote_interval > 0 AND games.last_vote + games.vote_interval <= ? AND NOT COALESCE(game_moderation.paused, FALSE) AND games.id NOT IN (SELECT game FROM rated_games);",
+        now
+    )
+    .fetch_all(pool)
+    .await
+    else {
+        return;
+    };
+    if due.is_empty() {
+        return;
+    }
+    let user = match server_user(pool, &keys.1).await {
+        Ok(user) => user,
+        Err(e) => {
+            tracing::warn!("Could not register the server user: {e}");
+            return;
+        }
+    };
+    let private_key = BASE64.encode(keys.0.to_bytes());
+    for game in due {
+        let Ok((state, _)) = GameAPI.load_cached::<i32>(pool, sync, game.id, user).await else {
+            continue;
+        };
+        if state.status() != GameStatus::Running || state.is_paused() {
+            continue;
+        }
+        let time = if now - game.due <= CLOCK_SKEW {
+            game.due
+        } else {
+            now
+        };
+        let clock: Signature = keys.0.sign(clock_data(time).as_bytes());
+        let mut line = MoveLine {
+            move_type: MoveLineType::HandleVotes,
+            x: None,
+            y: None,
+            target: None,
+            amount: None,
+            text: None,
+            time: Some(time),
+            clock: Some(BASE64.encode(clock.to_bytes())),
+            path: None,
+            rules_version: Some(state.settings.rules_version),
+            authorizer: user,
+            signature: String::new(),
+        };
+        if line
+            .sign(
+                state.lines.last().map(|l| l.signature.as_str()),
+                private_key.clone(),
+            )
+            .is_err()
+        {
+            tracing::warn!("The server key can't sign moves.");
+            return;
+        }
+        if let CustomResponse::UserError(e) | CustomResponse::ServerError(e) = GameAPI
+            .append(pool, mailer.clone(), client, sync, game.id, line)
+            .await
+        {
+            tracing::warn!("Could not handle the votes of game {}: {}", game.id, e.0);
+        }
+    }
+}
+
+/// Keeps appending pending moves as they become due.
+pub async fn run(
+    pool: MySqlPool,
+    client: reqwest::Client,
+    mailer: Option<Arc<Mailer>>,
+    sync: Arc<GameSync>,
+    maintenance: Maintenance,
+    keys: (SigningKey, String),
+) {
+    loop {
+        remind_voters(&pool, &client, &mailer).await;
+        append_due(&pool, &client, &mailer, &sync, &maintenance).await;
+        handle_rounds(&pool, &client, &mailer, &sync, &maintenance, &keys).await;
+        tokio::time::sleep(INTERVAL).await;
+    }
+}
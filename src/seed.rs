@@ -0,0 +1,125 @@
+//! Bulk creation of users and games, for tournaments and test environments.
+//!
+//! A JSON seed file holds `users`, each with a `public_key`, and `games`, each a `DataBaseGame` (its `id` is ignored)
+//! with an optional `creator` and `moderators` given as indices into `users`. A CSV seed file only holds users, one
+//! public key per line. Everything is created in a single transaction, so a bad entry leaves the database untouched.
+
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use serde::Deserialize;
+use sqlx::{mysql::MySqlPool, query};
+use std::path::Path;
+use tanktacticsgame::{get_key, DataBaseGame, PublicKey};
+
+use crate::{
+    admin::now,
+    moderation::set_moderators,
+    table::{CustomResponse, GameAPI},
+};
+
+#[derive(Deserialize)]
+pub struct SeedUser {
+    pub public_key: String,
+}
+
+#[derive(Deserialize)]
+pub struct SeedGame {
+    #[serde(flatten)]
+    pub settings: DataBaseGame,
+    /// The index of the user that created the game.
+    #[serde(default)]
+    pub creator: Option<usize>,
+    /// The indices of the users moderating the game.
+    #[serde(default)]
+    pub moderators: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct Seed {
+    #[serde(default)]
+    pub users: Vec<SeedUser>,
+    #[serde(default)]
+    pub games: Vec<SeedGame>,
+}
+impl Seed {
+    /// Reads a seed file, as CSV if its extension is `csv` and as JSON otherwise.
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Could not read {}.", path.display()))?;
+        if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("csv"))
+        {
+            let users = text
+                .lines()
+                .map(|line| line.trim().trim_matches('"'))
+                .filter(|key| !key.is_empty() && *key != "public_key")
+                .map(|key| SeedUser {
+                    public_key: key.to_string(),
+                })
+                .collect();
+            return Ok(Seed {
+                users,
+                games: Vec::new(),
+            });
+        }
+        serde_json::from_str(&text)
+            .wrap_err_with(|| format!("Malformed seed file {}.", path.display()))
+    }
+}
+
+/// Creates everything in `seed`, returning the ids of the new users and games.
+pub async fn run(pool: &MySqlPool, seed: &Seed) -> Result<(Vec<i32>, Vec<i32>)> {
+    for (index, user) in seed.users.iter().enumerate() {
+        let Some(PublicKey::K256(_)) = get_key(user.public_key.clone()) else {
+            bail!("User {index} has a malformed key.");
+        };
+    }
+    let mut transaction = pool.begin().await?;
+    let mut users = Vec::with_capacity(seed.users.len());
+    for user in &seed.users {
+        let id = query!(
+            "INSERT INTO users (public_key) VALUES (?);",
+            user.public_key
+        )
+        .execute(&mut *transaction)
+        .await?
+        .last_insert_id();
+        users.push(i32::try_from(id)?);
+    }
+    let user = |index: usize| {
+        users
+            .get(index)
+            .copied()
+            .ok_or_else(|| eyre!("There is no user {index}."))
+    };
+    let mut games = Vec::with_capacity(seed.games.len());
+    for (index, game) in seed.games.iter().enumerate() {
+        let id = match GameAPI.create_game(&mut *transaction, &game.settings).await {
+            CustomResponse::Ok(id) => id.0,
+            CustomResponse::UserError(e) | CustomResponse::ServerError(e) => {
+                bail!("Game {index}: {}", e.0)
+            }
+            CustomResponse::Unavailable(e) => bail!("Game {index}: {}", e.0.message),
+            CustomResponse::TileOccupied(_) | CustomResponse::QuotaExceeded(_) => unreachable!(),
+        };
+        if let Some(creator) = game.creator {
+            query!(
+                "INSERT INTO game_moderation (game, creator, created) VALUES (?, ?, ?);",
+                id,
+                user(creator)?,
+                now()
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        let moderators = game
+            .moderators
+            .iter()
+            .map(|&index| user(index))
+            .collect::<Result<Vec<_>>>()?;
+        set_moderators(&mut *transaction, id, &moderators).await?;
+        games.push(id);
+    }
+    transaction.commit().await?;
+    Ok((users, games))
+}
@@ -0,0 +1,479 @@
+//! Where users, games and their moves are kept.
+//!
+//! The server keeps them in MySQL, and `GameAPI` reads and writes users, games and moves through `Storage`.
+//! `MemoryStorage` keeps them in a `HashMap` instead, for the playtest server (see `playtest --persist`) and for demos
+//! and CI, with `serve --database-url memory:`. It can read a `Backup` on start and write one when it shuts down, with
+//! `memory:state.json`, so a playtest or demo can be picked up again.
+//! With the `sqlite` feature, `SqliteStorage` keeps them in a SQLite database.
+//!
+//! The full server also keeps moderation, quarantine, email, ratings and the other tables of `migrations` in MySQL,
+//! which the other endpoints query directly. Given a `sqlite:` or `memory:` database instead, `serve` only serves the
+//! game endpoints, from a `LocalStorage`. (see `local`)
+//!
+//! Moves are stored in the canonical JSON form of `MoveLine::to_canonical_json`, so the same move is always stored as
+//! the same bytes. Rows stored before that hold the JSON `poem_openapi` wrote, which `read_move` still reads.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+};
+
+use poem_openapi::payload::Json;
+use sqlx::mysql::MySqlPool;
+use tanktacticsgame::{DataBaseGame, MoveLine, User};
+use thiserror::Error;
+
+use crate::{
+    admin::now,
+    backup::{Backup, MoveRow},
+    table::{CustomResponse, GameAPI},
+};
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("SQL error: {0}.")]
+    Sql(#[from] sqlx::Error),
+    #[error("IO error: {0}.")]
+    Io(#[from] std::io::Error),
+    #[error("Corrupted state: {0}.")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Refused(String),
+}
+
+/// Reads a stored move, in the canonical form or the one rows were stored in before it.
+pub(crate) fn read_move(token: &str) -> Result<MoveLine, serde_json::Error> {
+    serde_json::from_str(token)
+}
+
+/// Keeps users, games and the moves of every game.
+pub trait Storage: Send + Sync {
+    fn users(&self) -> impl Future<Output = Result<Vec<User>, StorageError>> + Send;
+    /// Stores a new user, returning its id.
+    fn register(
+        &self,
+        public_key: String,
+    ) -> impl Future<Output = Result<i32, StorageError>> + Send;
+    fn games(&self) -> impl Future<Output = Result<Vec<DataBaseGame>, StorageError>> + Send;
+    /// The game with the id `game`, if there is one.
+    fn game(
+        &self,
+        game: i32,
+    ) -> impl Future<Output = Result<Option<DataBaseGame>, StorageError>> + Send;
+    /// Checks the settings of `game` and stores it, returning the id of the new game.
+    fn create_game(
+        &self,
+        game: DataBaseGame,
+    ) -> impl Future<Output = Result<i32, StorageError>> + Send;
+    /// The moves of `game`, in order.
+    fn tokens(&self, game: i32)
+        -> impl Future<Output = Result<Vec<MoveLine>, StorageError>> + Send;
+    /// Stores `line` as move `index` of `game`. Checking the move is up to the caller.
+    fn append(
+        &self,
+        game: i32,
+        index: usize,
+        line: MoveLine,
+    ) -> impl Future<Output = Result<(), StorageError>> + Send;
+}
+
+impl Storage for MySqlPool {
+    async fn users(&self) -> Result<Vec<User>, StorageError> {
+        Ok(
+            sqlx::query_as!(User, "SELECT id, public_key FROM users ORDER BY id")
+                .fetch_all(self)
+                .await?,
+        )
+    }
+    async fn register(&self, public_key: String) -> Result<i32, StorageError> {
+        let id = sqlx::query!("INSERT INTO users (public_key) VALUES (?);", public_key)
+            .execute(self)
+            .await?
+            .last_insert_id();
+        Ok(id.try_into().unwrap())
+    }
+    async fn games(&self) -> Result<Vec<DataBaseGame>, StorageError> {
+        Ok(sqlx::query_as!(DataBaseGame, "SELECT * FROM games")
+            .fetch_all(self)
+            .await?)
+    }
+    async fn game(&self, game: i32) -> Result<Option<DataBaseGame>, StorageError> {
+        Ok(
+            sqlx::query_as!(DataBaseGame, "SELECT * FROM games WHERE id = ?", game)
+                .fetch_optional(self)
+                .await?,
+        )
+    }
+    async fn create_game(&self, game: DataBaseGame) -> Result<i32, StorageError> {
+        match GameAPI.create_game(self, &game).await {
+            CustomResponse::Ok(Json(id)) => Ok(id),
+            CustomResponse::UserError(e) | CustomResponse::ServerError(e) => {
+                Err(StorageError::Refused(e.0))
+            }
+            CustomResponse::Unavailable(Json(e)) => Err(StorageError::Refused(e.message)),
+            CustomResponse::TileOccupied(_) | CustomResponse::QuotaExceeded(_) => unreachable!(),
+        }
+    }
+    async fn tokens(&self, game: i32) -> Result<Vec<MoveLine>, StorageError> {
+        sqlx::query!(
+            "SELECT token FROM moves WHERE game = ? AND canonical ORDER BY `index`",
+            game
+        )
+        .fetch_all(self)
+        .await?
+        .into_iter()
+        .map(|r| read_move(&r.token).map_err(StorageError::from))
+        .collect()
+    }
+    async fn append(&self, game: i32, index: usize, line: MoveLine) -> Result<(), StorageError> {
+        let index = i32::try_from(index).map_err(|e| StorageError::Refused(e.to_string()))?;
+        sqlx::query!(
+            "INSERT INTO moves (user, game, `index`, token) VALUES (?, ?, ?, ?);",
+            line.authorizer,
+            game,
+            index,
+            line.to_canonical_json()
+        )
+        .execute(self)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO game_players (game, user) VALUES (?, ?) ON DUPLICATE KEY UPDATE canonical = TRUE;",
+            game,
+            line.authorizer
+        )
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Memory {
+    users: Vec<User>,
+    games: Vec<DataBaseGame>,
+    moves: HashMap<i32, Vec<MoveLine>>,
+}
+
+/// Keeps everything in memory, optionally written to `path` as a `Backup` by `MemoryStorage::save`. A server on it only
+/// serves the game endpoints. (see the module docs)
+pub struct MemoryStorage {
+    path: Option<PathBuf>,
+    memory: Mutex<Memory>,
+}
+impl MemoryStorage {
+    /// Starts from the backup at `path` if there is one, or else empty.
+    pub fn open(path: Option<PathBuf>) -> Result<Self, StorageError> {
+        let mut memory = Memory::default();
+        if let Some(backup) = path.as_ref().filter(|path| path.exists()) {
+            let backup: Backup = serde_json::from_slice(&std::fs::read(backup)?)?;
+            memory.users = backup.users;
+            memory.games = backup.games;
+            for row in backup.moves.into_iter().filter(|row| row.canonical) {
+                memory
+                    .moves
+                    .entry(row.game)
+                    .or_default()
+                    .push(read_move(&row.token)?);
+            }
+        }
+        Ok(MemoryStorage {
+            path,
+            memory: Mutex::new(memory),
+        })
+    }
+    fn lock(&self) -> MutexGuard<'_, Memory> {
+        self.memory
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+    /// Writes everything to the backup file, if there is one.
+    pub fn save(&self) -> Result<(), StorageError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let memory = self.lock();
+        let mut moves = Vec::new();
+        for (&game, lines) in &memory.moves {
+            for (index, line) in (0..).zip(lines) {
+                moves.push(MoveRow {
+                    user: line.authorizer,
+                    game,
+                    index,
+                    token: line.to_canonical_json(),
+                    canonical: true,
+                });
+            }
+        }
+        let backup = Backup {
+            created: now(),
+            users: memory.users.clone(),
+            games: memory.games.clone(),
+            moves,
+        };
+        std::fs::write(path, serde_json::to_vec(&backup)?)?;
+        Ok(())
+    }
+}
+impl Storage for MemoryStorage {
+    async fn users(&self) -> Result<Vec<User>, StorageError> {
+        Ok(self.lock().users.clone())
+    }
+    async fn register(&self, public_key: String) -> Result<i32, StorageError> {
+        let mut memory = self.lock();
+        let id = i32::try_from(memory.users.len()).unwrap() + 1;
+        memory.users.push(User { id, public_key });
+        Ok(id)
+    }
+    async fn games(&self) -> Result<Vec<DataBaseGame>, StorageError> {
+        Ok(self.lock().games.clone())
+    }
+    async fn game(&self, game: i32) -> Result<Option<DataBaseGame>, StorageError> {
+        Ok(self.lock().games.iter().find(|g| g.id == game).cloned())
+    }
+    async fn create_game(&self, mut game: DataBaseGame) -> Result<i32, StorageError> {
+        game.settings()
+            .map_err(|e| StorageError::Refused(e.to_string()))?;
+        let mut memory = self.lock();
+        game.id = i32::try_from(memory.games.len()).unwrap() + 1;
+        let id = game.id;
+        memory.games.push(game);
+        Ok(id)
+    }
+    async fn tokens(&self, game: i32) -> Result<Vec<MoveLine>, StorageError> {
+        Ok(self.lock().moves.get(&game).cloned().unwrap_or_default())
+    }
+    async fn append(&self, game: i32, index: usize, line: MoveLine) -> Result<(), StorageError> {
+        let mut memory = self.lock();
+        let lines = memory.moves.entry(game).or_default();
+        if lines.len() != index {
+            return Err(StorageError::Refused(format!(
+                "Move {index} of game {game} is taken."
+            )));
+        }
+        lines.push(line);
+        Ok(())
+    }
+}
+
+/// The tables of `SqliteStorage`, created when the database is opened.
+#[cfg(feature = "sqlite")]
+const SQLITE_SCHEMA: [&str; 3] = [
+    "CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY AUTOINCREMENT, public_key TEXT NOT NULL)",
+    "CREATE TABLE IF NOT EXISTS games (id INTEGER PRIMARY KEY AUTOINCREMENT, settings TEXT NOT NULL)",
+    "CREATE TABLE IF NOT EXISTS moves (game INTEGER NOT NULL, `index` INTEGER NOT NULL, user INTEGER NOT NULL, \
+     token TEXT NOT NULL, PRIMARY KEY (game, `index`))",
+];
+
+/// Keeps everything in a SQLite database, so a server can be hosted without MySQL. A game is stored as the JSON of its
+/// `DataBaseGame`, like in a `Backup`.
+#[cfg(feature = "sqlite")]
+pub struct SqliteStorage(sqlx::SqlitePool);
+#[cfg(feature = "sqlite")]
+impl SqliteStorage {
+    /// Opens the database at `url`, like `sqlite:tanktactics.db`, creating it if it doesn't exist yet.
+    pub async fn open(url: &str) -> Result<Self, StorageError> {
+        use std::str::FromStr;
+        let options = sqlx::sqlite::SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        let pool = sqlx::SqlitePool::connect_with(options).await?;
+        for statement in SQLITE_SCHEMA {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+        Ok(SqliteStorage(pool))
+    }
+}
+#[cfg(feature = "sqlite")]
+impl Storage for SqliteStorage {
+    async fn users(&self) -> Result<Vec<User>, StorageError> {
+        use sqlx::Row;
+        sqlx::query("SELECT id, public_key FROM users ORDER BY id")
+            .fetch_all(&self.0)
+            .await?
+            .into_iter()
+            .map(|row| {
+                Ok(User {
+                    id: row.try_get("id")?,
+                    public_key: row.try_get("public_key")?,
+                })
+            })
+            .collect()
+    }
+    async fn register(&self, public_key: String) -> Result<i32, StorageError> {
+        let id = sqlx::query("INSERT INTO users (public_key) VALUES (?)")
+            .bind(public_key)
+            .execute(&self.0)
+            .await?
+            .last_insert_rowid();
+        Ok(id.try_into().unwrap())
+    }
+    async fn games(&self) -> Result<Vec<DataBaseGame>, StorageError> {
+        use sqlx::Row;
+        sqlx::query("SELECT id, settings FROM games ORDER BY id")
+            .fetch_all(&self.0)
+            .await?
+            .into_iter()
+            .map(|row| {
+                let settings: String = row.try_get("settings")?;
+                Ok(DataBaseGame {
+                    id: row.try_get("id")?,
+                    ..serde_json::from_str(&settings)?
+                })
+            })
+            .collect()
+    }
+    async fn game(&self, game: i32) -> Result<Option<DataBaseGame>, StorageError> {
+        use sqlx::Row;
+        let Some(row) = sqlx::query("SELECT settings FROM games WHERE id = ?")
+            .bind(game)
+            .fetch_optional(&self.0)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let settings: String = row.try_get("settings")?;
+        Ok(Some(DataBaseGame {
+            id: game,
+            ..serde_json::from_str(&settings)?
+        }))
+    }
+    async fn create_game(&self, game: DataBaseGame) -> Result<i32, StorageError> {
+        game.settings()
+            .map_err(|e| StorageError::Refused(e.to_string()))?;
+        let id = sqlx::query("INSERT INTO games (settings) VALUES (?)")
+            .bind(serde_json::to_string(&game)?)
+            .execute(&self.0)
+            .await?
+            .last_insert_rowid();
+        Ok(id.try_into().unwrap())
+    }
+    async fn tokens(&self, game: i32) -> Result<Vec<MoveLine>, StorageError> {
+        use sqlx::Row;
+        sqlx::query("SELECT token FROM moves WHERE game = ? ORDER BY `index`")
+            .bind(game)
+            .fetch_all(&self.0)
+            .await?
+            .into_iter()
+            .map(|row| Ok(read_move(row.try_get("token")?)?))
+            .collect()
+    }
+    async fn append(&self, game: i32, index: usize, line: MoveLine) -> Result<(), StorageError> {
+        let index = i32::try_from(index).map_err(|e| StorageError::Refused(e.to_string()))?;
+        let inserted =
+            sqlx::query("INSERT INTO moves (game, `index`, user, token) VALUES (?, ?, ?, ?)")
+                .bind(game)
+                .bind(index)
+                .bind(line.authorizer)
+                .bind(line.to_canonical_json())
+                .execute(&self.0)
+                .await;
+        match inserted {
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(StorageError::Refused(
+                format!("Move {index} of game {game} is taken."),
+            )),
+            inserted => inserted.map(|_| ()).map_err(StorageError::from),
+        }
+    }
+}
+
+/// The storage of a playtest or a server without MySQL: a `MemoryStorage` written to a JSON file, or with the `sqlite`
+/// feature a `SqliteStorage`.
+pub enum LocalStorage {
+    Memory(MemoryStorage),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteStorage),
+}
+impl LocalStorage {
+    /// Connects to the database at `url`, a `sqlite:` connection string, or `memory:` followed by the file for
+    /// `MemoryStorage::open`, if any.
+    pub async fn connect(url: &str) -> Result<Self, StorageError> {
+        if let Some(path) = url.strip_prefix("memory:") {
+            let path = Some(PathBuf::from(path)).filter(|_| !path.is_empty());
+            return Ok(LocalStorage::Memory(MemoryStorage::open(path)?));
+        }
+        match url {
+            #[cfg(feature = "sqlite")]
+            url if url.starts_with("sqlite:") => {
+                Ok(LocalStorage::Sqlite(SqliteStorage::open(url).await?))
+            }
+            url => Err(StorageError::Refused(format!(
+                "Unsupported database {url}, give a mysql: or memory: url, or a sqlite: url with the sqlite feature."
+            ))),
+        }
+    }
+    /// Opens `persist`, a `sqlite:` connection string or else a file for `MemoryStorage::open`.
+    pub async fn open(persist: Option<PathBuf>) -> Result<Self, StorageError> {
+        let url = persist
+            .as_ref()
+            .and_then(|path| path.to_str())
+            .filter(|path| path.starts_with("sqlite:"));
+        match url {
+            #[cfg(feature = "sqlite")]
+            Some(url) => Ok(LocalStorage::Sqlite(SqliteStorage::open(url).await?)),
+            #[cfg(not(feature = "sqlite"))]
+            Some(_) => Err(StorageError::Refused(
+                "The server was built without the sqlite feature.".into(),
+            )),
+            None => Ok(LocalStorage::Memory(MemoryStorage::open(persist)?)),
+        }
+    }
+    /// Writes a `MemoryStorage` to its file. A database is written on every change already.
+    pub fn save(&self) -> Result<(), StorageError> {
+        match self {
+            LocalStorage::Memory(storage) => storage.save(),
+            #[cfg(feature = "sqlite")]
+            LocalStorage::Sqlite(_) => Ok(()),
+        }
+    }
+}
+impl Storage for LocalStorage {
+    async fn users(&self) -> Result<Vec<User>, StorageError> {
+        match self {
+            LocalStorage::Memory(storage) => storage.users().await,
+            #[cfg(feature = "sqlite")]
+            LocalStorage::Sqlite(storage) => storage.users().await,
+        }
+    }
+    async fn register(&self, public_key: String) -> Result<i32, StorageError> {
+        match self {
+            LocalStorage::Memory(storage) => storage.register(public_key).await,
+            #[cfg(feature = "sqlite")]
+            LocalStorage::Sqlite(storage) => storage.register(public_key).await,
+        }
+    }
+    async fn games(&self) -> Result<Vec<DataBaseGame>, StorageError> {
+        match self {
+            LocalStorage::Memory(storage) => storage.games().await,
+            #[cfg(feature = "sqlite")]
+            LocalStorage::Sqlite(storage) => storage.games().await,
+        }
+    }
+    async fn game(&self, game: i32) -> Result<Option<DataBaseGame>, StorageError> {
+        match self {
+            LocalStorage::Memory(storage) => storage.game(game).await,
+            #[cfg(feature = "sqlite")]
+            LocalStorage::Sqlite(storage) => storage.game(game).await,
+        }
+    }
+    async fn create_game(&self, game: DataBaseGame) -> Result<i32, StorageError> {
+        match self {
+            LocalStorage::Memory(storage) => storage.create_game(game).await,
+            #[cfg(feature = "sqlite")]
+            LocalStorage::Sqlite(storage) => storage.create_game(game).await,
+        }
+    }
+    async fn tokens(&self, game: i32) -> Result<Vec<MoveLine>, StorageError> {
+        match self {
+            LocalStorage::Memory(storage) => storage.tokens(game).await,
+            #[cfg(feature = "sqlite")]
+            LocalStorage::Sqlite(storage) => storage.tokens(game).await,
+        }
+    }
+    async fn append(&self, game: i32, index: usize, line: MoveLine) -> Result<(), StorageError> {
+        match self {
+            LocalStorage::Memory(storage) => storage.append(game, index, line).await,
+            #[cfg(feature = "sqlite")]
+            LocalStorage::Sqlite(storage) => storage.append(game, index, line).await,
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Live game state over a websocket.
+//!
+//! A client opens `/ws/game/:game` and sends a `SyncHello` listing the formats and compressions it supports. The
+//! server answers with the ones it picked, as text, then sends the whole chain as a `SyncFrame::State` and every move
+//! appended afterwards as a `SyncFrame::Move`. CBOR frames are binary, and with `deflate` every frame is a binary raw
+//! deflate stream, which saves mobile clients most of the bandwidth of large games. The websocket library has no
+//! permessage-deflate, so compression is negotiated in the hello instead.
+//!
+//! Clients of boards too large to follow whole send a viewport in the hello, and may move it by sending another
+//! `Region`. They are sent a `SyncFrame::View` of the tanks in it and a `SyncFrame::Minimap` of the board instead of
+//! the chain, then a `SyncFrame::Update` for every move that changes a tile in view. The socket keeps its own copy of
+//! the game to tell which moves those are.
+
+use flate2::{write::DeflateEncoder, Compression};
+use futures::{SinkExt, StreamExt};
+use poem::{
+    handler,
+    web::{
+        websocket::{Message, WebSocket},
+        Data, Path,
+    },
+    IntoResponse,
+};
+use sqlx::mysql::MySqlPool;
+use std::{io::Write, sync::Arc};
+use tanktacticsgame::{
+    Game, Minimap, MoveLine, Region, Step, SyncCompression, SyncFormat, SyncFrame, SyncHello,
+};
+use tokio::sync::broadcast;
+
+use crate::{cache::GameCache, table::GameAPI};
+
+/// How many moves a slow client may fall behind before it is sent the whole chain again.
+const BACKLOG: usize = 256;
+/// The most squares on a side of the minimap sent to viewport clients.
+const MINIMAP_SIZE: u32 = 50;
+
+/// Moves appended to any game, with the index they were appended at, and the states of the games they were made in.
+pub struct GameSync {
+    moves: broadcast::Sender<(i32, usize, MoveLine)>,
+    pub cache: GameCache,
+}
+impl Default for GameSync {
+    fn default() -> Self {
+        Self {
+            moves: broadcast::channel(BACKLOG).0,
+            cache: GameCache::default(),
+        }
+    }
+}
+impl GameSync {
+    /// Sends `line`, appended to `game` at `index`, to every client following the game.
+    pub fn publish(&self, game: i32, index: usize, line: &MoveLine) {
+        let _ = self.moves.send((game, index, line.clone()));
+    }
+}
+
+/// The format and compression the server picks from a hello, the first supported one of each.
+fn negotiate(hello: &SyncHello) -> SyncHello {
+    SyncHello {
+        formats: vec![hello.formats.first().copied().unwrap_or_default()],
+        compression: vec![hello.compression.first().copied().unwrap_or_default()],
+        viewport: hello.viewport,
+    }
+}
+
+/// The frames a viewport client is sent for `line`, appended to `game` at `index`. Applies the line to `game`, and
+/// keeps `minimap` as last sent. `None` if the line doesn't apply to the copy of the game.
+fn view_frames(
+    game: &mut Game,
+    viewport: Region,
+    minimap: &mut Minimap,
+    index: usize,
+    line: MoveLine,
+) -> Option<Vec<SyncFrame>> {
+    let affected = game.affected_tiles(&line);
+    game.apply(line.clone()).ok()?;
+    // The copy is only looked at through its tanks.
+    game.drain_events();
+    let mut frames = Vec::new();
+    match affected {
+        None => frames.push(SyncFrame::View {
+            index: index + 1,
+            tanks: game.view(viewport),
+        }),
+        Some(mut tiles) => {
+            tiles.retain(|tile| viewport.contains(*tile));
+            tiles.sort_unstable();
+            tiles.dedup();
+            if !tiles.is_empty() {
+                let tanks = game
+                    .view(viewport)
+                    .into_iter()
+                    .filter(|tank| tiles.contains(&(tank.x, tank.y)))
+                    .collect();
+                let tiles = tiles.into_iter().map(|(x, y)| Step { x, y }).collect();
+                frames.push(SyncFrame::Update {
+                    index,
+                    line,
+                    tiles,
+                    tanks,
+                });
+            }
+        }
+    }
+    let map = game.minimap(MINIMAP_SIZE);
+    if map != *minimap {
+        *minimap = map.clone();
+        frames.push(SyncFrame::Minimap(map));
+    }
+    Some(frames)
+}
+
+fn encode(frame: &SyncFrame, format: SyncFormat, compression: SyncCompression) -> Option<Message> {
+    let data = match format {
+        SyncFormat::Json => serde_json::to_vec(frame).ok()?,
+        SyncFormat::Cbor => {
+            let mut data = Vec::new();
+            ciborium::into_writer(frame, &mut data).ok()?;
+            data
+        }
+    };
+    match (format, compression) {
+        (SyncFormat::Json, SyncCompression::None) => {
+            String::from_utf8(data).ok().map(Message::Text)
+        }
+        (SyncFormat::Cbor, SyncCompression::None) => Some(Message::Binary(data)),
+        (_, SyncCompression::Deflate) => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&data).ok()?;
+            encoder.finish().ok().map(Message::Binary)
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[handler]
+pub fn sync(
+    Path(game_id): Path<i32>,
+    ws: WebSocket,
+    pool: Data<&MySqlPool>,
+    sync: Data<&Arc<GameSync>>,
+) -> impl IntoResponse {
+    let pool = pool.0.clone();
+    let sync = sync.0.clone();
+    ws.on_upgrade(move |socket| async move {
+        let (mut sink, mut stream) = socket.split();
+        let hello = match stream.next().await {
+            Some(Ok(Message::Text(text))) => serde_json::from_str(&text).unwrap_or_default(),
+            Some(Ok(_)) => SyncHello::default(),
+            Some(Err(_)) | None => return,
+        };
+        let picked = negotiate(&hello);
+        let (format, compression) = (picked.formats[0], picked.compression[0]);
+        let Ok(picked) = serde_json::to_string(&picked) else {
+            return;
+        };
+        if sink.send(Message::Text(picked)).await.is_err() {
+            return;
+        }
+        let mut viewport = hello.viewport;
+        // Subscribed before the chain is loaded, so no move falls between the two.
+        let mut moves = sync.moves.subscribe();
+        let mut next = 0;
+        let mut resync = true;
+        // The copy of the game and the minimap last sent, for viewport clients.
+        let mut game = None;
+        let mut minimap = Minimap::default();
+        let mut frames = Vec::new();
+        loop {
+            if resync {
+                let Ok((state, _)) = GameAPI.load_cached::<i32>(&pool, &sync, game_id, 0).await
+                else {
+                    break;
+                };
+                next = state.lines.len();
+                if let Some(viewport) = viewport {
+                    minimap = state.minimap(MINIMAP_SIZE);
+                    frames.push(SyncFrame::View {
+                        index: next,
+                        tanks: state.view(viewport),
+                    });
+                    frames.push(SyncFrame::Minimap(minimap.clone()));
+                    game = Some(state);
+                } else {
+                    frames.push(SyncFrame::State { moves: state.lines });
+                }
+                resync = false;
+            }
+            for frame in frames.drain(..) {
+                let Some(message) = encode(&frame, format, compression) else {
+                    return;
+                };
+                if sink.send(message).await.is_err() {
+                    return;
+                }
+            }
+            tokio::select! {
+                message = stream.next() => match message {
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                    Some(Ok(Message::Text(text))) => {
+                        if let Ok(region) = serde_json::from_str::<Region>(&text) {
+                            viewport = Some(region);
+                            match &game {
+                                Some(game) => frames.push(SyncFrame::View {
+                                    index: next,
+                                    tanks: game.view(region),
+                                }),
+                                None => resync = true,
+                            }
+                        }
+                    }
+                    Some(Ok(_)) => {}
+                },
+                update = moves.recv() => match update {
+                    Ok((other, index, _)) if other != game_id || index < next => {}
+                    Ok((_, index, line)) if index == next => {
+                        next += 1;
+                        match (&mut game, viewport) {
+                            (Some(game), Some(viewport)) => {
+                                match view_frames(game, viewport, &mut minimap, index, line) {
+                                    Some(update) => frames = update,
+                                    None => resync = true,
+                                }
+                            }
+                            _ => frames.push(SyncFrame::Move { index, line }),
+                        }
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => resync = true,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+    })
+}
@@ -4,7 +4,7 @@ use k256::{
     ecdh::EphemeralSecret,
     ecdsa::{
         signature::{Signer, Verifier},
-        Signature, SigningKey, VerifyingKey,
+        Signature, SigningKey,
     },
     EncodedPoint,
 };
@@ -20,10 +20,10 @@ use poem::{
 };
 use poem_openapi::{
     error::ParseParamError,
-    param::Query,
+    param::{Header, Query},
     payload::{Form, Json, PlainText, Response},
     types::{ParseFromJSON, ParseFromParameter, ToJSON, Type},
-    ApiResponse, Enum, Object, OpenApi, OpenApiService,
+    ApiResponse, Enum, Object, OpenApi, OpenApiService, ResponseContent,
 };
 use rand_chacha::rand_core::OsRng;
 use serde::Deserialize;
@@ -37,27 +37,220 @@ use std::{
     sync::Arc,
 };
 use tanktacticsgame::{
-    get_key, DataBaseGame, Game, LevelRangeMap, MoveLine, Settings, User, BASE64,
+    clock_data, encode_tokens, get_key, DataBaseGame, Game, GameEvent, Item, LevelRangeMap,
+    LootMode, MoveLine, MoveLineType, Mutator, Player, PublicKey, RoundSummary, Settings,
+    ThresholdMode, Transfer, User, VerifiedCache, VoteTally, WinCondition, BASE64, RULES_VERSIONS,
 };
 use thiserror::Error;
 use tokio::sync::Mutex;
 
+use crate::admin::{now, read_log, LogEntry, Maintenance, Unavailable};
+use crate::email::{notify, Mailer, NotifyEvent};
+use crate::moderation::{
+    is_moderator, moderators_action, round_length, set_moderators, ModeratorAuth,
+};
+use crate::quota::{QuotaExceeded, Quotas};
+use crate::ratings;
+use crate::review;
+use crate::storage::{read_move, Storage};
+use crate::sync::GameSync;
+use crate::webhook::{deliver, Delivery};
+
 pub struct GameAPI;
 
+/// The most moves `/verify_chain` replays in one request.
+const MAX_VERIFY_MOVES: usize = 10_000;
+/// The most characters of the description of a game.
+const MAX_DESCRIPTION: usize = 2000;
+/// The shortest `vote_interval` a game can have, in seconds.
+const MIN_VOTE_INTERVAL: u64 = 60 * 60;
+/// How long a signed `view_branch` request lets a user read their branch, in seconds, so clients sign it about once a
+/// day instead of on every read.
+const VIEW_BRANCH_LIFETIME: u64 = 24 * 60 * 60;
+
+/// Whether `locale` looks like a BCP 47 tag, subtags of 1 to 8 letters and digits separated by `-`. Empty is allowed.
+fn valid_locale(locale: &str) -> bool {
+    locale.is_empty()
+        || locale.len() <= 35
+            && locale.split('-').all(|tag| {
+                (1..=8).contains(&tag.len()) && tag.bytes().all(|b| b.is_ascii_alphanumeric())
+            })
+}
+
 #[derive(Object)]
 struct SignedData {
-    /// The data. (Either a private key or random data. Both encrypted.)
+    /// The data. (A private key or random data, both encrypted, or an attestation.)
     data: String,
     /// The server signature.
     signature: String,
 }
+/// The place of a player in a finished game, see `Game::standings`.
+#[derive(Object)]
+struct Standing {
+    user: i32,
+    /// 1 for the winners, players that tie share a place.
+    place: u32,
+}
+/// The result of a finished game signed by the server, as proof of it for profiles and tournaments.
+#[derive(Object)]
+struct Certificate {
+    game: i32,
+    settings_hash: String,
+    chain_hash: String,
+    standings: Vec<Standing>,
+    /// The signed data, `Game::result_data` of the game.
+    data: String,
+    /// The server signature of `data`.
+    signature: String,
+}
+/// Reads a `Certificate` back from the result data it signs, see `Game::result_data`.
+fn certificate(game: i32, data: String, signature: String) -> CustomResponse<Certificate> {
+    let corrupted = || CustomResponse::error("Corrupted certificate.", true);
+    let mut parts = data.split('|').skip(2);
+    let (Some(settings_hash), Some(chain_hash), Some(standings)) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return corrupted();
+    };
+    let standings = standings
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let (user, place) = s.split_once(':')?;
+            Some(Standing {
+                user: user.parse().ok()?,
+                place: place.parse().ok()?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(corrupted)?;
+    CustomResponse::Ok(Json(Certificate {
+        game,
+        settings_hash: settings_hash.into(),
+        chain_hash: chain_hash.into(),
+        standings,
+        data,
+        signature,
+    }))
+}
+/// A unix time signed by the server, for moves that depend on the time. (see `MoveLine::check_clock`)
+#[derive(Object)]
+pub(crate) struct SignedTime {
+    time: u64,
+    /// The server signature of `clock_data(time)`, to put in the `clock` of the move.
+    signature: String,
+}
+#[derive(Object)]
+pub(crate) struct GameState {
+    /// The number of rounds that have been handled.
+    round: u32,
+    /// The unix time at which votes were last handled.
+    last_vote: u64,
+    /// The number of moves made.
+    moves: u32,
+    /// The number of living players.
+    alive: u32,
+}
+/// A game exported from this or another server, see `/export` and `/verify_chain`.
+#[derive(Object)]
+struct ChainBundle {
+    settings: DataBaseGame,
+    /// The keys of every user that made a move.
+    users: Vec<User>,
+    moves: Vec<MoveLine>,
+    /// The admin and moderator actions taken in the game. (see `/admin/log`)
+    #[oai(default)]
+    interventions: Vec<LogEntry>,
+}
+/// The verdict on a `ChainBundle`.
+#[derive(Object)]
+struct ChainVerdict {
+    /// Whether every move is valid.
+    valid: bool,
+    /// The number of moves that were replayed before the first invalid one.
+    moves: u32,
+    /// Why the first invalid move was refused.
+    error: Option<String>,
+    /// `Game::state_hash` of the game after the valid moves.
+    state_hash: String,
+}
+#[derive(Object)]
+pub(crate) struct PlayerState {
+    user: i32,
+    x: u32,
+    y: u32,
+    level: u32,
+    /// The range the player can reach this round.
+    range: u32,
+    points: u32,
+    health: u32,
+    alive: bool,
+    /// The moves that cost points the player may still make this round, absent if the game has no limit.
+    actions_left: Option<u32>,
+    /// The items in the inventory of the player. (see `Item`)
+    items: Vec<String>,
+    /// The items the player used this round.
+    effects: Vec<String>,
+    /// The round the tank was disabled in, until it is repaired or destroyed. (see `Settings::two_phase_kills`)
+    disabled_round: Option<u32>,
+}
+impl PlayerState {
+    fn new(game: &Game, player: &Player) -> Self {
+        PlayerState {
+            user: player.user,
+            x: player.x,
+            y: player.y,
+            level: player.level,
+            range: game.player_range(player),
+            points: player.points.get(),
+            health: player.health.get(),
+            alive: player.is_alive(true).is_ok(),
+            actions_left: game.actions_left(player.user),
+            items: player.items.iter().map(ToString::to_string).collect(),
+            effects: player.effects.iter().map(ToString::to_string).collect(),
+            disabled_round: player.disabled_round,
+        }
+    }
+}
+#[derive(Object)]
+pub(crate) struct TileState {
+    x: u32,
+    y: u32,
+    /// The player on the tile.
+    player: Option<PlayerState>,
+    /// The living enemies of the player on the tile that can shoot it, or of anyone if the tile is empty.
+    threats: Vec<i32>,
+}
+#[derive(Object)]
+pub(crate) struct Position {
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+}
+#[derive(ApiResponse)]
+pub(crate) enum JoinResponse {
+    /// The position the user will spawn at.
+    #[oai(status = 200)]
+    Ok(Json<Position>),
+    /// The user already joined the game.
+    #[oai(status = 409)]
+    AlreadyJoined,
+    /// The game has no room for more players.
+    #[oai(status = 403)]
+    GameFull,
+    /// The game does not exist.
+    #[oai(status = 400)]
+    UserError(PlainText<String>),
+    /// An error occured during the database lookup.
+    #[oai(status = 500)]
+    ServerError(PlainText<String>),
+}
 #[derive(Debug, Clone, Enum)]
 pub enum SignalType {
     SendRandom,
     SendKey,
 }
 #[derive(ApiResponse)]
-enum CustomResponse<T: Type + ToJSON> {
+pub(crate) enum CustomResponse<T: Type + ToJSON> {
     /// Request was successful.
     #[oai(status = 200)]
     Ok(Json<T>),
@@ -67,15 +260,85 @@ enum CustomResponse<T: Type + ToJSON> {
     /// An error occured during the database lookup.
     #[oai(status = 500)]
     ServerError(PlainText<String>),
+    /// The server is in maintenance mode.
+    #[oai(status = 503)]
+    Unavailable(Json<Unavailable>),
+    /// The tile a tank tried to move onto is taken by this player.
+    #[oai(status = 409)]
+    TileOccupied(Json<i32>),
+    /// The user went over one of the quotas of the server.
+    #[oai(status = 429)]
+    QuotaExceeded(Json<QuotaExceeded>),
+}
+/// Some of the moves of a game, starting at `index`.
+#[derive(Object)]
+pub(crate) struct TokensPage {
+    /// The index of the first move in `moves`.
+    index: u64,
+    moves: Vec<MoveLine>,
+}
+/// The moves of a game, either as JSON objects or in the compact text form of `encode_tokens` after a line with the
+/// index of the first move.
+#[derive(ResponseContent)]
+pub(crate) enum TokensContent {
+    Json(Json<TokensPage>),
+    Text(PlainText<String>),
+}
+#[derive(ApiResponse)]
+pub(crate) enum TokensResponse {
+    /// Request was successful.
+    #[oai(status = 200)]
+    Ok(TokensContent),
+    /// The request is malformed or not signed by the user whose branch it reads.
+    #[oai(status = 400)]
+    UserError(PlainText<String>),
+    /// A move in the database has been corrupted.
+    #[oai(status = 500)]
+    ServerError(PlainText<String>),
+}
+impl TokensResponse {
+    /// Responds with `moves`, the moves from `index` on, in the compact text form if the `accept` header asks for
+    /// `text/plain`.
+    pub(crate) fn new(index: usize, moves: Vec<MoveLine>, accept: Option<&str>) -> Self {
+        let index = index as u64;
+        if accept.is_some_and(|x| x.contains("text/plain")) {
+            let text = format!("{index}\n{}", encode_tokens(&moves));
+            TokensResponse::Ok(TokensContent::Text(PlainText(text)))
+        } else {
+            TokensResponse::Ok(TokensContent::Json(Json(TokensPage { index, moves })))
+        }
+    }
+    /// Responds with at most `limit` of `lines`, those after the index `after_index`, so clients that have the moves
+    /// up to it only download the new ones. For chains kept in memory, like those of a playtest or a mirror.
+    pub(crate) fn page(
+        mut lines: Vec<MoveLine>,
+        after_index: Option<usize>,
+        limit: Option<usize>,
+        accept: Option<&str>,
+    ) -> Self {
+        let index = after_index
+            .map_or(0, |i| i.saturating_add(1))
+            .min(lines.len());
+        lines.drain(..index);
+        lines.truncate(limit.unwrap_or(usize::MAX));
+        Self::new(index, lines, accept)
+    }
 }
 impl<T: Type + ToJSON> CustomResponse<T> {
-    fn error(text: &str, server: bool) -> CustomResponse<T> {
+    pub(crate) fn error(text: &str, server: bool) -> CustomResponse<T> {
         if server {
             CustomResponse::ServerError(PlainText(text.into()))
         } else {
             CustomResponse::UserError(PlainText(text.into()))
         }
     }
+    /// Refuses a move the engine rejected, telling the client who took the tile if that was the reason.
+    pub(crate) fn refused(e: &tanktacticsgame::Error) -> CustomResponse<T> {
+        match e {
+            tanktacticsgame::Error::TileOccupied(user) => CustomResponse::TileOccupied(Json(*user)),
+            e => CustomResponse::error(&format!("Malformed line given: {e}."), false),
+        }
+    }
 }
 impl<T: Type + ToJSON> Try for CustomResponse<T> {
     type Output = T;
@@ -101,6 +364,9 @@ impl<T: Type + ToJSON, P: Type + ToJSON> std::ops::FromResidual<CustomResponse<T
             CustomResponse::Ok(_) => panic!(),
             CustomResponse::UserError(s) => CustomResponse::UserError(s),
             CustomResponse::ServerError(s) => CustomResponse::ServerError(s),
+            CustomResponse::Unavailable(s) => CustomResponse::Unavailable(s),
+            CustomResponse::TileOccupied(s) => CustomResponse::TileOccupied(s),
+            CustomResponse::QuotaExceeded(s) => CustomResponse::QuotaExceeded(s),
         }
     }
 }
@@ -112,6 +378,513 @@ impl<T: Type + ToJSON> std::ops::FromResidual<Result<std::convert::Infallible, C
     }
 }
 
+impl GameAPI {
+    /// Reads all moves of `game` in order, or `None` if a move has been corrupted.
+    async fn read_tokens(pool: &MySqlPool, game: i32) -> Option<Vec<MoveLine>> {
+        pool.tokens(game).await.ok()
+    }
+    /// Reads the settings of `game`.
+    async fn read_game<T: Type + ToJSON>(
+        pool: &MySqlPool,
+        game: i32,
+    ) -> Result<DataBaseGame, CustomResponse<T>> {
+        pool.game(game)
+            .await
+            .map_err(|e| CustomResponse::error(&e.to_string(), true))?
+            .ok_or(CustomResponse::error("Game does not exist.", false))
+    }
+    /// The current time signed with the server key `keys`, see `/time`.
+    pub(crate) fn sign_time(keys: &(SigningKey, String)) -> SignedTime {
+        let time = now();
+        let signature: Signature = keys.0.sign(clock_data(time).as_bytes());
+        SignedTime {
+            time,
+            signature: signature.to_string(),
+        }
+    }
+    /// The round, moves and living players of `game`, see `/state`.
+    pub(crate) fn summarize(game: &Game) -> GameState {
+        GameState {
+            round: game.round,
+            last_vote: game.last_vote,
+            moves: game.lines.len().try_into().unwrap(),
+            alive: game
+                .players
+                .values()
+                .filter(|p| p.is_alive(true).is_ok())
+                .count()
+                .try_into()
+                .unwrap(),
+        }
+    }
+    /// The state of `player` in `game`, see `/player_state`.
+    pub(crate) fn player_state(game: &Game, player: i32) -> CustomResponse<PlayerState> {
+        let player = game
+            .players
+            .get(&player)
+            .ok_or(CustomResponse::error("Player not found.", false))?;
+        CustomResponse::Ok(Json(PlayerState::new(game, player)))
+    }
+    /// Who is on the tile at `x`, `y` of `game` and who can shoot it, see `/tile`.
+    pub(crate) fn tile_state(game: &Game, x: u32, y: u32) -> CustomResponse<TileState> {
+        if x >= game.settings.width || y >= game.settings.height {
+            return CustomResponse::error("Tile is outside the board.", false);
+        }
+        let player = game.players.values().find(|p| p.x == x && p.y == y);
+        let threats = game
+            .threat_map(player.map_or(0, |p| p.user))
+            .remove(&(x, y))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| t.enemy)
+            .collect();
+        CustomResponse::Ok(Json(TileState {
+            x,
+            y,
+            player: player.map(|p| PlayerState::new(game, p)),
+            threats,
+        }))
+    }
+    /// The position `user` would spawn at when joining `game` now, see `/join_position`.
+    pub(crate) fn spawn_position(game: &Game, user: i32) -> JoinResponse {
+        match game.can_join(user) {
+            Ok(()) => {
+                let (x, y) = game.get_pos();
+                JoinResponse::Ok(Json(Position { x, y }))
+            }
+            Err(tanktacticsgame::Error::Unautherized(_)) => JoinResponse::AlreadyJoined,
+            Err(_) => JoinResponse::GameFull,
+        }
+    }
+    /// The tally of the votes of `round` in a game with the settings `game` and the moves `lines`, see `/votes`.
+    pub(crate) fn tally(
+        game: DataBaseGame,
+        lines: Vec<MoveLine>,
+        round: u32,
+    ) -> CustomResponse<VoteTally> {
+        let game = game
+            .as_game(Vec::new(), &HashMap::new())
+            .map_err(|e| CustomResponse::error(&format!("Corrupted game: {e}."), true))?;
+        let tally = game
+            .tally_round(lines, round)
+            .map_err(|e| CustomResponse::refused(&e))?;
+        CustomResponse::Ok(Json(tally))
+    }
+    /// Reads at most `limit` of the moves of `game` after the index `after`, in order, or `None` if a move has been
+    /// corrupted. With a `user` these are the moves they see, see `read_branch`.
+    async fn read_page(
+        pool: &MySqlPool,
+        game: i32,
+        user: Option<i32>,
+        after: Option<usize>,
+        limit: Option<usize>,
+    ) -> Option<Vec<MoveLine>> {
+        let after = after.map_or(-1, |i| i64::try_from(i).unwrap_or(i64::MAX));
+        let limit = limit.map_or(u64::MAX, |l| l as u64);
+        // Every move from the first shadow move of `user` on is theirs. (see `read_branch`)
+        let fork = match user {
+            Some(user) => sqlx::query!(
+                "SELECT MIN(moves.index) AS fork FROM moves WHERE moves.game = ? AND moves.user = ? AND NOT moves.canonical;",
+                game,
+                user
+            )
+            .fetch_one(pool)
+            .await
+            .ok()?
+            .fork
+            .unwrap_or(i32::MAX),
+            None => i32::MAX,
+        };
+        sqlx::query!(
+            "SELECT moves.token FROM moves WHERE moves.game = ? AND moves.index > ? AND ((moves.canonical AND moves.index < ?) OR (NOT moves.canonical AND moves.user = ? AND moves.index >= ?)) ORDER BY moves.index LIMIT ?;",
+            game,
+            after,
+            fork,
+            user,
+            fork,
+            limit
+        )
+        .fetch_all(pool)
+        .await
+        .ok()?
+        .into_iter()
+        .map(|r| read_move(&r.token).ok())
+        .collect()
+    }
+    /// Reads the moves `user` sees: the canonical moves up to their first shadow move, followed by their shadow moves.
+    /// This is just the canonical chain for users that never made a move while quarantined. (see `admin::quarantine`)
+    ///
+    /// Shadow moves are signed on top of the branch, so the canonical moves made after the first of them can't be
+    /// shown on it: the quarantined user sees the others stop moving. Admins should lift or act on a quarantine before
+    /// the user takes that as a tell.
+    async fn read_branch(pool: &MySqlPool, game: i32, user: i32) -> Option<Vec<MoveLine>> {
+        Self::read_page(pool, game, Some(user), None, None).await
+    }
+    /// Rebuilds a game from the database, along with the keys of its players and of `user`.
+    pub(crate) async fn load_game<T: Type + ToJSON>(
+        &self,
+        pool: &MySqlPool,
+        game_id: i32,
+        user: i32,
+    ) -> Result<(Game, HashMap<i32, PublicKey>), CustomResponse<T>> {
+        let tokens = Self::read_tokens(pool, game_id)
+            .await
+            .ok_or(CustomResponse::error("Corrupted move.", true))?;
+        self.load_chain(pool, game_id, user, tokens, None).await
+    }
+    /// Like `load_game`, but starts from the state of the game in the cache of `sync` if the database still ends with
+    /// the same move, and only replays the game when it doesn't, checking the signatures not verified before.
+    pub(crate) async fn load_cached<T: Type + ToJSON>(
+        &self,
+        pool: &MySqlPool,
+        sync: &GameSync,
+        game_id: i32,
+        user: i32,
+    ) -> Result<(Game, HashMap<i32, PublicKey>), CustomResponse<T>> {
+        let last = sqlx::query!(
+            "SELECT moves.index, moves.token FROM moves WHERE moves.game = ? AND moves.canonical ORDER BY moves.index DESC LIMIT 1;",
+            game_id
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        let (moves, signature) = match last {
+            Some(r) => (
+                usize::try_from(r.index + 1).unwrap_or_default(),
+                read_move(&r.token)
+                    .map_err(|_| CustomResponse::error("Corrupted move.", true))?
+                    .signature,
+            ),
+            None => (0, String::new()),
+        };
+        if let Some(game) = sync.cache.get(game_id, moves, &signature) {
+            return Ok((game, self.load_keys(pool, game_id, user).await?));
+        }
+        let tokens = Self::read_tokens(pool, game_id)
+            .await
+            .ok_or(CustomResponse::error("Corrupted move.", true))?;
+        let (game, users) = self
+            .load_chain(pool, game_id, user, tokens, Some(&sync.cache.verified))
+            .await?;
+        sync.cache.insert(game_id, game.clone());
+        Ok((game, users))
+    }
+    /// Rebuilds a game from `tokens`, along with the keys of its players and of `user`. Signatures in `verified` are
+    /// not checked again.
+    async fn load_chain<T: Type + ToJSON>(
+        &self,
+        pool: &MySqlPool,
+        game_id: i32,
+        user: i32,
+        tokens: Vec<MoveLine>,
+        verified: Option<&VerifiedCache>,
+    ) -> Result<(Game, HashMap<i32, PublicKey>), CustomResponse<T>> {
+        let users = self.load_keys(pool, game_id, user).await?;
+        let settings = Self::read_game(pool, game_id).await?;
+        let game = match verified {
+            Some(verified) => settings.as_game_verified(tokens, &users, verified),
+            None => settings.as_game(tokens, &users),
+        }
+        .map_err(|e| CustomResponse::error(&format!("Corrupted game: {e}."), true))?;
+        Ok((game, users))
+    }
+    /// Reads the keys of the players of a game and of `user`.
+    async fn load_keys<T: Type + ToJSON>(
+        &self,
+        pool: &MySqlPool,
+        game_id: i32,
+        user: i32,
+    ) -> Result<HashMap<i32, PublicKey>, CustomResponse<T>> {
+        let mut users = Self::read_users(pool, game_id, Some(user))
+            .await
+            .into_iter()
+            .map(|user| get_key(user.public_key).map(|x| (user.id, x)))
+            .try_fold(HashMap::new(), |mut x, y| {
+                x.extend(y);
+                y.map(|_| x)
+            })
+            .ok_or(CustomResponse::error("Corrupted user key.", true))?;
+        if !users.contains_key(&user) {
+            let record = query!("SELECT public_key FROM users WHERE id = ?", user)
+                .fetch_one(pool)
+                .await
+                .ok()
+                .and_then(|r| get_key(r.public_key))
+                .map(|x| (user, x));
+            users.extend(record);
+        }
+        Ok(users)
+    }
+    /// Checks that the settings of `game` parse and stay within the limits of the server, before it is stored.
+    pub(crate) fn check_settings<T: Type + ToJSON>(
+        game: &DataBaseGame,
+    ) -> Result<(), CustomResponse<T>> {
+        LevelRangeMap::from_str(game.range.as_str())
+            .map_err(|_| CustomResponse::error("Malformed range map given.", false))?;
+        LootMode::from_str(game.loot_mode.as_str())
+            .map_err(|_| CustomResponse::error("Malformed loot mode given.", false))?;
+        if !RULES_VERSIONS.contains(&game.rules_version) {
+            return Err(CustomResponse::error(
+                "Unsupported rules version given.",
+                false,
+            ));
+        }
+        if !game.threshold_mode.is_empty() {
+            ThresholdMode::from_str(&game.threshold_mode)
+                .map_err(|_| CustomResponse::error("Malformed threshold mode given.", false))?;
+        }
+        if !game.win_condition.is_empty() {
+            WinCondition::from_str(&game.win_condition)
+                .map_err(|_| CustomResponse::error("Malformed win condition given.", false))?;
+        }
+        Mutator::parse_list(&game.mutators)
+            .map_err(|_| CustomResponse::error("Malformed mutators given.", false))?;
+        Item::parse_list(&game.items)
+            .map_err(|_| CustomResponse::error("Malformed items given.", false))?;
+        if i64::from(game.starting_level) > i64::from(game.max_level) {
+            return Err(CustomResponse::error(
+                "Tanks can't start above the max level.",
+                false,
+            ));
+        }
+        if game.drive_length == 0 {
+            return Err(CustomResponse::error(
+                "Drives must cover at least one tile.",
+                false,
+            ));
+        }
+        if !valid_locale(&game.locale) {
+            return Err(CustomResponse::error("Malformed locale given.", false));
+        }
+        if game.description.chars().count() > MAX_DESCRIPTION {
+            return Err(CustomResponse::error(
+                &format!("Descriptions can be at most {MAX_DESCRIPTION} characters."),
+                false,
+            ));
+        }
+        if game.vote_interval > 0 && game.vote_interval < MIN_VOTE_INTERVAL {
+            return Err(CustomResponse::error(
+                &format!(
+                    "Votes can be handled automatically at most every {MIN_VOTE_INTERVAL} seconds."
+                ),
+                false,
+            ));
+        }
+        Ok(())
+    }
+    /// Checks the settings of `game` and stores it. Returns the id of the new game.
+    pub(crate) async fn create_game<'c>(
+        &self,
+        pool: impl sqlx::Executor<'c, Database = sqlx::MySql>,
+        game: &DataBaseGame,
+    ) -> CustomResponse<i32> {
+        Self::check_settings(game)?;
+        let p = sqlx::query!("INSERT INTO games (seed, width, height, health, max_level, max_players, vote_threshold, `range`, last_vote, point_decay, jury_weight, loot_mode, drive_length, rules_version, mutators, threshold_mode, win_condition, starting_points, starting_level, actions_per_round, items, two_phase_kills, symmetric_spawns, gift_cooldown, vote_interval, locale, description) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);", game.seed, game.width, game.height, game.health, game.max_level, game.max_players, game.vote_threshold, game.range, game.last_vote, game.point_decay, game.jury_weight, game.loot_mode, game.drive_length, game.rules_version, game.mutators, game.threshold_mode, game.win_condition, game.starting_points, game.starting_level, game.actions_per_round, game.items, game.two_phase_kills, game.symmetric_spawns, game.gift_cooldown, game.vote_interval, game.locale, game.description).execute(pool)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(p.last_insert_id().try_into().unwrap()))
+    }
+    /// Stores `token` of a quarantined user on their shadow branch, where only they and admins see it.
+    /// The move is checked like any other, but nobody is notified.
+    async fn append_shadow(
+        &self,
+        pool: &MySqlPool,
+        game_id: i32,
+        token: MoveLine,
+    ) -> CustomResponse<i32> {
+        let tokens = Self::read_branch(pool, game_id, token.authorizer)
+            .await
+            .ok_or(CustomResponse::error("Corrupted move.", true))?;
+        let (mut game, users) = self
+            .load_chain(pool, game_id, token.authorizer, tokens, None)
+            .await?;
+        let len: i32 = game.lines.len().try_into().unwrap();
+        game.load(token.clone(), &users)
+            .map_err(|e| CustomResponse::refused(&e))?;
+        sqlx::query!(
+            "INSERT INTO moves (user, game, `index`, token, canonical) VALUES (?, ?, ?, ?, FALSE);",
+            token.authorizer,
+            game_id,
+            len,
+            token.to_canonical_json()
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        sqlx::query!(
+            "INSERT IGNORE INTO game_players (game, user, canonical) VALUES (?, ?, FALSE);",
+            game_id,
+            token.authorizer
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(0))
+    }
+    /// Appends `token` to the game, then notifies webhooks and mail subscribers.
+    pub(crate) async fn append(
+        &self,
+        pool: &MySqlPool,
+        mailer: Option<Arc<Mailer>>,
+        client: &reqwest::Client,
+        sync: &GameSync,
+        game_id: i32,
+        token: MoveLine,
+    ) -> CustomResponse<i32> {
+        if token.move_type == MoveLineType::HandleVotes
+            && token.time.map_or(true, |time| now().abs_diff(time) > 300)
+        {
+            return CustomResponse::error("Votes must be handled with the current time.", false);
+        }
+        if matches!(token.move_type, MoveLineType::Pause | MoveLineType::Resume) {
+            if token.time.map_or(true, |time| now().abs_diff(time) > 300) {
+                return CustomResponse::error("Games must be paused with the current time.", false);
+            }
+            if !is_moderator(pool, game_id, token.authorizer).await {
+                return CustomResponse::error("Only moderators may pause the game.", false);
+            }
+        }
+        let paused = query!(
+            "SELECT paused AS `paused: bool` FROM game_moderation WHERE game = ?",
+            game_id
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+        .is_some_and(|r| r.paused);
+        if paused {
+            return CustomResponse::error("The game is paused by a moderator.", false);
+        }
+        let quarantined = query!(
+            "SELECT quarantined AS `quarantined: bool` FROM users WHERE id = ?",
+            token.authorizer
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+        .is_some_and(|r| r.quarantined);
+        if quarantined {
+            return self.append_shadow(pool, game_id, token).await;
+        }
+        review::check_held(pool, game_id).await?;
+        if review::is_flagged(pool, token.authorizer).await? {
+            return review::hold(pool, game_id, token).await;
+        }
+        self.append_canonical(pool, mailer, client, sync, game_id, token)
+            .await
+    }
+    /// Appends `token` to the canonical chain of the game, past the checks of `GameAPI::append` on who made it.
+    pub(crate) async fn append_canonical(
+        &self,
+        pool: &MySqlPool,
+        mailer: Option<Arc<Mailer>>,
+        client: &reqwest::Client,
+        sync: &GameSync,
+        game_id: i32,
+        token: MoveLine,
+    ) -> CustomResponse<i32> {
+        let (mut game, users) = self
+            .load_cached(pool, sync, game_id, token.authorizer)
+            .await?;
+        if token.move_type == MoveLineType::HandleVotes {
+            let round_length = round_length(pool, game_id).await;
+            if token
+                .time
+                .is_some_and(|time| time < game.last_vote + round_length)
+                && !is_moderator(pool, game_id, token.authorizer).await
+            {
+                return CustomResponse::error(
+                    "Only moderators may handle the votes before the round is over.",
+                    false,
+                );
+            }
+        }
+        let len: i32 = game.lines.len().try_into().unwrap();
+        // Only the events of this move are delivered, not those of replaying the game.
+        game.drain_events();
+        game.load(token.clone(), &users)
+            .map_err(|e| CustomResponse::refused(&e))?;
+        let game_events = game.drain_events();
+        match pool
+            .append(game_id, game.lines.len() - 1, token.clone())
+            .await
+        {
+            Ok(()) => {
+                sync.publish(game_id, game.lines.len() - 1, &token);
+                sync.cache.insert(game_id, game.clone());
+                sqlx::query!(
+                    "UPDATE quick_play SET last_active = ? WHERE user = ?;",
+                    now(),
+                    token.authorizer
+                )
+                .execute(pool)
+                .await
+                .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+                if token.move_type == MoveLineType::HandleVotes {
+                    sqlx::query!(
+                        "UPDATE games SET last_vote = ? WHERE id = ?;",
+                        game.last_vote,
+                        game_id
+                    )
+                    .execute(pool)
+                    .await
+                    .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+                    if let Some(summary) = game.rounds.last() {
+                        sqlx::query!(
+                            "INSERT IGNORE INTO rounds (game, round, summary) VALUES (?, ?, ?);",
+                            game_id,
+                            summary.round,
+                            summary.to_json_string()
+                        )
+                        .execute(pool)
+                        .await
+                        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+                    }
+                }
+                if let Err(e) = ratings::rate(pool, game_id, &game).await {
+                    tracing::warn!("Could not rate game {game_id}: {e}");
+                }
+                let mut deliveries =
+                    vec![Delivery::made_move(game_id, game.lines.len() - 1, &token)];
+                deliveries.extend(game_events.iter().filter_map(|event| match *event {
+                    GameEvent::PlayerKilled { user, killer } => {
+                        Some(Delivery::kill(game_id, user, killer))
+                    }
+                    GameEvent::PlayerDisabled { user, shooter } => {
+                        Some(Delivery::disable(game_id, user, Some(shooter)))
+                    }
+                    GameEvent::RoundStarted { round } => Some(Delivery::vote_round(game_id, round)),
+                    _ => None,
+                }));
+                tokio::spawn(deliver(pool.clone(), client.clone(), game_id, deliveries));
+                if let Some(mailer) = mailer {
+                    let events = match token.move_type {
+                        MoveLineType::Shoot => token
+                            .target
+                            .map(|t| (t, NotifyEvent::Shot))
+                            .into_iter()
+                            .collect(),
+                        MoveLineType::Gift | MoveLineType::Lend => token
+                            .target
+                            .map(|t| (t, NotifyEvent::Points))
+                            .into_iter()
+                            .collect(),
+                        MoveLineType::HandleVotes => game
+                            .players
+                            .keys()
+                            .map(|p| (*p, NotifyEvent::VoteRound))
+                            .collect(),
+                        _ => Vec::new(),
+                    };
+                    tokio::spawn(notify(pool.clone(), mailer, game_id, events));
+                }
+                CustomResponse::Ok(Json(len))
+            }
+            Err(e) => CustomResponse::error(&e.to_string(), true),
+        }
+    }
+}
+
 #[OpenApi]
 impl GameAPI {
     /// Returns the last token from a game specified by the `game` query. Gives a server error if the game is corrupted.
@@ -121,144 +894,473 @@ impl GameAPI {
         pool: Data<&MySqlPool>,
         Query(game): Query<i32>,
     ) -> CustomResponse<String> {
-        let Ok(record) = sqlx::query!("SELECT token FROM moves WHERE moves.game = ? AND `index` = (SELECT MAX(`index`) FROM moves WHERE moves.game = ?);", game, game)
+        let Ok(record) = sqlx::query!("SELECT token FROM moves WHERE moves.game = ? AND moves.canonical AND `index` = (SELECT MAX(`index`) FROM moves WHERE moves.game = ? AND moves.canonical);", game, game)
             .fetch_one(pool.0)
             .await
         else { return CustomResponse::Ok(Json(String::new())); };
-        match MoveLine::parse_from_json_string(&record.token) {
+        match read_move(&record.token) {
             Ok(line) => CustomResponse::Ok(Json(line.signature)),
             Err(_) => CustomResponse::error("Corrupted game.", true),
         }
     }
+    /// Returns a statement signed by the server of the signature of the move at `index` in `game`, formatted as `game|index|signature`. Clients can use it as proof when the server serves conflicting histories.
+    #[oai(path = "/attest", method = "get")]
+    async fn attest(
+        &self,
+        pool: Data<&MySqlPool>,
+        keys: Data<&(SigningKey, String)>,
+        Query(game): Query<i32>,
+        Query(index): Query<i32>,
+    ) -> CustomResponse<SignedData> {
+        let record = sqlx::query!(
+            "SELECT token FROM moves WHERE moves.game = ? AND `index` = ? AND moves.canonical;",
+            game,
+            index
+        )
+        .fetch_one(pool.0)
+        .await
+        .map_err(|_| CustomResponse::error("Move does not exist.", false))?;
+        let line =
+            read_move(&record.token).map_err(|_| CustomResponse::error("Corrupted move.", true))?;
+        let data = format!("{game}|{index}|{}", line.signature);
+        let signature: Signature = keys.0 .0.sign(data.as_bytes());
+        let signature = BASE64.encode(signature.to_bytes());
+        CustomResponse::Ok(Json(SignedData { data, signature }))
+    }
+    /// Returns the result of a finished game signed by the server. Anyone with the server key can check it without
+    /// replaying the game, and anyone with the moves can check the hashes. The certificate covers the moves up to the
+    /// one that ended the game, so later moves like late votes don't change it, and is stored when first asked for.
+    /// Gives a user error if the game is not over.
+    #[oai(path = "/certificate", method = "get")]
+    async fn get_certificate(
+        &self,
+        pool: Data<&MySqlPool>,
+        sync: Data<&Arc<GameSync>>,
+        keys: Data<&(SigningKey, String)>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<Certificate> {
+        let sql = |e: sqlx::Error| CustomResponse::error(&format!("SQL error: {e}."), true);
+        let stored = query!(
+            "SELECT data, signature FROM certificates WHERE game = ?;",
+            game
+        )
+        .fetch_optional(pool.0)
+        .await
+        .map_err(sql)?;
+        if let Some(r) = stored {
+            return certificate(game, r.data, r.signature);
+        }
+        let (state, users) = self.load_cached(pool.0, sync.0, game, 0).await?;
+        if !state.status().is_over() {
+            return CustomResponse::error("The game is not over.", false);
+        }
+        let mut ended = Game::new(game, state.settings.clone());
+        for line in state.lines {
+            ended
+                .load(line, &users)
+                .map_err(|e| CustomResponse::error(&format!("Corrupted game: {e}."), true))?;
+            ended.drain_events();
+            if ended.status().is_over() {
+                break;
+            }
+        }
+        let data = ended.result_data(game);
+        let signature: Signature = keys.0 .0.sign(data.as_bytes());
+        let signature = BASE64.encode(signature.to_bytes());
+        // Signatures are deterministic, so a certificate stored by a concurrent request is this one.
+        query!(
+            "INSERT IGNORE INTO certificates (game, data, signature) VALUES (?, ?, ?);",
+            game,
+            data,
+            signature
+        )
+        .execute(pool.0)
+        .await
+        .map_err(sql)?;
+        certificate(game, data, signature)
+    }
+    /// Returns the current time of the server, signed with the server key. Votes can only be handled with a time from
+    /// here, so rounds don't depend on the clock of the client.
+    #[oai(path = "/time", method = "get")]
+    async fn get_time(&self, keys: Data<&(SigningKey, String)>) -> Json<SignedTime> {
+        Json(Self::sign_time(keys.0))
+    }
     /// Returns all active games and their settings.
     #[oai(path = "/games", method = "get")]
     async fn get_games(&self, pool: Data<&MySqlPool>) -> Json<Vec<DataBaseGame>> {
-        let games = sqlx::query_as!(DataBaseGame, "SELECT * FROM games")
-            .fetch_all(pool.0)
+        Json(pool.0.games().await.unwrap_or_default())
+    }
+    /// Reads the users that made a move in `game`, including `user` when they only made moves while quarantined.
+    async fn read_users(pool: &MySqlPool, game: i32, user: Option<i32>) -> Vec<User> {
+        sqlx::query_as!(User, "SELECT users.id, users.public_key FROM game_players, users WHERE game_players.game = ? AND game_players.user = users.id AND (game_players.canonical OR game_players.user = ?)", game, user)
+            .fetch_all(pool)
             .await
-            .unwrap_or_default();
-        Json(games)
+            .unwrap_or_default()
     }
-    /// Gets all signed moves for a specific game. Gives a server error if a move has been corrupted.
+    /// Checks that `user` signed `view_branch|time` in the last `VIEW_BRANCH_LIFETIME`, to read their own branch.
+    /// Reads without a `user` see the canonical chain and need no signature.
+    async fn check_viewer<T: Type + ToJSON>(
+        pool: &MySqlPool,
+        user: Option<i32>,
+        time: Option<u64>,
+        signature: Option<&str>,
+    ) -> Result<(), CustomResponse<T>> {
+        let Some(user) = user else {
+            return Ok(());
+        };
+        let (Some(time), Some(signature)) = (time, signature) else {
+            return Err(CustomResponse::error(
+                "Reading the moves of a user needs their signature of view_branch.",
+                false,
+            ));
+        };
+        ModeratorAuth::check_signed_within(
+            pool,
+            user,
+            "view_branch",
+            time,
+            signature,
+            VIEW_BRANCH_LIFETIME,
+        )
+        .await
+    }
+    /// Gets all signed moves for a specific game, in the compact text form of `encode_tokens` when requested with `Accept: text/plain`. Gives a server error if a move has been corrupted.
+    /// Clients pass their own `user` with their signature of `view_branch|time`, so they see the moves they made while
+    /// quarantined. (see `check_viewer`)
+    /// With `after_index` only the moves after that index are sent, at most `limit` of them, along with the index of
+    /// the first move sent.
     #[oai(path = "/tokens", method = "get")]
     async fn get_tokens(
         &self,
         pool: Data<&MySqlPool>,
         Query(game): Query<i32>,
-    ) -> CustomResponse<Vec<MoveLine>> {
-        let lines = sqlx::query!(
-            "SELECT moves.token FROM moves WHERE moves.game = ? ORDER BY moves.index;",
-            game
+        Query(user): Query<Option<i32>>,
+        Query(time): Query<Option<u64>>,
+        Query(signature): Query<Option<String>>,
+        Query(after_index): Query<Option<usize>>,
+        Query(limit): Query<Option<usize>>,
+        #[oai(name = "Accept")] Header(accept): Header<Option<String>>,
+    ) -> TokensResponse {
+        match Self::check_viewer::<i32>(pool.0, user, time, signature.as_deref()).await {
+            Ok(()) => {}
+            Err(CustomResponse::ServerError(text)) => return TokensResponse::ServerError(text),
+            Err(CustomResponse::UserError(text)) => return TokensResponse::UserError(text),
+            Err(_) => return TokensResponse::UserError(PlainText("Invalid signature.".into())),
+        }
+        match Self::read_page(pool.0, game, user, after_index, limit).await {
+            Some(lines) => {
+                let index = after_index.map_or(0, |i| i.saturating_add(1));
+                TokensResponse::new(index, lines, accept.as_deref())
+            }
+            None => TokensResponse::ServerError(PlainText("Corrupted move.".into())),
+        }
+    }
+    /// Gets the public key of all players in a specific game, including `user` when they only made moves while quarantined.
+    /// Like `/tokens`, a `user` needs their signature of `view_branch|time`.
+    #[oai(path = "/users", method = "get")]
+    async fn get_users(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+        Query(user): Query<Option<i32>>,
+        Query(time): Query<Option<u64>>,
+        Query(signature): Query<Option<String>>,
+    ) -> CustomResponse<Vec<User>> {
+        Self::check_viewer(pool.0, user, time, signature.as_deref()).await?;
+        CustomResponse::Ok(Json(Self::read_users(pool.0, game, user).await))
+    }
+    /// Gets the games `user` made a move in, outside of quarantine.
+    #[oai(path = "/player_games", method = "get")]
+    async fn player_games(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(user): Query<i32>,
+    ) -> Json<Vec<i32>> {
+        let games = sqlx::query!(
+            "SELECT game FROM game_players WHERE user = ? AND canonical ORDER BY game",
+            user
         )
         .fetch_all(pool.0)
         .await
-        .into_iter()
-        .flat_map(Vec::into_iter)
-        .map(|r| MoveLine::parse_from_json_string(&r.token))
-        .try_fold(Vec::new(), |mut x, y| {
-            y.as_ref().ok()?;
-            x.extend(y);
-            Some(x)
-        })
-        .ok_or(CustomResponse::error("Corrupted move.", true))?;
-        CustomResponse::Ok(Json(lines))
+        .unwrap_or_default();
+        Json(games.into_iter().map(|r| r.game).collect())
     }
-    /// Gets the public key of all players in a specific game.
-    #[oai(path = "/users", method = "get")]
-    async fn get_users(&self, pool: Data<&MySqlPool>, Query(game): Query<i32>) -> Json<Vec<User>> {
-        let users = sqlx::query_as!(User, "SELECT DISTINCT users.id, users.public_key FROM moves, users WHERE moves.game = ? AND moves.user = users.id", game)
-            .fetch_all(pool.0)
-            .await
-            .unwrap_or_default();
-        Json(users)
-    }
-    /// Make a move. Gives a server error if a move, a user key or a game has been corrupted. Gives a user error if the game does not exist
+    /// Make a move and return its index. Gives a server error if a move, a user key or a game has been corrupted. Gives a user error if the game does not exist
+    /// With `schedule` (unix seconds) the move is held and appended at that time instead, if it is still valid then. (see `schedule`)
+    /// Moves handling the votes right away need a `time` and `clock` from `/time`. Joins count against the active
+    /// games quota, see `Quotas`.
     #[oai(path = "/move", method = "post")]
+    #[allow(clippy::too_many_arguments)]
     async fn make_move(
         &self,
         pool: Data<&MySqlPool>,
+        maintenance: Data<&Maintenance>,
+        mailer: Data<&Option<Arc<Mailer>>>,
+        client: Data<&reqwest::Client>,
+        sync: Data<&Arc<GameSync>>,
+        keys: Data<&(SigningKey, String)>,
+        quotas: Data<&Quotas>,
         Query(game): Query<i32>,
+        Query(schedule): Query<Option<u64>>,
         Json(token): Json<MoveLine>,
     ) -> CustomResponse<i32> {
-        let game_id = game;
-        let mut users = self
-            .get_users(Data(pool.0), Query(game_id))
+        maintenance.check()?;
+        if matches!(
+            token.move_type,
+            MoveLineType::Join | MoveLineType::JoinRandom
+        ) {
+            quotas.check_join(pool.0, sync.0, token.authorizer).await?;
+        }
+        match schedule {
+            Some(at) => crate::schedule::hold(pool.0, game, token, at).await,
+            None => {
+                if token.move_type == MoveLineType::HandleVotes {
+                    let key = get_key(keys.1.clone())
+                        .ok_or(CustomResponse::error("Malformed server key.", true))?;
+                    token
+                        .check_clock(&key)
+                        .map_err(|e| CustomResponse::refused(&e))?;
+                }
+                self.append(pool.0, mailer.0.clone(), client.0, sync.0, game, token)
+                    .await
+            }
+        }
+    }
+    /// Returns the current round of a game, when votes were last handled and how many moves and living players it has.
+    #[oai(path = "/state", method = "get")]
+    async fn get_state(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<GameState> {
+        let (game, _) = self.load_game(pool.0, game, 0).await?;
+        CustomResponse::Ok(Json(Self::summarize(&game)))
+    }
+    /// Exports a game with everything needed to replay it elsewhere, see `/verify_chain`.
+    #[oai(path = "/export", method = "get")]
+    async fn export(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<ChainBundle> {
+        let settings = Self::read_game(pool.0, game).await?;
+        let moves = Self::read_tokens(pool.0, game)
             .await
-            .0
-            .into_iter()
-            .map(|user| get_key(user.public_key).map(|x| (user.id, x)))
-            .try_fold(HashMap::new(), |mut x, y| {
-                x.extend(y);
-                y.map(|_| x)
-            })
-            .ok_or(CustomResponse::error("Corrupted user key.", true))?;
-        if !users.contains_key(&token.authorizer) {
-            let record = query!(
-                "SELECT public_key FROM users WHERE id = ?",
-                token.authorizer
-            )
-            .fetch_one(pool.0)
+            .ok_or(CustomResponse::error("Corrupted move.", true))?;
+        let users = Self::read_users(pool.0, game, None).await;
+        let interventions = read_log(pool.0, Some(game))
             .await
-            .ok()
-            .and_then(|r| get_key(r.public_key))
-            .map(|x| (token.authorizer, x));
-            users.extend(record);
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(ChainBundle {
+            settings,
+            users,
+            moves,
+            interventions,
+        }))
+    }
+    /// Replays a game exported from this or another server, checking every move and signature like the server does.
+    /// Returns how far the game is valid and the hash of its state there, to cross-check archives. Counts as a read
+    /// for the rate limits, see `api_keys`.
+    #[oai(path = "/verify_chain", method = "post")]
+    async fn verify_chain(&self, Json(bundle): Json<ChainBundle>) -> CustomResponse<ChainVerdict> {
+        if bundle.moves.len() > MAX_VERIFY_MOVES {
+            return CustomResponse::error(
+                &format!("At most {MAX_VERIFY_MOVES} moves can be verified at once."),
+                false,
+            );
         }
-        let tokens = self.get_tokens(Data(pool.0), Query(game_id)).await?;
-        let len: i32 = tokens.len().try_into().unwrap();
-        let mut game = sqlx::query_as!(
-            DataBaseGame,
-            "SELECT * FROM games WHERE games.id = ?",
-            game_id
-        )
-        .fetch_one(pool.0)
+        let settings = bundle.settings.settings().map_err(|e| {
+            CustomResponse::error(&format!("Malformed settings given: {e}."), false)
+        })?;
+        let users = bundle
+            .users
+            .into_iter()
+            .map(|user| get_key(user.public_key).map(|key| (user.id, key)))
+            .collect::<Option<HashMap<_, _>>>()
+            .ok_or(CustomResponse::error("Malformed key given.", false))?;
+        let id = bundle.settings.id;
+        // Checking every signature takes a while, keep it off the threads that serve the other requests.
+        let verdict = tokio::task::spawn_blocking(move || {
+            let mut game = Game::new(id, settings);
+            let mut error = None;
+            for line in bundle.moves {
+                if let Err(e) = game.load(line, &users) {
+                    error = Some(e.to_string());
+                    break;
+                }
+            }
+            ChainVerdict {
+                valid: error.is_none(),
+                moves: game.lines.len().try_into().unwrap(),
+                error,
+                state_hash: game.state_hash(),
+            }
+        })
         .await
-        .map_err(|_| CustomResponse::error("Game does not exist.", false))?
-        .as_game(tokens, &users)
-        .map_err(|e| CustomResponse::error(&format!("Corrupted game: {e}."), true))?;
-        game.load(token.clone(), &users)
-            .map_err(|e| CustomResponse::error(&format!("Malformed line given: {e}."), false))?;
-        match sqlx::query!(
-            "INSERT INTO moves VALUES (?, ?, ?, ?);",
-            token.authorizer as i32,
-            game_id,
-            len,
-            token.to_json_string()
+        .map_err(|e| CustomResponse::error(&format!("Verification failed: {e}."), true))?;
+        CustomResponse::Ok(Json(verdict))
+    }
+    /// Returns a summary of every round of a game that ended, oldest first.
+    #[oai(path = "/rounds", method = "get")]
+    async fn get_rounds(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<Vec<RoundSummary>> {
+        let rows = query!(
+            "SELECT summary FROM rounds WHERE game = ? ORDER BY round",
+            game
         )
-        .execute(pool.0)
+        .fetch_all(pool.0)
         .await
-        {
-            Ok(r) => CustomResponse::Ok(Json(r.last_insert_id().try_into().unwrap())),
-            Err(e) => CustomResponse::error(&format!("SQL error: {e}."), true),
-        }
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        rows.into_iter()
+            .map(|r| RoundSummary::parse_from_json_string(&r.summary).ok())
+            .collect::<Option<_>>()
+            .map_or(CustomResponse::error("Corrupted round.", true), |rounds| {
+                CustomResponse::Ok(Json(rounds))
+            })
+    }
+    /// Returns the tally of the votes of `round` of a game, replayed from its moves: who voted for whom and who
+    /// reached the threshold. For the current round it holds the votes cast so far.
+    #[oai(path = "/votes", method = "get")]
+    async fn get_votes(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+        Query(round): Query<u32>,
+    ) -> CustomResponse<VoteTally> {
+        let tokens = Self::read_tokens(pool.0, game)
+            .await
+            .ok_or(CustomResponse::error("Corrupted move.", true))?;
+        Self::tally(Self::read_game(pool.0, game).await?, tokens, round)
+    }
+    /// Returns the players and board of a game as url safe base 64 of `Game::pack_board`, a few KB even for large boards.
+    #[oai(path = "/state/packed", method = "get")]
+    async fn get_packed_state(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<String> {
+        let (game, _) = self.load_game(pool.0, game, 0).await?;
+        CustomResponse::Ok(Json(BASE64.encode(game.pack_board())))
+    }
+    /// Returns the state of a single player in a game.
+    #[oai(path = "/player_state", method = "get")]
+    async fn get_player_state(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+        Query(player): Query<i32>,
+    ) -> CustomResponse<PlayerState> {
+        let (game, _) = self.load_game(pool.0, game, 0).await?;
+        Self::player_state(&game, player)
+    }
+    /// Returns who is on a single tile of a game and who can shoot it.
+    #[oai(path = "/tile", method = "get")]
+    async fn get_tile(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+        Query(x): Query<u32>,
+        Query(y): Query<u32>,
+    ) -> CustomResponse<TileState> {
+        let (game, _) = self.load_game(pool.0, game, 0).await?;
+        Self::tile_state(&game, x, y)
+    }
+    /// Returns every gift and loot of a game, summed up per giver, receiver and kind.
+    #[oai(path = "/flows", method = "get")]
+    async fn get_flows(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+    ) -> CustomResponse<Vec<Transfer>> {
+        let (game, _) = self.load_game(pool.0, game, 0).await?;
+        CustomResponse::Ok(Json(game.flows()))
+    }
+    /// Returns the position `user` would spawn at when joining `game` now. Gives a conflict if the user already joined and forbidden if the game is full.
+    #[oai(path = "/join_position", method = "get")]
+    async fn join_position(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(game): Query<i32>,
+        Query(user): Query<i32>,
+    ) -> JoinResponse {
+        let game = match self.load_game::<i32>(pool.0, game, user).await {
+            Ok((game, _)) => game,
+            Err(CustomResponse::ServerError(e)) => return JoinResponse::ServerError(e),
+            Err(CustomResponse::UserError(e)) => return JoinResponse::UserError(e),
+            Err(
+                CustomResponse::Ok(_)
+                | CustomResponse::Unavailable(_)
+                | CustomResponse::TileOccupied(_)
+                | CustomResponse::QuotaExceeded(_),
+            ) => unreachable!(),
+        };
+        Self::spawn_position(&game, user)
     }
     /// Regester a new user with a public key. Returns the id of the new user. Game error and not found error should never be returned. DataBaseError GameError
     #[oai(path = "/regester", method = "post")]
     async fn regester(
         &self,
         pool: Data<&MySqlPool>,
+        maintenance: Data<&Maintenance>,
         Json(public_key): Json<String>,
     ) -> CustomResponse<i32> {
-        get_key(public_key.clone()).ok_or(CustomResponse::error("Malformed key given.", false))?;
-        sqlx::query!("INSERT INTO users (public_key) VALUES (?);", public_key)
-            .execute(pool.0)
+        maintenance.check()?;
+        let Some(PublicKey::K256(_)) = get_key(public_key.clone()) else {
+            return CustomResponse::error("Malformed key given.", false);
+        };
+        pool.0
+            .register(public_key)
             .await
-            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))
-            .map(|r| CustomResponse::Ok(Json(r.last_insert_id().try_into().unwrap())))?
+            .map_err(|e| CustomResponse::error(&e.to_string(), true))
+            .map(|id| CustomResponse::Ok(Json(id)))?
     }
     /// Create a new game with settings. Returns the id of the new game. Game error and not found error should never be returned. GameError
+    /// A `creator` may appoint `moderators` by signing `make_game|user,user` with `time`, see `ModeratorAuth`.
+    /// Games need a `creator` while the games a user creates per day are limited, see `Quotas`.
     #[oai(path = "/make_game", method = "post")]
+    #[allow(clippy::too_many_arguments)]
     async fn make_game(
         &self,
         pool: Data<&MySqlPool>,
+        maintenance: Data<&Maintenance>,
+        quotas: Data<&Quotas>,
+        Query(creator): Query<Option<i32>>,
+        Query(moderators): Query<Option<Vec<i32>>>,
+        Query(time): Query<Option<u64>>,
+        Query(signature): Query<Option<String>>,
         Json(game): Json<DataBaseGame>,
     ) -> CustomResponse<i32> {
-        LevelRangeMap::from_str(game.range.as_str())
-            .map_err(|_| CustomResponse::error("Malformed range map given.", false))?;
-        let p = sqlx::query!("INSERT INTO games (seed, width, height, health, max_level, max_players, vote_threshold, `range`, last_vote) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?);", game.seed, game.width, game.height, game.health, game.max_level, game.max_players, game.vote_threshold, game.range, game.last_vote).execute(pool.0)
+        maintenance.check()?;
+        quotas.check_create(pool.0, creator).await?;
+        let moderators = moderators.unwrap_or_default();
+        let Some(creator) = creator else {
+            return self.create_game(pool.0, &game).await;
+        };
+        ModeratorAuth::check_signed(
+            pool.0,
+            creator,
+            &moderators_action("make_game", None, &moderators),
+            time.unwrap_or_default(),
+            signature.as_deref().unwrap_or_default(),
+        )
+        .await?;
+        let id = self.create_game(pool.0, &game).await?;
+        query!(
+            "INSERT INTO game_moderation (game, creator, created) VALUES (?, ?, ?);",
+            id,
+            creator,
+            now()
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        set_moderators(pool.0, id, &moderators)
             .await
             .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
-        CustomResponse::Ok(Json(p.last_insert_id().try_into().unwrap()))
+        CustomResponse::Ok(Json(id))
     }
     /// Sends Signal to the client. Either it is a request for random data or a request for the users private key. The private key will only be returned if the clients have confirmed security using a random packet.
     #[oai(path = "/sendclient", method = "post")]
@@ -266,7 +1368,7 @@ impl GameAPI {
         &self,
         connections: Data<&Arc<Mutex<HashMap<i32, WebSocketStream>>>>,
         pool: Data<&MySqlPool>,
-        keys: Data<&(SigningKey, &'static str)>,
+        keys: Data<&(SigningKey, String)>,
         Query(user): Query<i32>,
         Query(message): Query<SignalType>,
         Json(mut encryption_key): Json<String>,
@@ -275,7 +1377,10 @@ impl GameAPI {
             .fetch_one(pool.0)
             .await
             .ok()
-            .and_then(|x| get_key(x.public_key))
+            .and_then(|x| match get_key(x.public_key) {
+                Some(PublicKey::K256(key)) => Some(key),
+                _ => None,
+            })
             .ok_or(CustomResponse::error("User not availible.", false))?;
 
         let mut lock = connections.0.lock().await;
@@ -0,0 +1,211 @@
+//! Game event webhooks.
+//!
+//! Admins register urls that receive a JSON `POST` whenever something they asked for happens in a game. Every body is
+//! signed with the secret of the hook: the `X-TankTactics-Signature` header holds `sha256=` followed by the hex
+//! encoded HMAC-SHA256 of the body. Hooks are kept in the `webhooks` table (`id`, `url`, `secret`, `events`, `game`),
+//! where `events` is a comma separated list of event names and a `NULL` game means every game.
+
+use hmac::{Hmac, Mac};
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, Enum, Object, OpenApi};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::{mysql::MySqlPool, query};
+use std::time::Duration;
+
+use crate::{admin::AdminAuth, table::CustomResponse};
+
+/// How often a delivery is tried before it is dropped.
+const ATTEMPTS: u32 = 3;
+
+/// Something that happened in a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A move was made.
+    Move,
+    /// A player died.
+    Kill,
+    /// A tank was disabled, and dies unless it is repaired. (see `Settings::two_phase_kills`)
+    Disable,
+    /// The votes of a round were handled.
+    VoteRound,
+    /// The votes of a round are handled soon, some jurors haven't voted yet.
+    VoteReminder,
+}
+impl WebhookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::Move => "move",
+            WebhookEvent::Kill => "kill",
+            WebhookEvent::Disable => "disable",
+            WebhookEvent::VoteRound => "vote_round",
+            WebhookEvent::VoteReminder => "vote_reminder",
+        }
+    }
+}
+
+/// An event together with the JSON describing it.
+pub struct Delivery {
+    pub event: WebhookEvent,
+    pub body: Value,
+}
+impl Delivery {
+    /// A move was added to `game` at `index`.
+    pub fn made_move(game: i32, index: usize, line: &impl serde::Serialize) -> Self {
+        Self {
+            event: WebhookEvent::Move,
+            body: json!({ "event": "move", "game": game, "index": index, "move": line }),
+        }
+    }
+    /// `player` died in `game`, `killer` gets the credit if there is one.
+    pub fn kill(game: i32, player: i32, killer: Option<i32>) -> Self {
+        Self {
+            event: WebhookEvent::Kill,
+            body: json!({ "event": "kill", "game": game, "player": player, "killer": killer }),
+        }
+    }
+    /// `player` was disabled in `game` by `shooter`.
+    pub fn disable(game: i32, player: i32, shooter: Option<i32>) -> Self {
+        Self {
+            event: WebhookEvent::Disable,
+            body: json!({ "event": "disable", "game": game, "player": player, "shooter": shooter }),
+        }
+    }
+    /// The votes of `game` were handled and `round` started.
+    pub fn vote_round(game: i32, round: u32) -> Self {
+        Self {
+            event: WebhookEvent::VoteRound,
+            body: json!({ "event": "vote_round", "game": game, "round": round }),
+        }
+    }
+
+    /// The votes of `game` are handled at `at` and `jurors` haven't voted yet.
+    pub fn vote_reminder(game: i32, at: u64, jurors: &[i32]) -> Self {
+        Self {
+            event: WebhookEvent::VoteReminder,
+            body: json!({ "event": "vote_reminder", "game": game, "at": at, "jurors": jurors }),
+        }
+    }
+}
+
+/// Returns the `X-TankTactics-Signature` header value of `body`.
+fn signature(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length works");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Posts the deliveries of `game` to every hook that wants them.
+pub async fn deliver(
+    pool: MySqlPool,
+    client: reqwest::Client,
+    game: i32,
+    deliveries: Vec<Delivery>,
+) {
+    if deliveries.is_empty() {
+        return;
+    }
+    let Ok(hooks) = query!(
+        "SELECT id, url, secret, events FROM webhooks WHERE game IS NULL OR game = ?",
+        game
+    )
+    .fetch_all(&pool)
+    .await
+    else {
+        return;
+    };
+    for delivery in deliveries {
+        let body = delivery.body.to_string();
+        for hook in &hooks {
+            if !hook.events.split(',').any(|e| e == delivery.event.name()) {
+                continue;
+            }
+            let signature = signature(&hook.secret, &body);
+            for attempt in 1..=ATTEMPTS {
+                let result = client
+                    .post(&hook.url)
+                    .header("Content-Type", "application/json")
+                    .header("X-TankTactics-Signature", &signature)
+                    .body(body.clone())
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status);
+                match result {
+                    Ok(_) => break,
+                    Err(e) if attempt == ATTEMPTS => {
+                        tracing::warn!("Could not deliver to webhook ({}): {e}", hook.id);
+                    }
+                    Err(_) => tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Object)]
+struct NewWebhook {
+    auth: AdminAuth,
+    /// The url to post events to.
+    url: String,
+    /// The secret the bodies are signed with.
+    secret: String,
+    /// The events to post.
+    events: Vec<WebhookEvent>,
+    /// Only post events of this game, all games if empty.
+    game: Option<i32>,
+}
+
+pub struct WebhookAPI;
+
+#[OpenApi]
+impl WebhookAPI {
+    /// Registers a webhook. Returns the id of the webhook. (admin action `webhook`)
+    #[oai(path = "/admin/webhooks", method = "post")]
+    async fn add_webhook(
+        &self,
+        pool: Data<&MySqlPool>,
+        Json(hook): Json<NewWebhook>,
+    ) -> CustomResponse<u64> {
+        hook.auth.check(pool.0, "webhook").await?;
+        if !hook.url.starts_with("https://") && !hook.url.starts_with("http://") {
+            return CustomResponse::error("Webhooks need a http(s) url.", false);
+        }
+        if hook.events.is_empty() {
+            return CustomResponse::error("Webhooks need at least one event.", false);
+        }
+        let events = hook
+            .events
+            .iter()
+            .map(|e| e.name())
+            .collect::<Vec<_>>()
+            .join(",");
+        let id = query!(
+            "INSERT INTO webhooks (url, secret, events, game) VALUES (?, ?, ?, ?);",
+            hook.url,
+            hook.secret,
+            events,
+            hook.game
+        )
+        .execute(pool.0)
+        .await
+        .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?
+        .last_insert_id();
+        CustomResponse::Ok(Json(id))
+    }
+    /// Removes a webhook. (admin action `webhook|id`)
+    #[oai(path = "/admin/webhooks/remove", method = "post")]
+    async fn remove_webhook(
+        &self,
+        pool: Data<&MySqlPool>,
+        Query(id): Query<u64>,
+        Json(auth): Json<AdminAuth>,
+    ) -> CustomResponse<bool> {
+        auth.check(pool.0, &format!("webhook|{id}")).await?;
+        let result = query!("DELETE FROM webhooks WHERE id = ?;", id)
+            .execute(pool.0)
+            .await
+            .map_err(|e| CustomResponse::error(&format!("SQL error: {e}."), true))?;
+        CustomResponse::Ok(Json(result.rows_affected() > 0))
+    }
+}
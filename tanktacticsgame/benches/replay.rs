@@ -0,0 +1,126 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use tanktacticsgame::{verify_data, VerifiedCache};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+fn replay(c: &mut Criterion) {
+    let mut group = c.benchmark_group("replay");
+    group.sample_size(10);
+    for len in [1_000, 10_000, 100_000] {
+        let chain = common::chain(len + 1);
+        let (next, moves) = chain.moves.split_last().unwrap();
+        group.bench_with_input(BenchmarkId::new("cold", len), moves, |b, moves| {
+            b.iter(|| {
+                chain
+                    .game
+                    .clone()
+                    .as_game(moves.to_vec(), &chain.users)
+                    .unwrap()
+            });
+        });
+        // Replaying a chain again, like a server does, with the signatures verified by an earlier replay.
+        let verified = VerifiedCache::new(len);
+        chain
+            .game
+            .clone()
+            .as_game_verified(moves.to_vec(), &chain.users, &verified)
+            .unwrap();
+        group.bench_with_input(BenchmarkId::new("verified", len), moves, |b, moves| {
+            b.iter(|| {
+                chain
+                    .game
+                    .clone()
+                    .as_game_verified(moves.to_vec(), &chain.users, &verified)
+                    .unwrap()
+            });
+        });
+        let cached = chain
+            .game
+            .clone()
+            .as_game(moves.to_vec(), &chain.users)
+            .unwrap();
+        group.bench_with_input(BenchmarkId::new("cached", len), next, |b, next| {
+            b.iter_batched(
+                || cached.clone(),
+                |mut game| game.load(next.clone(), &chain.users).unwrap(),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+/// Checking and applying moves without their signatures, and writing the data they are signed over.
+fn hot_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hot_path");
+    group.sample_size(10);
+    for len in [1_000, 10_000] {
+        let chain = common::chain(len);
+        let empty = chain
+            .game
+            .clone()
+            .as_game(Vec::new(), &chain.users)
+            .unwrap();
+        group.bench_with_input(BenchmarkId::new("apply", len), &chain.moves, |b, moves| {
+            b.iter_batched(
+                || (empty.clone(), moves.clone()),
+                |(mut game, moves)| {
+                    for line in moves {
+                        game.apply(line).unwrap();
+                    }
+                    game
+                },
+                BatchSize::LargeInput,
+            );
+        });
+        group.bench_with_input(
+            BenchmarkId::new("signing_data", len),
+            &chain.moves,
+            |b, moves| {
+                let mut data = String::new();
+                b.iter(|| {
+                    let mut last = None;
+                    for line in moves {
+                        line.write_signing_data(last, &mut data);
+                        last = Some(line.signature.as_str());
+                    }
+                    data.len()
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Checking the signatures of moves on their own, which bounds a cold replay.
+fn signatures(c: &mut Criterion) {
+    let mut group = c.benchmark_group("signatures");
+    group.sample_size(10);
+    let chain = common::chain(1_000);
+    let mut last = None;
+    let signed = chain
+        .moves
+        .iter()
+        .map(|line| {
+            let data = line.signing_data(last);
+            last = Some(line.signature.as_str());
+            (
+                &chain.users[&line.authorizer],
+                data,
+                line.signature.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    group.bench_with_input(BenchmarkId::new("verify", 1_000), &signed, |b, signed| {
+        b.iter(|| {
+            signed
+                .iter()
+                .all(|(key, data, signature)| verify_data(key, data, signature))
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, replay, hot_path, signatures);
+criterion_main!(benches);
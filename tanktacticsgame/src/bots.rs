@@ -0,0 +1,68 @@
+//! Computer players.
+//!
+//! A `Strategy` picks the next move of a tank by looking at the game. Moves are returned unsigned and without a time:
+//! the host signs them and sets the time of a `HandleVotes`. The demo game of the server and the practice sandbox of
+//! the frontend are played by `Hunter`s.
+
+use crate::{Game, MoveLine, MoveLineType, Topology};
+
+pub trait Strategy {
+    /// The next move of `bot`.
+    fn next_move(&mut self, game: &Game, bot: i32) -> MoveLine;
+}
+
+/// Shoots the nearest enemy in range, or else drives towards it. Handles the votes when it can do neither.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hunter;
+impl Strategy for Hunter {
+    fn next_move(&mut self, game: &Game, bot: i32) -> MoveLine {
+        let line = MoveLine {
+            move_type: MoveLineType::HandleVotes,
+            x: None,
+            y: None,
+            target: None,
+            amount: None,
+            text: None,
+            time: None,
+            clock: None,
+            path: None,
+            rules_version: Some(game.settings.rules_version),
+            authorizer: bot,
+            signature: alloc::string::String::new(),
+        };
+        let board = game.settings.board();
+        let Some(player) = game.players.get(&bot) else {
+            return line;
+        };
+        let at = (player.x, player.y);
+        let Some(enemy) = game
+            .players
+            .values()
+            .filter(|p| p.user != bot && p.alive())
+            .min_by_key(|p| (board.chebyshev(at, (p.x, p.y), Topology::Flat), p.user))
+        else {
+            return line;
+        };
+        let shoot = MoveLine {
+            move_type: MoveLineType::Shoot,
+            target: Some(enemy.user),
+            ..line.clone()
+        };
+        if game.check(&shoot).is_ok() {
+            return shoot;
+        }
+        let drive = board
+            .neighbors(player.x, player.y, 1, Topology::Flat)
+            .min_by_key(|&tile| board.chebyshev(tile, (enemy.x, enemy.y), Topology::Flat))
+            .map(|(x, y)| MoveLine {
+                move_type: MoveLineType::Drive,
+                x: Some(x),
+                y: Some(y),
+                ..line.clone()
+            });
+        match drive {
+            Some(drive) if game.check(&drive).is_ok() => drive,
+            _ => line,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! The canonical JSON form of moves, the way hosts store and export them.
+//!
+//! The form follows the JSON canonicalization scheme (RFC 8785) for the values a move holds: the keys of every object
+//! sorted, no whitespace, unset fields left out and only the characters JSON requires escaped. So the same move is
+//! always written as the same bytes, which outside tools can hash or compare without parsing. It reads back with any
+//! JSON reader of `MoveLine`, like the JSON written before this form existed.
+
+use alloc::{format, string::String};
+use core::fmt::Write;
+
+use crate::MoveLine;
+
+/// Writes `text` as a JSON string, escaping only what JSON requires.
+fn write_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\u{c}' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl MoveLine {
+    /// The canonical JSON form of this move, see the `canonical` module.
+    #[must_use]
+    pub fn to_canonical_json(&self) -> String {
+        let mut out = String::from("{");
+        let key = |out: &mut String, name: &str| {
+            if out.len() > 1 {
+                out.push(',');
+            }
+            write_string(out, name);
+            out.push(':');
+        };
+        // The keys in the order RFC 8785 sorts them.
+        if let Some(amount) = self.amount {
+            key(&mut out, "amount");
+            let _ = write!(out, "{amount}");
+        }
+        key(&mut out, "authorizer");
+        let _ = write!(out, "{}", self.authorizer);
+        if let Some(clock) = &self.clock {
+            key(&mut out, "clock");
+            write_string(&mut out, clock);
+        }
+        key(&mut out, "move_type");
+        write_string(&mut out, &format!("{:?}", self.move_type));
+        if let Some(path) = &self.path {
+            key(&mut out, "path");
+            out.push('[');
+            for (i, step) in path.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "{{\"x\":{},\"y\":{}}}", step.x, step.y);
+            }
+            out.push(']');
+        }
+        if let Some(version) = self.rules_version {
+            key(&mut out, "rules_version");
+            let _ = write!(out, "{version}");
+        }
+        key(&mut out, "signature");
+        write_string(&mut out, &self.signature);
+        if let Some(target) = self.target {
+            key(&mut out, "target");
+            let _ = write!(out, "{target}");
+        }
+        if let Some(text) = &self.text {
+            key(&mut out, "text");
+            write_string(&mut out, text);
+        }
+        if let Some(time) = self.time {
+            key(&mut out, "time");
+            let _ = write!(out, "{time}");
+        }
+        if let Some(x) = self.x {
+            key(&mut out, "x");
+            let _ = write!(out, "{x}");
+        }
+        if let Some(y) = self.y {
+            key(&mut out, "y");
+            let _ = write!(out, "{y}");
+        }
+        out.push('}');
+        out
+    }
+}
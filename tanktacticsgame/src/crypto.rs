@@ -0,0 +1,447 @@
+//! Signing and checking moves, with the k256 keys of the frontend and with passkeys.
+
+use base64::{
+    alphabet::URL_SAFE,
+    engine::{general_purpose::URL_SAFE_NO_PAD, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use k256::ecdsa::{signature::Signer, signature::Verifier, Signature, SigningKey, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+use rand_chacha::rand_core::OsRng;
+use sha2::{Digest, Sha256};
+
+use crate::{write_varint, Error, Game, MoveLine};
+
+pub const BASE64: GeneralPurpose = GeneralPurpose::new(&URL_SAFE, GeneralPurposeConfig::new());
+
+pub fn get_random_keys() -> (String, String) {
+    let signing_key = SigningKey::random(&mut OsRng);
+    let verifying_key = VerifyingKey::from(&signing_key);
+    (
+        BASE64.encode(signing_key.to_bytes()),
+        BASE64.encode(verifying_key.to_encoded_point(true).as_bytes()),
+    )
+}
+/// A key users sign their moves with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicKey {
+    /// A k256 key generated by the frontend. Stored as url safe base 64 of the point.
+    K256(VerifyingKey),
+    /// A P-256 passkey, which signs through `WebAuthn` assertions for the site `rp_id`. Stored as `P256:{rp_id}:`
+    /// followed by url safe base 64 of the point. Passkeys registered before the site was kept have no `rp_id` and
+    /// are stored as `P256:` followed by the point, their assertions are checked without it so their games replay.
+    P256 {
+        key: p256::ecdsa::VerifyingKey,
+        rp_id: Option<String>,
+    },
+}
+impl PublicKey {
+    /// Gets the stored form of a passkey from its DER encoded `SubjectPublicKeyInfo`, as given by `WebAuthn`
+    /// registrations, for the site `rp_id`.
+    #[must_use]
+    pub fn passkey_from_der(der: &[u8], rp_id: &str) -> Option<String> {
+        let key = p256::ecdsa::VerifyingKey::from_public_key_der(der).ok()?;
+        Some(format!(
+            "P256:{rp_id}:{}",
+            BASE64.encode(key.to_encoded_point(true).as_bytes())
+        ))
+    }
+}
+pub fn get_key(key: String) -> Option<PublicKey> {
+    if let Some(key) = key.strip_prefix("P256:") {
+        let (rp_id, point) = match key.rsplit_once(':') {
+            Some((rp_id, point)) => (Some(rp_id.to_string()), point),
+            None => (None, key),
+        };
+        return BASE64
+            .decode(point)
+            .ok()
+            .and_then(|x| p256::ecdsa::VerifyingKey::from_sec1_bytes(&x).ok())
+            .map(|key| PublicKey::P256 { key, rp_id });
+    }
+    BASE64
+        .decode(key)
+        .ok()
+        .as_deref()
+        .map(VerifyingKey::from_sec1_bytes)
+        .and_then(Result::ok)
+        .map(PublicKey::K256)
+}
+/// The words fingerprints are made of, each word encodes 6 bits of the key hash.
+const FINGERPRINT_WORDS: [&str; 64] = [
+    "acorn", "anchor", "apple", "arrow", "badge", "banjo", "beacon", "birch", "bison", "bolt",
+    "cactus", "camel", "candle", "cedar", "cobra", "comet", "coral", "crane", "delta", "dingo",
+    "eagle", "ember", "falcon", "fern", "fjord", "flint", "gecko", "glacier", "goose", "granite",
+    "harbor", "hazel", "heron", "igloo", "iris", "jade", "jaguar", "kayak", "koala", "lantern",
+    "lemon", "lotus", "lynx", "maple", "marble", "meadow", "nectar", "nutmeg", "oasis", "olive",
+    "otter", "panda", "pepper", "quartz", "raven", "river", "saffron", "salmon", "tiger", "tulip",
+    "violet", "walnut", "yak", "zebra",
+];
+/// A short, human readable fingerprint of `key`, for players to compare outside of the game.
+#[must_use]
+pub fn key_fingerprint(key: &PublicKey) -> String {
+    let hash = match key {
+        PublicKey::K256(key) => Sha256::digest(key.to_encoded_point(true).as_bytes()),
+        PublicKey::P256 { key, .. } => Sha256::digest(key.to_encoded_point(true).as_bytes()),
+    };
+    let bits = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]);
+    (0..5)
+        .map(|i| FINGERPRINT_WORDS[(bits >> (26 - 6 * i)) as usize & 63])
+        .collect::<Vec<_>>()
+        .join("-")
+}
+/// The challenge a passkey has to sign to authorize `data`.
+#[must_use]
+pub fn passkey_challenge(data: &str) -> Vec<u8> {
+    Sha256::digest(data.as_bytes()).to_vec()
+}
+/// Combines the parts of a `WebAuthn` assertion into a signature, each part being url safe base 64.
+#[must_use]
+pub fn passkey_signature(
+    authenticator_data: &str,
+    client_data_json: &str,
+    signature: &str,
+) -> String {
+    format!("{authenticator_data}.{client_data_json}.{signature}")
+}
+/// The site a passkey belongs to, from the `origin` of its client data: the host, which browsers use as the relying
+/// party id when none is given. Only `https` origins count, and `http` ones on `localhost` for development.
+#[must_use]
+pub fn passkey_rp_id(origin: &str) -> Option<&str> {
+    let host = match origin.strip_prefix("https://") {
+        Some(host) => host,
+        None => origin
+            .strip_prefix("http://")
+            .filter(|host| host.split(':').next() == Some("localhost"))?,
+    };
+    let host = host.split(':').next()?;
+    (!host.is_empty() && !host.contains('/')).then_some(host)
+}
+/// Checks the authenticator data of a `WebAuthn` registration: that it was made for the site `rp_id` with the user
+/// present, and that it attests the credential `credential_id` with the DER encoded key `der`.
+/// The key is found in the COSE encoding of the authenticator data by its coordinates, so no CBOR parser is needed.
+#[must_use]
+pub fn verify_passkey_registration(
+    authenticator_data: &[u8],
+    rp_id: &str,
+    credential_id: &[u8],
+    der: &[u8],
+) -> bool {
+    let Ok(key) = p256::ecdsa::VerifyingKey::from_public_key_der(der) else {
+        return false;
+    };
+    // The rpIdHash, the flags, with the user present and the credential attested, and the signature counter.
+    let Some((header, rest)) = authenticator_data.split_at_checked(37) else {
+        return false;
+    };
+    // Then the aaguid, the length of the credential id, the credential id and the COSE key.
+    let Some(([.., high, low], rest)) = rest.split_at_checked(18) else {
+        return false;
+    };
+    let Some((id, cose)) = rest.split_at_checked(usize::from(u16::from_be_bytes([*high, *low])))
+    else {
+        return false;
+    };
+    let point = key.to_encoded_point(false);
+    let coordinate = |label: u8, value: Option<&[u8]>| {
+        let mut entry = vec![label, 0x58, 0x20];
+        entry.extend(value.unwrap_or_default());
+        cose.windows(entry.len()).any(|w| w == entry)
+    };
+    header[..32] == *Sha256::digest(rp_id.as_bytes())
+        && header[32] & 0x41 == 0x41
+        && id == credential_id
+        && coordinate(0x21, point.x().map(|x| &x[..]))
+        && coordinate(0x22, point.y().map(|y| &y[..]))
+}
+/// Checks a `WebAuthn` assertion made by `key` over the challenge of `data`, for the site `rp_id` if it is known.
+/// The client data is checked against the serialization `WebAuthn` prescribes, so no JSON parser is needed.
+fn verify_passkey(
+    key: &p256::ecdsa::VerifyingKey,
+    rp_id: Option<&str>,
+    data: &str,
+    signature: &str,
+) -> bool {
+    let parts = signature
+        .split('.')
+        .map(|x| BASE64.decode(x).ok())
+        .collect::<Option<Vec<_>>>();
+    let Some([authenticator_data, client_data, signature]) = parts.as_deref() else {
+        return false;
+    };
+    let prefix = format!(
+        "{{\"type\":\"webauthn.get\",\"challenge\":\"{}\"",
+        URL_SAFE_NO_PAD.encode(passkey_challenge(data))
+    );
+    let Some(rest) = client_data.strip_prefix(prefix.as_bytes()) else {
+        return false;
+    };
+    // The first flag of the authenticator data says the user was present.
+    if authenticator_data
+        .get(32)
+        .is_none_or(|flags| flags & 1 == 0)
+    {
+        return false;
+    }
+    if let Some(rp_id) = rp_id {
+        // The origin follows the challenge, and an assertion made in a frame of another site is refused.
+        let origin = rest
+            .strip_prefix(b",\"origin\":\"")
+            .and_then(|rest| rest.split(|&c| c == b'"').next())
+            .and_then(|origin| core::str::from_utf8(origin).ok());
+        if authenticator_data[..32] != *Sha256::digest(rp_id.as_bytes())
+            || origin.and_then(passkey_rp_id) != Some(rp_id)
+            || rest.windows(18).any(|w| w == b"\"crossOrigin\":true")
+        {
+            return false;
+        }
+    }
+    let mut message = authenticator_data.clone();
+    message.extend(Sha256::digest(client_data));
+    p256::ecdsa::Signature::from_der(signature).is_ok_and(|s| key.verify(&message, &s).is_ok())
+}
+/// Signs `data` with `private_key`, for requests that are authorized outside of a move.
+/// # Errors
+/// If the `private_key` is not correctly formated (url safe base 64 string of a point on the k256 curve).
+pub fn sign_data(data: &str, private_key: String) -> Result<String, Error> {
+    let key = BASE64
+        .decode(private_key)
+        .ok()
+        .and_then(|x| SigningKey::from_slice(x.as_slice()).ok())
+        .ok_or_else(|| Error::Other("Malformed private key.".into()))?;
+    let signature: Signature = key.sign(data.as_bytes());
+    Ok(signature.to_string())
+}
+/// Checks that `signature` is a signature of `data` made by the owner of `key`.
+#[must_use]
+pub fn verify_data(key: &PublicKey, data: &str, signature: &str) -> bool {
+    match key {
+        PublicKey::K256(key) => signature
+            .parse::<Signature>()
+            .is_ok_and(|s| key.verify(data.as_bytes(), &s).is_ok()),
+        PublicKey::P256 { key, rp_id } => verify_passkey(key, rp_id.as_deref(), data, signature),
+    }
+}
+/// Signatures that verified before, so replaying a chain again only hashes its moves instead of checking every
+/// signature. Holds at most `capacity` of them, and starts over when full. (see `DataBaseGame::as_game_verified`)
+#[derive(Debug)]
+pub struct VerifiedCache {
+    capacity: usize,
+    digests: std::sync::Mutex<hashbrown::HashSet<[u8; 32]>>,
+}
+impl VerifiedCache {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        VerifiedCache {
+            capacity,
+            digests: std::sync::Mutex::default(),
+        }
+    }
+    /// A hash of `signature` of `data` by `key`, each part prefixed by its length.
+    fn digest(key: &PublicKey, data: &str, signature: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        let mut part = |bytes: &[u8]| {
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        };
+        match key {
+            PublicKey::K256(key) => part(key.to_encoded_point(true).as_bytes()),
+            PublicKey::P256 { key, rp_id } => {
+                part(key.to_encoded_point(true).as_bytes());
+                part(rp_id.as_deref().unwrap_or_default().as_bytes());
+            }
+        }
+        part(data.as_bytes());
+        part(signature.as_bytes());
+        hasher.finalize().into()
+    }
+    fn lock(&self) -> std::sync::MutexGuard<'_, hashbrown::HashSet<[u8; 32]>> {
+        self.digests
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+    /// `verify_data`, without checking signatures that verified before again.
+    #[must_use]
+    pub fn verify(&self, key: &PublicKey, data: &str, signature: &str) -> bool {
+        let digest = Self::digest(key, data, signature);
+        if self.lock().contains(&digest) {
+            return true;
+        }
+        if !verify_data(key, data, signature) {
+            return false;
+        }
+        let mut digests = self.lock();
+        if digests.len() >= self.capacity {
+            digests.clear();
+        }
+        digests.insert(digest);
+        true
+    }
+}
+
+/// A private key encrypted with a passphrase, as the frontend keeps it at rest.
+/// The passphrase is stretched with `kdf` over `salt` into an AES-GCM key, which encrypts the key with `nonce`. With
+/// argon2id, `iterations` is the number of passes over `SealedKey::MEMORY`.
+/// Stored as `sealed:{kdf}:{iterations}:{salt}:{nonce}:{ciphertext}`, the last three url safe base 64.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedKey {
+    pub kdf: String,
+    pub iterations: u32,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+impl SealedKey {
+    pub const PREFIX: &'static str = "sealed:";
+    /// The key derivation new keys are sealed with.
+    pub const KDF: &'static str = "argon2id";
+    /// The iterations of `SealedKey::KDF` new keys are sealed with.
+    pub const ITERATIONS: u32 = 2;
+    /// The memory argon2id uses, in KiB, with a single lane.
+    pub const MEMORY: u32 = 19_456;
+    /// The key derivation keys were sealed with before argon2id, which they can still be opened with.
+    pub const PBKDF2: &'static str = "pbkdf2-sha256";
+    /// Whether the stored private key `stored` is sealed, rather than a plain key or a passkey.
+    #[must_use]
+    pub fn is_sealed(stored: &str) -> bool {
+        stored.starts_with(Self::PREFIX)
+    }
+    /// Reads a stored sealed key, `None` if it is malformed or uses a key derivation this version doesn't know.
+    #[must_use]
+    pub fn parse(stored: &str) -> Option<SealedKey> {
+        let mut parts = stored.strip_prefix(Self::PREFIX)?.split(':');
+        let sealed = SealedKey {
+            kdf: parts.next()?.into(),
+            iterations: parts.next()?.parse().ok()?,
+            salt: parts.next()?.into(),
+            nonce: parts.next()?.into(),
+            ciphertext: parts.next()?.into(),
+        };
+        let decoded = |part: &str, len: Option<usize>| {
+            BASE64
+                .decode(part)
+                .is_ok_and(|x| len.map_or(!x.is_empty(), |len| x.len() == len))
+        };
+        (parts.next().is_none()
+            && [Self::KDF, Self::PBKDF2].contains(&sealed.kdf.as_str())
+            && sealed.iterations > 0
+            && decoded(&sealed.salt, Some(16))
+            && decoded(&sealed.nonce, Some(12))
+            && decoded(&sealed.ciphertext, None))
+        .then_some(sealed)
+    }
+}
+impl core::fmt::Display for SealedKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}{}:{}:{}:{}:{}",
+            Self::PREFIX,
+            self.kdf,
+            self.iterations,
+            self.salt,
+            self.nonce,
+            self.ciphertext
+        )
+    }
+}
+
+/// The data the server clock signs for the unix time `time`. (see `MoveLine::check_clock`)
+#[must_use]
+pub fn clock_data(time: u64) -> String {
+    format!("time|{time}")
+}
+impl MoveLine {
+    /// Checks that the `time` of this move was signed by the server clock with `key`, so hosts don't have to trust
+    /// the clock of the authorizer.
+    /// # Errors
+    /// If the move has no time or no clock, or the clock was not signed with `key` over the time.
+    pub fn check_clock(&self, key: &PublicKey) -> Result<(), Error> {
+        let time = self.time.ok_or(Error::MalformedMove)?;
+        let clock = self
+            .clock
+            .as_deref()
+            .ok_or_else(|| Error::Other("The time is not signed by the server.".into()))?;
+        if verify_data(key, &clock_data(time), clock) {
+            Ok(())
+        } else {
+            Err(Error::Other("Invalid server time signature.".into()))
+        }
+    }
+}
+
+impl Game {
+    /// A hash of the state of the game, to compare implementations replaying the same moves.
+    /// Covers the board and players (see `Game::pack_board`), the round and the time of the last vote.
+    #[must_use]
+    pub fn state_hash(&self) -> String {
+        let mut data = self.pack_board();
+        write_varint(&mut data, u64::from(self.round));
+        write_varint(&mut data, self.last_vote);
+        BASE64.encode(Sha256::digest(&data))
+    }
+    /// A hash of the settings of the game, over every setting in the order `Settings` declares them.
+    #[must_use]
+    pub fn settings_hash(&self) -> String {
+        let s = &self.settings;
+        let list = |items: Vec<String>| items.join(",");
+        let data = format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{},{},{},{},{}|{}|{}|{}|{}|{}|{}|{}|{}",
+            s.seed,
+            s.width,
+            s.height,
+            s.health,
+            s.max_level,
+            s.max_players,
+            s.threshold_mode,
+            s.range,
+            s.point_decay,
+            s.jury_weight,
+            s.loot_mode,
+            s.drive_length,
+            s.rules_version,
+            list(s.mutators.iter().map(ToString::to_string).collect()),
+            s.costs.drive,
+            s.costs.shoot,
+            s.costs.gift,
+            s.costs.item,
+            s.costs.repair,
+            s.win_condition,
+            s.starting_points,
+            s.starting_level,
+            s.actions_per_round.unwrap_or_default(),
+            list(s.items.iter().map(ToString::to_string).collect()),
+            s.two_phase_kills,
+            s.symmetric_spawns,
+            s.gift_cooldown,
+        );
+        BASE64.encode(Sha256::digest(data.as_bytes()))
+    }
+    /// A hash of the moves of the game, over their canonical JSON (see `MoveLine::to_canonical_json`) one per line.
+    #[must_use]
+    pub fn chain_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        for line in &self.lines {
+            hasher.update(line.to_canonical_json().as_bytes());
+            hasher.update(b"\n");
+        }
+        BASE64.encode(hasher.finalize())
+    }
+    /// The data the server signs to certify the result of this game, stored as game `id`: the hashes of its settings
+    /// and moves and the place of every player, as `result|id|settings|chain|user:place,user:place`. (see
+    /// `Game::standings`)
+    #[must_use]
+    pub fn result_data(&self, id: i32) -> String {
+        let standings = self
+            .standings()
+            .iter()
+            .map(|(user, place)| format!("{user}:{place}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "result|{id}|{}|{}|{standings}",
+            self.settings_hash(),
+            self.chain_hash()
+        )
+    }
+}
@@ -0,0 +1,68 @@
+//! What happened in a game, as moves are applied.
+//!
+//! Applying a move queues a `GameEvent` for everything it changed on the board: tanks joining, driving, being shot,
+//! disabled, repaired or killed, points handed out and rounds starting. Hosts and clients drain the queue after
+//! applying a move, instead of comparing the players and board before and after it. Points moving between tanks are
+//! kept in `Game::transfers` as before.
+
+use alloc::vec::Vec;
+
+use crate::{Game, Step};
+
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    PlayerJoined {
+        user: i32,
+        at: Step,
+    },
+    PlayerMoved {
+        user: i32,
+        from: Step,
+        to: Step,
+    },
+    /// `user` lost a health to a shot of `shooter`.
+    PlayerShot {
+        user: i32,
+        shooter: i32,
+    },
+    /// `user` was shot to 0 health and disabled, see `Settings::two_phase_kills`.
+    PlayerDisabled {
+        user: i32,
+        shooter: i32,
+    },
+    PlayerRepaired {
+        user: i32,
+        by: i32,
+    },
+    /// `user` died, `killer` gets the credit if there is one.
+    PlayerKilled {
+        user: i32,
+        killer: Option<i32>,
+    },
+    PlayerUpgraded {
+        user: i32,
+        level: u32,
+    },
+    /// `user` got `amount` points for the votes of the jury or by collecting a bounty.
+    PointsAwarded {
+        user: i32,
+        amount: u32,
+    },
+    /// The votes were handled and `round` started.
+    RoundStarted {
+        round: u32,
+    },
+}
+
+impl Game {
+    /// Queues `event`, see the `events` module.
+    pub(crate) fn emit(&mut self, event: GameEvent) {
+        self.events.push(event);
+    }
+    /// Takes the events queued since the last call, in the order they happened.
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        core::mem::take(&mut self.events)
+    }
+}
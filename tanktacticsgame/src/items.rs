@@ -0,0 +1,222 @@
+//! Consumable items that tanks keep in their inventory, see `Settings::items`.
+//!
+//! Items are bought with `MoveLineType::BuyItem` and used with `MoveLineType::UseItem`, both naming the item in
+//! `text`. A used item stays active until the votes are handled. Every time the votes are handled one of the items of
+//! the game is dropped on a random tile, for the first tank to drive over it. The inventory of a tank is lost when it
+//! dies.
+
+use alloc::{format, vec::Vec};
+use core::fmt::Display;
+use rand_chacha::rand_core::RngCore;
+
+use crate::{read_varint, write_varint, Error, Game, MoveLine, MoveLineType, Player};
+
+/// A consumable a tank can keep in its inventory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Item {
+    /// The tank can't be shot until the round ends.
+    Smoke,
+    /// The next tile the tank drives this round is free.
+    ExtraMove,
+    /// The range of the tank is one longer until the round ends.
+    Radar,
+}
+impl Item {
+    pub const ALL: [Item; 3] = [Item::Smoke, Item::ExtraMove, Item::Radar];
+    /// Reads a comma separated list, an empty string is an empty list.
+    /// # Errors
+    /// If an item is unknown.
+    pub fn parse_list(text: &str) -> Result<Vec<Item>, Error> {
+        text.split(',')
+            .filter(|x| !x.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+    /// What the item does.
+    #[must_use]
+    pub fn describe(self) -> &'static str {
+        match self {
+            Item::Smoke => "Smoke screen: the tank can't be shot until the round ends.",
+            Item::ExtraMove => "Extra move: the next tile driven this round is free.",
+            Item::Radar => "Radar ping: the range of the tank is one longer until the round ends.",
+        }
+    }
+    /// The position of the item in `Item::ALL`, used by `Game::pack_board`.
+    fn index(self) -> u64 {
+        match self {
+            Item::Smoke => 0,
+            Item::ExtraMove => 1,
+            Item::Radar => 2,
+        }
+    }
+    /// The item named in the `text` of `line`.
+    fn read(line: &MoveLine) -> Result<Item, Error> {
+        line.text.as_deref().ok_or(Error::MalformedMove)?.parse()
+    }
+}
+impl Display for Item {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Item::Smoke => write!(f, "smoke"),
+            Item::ExtraMove => write!(f, "extra_move"),
+            Item::Radar => write!(f, "radar"),
+        }
+    }
+}
+impl core::str::FromStr for Item {
+    type Err = Error;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "smoke" => Ok(Item::Smoke),
+            "extra_move" => Ok(Item::ExtraMove),
+            "radar" => Ok(Item::Radar),
+            _ => Err(Error::Other("Malformed Item.".into())),
+        }
+    }
+}
+
+impl Game {
+    /// The range of the tank of `player` this round, one longer with an active `Item::Radar`.
+    #[must_use]
+    pub fn player_range(&self, player: &Player) -> u32 {
+        let range = self.range(player.level);
+        if player.effects.contains(&Item::Radar) {
+            range.saturating_add(1)
+        } else {
+            range
+        }
+    }
+    /// What driving `length` tiles costs the tank of `player`, the first tile is free with an active
+    /// `Item::ExtraMove`.
+    #[must_use]
+    pub fn drive_cost(&self, player: &Player, length: u32) -> u32 {
+        let paid = if player.effects.contains(&Item::ExtraMove) {
+            length.saturating_sub(1)
+        } else {
+            length
+        };
+        paid.saturating_mul(self.settings.costs.drive)
+    }
+    /// Check if buying or using an item is valid.
+    /// # Errors
+    /// If the `line` is not valid.
+    pub(crate) fn check_item(&self, line: &MoveLine) -> Result<(), Error> {
+        let item = Item::read(line)?;
+        let p = self.get_player(line.authorizer)?;
+        p.is_alive(true)?;
+        match line.move_type {
+            MoveLineType::BuyItem => {
+                if !self.settings.items.contains(&item) {
+                    return Err(Error::NotFound(format!("item ({item}) in this game")));
+                }
+                p.can_afford(self.settings.costs.item)
+            }
+            MoveLineType::UseItem => {
+                if !p.items.contains(&item) {
+                    return Err(Error::NotFound(format!("item ({item}) in the inventory")));
+                }
+                if p.effects.contains(&item) {
+                    return Err(Error::Other(format!("The {item} is already active.")));
+                }
+                Ok(())
+            }
+            _ => Err(Error::MalformedMove),
+        }
+    }
+    /// Applies an item `line` that passed `Game::check`.
+    /// # Errors
+    /// If the `line` is not an item move.
+    pub(crate) fn handle_item(&mut self, line: &MoveLine) -> Result<(), Error> {
+        let item = Item::read(line)?;
+        let cost = self.settings.costs.item;
+        let player = self.get_player_mut(line.authorizer)?;
+        match line.move_type {
+            MoveLineType::BuyItem => {
+                player.points.take(cost, "Points")?;
+                player.items.push(item);
+            }
+            MoveLineType::UseItem => {
+                let index = player
+                    .items
+                    .iter()
+                    .position(|x| *x == item)
+                    .ok_or_else(|| Error::NotFound(format!("item ({item}) in the inventory")))?;
+                player.items.remove(index);
+                player.effects.push(item);
+            }
+            _ => return Err(Error::MalformedMove),
+        }
+        Ok(())
+    }
+    /// Gives `user` the items lying on `tiles`.
+    pub(crate) fn pick_up(&mut self, user: i32, tiles: &[(u32, u32)]) -> Result<(), Error> {
+        let items = tiles
+            .iter()
+            .filter_map(|tile| self.pickups.remove(tile))
+            .flatten()
+            .collect::<Vec<_>>();
+        if !items.is_empty() {
+            self.get_player_mut(user)?.items.extend(items);
+        }
+        Ok(())
+    }
+    /// Ends the items used this round and drops one of the items of the game on a random tile, using the game RNG.
+    /// Nothing is dropped when the tile holds a tank.
+    pub(crate) fn drop_supplies(&mut self) {
+        for player in self.players.values_mut() {
+            player.effects.clear();
+        }
+        if self.settings.items.is_empty() {
+            return;
+        }
+        let items = self.settings.items.len() as u64;
+        #[allow(clippy::cast_possible_truncation)]
+        let item = self.settings.items[(self.rand.next_u64() % items) as usize];
+        let x = self.rand.next_u32() % self.settings.width.max(1);
+        let y = self.rand.next_u32() % self.settings.height.max(1);
+        if !self.board.contains_key(&(x, y)) {
+            self.pickups.entry((x, y)).or_default().push(item);
+        }
+    }
+}
+
+/// Writes the items and effects of the `players` that have any, by their position in `players`.
+pub(crate) fn write_inventories(out: &mut Vec<u8>, players: &[&Player]) {
+    let holders = players
+        .iter()
+        .zip(0u64..)
+        .filter(|(p, _)| !p.items.is_empty() || !p.effects.is_empty())
+        .collect::<Vec<_>>();
+    write_varint(out, holders.len() as u64);
+    for (p, index) in holders {
+        write_varint(out, index);
+        for items in [&p.items, &p.effects] {
+            write_varint(out, items.len() as u64);
+            for item in items {
+                write_varint(out, item.index());
+            }
+        }
+    }
+}
+/// Reads the items and effects written by `write_inventories` into `players`.
+pub(crate) fn read_inventories(data: &mut &[u8], players: &mut [Player]) -> Result<(), Error> {
+    let read_items = |data: &mut &[u8]| -> Result<Vec<Item>, Error> {
+        (0..read_varint(data)?)
+            .map(|_| {
+                usize::try_from(read_varint(data)?)
+                    .ok()
+                    .and_then(|i| Item::ALL.get(i).copied())
+                    .ok_or(Error::MalformedMove)
+            })
+            .collect()
+    };
+    for _ in 0..read_varint(data)? {
+        let player = usize::try_from(read_varint(data)?)
+            .ok()
+            .and_then(|i| players.get_mut(i))
+            .ok_or(Error::MalformedMove)?;
+        player.items = read_items(data)?;
+        player.effects = read_items(data)?;
+    }
+    Ok(())
+}
@@ -1,65 +1,300 @@
+//! The rules of tank tactics.
+//!
+//! The rules only need `alloc`, so the engine also runs on hosts without `std`. Signing and checking moves lives in
+//! the `crypto` feature, which needs `std`. Without it moves are loaded with `Game::apply`, leaving their signatures
+//! to the host.
+#![cfg_attr(not(feature = "std"), no_std)]
 #![warn(clippy::all, clippy::pedantic)]
 
-use base64::{
-    alphabet::URL_SAFE,
-    engine::{GeneralPurpose, GeneralPurposeConfig},
-    Engine,
+extern crate alloc;
+
+mod bots;
+mod canonical;
+#[cfg(feature = "crypto")]
+mod crypto;
+mod events;
+mod items;
+mod loans;
+mod pause;
+mod random;
+mod region;
+mod repair;
+mod spawn;
+mod votes;
+
+use alloc::{
+    boxed::Box,
+    collections::BinaryHeap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
 };
-use k256::ecdsa::{signature::Signer, signature::Verifier, Signature, SigningKey, VerifyingKey};
+use core::{cmp::Reverse, fmt::Display};
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
 #[cfg(feature = "openapi")]
 use poem_openapi::{self, Enum, Object};
-use rand_chacha::rand_core::{OsRng, RngCore, SeedableRng};
-use std::{collections::HashMap, fmt::Display /* time::SystemTime, */, usize};
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 
-pub const BASE64: GeneralPurpose = GeneralPurpose::new(&URL_SAFE, GeneralPurposeConfig::new());
+pub use bots::{Hunter, Strategy};
+#[cfg(feature = "crypto")]
+pub use crypto::*;
+pub use events::GameEvent;
+pub use items::Item;
+pub use loans::Loan;
+pub use region::{Minimap, Region, ViewTank};
+pub use votes::{Candidate, VoteTally};
 
-pub fn get_random_keys() -> (String, String) {
-    let signing_key = SigningKey::random(&mut OsRng);
-    let verifying_key = VerifyingKey::from(&signing_key);
-    (
-        BASE64.encode(signing_key.to_bytes().as_slice()),
-        BASE64.encode(verifying_key.to_encoded_point(true).as_bytes()),
-    )
-}
-pub fn get_key(key: String) -> Option<VerifyingKey> {
-    BASE64
-        .decode(key)
-        .ok()
-        .as_deref()
-        .map(VerifyingKey::from_sec1_bytes)
-        .and_then(Result::ok)
-}
+/// The rules version new games and moves are made with.
+pub const RULES_VERSION: u32 = 4;
+/// The rules versions this engine can play, games keep the version they were created with.
+pub const RULES_VERSIONS: [u32; 4] = [1, 2, 3, 4];
 
 #[cfg_attr(feature = "openapi", derive(Object))]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
-#[derive(Hash, PartialEq, Eq)]
+#[derive(Clone, Hash, PartialEq, Eq)]
 pub struct User {
     /// The user id.
     pub id: i32,
     /// The user public key.
     pub public_key: String,
 }
+/// An amount of health or points that fails instead of wrapping around.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Resource(u32);
+impl Resource {
+    #[must_use]
+    pub fn get(self) -> u32 {
+        self.0
+    }
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    /// Removes `amount` from the resource.
+    /// # Errors
+    /// If there is less than `amount` left. The resource is left unchanged.
+    pub fn take(&mut self, amount: u32, what: &str) -> Result<(), Error> {
+        self.0 = self
+            .0
+            .checked_sub(amount)
+            .ok_or_else(|| Error::OutOfRange(what.into(), format!(">= {amount}")))?;
+        Ok(())
+    }
+    /// Adds `amount` to the resource.
+    /// # Errors
+    /// If the resource would overflow. The resource is left unchanged.
+    pub fn give(&mut self, amount: u32, what: &str) -> Result<(), Error> {
+        self.0 = self
+            .0
+            .checked_add(amount)
+            .ok_or_else(|| Error::OutOfRange(what.into(), format!("<= {}", u32::MAX)))?;
+        Ok(())
+    }
+    /// Removes up to `amount` from the resource, stopping at zero.
+    pub fn saturating_take(&mut self, amount: u32) {
+        self.0 = self.0.saturating_sub(amount);
+    }
+    /// Empties the resource, returning what was in it.
+    pub fn take_all(&mut self) -> u32 {
+        core::mem::take(&mut self.0)
+    }
+}
+impl From<u32> for Resource {
+    fn from(value: u32) -> Self {
+        Resource(value)
+    }
+}
+impl Display for Resource {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+/// How the tiles of a board connect.
+/// Games are played on `Topology::Flat`, the others are for tools that want to reason about other boards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Topology {
+    /// A rectangle, tiles touch the eight tiles around them.
+    #[default]
+    Flat,
+    /// A rectangle whose opposite edges touch.
+    Torus,
+    /// Hexagons in rows, odd rows shifted half a tile to the right.
+    Hex,
+}
+/// The coordinate math of a board of `width` by `height` tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Board {
+    pub width: u32,
+    pub height: u32,
+}
+impl Board {
+    /// The distance between `a` and `b` along one axis of `size` tiles.
+    fn axis(a: u32, b: u32, size: u32, topology: Topology) -> u32 {
+        let distance = a.abs_diff(b);
+        match topology {
+            Topology::Torus => distance.min(size.saturating_sub(distance)),
+            Topology::Flat | Topology::Hex => distance,
+        }
+    }
+    fn hex(a: (u32, u32), b: (u32, u32)) -> u32 {
+        let cube = |(x, y): (u32, u32)| {
+            let (x, y) = (i64::from(x), i64::from(y));
+            (x - (y - (y & 1)) / 2, y)
+        };
+        let ((aq, ar), (bq, br)) = (cube(a), cube(b));
+        let (dq, dr) = (aq - bq, ar - br);
+        u32::try_from((dq.abs() + dr.abs() + (dq + dr).abs()) / 2).unwrap_or(u32::MAX)
+    }
+    /// The most steps along either axis, the distance ranges are measured in. Hexagons use their own distance.
+    #[must_use]
+    pub fn chebyshev(&self, a: (u32, u32), b: (u32, u32), topology: Topology) -> u32 {
+        if topology == Topology::Hex {
+            return Self::hex(a, b);
+        }
+        Self::axis(a.0, b.0, self.width, topology).max(Self::axis(a.1, b.1, self.height, topology))
+    }
+    /// The steps along both axes together. Hexagons use their own distance.
+    #[must_use]
+    pub fn manhattan(&self, a: (u32, u32), b: (u32, u32), topology: Topology) -> u32 {
+        if topology == Topology::Hex {
+            return Self::hex(a, b);
+        }
+        Self::axis(a.0, b.0, self.width, topology) + Self::axis(a.1, b.1, self.height, topology)
+    }
+    #[must_use]
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height
+    }
+    /// Every tile, row by row.
+    pub fn tiles(&self) -> impl Iterator<Item = (u32, u32)> {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+    /// The tiles along one axis at most `range` away from `center`, once each.
+    fn span(center: u32, range: u32, size: u32, topology: Topology) -> Vec<u32> {
+        if topology != Topology::Torus {
+            return (center.saturating_sub(range)
+                ..=center.saturating_add(range).min(size.saturating_sub(1)))
+                .collect();
+        }
+        if range.saturating_mul(2) >= size.saturating_sub(1) {
+            return (0..size).collect();
+        }
+        (0..=range * 2)
+            .map(|i| (center + size - range + i) % size)
+            .collect()
+    }
+    /// Every tile at most `range` away from `(x, y)` by `Board::chebyshev`, the tile itself excluded. Row by row.
+    pub fn neighbors(
+        self,
+        x: u32,
+        y: u32,
+        range: u32,
+        topology: Topology,
+    ) -> impl Iterator<Item = (u32, u32)> {
+        let xs = Self::span(x, range, self.width, topology);
+        Self::span(y, range, self.height, topology)
+            .into_iter()
+            .flat_map(move |ty| xs.clone().into_iter().map(move |tx| (tx, ty)))
+            .filter(move |&tile| tile != (x, y) && self.chebyshev((x, y), tile, topology) <= range)
+    }
+    /// The shortest way from `from` to `to` of at most `limit` steps of one tile, avoiding every tile `blocked` returns
+    /// true for. Found with A*, so only tiles that could be on such a way are visited.
+    /// Returns the tiles passed, ending at `to`, or `None` if there is no such way.
+    pub fn shortest_path(
+        self,
+        from: (u32, u32),
+        to: (u32, u32),
+        limit: u32,
+        blocked: impl Fn((u32, u32)) -> bool,
+        topology: Topology,
+    ) -> Option<Vec<(u32, u32)>> {
+        if from == to {
+            return Some(Vec::new());
+        }
+        if !self.contains(to.0, to.1) || blocked(to) {
+            return None;
+        }
+        let mut steps = HashMap::new();
+        steps.insert(from, 0);
+        let mut came_from = HashMap::new();
+        let mut open = BinaryHeap::from([Reverse((self.chebyshev(from, to, topology), 0, from))]);
+        while let Some(Reverse((_, taken, tile))) = open.pop() {
+            if tile == to {
+                let mut path = vec![to];
+                while let Some(&previous) = came_from.get(&path[path.len() - 1]) {
+                    if previous == from {
+                        break;
+                    }
+                    path.push(previous);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if steps.get(&tile).is_some_and(|&best| best < taken) {
+                continue;
+            }
+            for next in self.neighbors(tile.0, tile.1, 1, topology) {
+                let estimate = taken + 1 + self.chebyshev(next, to, topology);
+                if estimate > limit
+                    || blocked(next)
+                    || steps.get(&next).is_some_and(|&best| best <= taken + 1)
+                {
+                    continue;
+                }
+                steps.insert(next, taken + 1);
+                came_from.insert(next, tile);
+                open.push(Reverse((estimate, taken + 1, next)));
+            }
+        }
+        None
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Player {
     pub user: i32,
     pub x: u32,
     pub y: u32,
     pub level: u32,
-    pub points: u32,
-    pub health: u32,
+    pub points: Resource,
+    pub health: Resource,
+    /// Index of the last line authorized by this player.
+    pub last_action: usize,
+    /// The round in which the player died.
+    pub died_round: Option<u32>,
+    /// The last player that shot this player.
+    pub last_hit_by: Option<i32>,
+    /// The items the player has not used yet.
+    pub items: Vec<Item>,
+    /// The items the player used this round.
+    pub effects: Vec<Item>,
+    /// The round in which the tank was disabled, see `Settings::two_phase_kills`.
+    pub disabled_round: Option<u32>,
 }
 impl Player {
+    /// Whether the tank is alive and not disabled, `is_alive(true)` without building an error.
+    #[must_use]
+    pub fn alive(&self) -> bool {
+        self.disabled_round.is_none() && !self.health.is_empty()
+    }
     /// Check the alive state of the player.
-    /// Does nothing and returns `Result::Ok()` if the states match.
+    /// Does nothing and returns `Result::Ok()` if the states match. A disabled tank is neither alive nor dead.
     /// # Errors
     /// If the states do not match.
     pub fn is_alive(&self, alive: bool) -> Result<(), Error> {
-        if (self.health != 0) == alive {
-            Ok(())
-        } else {
+        if self.disabled_round.is_some() {
+            Err(Error::Other("The tank is disabled.".into()))
+        } else if self.health.is_empty() == alive {
             Err(Error::OutOfRange(
                 "Health".into(),
                 if alive { "> 0" } else { " == 0" }.into(),
             ))
+        } else {
+            Ok(())
         }
     }
     /// Check if the player has at least one point.
@@ -67,12 +302,22 @@ impl Player {
     /// # Errors
     /// If the player has no points.
     pub fn has_points(&self) -> Result<(), Error> {
-        if self.points == 0 {
+        if self.points.is_empty() {
             Err(Error::OutOfRange("Points".into(), "> 0".into()))
         } else {
             Ok(())
         }
     }
+    /// Check if the player has at least `cost` points.
+    /// # Errors
+    /// If the player has fewer points.
+    pub fn can_afford(&self, cost: u32) -> Result<(), Error> {
+        if self.points.get() < cost {
+            Err(Error::OutOfRange("Points".into(), format!(">= {cost}")))
+        } else {
+            Ok(())
+        }
+    }
     /// Check if the position (`x`,`y`) is in the range of `distance`.
     /// Does nothing and returns `Result::Ok()` if the player is in range.
     /// # Errors
@@ -113,10 +358,74 @@ pub enum MoveLineType {
     Gift,
     /// Vote for a target to gain a point.
     Vote,
-    /// Count all votes and distribute points. (giving all players exceeding the threshold an extra point)
+    /// Count all votes and distribute points at unix time `time`. (giving all players exceeding the threshold an extra point)
     HandleVotes,
     /// Go up a level. (uses one point)
     Upgrade,
+    /// Place `amount` points on an empty tile, to be collected by the first tank driving onto it. (uses `amount` points)
+    Bounty,
+    /// Open a non-binding poll with the question and options in `text`.
+    Poll,
+    /// Vote for option `amount` in the poll `target`.
+    PollVote,
+    /// Close the poll `target`. (only by its creator)
+    ClosePoll,
+    /// Die voluntarily, leaving the points as loot and joining the jury.
+    Surrender,
+    /// Buy the item named in `text` for the inventory. (see `Settings::items`)
+    BuyItem,
+    /// Use the item named in `text` from the inventory, until the round ends.
+    UseItem,
+    /// Repair the disabled tank `target` back to one health. (see `Settings::two_phase_kills`)
+    Repair,
+    /// Join at the tile `Game::get_pos` picks. Signed without the previous move, so it can be signed before the game
+    /// exists and appended by someone else, `text` naming what it was signed for. (see the matchmaking queue)
+    JoinRandom,
+    /// Pause the game at unix time `time`, until it is resumed. (see `Game::is_paused`)
+    Pause,
+    /// Resume a paused game at unix time `time`.
+    Resume,
+    /// Lend target `amount` points, paid back when the votes are handled `x` rounds from now, this round being the
+    /// first. (uses `amount` points, see `Game::loans`)
+    Lend,
+}
+impl MoveLineType {
+    /// Whether the move costs points, which `Settings::actions_per_round` limits.
+    #[must_use]
+    pub fn spends_points(&self) -> bool {
+        matches!(
+            self,
+            MoveLineType::Drive
+                | MoveLineType::Shoot
+                | MoveLineType::Gift
+                | MoveLineType::Bounty
+                | MoveLineType::BuyItem
+                | MoveLineType::Repair
+                | MoveLineType::Lend
+        )
+    }
+}
+/// A tile a drive passes on its way to its destination.
+#[cfg_attr(feature = "openapi", derive(Object))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub x: u32,
+    pub y: u32,
+}
+impl Step {
+    /// Reads the `x,y` steps of the compact form of a drive, separated by `;`.
+    fn parse_path(text: &str) -> Result<Vec<Step>, Error> {
+        text.split(';')
+            .map(|step| {
+                let (x, y) = step.split_once(',').ok_or(Error::MalformedMove)?;
+                Ok(Step {
+                    x: x.parse().map_err(|_| Error::MalformedMove)?,
+                    y: y.parse().map_err(|_| Error::MalformedMove)?,
+                })
+            })
+            .collect()
+    }
 }
 #[cfg_attr(feature = "openapi", derive(Object))]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
@@ -130,6 +439,18 @@ pub struct MoveLine {
     pub y: Option<u32>,
     /// The target of the move.
     pub target: Option<i32>,
+    /// The amount of points used by the move.
+    pub amount: Option<u32>,
+    /// The text of the move. (the question and options of a poll)
+    pub text: Option<String>,
+    /// The unix time covered by the move. (when votes were handled)
+    pub time: Option<u64>,
+    /// The signature of the server clock over `time`, from its `/time` endpoint. (see `MoveLine::check_clock`)
+    pub clock: Option<String>,
+    /// The tiles a drive passes before reaching `x`, `y`, in order. (see `Settings::drive_length`)
+    pub path: Option<Vec<Step>>,
+    /// The rules version the move was made for, it must match the game. (moves from before versioning have none)
+    pub rules_version: Option<u32>,
     /// The user that authorized this move.
     pub authorizer: i32,
     /// The move signed by the authorizer.
@@ -139,50 +460,356 @@ impl MoveLine {
     /// Calculates the signature of this move and stores it in the signature field.
     /// # Errors
     /// If the `private_key` is not correctly formated (url safe base 64 string of a point on the k256 curve).
+    #[cfg(feature = "crypto")]
     pub fn sign(&mut self, last: Option<&str>, private_key: String) -> Result<(), Error> {
-        let key = BASE64
-            .decode(private_key)
-            .ok()
-            .and_then(|x| SigningKey::from_slice(x.as_slice()).ok())
-            .ok_or_else(|| Error::Other("Malformed private key.".into()))?;
-        self.signature = String::default();
-        let mut data = self.to_string();
-        if let Some(last) = last {
-            data.push_str(last);
-        }
-        let signature: Signature = key.sign(data.as_bytes());
-        self.signature = signature.to_string();
+        self.signature = sign_data(&self.signing_data(last), private_key)?;
         Ok(())
     }
-}
-impl Display for MoveLine {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    /// Reads the `v{version}:` and the authorizer that start the compact form of a move, returning the rest of it.
+    fn parse_prefix(text: &str) -> Result<(Option<u32>, i32, &str), Error> {
+        let (rules_version, text) = match text.strip_prefix('v').and_then(|x| x.split_once(':')) {
+            Some((version, text)) => (
+                Some(version.parse().map_err(|_| Error::MalformedMove)?),
+                text,
+            ),
+            None => (None, text),
+        };
+        let start = text
+            .char_indices()
+            .find(|(i, c)| !(c.is_ascii_digit() || (*i == 0 && *c == '-')))
+            .ok_or(Error::MalformedMove)?
+            .0;
+        let authorizer = text[..start].parse().map_err(|_| Error::MalformedMove)?;
+        Ok((rules_version, authorizer, &text[start..]))
+    }
+    /// Reads the `time@clock` of the compact form of a `HandleVotes`, the clock being optional.
+    fn parse_clock(text: &str) -> Result<(u64, Option<String>), Error> {
+        let (time, clock) = match text.split_once('@') {
+            Some((time, clock)) => (time, Some(clock.to_string())),
+            None => (text, None),
+        };
+        Ok((time.parse().map_err(|_| Error::MalformedMove)?, clock))
+    }
+    /// The data the authorizer signs, this move without its signature followed by the signature of the `last` move.
+    /// A `MoveLineType::JoinRandom` is signed without the `last` move.
+    #[must_use]
+    pub fn signing_data(&self, last: Option<&str>) -> String {
+        let mut data = String::new();
+        self.write_signing_data(last, &mut data);
+        data
+    }
+    /// Writes the `signing_data` into `out`, replacing what it held, so replays can reuse one buffer for every move.
+    pub fn write_signing_data(&self, last: Option<&str>, out: &mut String) {
+        out.clear();
+        // Writing into a `String` only fails for moves missing fields, which then can't have a valid signature.
+        let _ = self.write_body(out);
+        out.push('|');
+        if let Some(last) = last.filter(|_| self.move_type != MoveLineType::JoinRandom) {
+            out.push_str(last);
+        }
+    }
+    /// Writes the text form of this move without the signature.
+    fn write_body<W: core::fmt::Write>(&self, f: &mut W) -> core::fmt::Result {
+        if let Some(version) = self.rules_version {
+            write!(f, "v{version}:")?;
+        }
         write!(f, "{}", self.authorizer)?;
 
         match self.move_type {
             MoveLineType::Join => write!(
                 f,
                 "J{},{}",
-                self.x.ok_or(std::fmt::Error)?,
-                self.y.ok_or(std::fmt::Error)?
+                self.x.ok_or(core::fmt::Error)?,
+                self.y.ok_or(core::fmt::Error)?
+            ),
+            MoveLineType::Drive => {
+                write!(f, "D")?;
+                for step in self.path.iter().flatten() {
+                    write!(f, "{},{};", step.x, step.y)?;
+                }
+                write!(
+                    f,
+                    "{},{}",
+                    self.x.ok_or(core::fmt::Error)?,
+                    self.y.ok_or(core::fmt::Error)?
+                )
+            }
+            MoveLineType::Shoot => write!(f, "S{}", self.target.ok_or(core::fmt::Error)?),
+            MoveLineType::Gift => write!(f, "G{}", self.target.ok_or(core::fmt::Error)?),
+            MoveLineType::Repair => write!(f, "R{}", self.target.ok_or(core::fmt::Error)?),
+            MoveLineType::Vote => write!(f, "V{}", self.target.ok_or(core::fmt::Error)?),
+            MoveLineType::HandleVotes => match (self.time, &self.clock) {
+                (Some(time), Some(clock)) => write!(f, "H{time}@{clock}"),
+                (Some(time), None) => write!(f, "H{time}"),
+                _ => write!(f, "H"),
+            },
+            MoveLineType::Upgrade => write!(f, "U"),
+            MoveLineType::Surrender => write!(f, "X"),
+            MoveLineType::Bounty => write!(
+                f,
+                "B{},{},{}",
+                self.x.ok_or(core::fmt::Error)?,
+                self.y.ok_or(core::fmt::Error)?,
+                self.amount.ok_or(core::fmt::Error)?
             ),
-            MoveLineType::Drive => write!(
+            MoveLineType::Poll => write!(f, "P{}", self.text.as_ref().ok_or(core::fmt::Error)?),
+            MoveLineType::PollVote => write!(
                 f,
-                "D{},{}",
-                self.x.ok_or(std::fmt::Error)?,
-                self.y.ok_or(std::fmt::Error)?
+                "Q{},{}",
+                self.target.ok_or(core::fmt::Error)?,
+                self.amount.ok_or(core::fmt::Error)?
             ),
-            MoveLineType::Shoot => write!(f, "S{}", self.target.ok_or(std::fmt::Error)?),
-            MoveLineType::Gift => write!(f, "G{}", self.target.ok_or(std::fmt::Error)?),
-            MoveLineType::Vote => write!(f, "V{}", self.target.ok_or(std::fmt::Error)?),
-            MoveLineType::HandleVotes => write!(f, "H"),
-            MoveLineType::Upgrade => write!(f, "U"),
-        }?;
+            MoveLineType::ClosePoll => write!(f, "C{}", self.target.ok_or(core::fmt::Error)?),
+            MoveLineType::BuyItem => write!(f, "O{}", self.text.as_ref().ok_or(core::fmt::Error)?),
+            MoveLineType::UseItem => write!(f, "I{}", self.text.as_ref().ok_or(core::fmt::Error)?),
+            MoveLineType::JoinRandom => {
+                write!(f, "A{}", self.text.as_ref().ok_or(core::fmt::Error)?)
+            }
+            MoveLineType::Pause => write!(f, "Z{}", self.time.ok_or(core::fmt::Error)?),
+            MoveLineType::Resume => write!(f, "W{}", self.time.ok_or(core::fmt::Error)?),
+            MoveLineType::Lend => write!(
+                f,
+                "L{},{},{}",
+                self.target.ok_or(core::fmt::Error)?,
+                self.amount.ok_or(core::fmt::Error)?,
+                self.x.ok_or(core::fmt::Error)?
+            ),
+        }
+    }
+}
+impl Display for MoveLine {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.write_body(f)?;
         write!(f, "|{}", self.signature)
     }
 }
+impl core::str::FromStr for MoveLine {
+    type Err = Error;
+    /// Parses the text form written by `Display`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (text, signature) = text.rsplit_once('|').ok_or(Error::MalformedMove)?;
+        let (rules_version, authorizer, text) = MoveLine::parse_prefix(text)?;
+        let mut chars = text.chars();
+        let move_type = chars.next();
+        let rest = chars.as_str();
+        let mut numbers = rest.split(',').map(|x| x.parse::<u32>().ok());
+        let mut number = || numbers.next().flatten().ok_or(Error::MalformedMove);
+        let target = || rest.parse::<i32>().map_err(|_| Error::MalformedMove);
+        let mut line = MoveLine {
+            move_type: MoveLineType::HandleVotes,
+            x: None,
+            y: None,
+            target: None,
+            amount: None,
+            text: None,
+            time: None,
+            clock: None,
+            path: None,
+            rules_version,
+            authorizer,
+            signature: signature.to_string(),
+        };
+        match move_type {
+            Some('J') => {
+                line.move_type = MoveLineType::Join;
+                line.x = Some(number()?);
+                line.y = Some(number()?);
+            }
+            Some('D') => {
+                let mut steps = Step::parse_path(rest)?;
+                let end = steps.pop().ok_or(Error::MalformedMove)?;
+                line.move_type = MoveLineType::Drive;
+                line.x = Some(end.x);
+                line.y = Some(end.y);
+                line.path = (!steps.is_empty()).then_some(steps);
+            }
+            Some(c @ ('S' | 'G' | 'R' | 'V' | 'C')) => {
+                line.move_type = match c {
+                    'S' => MoveLineType::Shoot,
+                    'G' => MoveLineType::Gift,
+                    'R' => MoveLineType::Repair,
+                    'V' => MoveLineType::Vote,
+                    _ => MoveLineType::ClosePoll,
+                };
+                line.target = Some(target()?);
+            }
+            Some('H') if rest.is_empty() => {}
+            Some('H') => {
+                let (time, clock) = MoveLine::parse_clock(rest)?;
+                line.time = Some(time);
+                line.clock = clock;
+            }
+            Some('U') if rest.is_empty() => line.move_type = MoveLineType::Upgrade,
+            Some('X') if rest.is_empty() => line.move_type = MoveLineType::Surrender,
+            Some('B') => {
+                line.move_type = MoveLineType::Bounty;
+                line.x = Some(number()?);
+                line.y = Some(number()?);
+                line.amount = Some(number()?);
+            }
+            Some(c @ ('P' | 'O' | 'I' | 'A')) => {
+                line.move_type = match c {
+                    'P' => MoveLineType::Poll,
+                    'O' => MoveLineType::BuyItem,
+                    'A' => MoveLineType::JoinRandom,
+                    _ => MoveLineType::UseItem,
+                };
+                line.text = Some(rest.to_string());
+            }
+            Some(c @ ('Z' | 'W')) => {
+                line.move_type = if c == 'Z' {
+                    MoveLineType::Pause
+                } else {
+                    MoveLineType::Resume
+                };
+                line.time = Some(rest.parse().map_err(|_| Error::MalformedMove)?);
+            }
+            Some(c @ ('Q' | 'L')) => {
+                let (target, amount) = rest.split_once(',').ok_or(Error::MalformedMove)?;
+                line.target = Some(target.parse().map_err(|_| Error::MalformedMove)?);
+                line.move_type = MoveLineType::PollVote;
+                let amount = if c == 'L' {
+                    let (amount, rounds) = amount.split_once(',').ok_or(Error::MalformedMove)?;
+                    line.move_type = MoveLineType::Lend;
+                    line.x = Some(rounds.parse().map_err(|_| Error::MalformedMove)?);
+                    amount
+                } else {
+                    amount
+                };
+                line.amount = Some(amount.parse().map_err(|_| Error::MalformedMove)?);
+            }
+            _ => return Err(Error::MalformedMove),
+        }
+        Ok(line)
+    }
+}
+/// Writes `lines` in the compact wire format, one `Display` form per line with backslashes and newlines escaped.
+#[must_use]
+pub fn encode_tokens(lines: &[MoveLine]) -> String {
+    lines
+        .iter()
+        .map(|x| x.to_string().replace('\\', "\\\\").replace('\n', "\\n"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+/// Reads lines written by `encode_tokens`.
+/// # Errors
+/// If a line is not a valid move.
+pub fn decode_tokens(text: &str) -> Result<Vec<MoveLine>, Error> {
+    text.lines()
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let mut line = String::with_capacity(x.len());
+            let mut chars = x.chars();
+            while let Some(c) = chars.next() {
+                match (c == '\\').then(|| chars.next()) {
+                    Some(Some('n')) => line.push('\n'),
+                    Some(Some(c)) => line.push(c),
+                    Some(None) => return Err(Error::MalformedMove),
+                    None => line.push(c),
+                }
+            }
+            line.parse()
+        })
+        .collect()
+}
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        #[allow(clippy::cast_possible_truncation)]
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    out.push(value as u8);
+}
+fn read_varint(data: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = data.split_first().ok_or(Error::MalformedMove)?;
+        *data = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return Ok(value);
+        }
+    }
+    Err(Error::MalformedMove)
+}
+/// Writes an optional id, `None` as 0 and ids zigzag encoded and shifted by one.
+fn write_id(out: &mut Vec<u8>, id: Option<i32>) {
+    let zigzag = id.map_or(0, |id| {
+        u64::from(((id << 1) ^ (id >> 31)).cast_unsigned()) + 1
+    });
+    write_varint(out, zigzag);
+}
+fn read_id(data: &mut &[u8]) -> Result<Option<i32>, Error> {
+    match read_varint(data)? {
+        0 => Ok(None),
+        zigzag => {
+            let zigzag = u32::try_from(zigzag - 1).map_err(|_| Error::MalformedMove)?;
+            Ok(Some(
+                (zigzag >> 1).cast_signed() ^ -(zigzag & 1).cast_signed(),
+            ))
+        }
+    }
+}
+fn read_u32(data: &mut &[u8]) -> Result<u32, Error> {
+    u32::try_from(read_varint(data)?).map_err(|_| Error::MalformedMove)
+}
+/// A non-binding poll among the living players of a game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Poll {
+    /// The player that opened the poll.
+    pub creator: i32,
+    pub question: String,
+    pub options: Vec<String>,
+    /// The option each player voted for.
+    pub votes: HashMap<i32, u32>,
+    pub open: bool,
+}
+impl Poll {
+    pub const MAX_OPTIONS: usize = 10;
+    pub const MAX_LENGTH: usize = 500;
+    /// Parses the text of a poll move, the question followed by one option per line.
+    /// # Errors
+    /// If the text is too long or there are less than 2 or more than `Poll::MAX_OPTIONS` options.
+    pub fn from_text(creator: i32, text: &str) -> Result<Self, Error> {
+        if text.len() > Self::MAX_LENGTH {
+            return Err(Error::OutOfRange(
+                "Text".into(),
+                format!("<= {} bytes", Self::MAX_LENGTH),
+            ));
+        }
+        let mut lines = text.lines().map(str::trim).filter(|x| !x.is_empty());
+        let question = lines.next().ok_or(Error::MalformedMove)?.to_string();
+        let options = lines.map(String::from).collect::<Vec<_>>();
+        if !(2..=Self::MAX_OPTIONS).contains(&options.len()) {
+            return Err(Error::OutOfRange(
+                "Options".into(),
+                format!("2..={}", Self::MAX_OPTIONS),
+            ));
+        }
+        Ok(Poll {
+            creator,
+            question,
+            options,
+            votes: HashMap::new(),
+            open: true,
+        })
+    }
+    /// The number of votes for each option.
+    #[must_use]
+    pub fn tally(&self) -> Vec<u32> {
+        let mut tally = vec![0; self.options.len()];
+        for option in self.votes.values() {
+            if let Some(count) = usize::try_from(*option).ok().and_then(|x| tally.get_mut(x)) {
+                *count += 1;
+            }
+        }
+        tally
+    }
+}
 #[cfg_attr(feature = "openapi", derive(Object))]
-#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[derive(Clone, PartialEq)]
 pub struct DataBaseGame {
     /// The game id.
@@ -205,46 +832,313 @@ pub struct DataBaseGame {
     pub vote_threshold: u32,
     /// The method for calculating the range from the level.
     pub range: String,
+    /// Whether players that took no action during a round lose a point when votes are handled.
+    pub point_decay: bool,
+    /// The extra weight of a vote for every round its voter has been dead. (may be negative)
+    pub jury_weight: i32,
+    /// Who gets the points of a dead player. (see `LootMode`)
+    pub loot_mode: String,
+    /// The max amount of tiles a single drive can cover, one point per tile.
+    pub drive_length: u32,
+    /// The version of the rules the game is played with. (see `RULES_VERSIONS`)
+    pub rules_version: u32,
+    /// The comma separated mutators drawn from each round, none if empty. (see `Mutator`)
+    pub mutators: String,
+    /// How the vote threshold follows the size of the jury, `vote_threshold` is used if empty. (see `ThresholdMode`)
+    pub threshold_mode: String,
+    /// How the game is won, the last tank alive wins if empty. (see `WinCondition`)
+    pub win_condition: String,
+    /// The points of a tank that just joined.
+    pub starting_points: u32,
+    /// The level of a tank that just joined, at most `max_level`.
+    pub starting_level: u32,
+    /// The max amount of moves that cost points a player may make in a single round, unlimited if 0.
+    pub actions_per_round: u32,
+    /// The comma separated items tanks can buy and pick up, none if empty. (see `Item`)
+    pub items: String,
+    /// Whether a tank shot to 0 health is disabled for a round before it is destroyed.
+    pub two_phase_kills: bool,
+    /// Whether tanks join as far as possible from the other tanks.
+    pub symmetric_spawns: bool,
+    /// The rounds a player waits after a gift before gifting the same player again, no wait if 0.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub gift_cooldown: u32,
+    /// The seconds after `last_vote` at which the server handles the votes by itself, never if 0. Only moderators may
+    /// handle them sooner.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub vote_interval: u64,
+    /// The language the game is hosted in, as a BCP 47 tag like `nl` or `pt-BR`, none if empty.
+    pub locale: String,
+    /// What the creator wrote about the game and its house rules, in the language of `locale`.
+    pub description: String,
 }
 impl DataBaseGame {
+    /// Gets the settings of the game from the database item.
+    /// # Errors
+    /// If the `LevelRangeMap`, `LootMode`, `ThresholdMode`, `WinCondition`, a `Mutator` or an `Item` is not correctly
+    /// formatted, or the starting level is above the max level.
+    pub fn settings(&self) -> Result<Settings, Error> {
+        let Ok(range) = self.range.parse::<LevelRangeMap>() else {
+            return Err(Error::Other("Malformed LevelRangeMap.".into()));
+        };
+        let Ok(loot_mode) = self.loot_mode.parse::<LootMode>() else {
+            return Err(Error::Other("Malformed LootMode.".into()));
+        };
+        let mutators = Mutator::parse_list(&self.mutators)?;
+        let items = Item::parse_list(&self.items)?;
+        let threshold_mode = if self.threshold_mode.is_empty() {
+            ThresholdMode::Fixed(self.vote_threshold)
+        } else {
+            self.threshold_mode
+                .parse()
+                .map_err(|_| Error::Other("Malformed ThresholdMode.".into()))?
+        };
+        let win_condition = if self.win_condition.is_empty() {
+            WinCondition::LastAlive
+        } else {
+            self.win_condition
+                .parse()
+                .map_err(|_| Error::Other("Malformed WinCondition.".into()))?
+        };
+        if i64::from(self.starting_level) > i64::from(self.max_level) {
+            return Err(Error::OutOfRange(
+                "Starting level".into(),
+                format!("<= {}", self.max_level),
+            ));
+        }
+        Ok(Settings {
+            health: self.health,
+            width: self.width,
+            height: self.height,
+            max_level: self.max_level,
+            max_players: self.max_players,
+            threshold_mode,
+            seed: self.seed,
+            range,
+            point_decay: self.point_decay,
+            jury_weight: self.jury_weight,
+            loot_mode,
+            drive_length: self.drive_length,
+            rules_version: self.rules_version,
+            mutators,
+            costs: Costs::default(),
+            win_condition,
+            starting_points: self.starting_points,
+            starting_level: self.starting_level,
+            actions_per_round: (self.actions_per_round > 0).then_some(self.actions_per_round),
+            items,
+            two_phase_kills: self.two_phase_kills,
+            symmetric_spawns: self.symmetric_spawns,
+            gift_cooldown: self.gift_cooldown,
+        })
+    }
     /// Gets the actual game without any moves from the database item.
     /// # Errors
     /// If the `LevelRangeMap` is not correctly formatted.
+    #[cfg(feature = "crypto")]
     pub fn as_game(
         self,
         moves: Vec<MoveLine>,
-        users: &HashMap<i32, VerifyingKey>,
+        users: &HashMap<i32, PublicKey>,
     ) -> Result<Game, Error> {
-        let Ok(range) = self.range.parse::<LevelRangeMap>() else {
-            return Err(Error::Other("Malformed LevelRangeMap.".into()));
-        };
-        let mut game = Game::new(
-            self.id,
-            Settings {
-                health: self.health,
-                width: self.width,
-                height: self.height,
-                max_level: self.max_level,
-                max_players: self.max_players,
-                vote_threshold: self.vote_threshold,
-                seed: self.seed,
-                range,
-            },
-        );
+        self.replay(moves, users, None)
+    }
+    /// `as_game`, checking only the signatures that are not in `verified` yet and adding them. Servers replaying the
+    /// same chains over and over keep one `VerifiedCache` for all of them.
+    /// # Errors
+    /// If the settings are invalid or a move can't be loaded.
+    #[cfg(feature = "crypto")]
+    pub fn as_game_verified(
+        self,
+        moves: Vec<MoveLine>,
+        users: &HashMap<i32, PublicKey>,
+        verified: &VerifiedCache,
+    ) -> Result<Game, Error> {
+        self.replay(moves, users, Some(verified))
+    }
+    #[cfg(feature = "crypto")]
+    fn replay(
+        self,
+        moves: Vec<MoveLine>,
+        users: &HashMap<i32, PublicKey>,
+        verified: Option<&VerifiedCache>,
+    ) -> Result<Game, Error> {
+        let mut game = Game::new(self.id, self.settings()?);
+        let mut data = String::new();
         for m in moves {
-            game.load(m, users)?;
+            game.load_with(m, users, &mut data, verified)?;
+            // Nobody drains the events of a replay, keep the queue from growing with the chain.
+            game.events.clear();
         }
         Ok(game)
     }
 }
+/// Why points moved from one player to another.
+#[cfg_attr(feature = "openapi", derive(Enum))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TransferKind {
+    /// A `Gift` move.
+    Gift,
+    /// The points of a dead player, see `LootMode`.
+    Loot,
+    /// A `Lend` move.
+    Loan,
+    /// Points of a loan paid back, see `Game::loans`.
+    Repayment,
+}
+/// Points that moved from one player to another, see `Game::transfers`.
+#[cfg_attr(feature = "openapi", derive(Object))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    pub from: i32,
+    pub to: i32,
+    pub kind: TransferKind,
+    pub amount: u32,
+}
+/// A vote cast during a round, see `RoundSummary`.
+#[cfg_attr(feature = "openapi", derive(Object))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CastVote {
+    pub voter: i32,
+    pub target: i32,
+}
+/// Where a player stands at the end of a round, see `RoundSummary`.
+#[cfg_attr(feature = "openapi", derive(Object))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Standing {
+    pub user: i32,
+    pub alive: bool,
+    pub health: u32,
+    pub points: u32,
+    pub level: u32,
+}
+/// What happened in a round, made when its votes are handled, see `Game::rounds`.
+#[cfg_attr(feature = "openapi", derive(Object))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundSummary {
+    /// The round that ended.
+    pub round: u32,
+    pub votes: Vec<CastVote>,
+    /// The players that got the extra point of the vote.
+    pub elected: Vec<i32>,
+    /// The points every player got for the next round, besides the vote.
+    pub points: u32,
+    /// The players that died during the round.
+    pub kills: Vec<i32>,
+    /// Living players first, then by health and points.
+    pub standings: Vec<Standing>,
+}
+/// A frame of the game sync socket of the server, see `SyncHello`.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncFrame {
+    /// Every move of the game, sent first and whenever the client may have missed a move.
+    State { moves: Vec<MoveLine> },
+    /// A move appended at `index`.
+    Move { index: usize, line: MoveLine },
+    /// The tanks in the viewport after the first `index` moves, sent instead of the moves when the client asked for a
+    /// viewport, and again whenever a move changed the whole board.
+    View { index: usize, tanks: Vec<ViewTank> },
+    /// A move appended at `index` that changed `tiles` of the viewport, with the tanks now on them. The other tiles
+    /// of `tiles` are now empty.
+    Update {
+        index: usize,
+        line: MoveLine,
+        tiles: Vec<Step>,
+        tanks: Vec<ViewTank>,
+    },
+    /// The whole board at low resolution, sent along with a view and whenever it changes.
+    Minimap(Minimap),
+}
+/// How sync frames are encoded.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncFormat {
+    /// Text frames.
+    #[default]
+    Json,
+    /// Binary CBOR frames.
+    Cbor,
+}
+/// How binary sync frames are compressed.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncCompression {
+    #[default]
+    None,
+    /// Raw deflate of every frame, which also makes JSON frames binary.
+    Deflate,
+}
+/// The first message on a sync socket. The client lists what it supports, most preferred first, and the server
+/// answers with a `SyncHello` holding the single format and compression it picked.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncHello {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub formats: Vec<SyncFormat>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub compression: Vec<SyncCompression>,
+    /// The part of the board the client shows, to only be sent what changes in it. (see `SyncFrame::View`)
+    /// The client can move it later by sending another `Region`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub viewport: Option<Region>,
+}
+/// An enemy that can shoot a tile, see `Game::threat_map`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Threat {
+    pub enemy: i32,
+    /// The damage the enemy can deal this round. (one per point)
+    pub damage: u32,
+}
+#[derive(Clone)]
 pub struct Game {
     pub id: i32,
+    /// The time of the last `HandleVotes` move that carried one.
     pub last_vote: u64,
     pub settings: Settings,
     pub players: HashMap<i32, Player>,
     pub board: HashMap<(u32, u32), i32>,
+    /// Points waiting on a tile for the first tank to drive onto it.
+    pub bounties: HashMap<(u32, u32), u32>,
+    /// Items waiting on a tile for the first tank to drive onto it. (see `Settings::items`)
+    pub pickups: HashMap<(u32, u32), Vec<Item>>,
     pub votes: HashMap<i32, i32>,
+    /// The polls of this game, the `target` of poll moves is an index into this list.
+    pub polls: Vec<Poll>,
+    /// Every gift and loot in the order they happened.
+    pub transfers: Vec<Transfer>,
+    /// The round of the last gift of every giver to every receiver. (see `Settings::gift_cooldown`)
+    pub gifts: HashMap<(i32, i32), u32>,
+    /// The loans that aren't paid back yet, in the order they were made.
+    pub loans: Vec<Loan>,
+    /// The events of the moves applied since they were last drained. (see `Game::drain_events`)
+    pub events: Vec<GameEvent>,
+    /// The mutator of the current round.
+    pub mutator: Option<Mutator>,
+    /// The mutators drawn so far, with the round they applied to.
+    pub mutator_events: Vec<(u32, Mutator)>,
+    /// A summary of every round that ended.
+    pub rounds: Vec<RoundSummary>,
+    /// The tank on the flag, the center tile, with the number of rounds in a row it ended there.
+    pub flag: Option<(i32, u32)>,
+    /// The key fingerprint of every user that made a move. (see `key_fingerprint`)
+    pub fingerprints: HashMap<i32, String>,
+    /// Index of the line that started the current round.
+    pub round_start: usize,
+    /// The unix time the game was paused at, while it is paused. (see `MoveLineType::Pause`)
+    pub paused: Option<u64>,
+    /// The number of rounds that have been handled.
+    pub round: u32,
     pub lines: Vec<MoveLine>,
+    /// The random numbers of the move being applied. (see `Game::next_rng`)
     pub rand: rand_chacha::ChaCha12Rng,
 }
 impl Game {
@@ -256,12 +1150,134 @@ impl Game {
             id,
             players: HashMap::new(),
             board: HashMap::new(),
+            bounties: HashMap::new(),
+            pickups: HashMap::new(),
             votes: HashMap::new(),
+            polls: Vec::new(),
+            transfers: Vec::new(),
+            gifts: HashMap::new(),
+            loans: Vec::new(),
+            events: Vec::new(),
+            mutator: None,
+            mutator_events: Vec::new(),
+            rounds: Vec::new(),
+            flag: None,
+            fingerprints: HashMap::new(),
+            round_start: 0,
+            paused: None,
+            round: 0,
             lines: Vec::new(),
             settings,
             last_vote: 0,
         }
     }
+    fn get_poll(&self, line: &MoveLine) -> Result<&Poll, Error> {
+        let id = line.target.ok_or(Error::MalformedMove)?;
+        usize::try_from(id)
+            .ok()
+            .and_then(|x| self.polls.get(x))
+            .ok_or_else(|| Error::NotFound(format!("poll ({id})")))
+    }
+    /// Checks that every tile of the path of a drive is free, next to the one before and paid for.
+    fn check_drive(&self, line: &MoveLine) -> Result<(), Error> {
+        let steps = Self::drive_steps(line)?;
+        let player = self.get_player(line.authorizer)?;
+        player.is_alive(true)?;
+        let length = u32::try_from(steps.len()).unwrap_or(u32::MAX);
+        if length > self.settings.drive_length {
+            return Err(Error::OutOfRange(
+                "Path".into(),
+                format!("<= {} tiles", self.settings.drive_length),
+            ));
+        }
+        player.can_afford(self.drive_cost(player, length))?;
+        let end = steps[steps.len() - 1];
+        if let Some(&user) = self.board.get(&end) {
+            return Err(Error::TileOccupied(user));
+        }
+        if self.drive_path(line.authorizer, end).is_none() {
+            return Err(Error::OutOfRange(
+                "Position".into(),
+                format!("reachable in <= {} tiles", self.settings.drive_length),
+            ));
+        }
+        let board = self.settings.board();
+        let mut from = (player.x, player.y);
+        for (x, y) in steps {
+            if !board.contains(x, y) {
+                return Err(Error::OutOfRange("Position".into(), "on the board".into()));
+            }
+            if let Some(&user) = self.board.get(&(x, y)) {
+                return Err(Error::TileOccupied(user));
+            }
+            if board.chebyshev(from, (x, y), Topology::Flat) > 1 {
+                return Err(Error::OutOfRange("Position".into(), "distance <= 1".into()));
+            }
+            from = (x, y);
+        }
+        Ok(())
+    }
+    /// The cheapest drive of `user` to `to` the drive length allows, driving around every tank. Returns the tiles
+    /// passed, ending at `to`, see `Board::shortest_path`.
+    #[must_use]
+    pub fn drive_path(&self, user: i32, to: (u32, u32)) -> Option<Vec<(u32, u32)>> {
+        let player = self.players.get(&user)?;
+        self.settings.board().shortest_path(
+            (player.x, player.y),
+            to,
+            self.settings.drive_length,
+            |tile| self.board.contains_key(&tile),
+            Topology::Flat,
+        )
+    }
+    /// Moves the tank to the end of the path, collecting every bounty and item on the way.
+    fn handle_drive(&mut self, line: &MoveLine) -> Result<(), Error> {
+        let steps = Self::drive_steps(line)?;
+        let (x, y) = steps[steps.len() - 1];
+        let length = u32::try_from(steps.len()).unwrap_or(u32::MAX);
+        let cost = self.drive_cost(self.get_player(line.authorizer)?, length);
+        let player = self.get_player_mut(line.authorizer)?;
+        player.points.take(cost, "Points")?;
+        player.effects.retain(|x| *x != Item::ExtraMove);
+        let old = (player.x, player.y);
+        player.x = x;
+        player.y = y;
+        self.board.remove(&old);
+        self.board.insert((x, y), line.authorizer);
+        self.emit(GameEvent::PlayerMoved {
+            user: line.authorizer,
+            from: Step { x: old.0, y: old.1 },
+            to: Step { x, y },
+        });
+        let bounty = steps
+            .iter()
+            .filter_map(|tile| self.bounties.remove(tile))
+            .sum::<u32>();
+        if bounty > 0 {
+            self.get_player_mut(line.authorizer)?
+                .points
+                .give(bounty, "Points")?;
+            self.emit(GameEvent::PointsAwarded {
+                user: line.authorizer,
+                amount: bounty,
+            });
+        }
+        self.pick_up(line.authorizer, &steps)
+    }
+    /// The tiles a drive passes, ending at its destination.
+    fn drive_steps(line: &MoveLine) -> Result<Vec<(u32, u32)>, Error> {
+        let end = (
+            line.x.ok_or(Error::MalformedMove)?,
+            line.y.ok_or(Error::MalformedMove)?,
+        );
+        Ok(line
+            .path
+            .iter()
+            .flatten()
+            .map(|step| (step.x, step.y))
+            .chain([end])
+            .collect())
+    }
     fn get_player(&self, id: i32) -> Result<&Player, Error> {
         if let Some(player) = self.players.get(&id) {
             Ok(player)
@@ -269,111 +1285,338 @@ impl Game {
             Err(Error::NotFound(format!("player ({id})")))
         }
     }
+    fn get_player_mut(&mut self, id: i32) -> Result<&mut Player, Error> {
+        if let Some(player) = self.players.get_mut(&id) {
+            Ok(player)
+        } else {
+            Err(Error::NotFound(format!("player ({id})")))
+        }
+    }
+    fn get_poll_mut(&mut self, line: &MoveLine) -> Result<&mut Poll, Error> {
+        let id = line.target.ok_or(Error::MalformedMove)?;
+        usize::try_from(id)
+            .ok()
+            .and_then(|x| self.polls.get_mut(x))
+            .ok_or_else(|| Error::NotFound(format!("poll ({id})")))
+    }
     /// Load a `MoveLine` into the game object.
     /// # Errors
     /// * If the `line` is in any way invalid.
     /// * If the signature of the user is invalid. (url safe base 64 string of a point on the k256 curve)
-    pub fn load(
+    #[cfg(feature = "crypto")]
+    pub fn load(&mut self, line: MoveLine, users: &HashMap<i32, PublicKey>) -> Result<(), Error> {
+        self.load_with(line, users, &mut String::new(), None)
+    }
+    /// `load`, writing the signing data into `data` so a replay allocates it once, and skipping the signatures in
+    /// `verified`.
+    #[cfg(feature = "crypto")]
+    fn load_with(
         &mut self,
-        mut line: MoveLine,
-        users: &HashMap<i32, VerifyingKey>,
+        line: MoveLine,
+        users: &HashMap<i32, PublicKey>,
+        data: &mut String,
+        verified: Option<&VerifiedCache>,
     ) -> Result<(), Error> {
-        let mut signature = String::new();
-        std::mem::swap(&mut signature, &mut line.signature);
-
-        let mut data = line.to_string();
-        if let Some(last) = self.lines.last() {
-            data.push_str(&last.signature.to_string());
-        }
-        signature
-            .parse::<Signature>()
-            .ok()
-            .and_then(|s| {
-                users
-                    .get(&line.authorizer)?
-                    .verify(data.as_bytes(), &s)
-                    .ok()
+        line.write_signing_data(self.lines.last().map(|x| x.signature.as_str()), data);
+        let key = users
+            .get(&line.authorizer)
+            .filter(|key| match verified {
+                Some(verified) => verified.verify(key, data, &line.signature),
+                None => verify_data(key, data, &line.signature),
             })
-            .ok_or(Error::Other("Invalid signature.".into()))?;
+            .ok_or_else(|| Error::Other("Invalid signature.".into()))?;
 
-        std::mem::swap(&mut signature, &mut line.signature);
-        self.check(&line)?;
-        self.handle_unchecked(line);
+        let authorizer = line.authorizer;
+        self.apply(line)?;
+        self.fingerprints
+            .entry(authorizer)
+            .or_insert_with(|| key_fingerprint(key));
         Ok(())
     }
+    /// Checks that every move of `lines` is signed by its authorizer on top of the move before it and follows the rules,
+    /// as if they were loaded into this game, which is left as it is. For clients auditing a game they downloaded.
+    /// # Errors
+    /// The position of the first move that doesn't verify, and why.
+    #[cfg(feature = "crypto")]
+    pub fn verify_chain(
+        &self,
+        lines: &[MoveLine],
+        users: &HashMap<i32, PublicKey>,
+    ) -> Result<(), ChainBreak> {
+        let mut game = self.clone();
+        let mut data = String::new();
+        for (index, line) in lines.iter().enumerate() {
+            game.load_with(line.clone(), users, &mut data, None)
+                .map_err(|error| ChainBreak { index, error })?;
+            game.events.clear();
+        }
+        Ok(())
+    }
+    /// Load a `MoveLine` into the game object without checking its signature, for hosts that check it themselves.
+    /// # Errors
+    /// If the `line` is in any way invalid.
+    pub fn apply(&mut self, line: MoveLine) -> Result<(), Error> {
+        self.check(&line)?;
+        self.handle_unchecked(line)
+    }
+    /// The tile the next tank would join at. (see `Settings::symmetric_spawns`)
     #[must_use]
     pub fn get_pos(&self) -> (u32, u32) {
-        let mut rand = self.rand.clone();
-        let mut x = 0;
-        let mut y = 0;
-        while self.board.contains_key(&(x, y)) {
-            let random = rand.next_u64();
-            #[allow(clippy::cast_possible_truncation)]
-            let low: u32 = random as u32;
-            #[allow(clippy::cast_possible_truncation)]
-            let high: u32 = (random >> 32) as u32;
-            x = low % self.settings.width;
-            y = high % self.settings.height;
-        }
-        (x, y)
+        self.spawn(&mut self.next_rng())
     }
+    /// The tile the next tank joins at, advancing the random state of the game.
     pub fn get_pos_mut(&mut self) -> (u32, u32) {
-        let mut x = 0;
-        let mut y = 0;
-        while self.board.contains_key(&(x, y)) {
-            let random = self.rand.next_u64();
-            #[allow(clippy::cast_possible_truncation)]
-            let low: u32 = random as u32;
-            #[allow(clippy::cast_possible_truncation)]
-            let high: u32 = (random >> 32) as u32;
-            x = low % self.settings.width;
-            y = high % self.settings.height;
-        }
-        (x, y)
+        let mut rand = self.rand.clone();
+        let pos = self.spawn(&mut rand);
+        self.rand = rand;
+        pos
     }
-    /// Check if a `MoveLine` is valid
-    /// # Errors
-    /// If the `line` is not valid.
-    pub fn check(&self, line: &MoveLine) -> Result<(), Error> {
-        match line.move_type {
-            MoveLineType::Join => {
-                if self.players.contains_key(&line.authorizer) {
-                    return Err(Error::Unautherized(line.authorizer));
-                }
-                let pos = self.get_pos();
-                if line.x.ok_or(Error::MalformedMove)? != pos.0
-                    || line.y.ok_or(Error::MalformedMove)? != pos.1
-                {
-                    Err(Error::OutOfRange(
-                        "Position".into(),
-                        format!("({}, {})", pos.0, pos.1),
-                    ))
-                } else {
-                    Ok(())
-                }
+    /// Lists, per tile, the living enemies of `player` that could shoot it right now, sorted by enemy.
+    /// Tiles nobody can shoot are left out.
+    #[must_use]
+    pub fn threat_map(&self, player: i32) -> HashMap<(u32, u32), Vec<Threat>> {
+        let mut map = HashMap::<_, Vec<_>>::new();
+        let mut enemies = self
+            .players
+            .values()
+            .filter(|p| {
+                p.user != player && p.alive() && p.can_afford(self.settings.costs.shoot).is_ok()
+            })
+            .collect::<Vec<_>>();
+        enemies.sort_by_key(|p| p.user);
+        for enemy in enemies {
+            let range = self.player_range(enemy);
+            let board = self.settings.board();
+            let tiles = board.neighbors(enemy.x, enemy.y, range, Topology::Flat);
+            for tile in tiles.chain([(enemy.x, enemy.y)]) {
+                map.entry(tile).or_default().push(Threat {
+                    enemy: enemy.user,
+                    damage: enemy.points.get(),
+                });
             }
-            MoveLineType::Drive => {
-                let x = line.x.ok_or(Error::MalformedMove)?;
-                let y = line.y.ok_or(Error::MalformedMove)?;
-                let player = self.get_player(line.authorizer)?;
-                player.is_alive(true)?;
-                player.has_points()?;
-                if self.board.contains_key(&(x, y)) {
-                    return Err(Error::NotFound("free tile".into()));
+        }
+        map
+    }
+    /// Writes the players, and with them the board, in a dense binary form for sending full states.
+    /// Players are sorted by user, each as varints followed by the players that have items, the position of the random
+    /// numbers of older games and a bit field holding the health and level of all players.
+    #[must_use]
+    pub fn pack_board(&self) -> Vec<u8> {
+        let mut players = self.players.values().collect::<Vec<_>>();
+        players.sort_by_key(|p| p.user);
+        let bits = |max: u32| u32::BITS - max.leading_zeros();
+        let health_bits = bits(players.iter().map(|p| p.health.get()).max().unwrap_or(0));
+        let level_bits = bits(players.iter().map(|p| p.level).max().unwrap_or(0));
+        let mut out = Vec::new();
+        write_varint(&mut out, players.len() as u64);
+        #[allow(clippy::cast_possible_truncation)]
+        out.extend([health_bits as u8, level_bits as u8]);
+        for p in &players {
+            write_id(&mut out, Some(p.user));
+            for value in [p.x, p.y, p.points.get()] {
+                write_varint(&mut out, u64::from(value));
+            }
+            write_varint(&mut out, p.last_action as u64);
+            write_varint(&mut out, p.died_round.map_or(0, |r| u64::from(r) + 1));
+            write_id(&mut out, p.last_hit_by);
+        }
+        items::write_inventories(&mut out, &players);
+        repair::write_disabled(&mut out, &players);
+        self.write_rng(&mut out);
+        let mut field = 0u64;
+        let mut length = 0;
+        for p in &players {
+            for (value, bits) in [(p.health.get(), health_bits), (p.level, level_bits)] {
+                field |= u64::from(value) << length;
+                length += bits;
+                while length >= 8 {
+                    #[allow(clippy::cast_possible_truncation)]
+                    out.push(field as u8);
+                    field >>= 8;
+                    length -= 8;
                 }
-                player.in_range(x, y, 1)?;
-                Ok(())
             }
+        }
+        if length > 0 {
+            #[allow(clippy::cast_possible_truncation)]
+            out.push(field as u8);
+        }
+        out
+    }
+    /// Replaces the players, the board and the position of the random numbers with the ones written by
+    /// `Game::pack_board`.
+    /// # Errors
+    /// If `data` is not a packed board, or places two tanks on a tile. The game is left unchanged.
+    pub fn unpack_board(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        let data = &mut data;
+        let count = read_varint(data)?;
+        let (&[health_bits, level_bits], rest) =
+            data.split_first_chunk().ok_or(Error::MalformedMove)?;
+        *data = rest;
+        if health_bits > 32 || level_bits > 32 {
+            return Err(Error::MalformedMove);
+        }
+        let mut players = Vec::new();
+        for _ in 0..count {
+            let user = read_id(data)?.ok_or(Error::MalformedMove)?;
+            let (x, y, points) = (read_u32(data)?, read_u32(data)?, read_u32(data)?);
+            let last_action =
+                usize::try_from(read_varint(data)?).map_err(|_| Error::MalformedMove)?;
+            let died_round = read_u32(data)?.checked_sub(1);
+            let last_hit_by = read_id(data)?;
+            players.push(Player {
+                user,
+                x,
+                y,
+                level: 0,
+                points: Resource(points),
+                health: Resource(0),
+                last_action,
+                died_round,
+                last_hit_by,
+                items: Vec::new(),
+                effects: Vec::new(),
+                disabled_round: None,
+            });
+        }
+        items::read_inventories(data, &mut players)?;
+        repair::read_disabled(data, &mut players)?;
+        let rand = self.read_rng(data)?;
+        let mut bytes = data.iter();
+        let mut field = 0u64;
+        let mut length = 0;
+        let mut read = |bits: u8| -> Result<u32, Error> {
+            while length < bits {
+                field |= u64::from(*bytes.next().ok_or(Error::MalformedMove)?) << length;
+                length += 8;
+            }
+            #[allow(clippy::cast_possible_truncation)]
+            let value = (field & ((1 << bits) - 1)) as u32;
+            field >>= bits;
+            length -= bits;
+            Ok(value)
+        };
+        for p in &mut players {
+            p.health = Resource(read(health_bits)?);
+            p.level = read(level_bits)?;
+        }
+        let mut board = HashMap::new();
+        for p in &players {
+            if board.insert((p.x, p.y), p.user).is_some() {
+                return Err(Error::Other("Two tanks on one tile.".into()));
+            }
+        }
+        self.players = players.into_iter().map(|p| (p.user, p)).collect();
+        self.board = board;
+        self.rand = rand;
+        Ok(())
+    }
+    /// Sums up `Game::transfers` per giver, receiver and kind, sorted in that order.
+    #[must_use]
+    pub fn flows(&self) -> Vec<Transfer> {
+        let mut flows = HashMap::<_, u32>::new();
+        for t in &self.transfers {
+            let flow = flows.entry((t.from, t.to, t.kind)).or_default();
+            *flow = flow.saturating_add(t.amount);
+        }
+        let mut flows = flows
+            .into_iter()
+            .map(|((from, to, kind), amount)| Transfer {
+                from,
+                to,
+                kind,
+                amount,
+            })
+            .collect::<Vec<_>>();
+        flows.sort_by_key(|t| (t.from, t.to, t.kind));
+        flows
+    }
+    /// Check if `user` can join the game.
+    /// Does nothing and returns `Result::Ok()` if the user can join.
+    /// # Errors
+    /// * If the user already joined. (`Error::Unautherized`)
+    /// * If the board is full, or from the third rules on the game. (`Error::OutOfRange`)
+    pub fn can_join(&self, user: i32) -> Result<(), Error> {
+        if self.players.contains_key(&user) {
+            return Err(Error::Unautherized(user));
+        }
+        // Older games never refused a join on a full board either, but no chain can have one as a join there finds no
+        // free tile to spawn at.
+        let tiles = u64::from(self.settings.width) * u64::from(self.settings.height);
+        let full = self.settings.rules_version >= 3
+            && usize::try_from(self.settings.max_players)
+                .map_or(true, |max| self.players.len() >= max);
+        if full || self.board.len() as u64 >= tiles {
+            return Err(Error::OutOfRange(
+                "Players".into(),
+                format!("< {}", self.settings.max_players),
+            ));
+        }
+        Ok(())
+    }
+    /// Check if a `MoveLine` is valid under the rules version of the game.
+    /// # Errors
+    /// * If the `line` is not valid.
+    /// * If the `line` was made for another rules version, or the engine can't play the version of the game.
+    pub fn check(&self, line: &MoveLine) -> Result<(), Error> {
+        let version = self.settings.rules_version;
+        if line.rules_version.is_some_and(|x| x != version) {
+            return Err(Error::OutOfRange(
+                "Rules version".into(),
+                format!("== {version}"),
+            ));
+        }
+        match version {
+            1..=4 => self.check_v1(line),
+            _ => Err(Error::OutOfRange(
+                "Rules version".into(),
+                format!("in {RULES_VERSIONS:?}"),
+            )),
+        }
+    }
+    /// Checks a line under the first rules, which the second only changes the random numbers of, the third only
+    /// refuses joins past `Settings::max_players` in (see `Game::can_join`) and the fourth only refuses everything but
+    /// votes once the game is over in.
+    fn check_v1(&self, line: &MoveLine) -> Result<(), Error> {
+        let voting = matches!(
+            line.move_type,
+            MoveLineType::Vote | MoveLineType::HandleVotes
+        );
+        if self.settings.rules_version >= 4 && !voting && self.status().is_over() {
+            return Err(Error::Other("The game is over.".into()));
+        }
+        self.check_pause(line)?;
+        if line.move_type.spends_points() && self.actions_left(line.authorizer) == Some(0) {
+            return Err(Error::OutOfRange(
+                "Actions this round".into(),
+                format!("<= {}", self.settings.actions_per_round.unwrap_or_default()),
+            ));
+        }
+        match line.move_type {
+            MoveLineType::Join | MoveLineType::JoinRandom => self.check_join(line),
+            MoveLineType::Drive => self.check_drive(line),
             MoveLineType::Shoot | MoveLineType::Gift => {
                 let target = line.target.ok_or(Error::MalformedMove)?;
                 let t = self.get_player(target)?;
                 let p = self.get_player(line.authorizer)?;
                 t.is_alive(true)?;
                 p.is_alive(true)?;
-                p.has_points()?;
-                p.in_range(t.x, t.y, self.settings.range.get_range(p.level))?;
+                if line.move_type == MoveLineType::Shoot && t.effects.contains(&Item::Smoke) {
+                    return Err(Error::Other("The target is hidden by smoke.".into()));
+                }
+                p.can_afford(if line.move_type == MoveLineType::Shoot {
+                    self.settings.costs.shoot
+                } else {
+                    self.settings.costs.gift
+                })?;
+                p.in_range(t.x, t.y, self.player_range(p))?;
+                if line.move_type == MoveLineType::Gift {
+                    self.check_gift_cooldown(line.authorizer, target)?;
+                }
                 Ok(())
             }
+            MoveLineType::Repair => self.check_repair(line),
+            MoveLineType::Lend => self.check_lend(line),
             MoveLineType::Vote => {
                 let target = line.target.ok_or(Error::MalformedMove)?;
                 let t = self.get_player(target)?;
@@ -382,7 +1625,13 @@ impl Game {
                 p.is_alive(false)?;
                 Ok(())
             }
-            MoveLineType::HandleVotes => Ok(()),
+            MoveLineType::HandleVotes => match line.time {
+                Some(time) if time < self.last_vote => Err(Error::OutOfRange(
+                    "Time".into(),
+                    format!(">= {}", self.last_vote),
+                )),
+                _ => Ok(()),
+            },
             MoveLineType::Upgrade => {
                 let p = self.get_player(line.authorizer)?;
                 p.is_alive(true)?;
@@ -390,88 +1639,512 @@ impl Game {
                 p.has_points()?;
                 Ok(())
             }
+            MoveLineType::Surrender => self.get_player(line.authorizer)?.is_alive(true),
+            MoveLineType::Bounty => {
+                let x = line.x.ok_or(Error::MalformedMove)?;
+                let y = line.y.ok_or(Error::MalformedMove)?;
+                let amount = line.amount.ok_or(Error::MalformedMove)?;
+                let p = self.get_player(line.authorizer)?;
+                p.is_alive(true)?;
+                if amount == 0 {
+                    return Err(Error::OutOfRange("Amount".into(), "> 0".into()));
+                }
+                if p.points.get() < amount {
+                    return Err(Error::OutOfRange("Points".into(), format!(">= {amount}")));
+                }
+                if x >= self.settings.width || y >= self.settings.height {
+                    return Err(Error::OutOfRange(
+                        "Position".into(),
+                        format!("< ({}, {})", self.settings.width, self.settings.height),
+                    ));
+                }
+                if let Some(&user) = self.board.get(&(x, y)) {
+                    return Err(Error::TileOccupied(user));
+                }
+                Ok(())
+            }
+            MoveLineType::Poll | MoveLineType::PollVote | MoveLineType::ClosePoll => {
+                self.check_poll(line)
+            }
+            MoveLineType::BuyItem | MoveLineType::UseItem => self.check_item(line),
+            MoveLineType::Pause | MoveLineType::Resume => Ok(()),
         }
     }
-    fn handle_unchecked(&mut self, line: MoveLine) {
+    /// Check if a poll `MoveLine` is valid. Only living players take part in polls.
+    /// # Errors
+    /// If the `line` is not valid.
+    fn check_poll(&self, line: &MoveLine) -> Result<(), Error> {
+        self.get_player(line.authorizer)?.is_alive(true)?;
         match line.move_type {
-            MoveLineType::Join => {
-                let (x, y) = self.get_pos_mut();
-                self.players.insert(
+            MoveLineType::Poll => {
+                Poll::from_text(
                     line.authorizer,
-                    Player {
-                        user: line.authorizer,
-                        health: self.settings.health,
-                        level: 0,
-                        points: 1,
-                        x,
-                        y,
-                    },
+                    line.text.as_ref().ok_or(Error::MalformedMove)?,
+                )?;
+            }
+            MoveLineType::PollVote => {
+                let poll = self.get_poll(line)?;
+                let option = line.amount.ok_or(Error::MalformedMove)?;
+                if !poll.open {
+                    return Err(Error::NotFound("open poll".into()));
+                }
+                if usize::try_from(option).map_or(true, |x| x >= poll.options.len()) {
+                    return Err(Error::OutOfRange(
+                        "Option".into(),
+                        format!("< {}", poll.options.len()),
+                    ));
+                }
+            }
+            MoveLineType::ClosePoll => {
+                let poll = self.get_poll(line)?;
+                if poll.creator != line.authorizer {
+                    return Err(Error::Unautherized(line.authorizer));
+                }
+                if !poll.open {
+                    return Err(Error::NotFound("open poll".into()));
+                }
+            }
+            _ => return Err(Error::MalformedMove),
+        }
+        Ok(())
+    }
+    /// Distributes the points of a round and starts a new one.
+    /// # Errors
+    /// If a player's points would overflow.
+    fn handle_votes(&mut self) -> Result<(), Error> {
+        // Points can overflow until the loans are settled, so what changes until then is put back if they do.
+        let saved = (
+            self.players.clone(),
+            self.votes.clone(),
+            self.loans.clone(),
+            self.round_start,
+        );
+        let (events, transfers) = (self.events.len(), self.transfers.len());
+        let (cast, elected, points) = match self.score_round() {
+            Ok(scored) => scored,
+            Err(e) => {
+                (self.players, self.votes, self.loans, self.round_start) = saved;
+                self.events.truncate(events);
+                self.transfers.truncate(transfers);
+                return Err(e);
+            }
+        };
+        self.rounds.push(self.round_summary(cast, elected, points));
+        let board = self.settings.board();
+        let holder = self
+            .board
+            .get(&(board.width / 2, board.height / 2))
+            .copied()
+            .filter(|user| self.players.get(user).is_some_and(Player::alive));
+        self.flag = match (holder, self.flag) {
+            (Some(holder), Some((held, rounds))) if held == holder => Some((holder, rounds + 1)),
+            (Some(holder), _) => Some((holder, 1)),
+            (None, _) => None,
+        };
+        self.round += 1;
+        self.emit(GameEvent::RoundStarted { round: self.round });
+        self.mutator = None;
+        if !self.settings.mutators.is_empty() {
+            let index = self.rand.next_u64() % self.settings.mutators.len() as u64;
+            #[allow(clippy::cast_possible_truncation)]
+            let mutator = self.settings.mutators[index as usize];
+            self.mutator = Some(mutator);
+            self.mutator_events.push((self.round, mutator));
+        }
+        self.drop_supplies();
+        Ok(())
+    }
+    /// Hands out the points of a round and destroys the tanks that weren't repaired. Returns the votes that were cast,
+    /// the elected tanks and the points every tank got.
+    /// # Errors
+    /// If the points of a tank would overflow, with some of them handed out already.
+    fn score_round(&mut self) -> Result<(Vec<CastVote>, Vec<i32>, u32), Error> {
+        if self.settings.point_decay {
+            let round_start = self.round_start;
+            self.players
+                .values_mut()
+                .filter(|p| p.last_action < round_start)
+                .for_each(|p| p.points.saturating_take(1));
+        }
+        self.round_start = self.lines.len();
+        let points = if self.mutator == Some(Mutator::DoublePoints) {
+            2
+        } else {
+            1
+        };
+        for player in self.players.values_mut() {
+            player.points.give(points, "Points")?;
+        }
+        let tally = self.tally();
+        let votes = core::mem::take(&mut self.votes);
+        let mut cast = votes
+            .iter()
+            .map(|(&voter, &target)| CastVote { voter, target })
+            .collect::<Vec<_>>();
+        cast.sort_by_key(|v| v.voter);
+        let mut elected = Vec::new();
+        for candidate in tally.candidates.into_iter().filter(|c| c.elected) {
+            self.get_player_mut(candidate.target)?
+                .points
+                .give(1, "Points")?;
+            self.emit(GameEvent::PointsAwarded {
+                user: candidate.target,
+                amount: 1,
+            });
+            elected.push(candidate.target);
+        }
+        elected.sort_unstable();
+        self.destroy_disabled()?;
+        self.settle_loans()?;
+        Ok((cast, elected, points))
+    }
+    /// Summarizes the current round as its votes are handled.
+    fn round_summary(&self, votes: Vec<CastVote>, elected: Vec<i32>, points: u32) -> RoundSummary {
+        let mut kills = self
+            .players
+            .values()
+            .filter(|p| p.died_round == Some(self.round))
+            .map(|p| p.user)
+            .collect::<Vec<_>>();
+        kills.sort_unstable();
+        let mut standings = self
+            .players
+            .values()
+            .map(|p| Standing {
+                user: p.user,
+                alive: p.alive(),
+                health: p.health.get(),
+                points: p.points.get(),
+                level: p.level,
+            })
+            .collect::<Vec<_>>();
+        standings.sort_by_key(|s| (core::cmp::Reverse((s.alive, s.health, s.points)), s.user));
+        RoundSummary {
+            round: self.round,
+            votes,
+            elected,
+            points,
+            kills,
+            standings,
+        }
+    }
+    /// Whether the game is over and who won, following `Settings::win_condition`.
+    #[must_use]
+    pub fn status(&self) -> GameStatus {
+        // Checked before every move, so only a won `SurviveRounds` allocates.
+        let alive = || self.players.values().filter(|p| p.alive());
+        let won = match self.settings.win_condition {
+            WinCondition::PointsAtLeast(n) => alive()
+                .filter(|p| p.points.get() >= n)
+                .max_by_key(|p| (p.points, core::cmp::Reverse(p.user)))
+                .map(|p| vec![p.user]),
+            WinCondition::SurviveRounds(n) if self.round >= n => {
+                let mut users = alive().map(|p| p.user).collect::<Vec<_>>();
+                users.sort_unstable();
+                Some(users).filter(|users| !users.is_empty())
+            }
+            WinCondition::FlagHeld(n) => self
+                .flag
+                .filter(|&(_, rounds)| rounds >= n)
+                .map(|(holder, _)| vec![holder]),
+            WinCondition::LastAlive | WinCondition::SurviveRounds(_) => None,
+        };
+        if let Some(won) = won {
+            return GameStatus::Won(won);
+        }
+        if self.players.len() < 2 {
+            return GameStatus::Waiting;
+        }
+        // Disabled tanks may still be repaired, the game goes on until they are destroyed.
+        if self.players.values().any(|p| p.disabled_round.is_some()) {
+            return GameStatus::Running;
+        }
+        let mut alive = alive();
+        match (alive.next(), alive.next()) {
+            (None, _) => GameStatus::Draw,
+            (Some(winner), None) => GameStatus::Won(vec![winner.user]),
+            _ => GameStatus::Running,
+        }
+    }
+    /// The place of every player, best first: the winners, then the tanks still alive, then the dead tanks, the last
+    /// to die first. Places start at 1 and players that tie share one, like `1, 2, 2, 4`.
+    #[must_use]
+    pub fn standings(&self) -> Vec<(i32, u32)> {
+        let winners = match self.status() {
+            GameStatus::Won(winners) => winners,
+            GameStatus::Draw | GameStatus::Waiting | GameStatus::Running => Vec::new(),
+        };
+        let mut ranks = self
+            .players
+            .values()
+            .map(|p| {
+                let rank = (
+                    !winners.contains(&p.user),
+                    p.died_round.is_some(),
+                    Reverse(p.died_round),
                 );
-                self.board.insert((x, y), line.authorizer);
+                (rank, p.user)
+            })
+            .collect::<Vec<_>>();
+        ranks.sort_unstable();
+        let mut place = 0;
+        ranks
+            .iter()
+            .enumerate()
+            .map(|(i, (rank, user))| {
+                if i == 0 || ranks[i - 1].0 != *rank {
+                    place = u32::try_from(i).unwrap_or(u32::MAX - 1) + 1;
+                }
+                (*user, place)
+            })
+            .collect()
+    }
+    /// How many more moves that cost points `user` may make this round, `None` if there is no limit.
+    #[must_use]
+    pub fn actions_left(&self, user: i32) -> Option<u32> {
+        let limit = self.settings.actions_per_round?;
+        let made = self.lines[self.round_start..]
+            .iter()
+            .filter(|l| l.authorizer == user && l.move_type.spends_points())
+            .count();
+        Some(limit.saturating_sub(u32::try_from(made).unwrap_or(u32::MAX)))
+    }
+    /// Checks that `giver` waited `Settings::gift_cooldown` rounds since they last gifted `receiver`.
+    fn check_gift_cooldown(&self, giver: i32, receiver: i32) -> Result<(), Error> {
+        match self.gifts.get(&(giver, receiver)) {
+            Some(&round) if self.round < round.saturating_add(self.settings.gift_cooldown) => {
+                Err(Error::OutOfRange(
+                    "Rounds since the last gift to this tank".into(),
+                    format!(">= {}", self.settings.gift_cooldown),
+                ))
             }
-            MoveLineType::Drive => {
-                let x = line.x.ok_or(Error::MalformedMove).unwrap();
-                let y = line.y.ok_or(Error::MalformedMove).unwrap();
-                let player = self.players.get_mut(&line.authorizer).unwrap();
-                player.points -= 1;
-                self.board.remove(&(player.x, player.y));
-                player.x = x;
-                player.y = y;
-                self.board.insert((x, y), line.authorizer);
+            _ => Ok(()),
+        }
+    }
+    /// The range of a tank at `level` this round.
+    #[must_use]
+    pub fn range(&self, level: u32) -> u32 {
+        let range = self.settings.range.get_range(level);
+        match self.mutator {
+            Some(Mutator::Fog) => range.saturating_sub(1).max(1),
+            Some(Mutator::ExtraRange) => range.saturating_add(1),
+            _ => range,
+        }
+    }
+    /// Marks `victim` as died this round and hands out their points according to `Settings::loot_mode`.
+    /// # Errors
+    /// If a player's points would overflow.
+    pub(crate) fn kill(&mut self, victim: i32, killer: Option<i32>) -> Result<(), Error> {
+        let shares = self.loot(victim, killer)?;
+        let round = self.round;
+        self.get_player_mut(victim)?.died_round = Some(round);
+        self.emit(GameEvent::PlayerKilled {
+            user: victim,
+            killer,
+        });
+        let player = self.get_player_mut(victim)?;
+        player.disabled_round = None;
+        player.items.clear();
+        player.effects.clear();
+        player.points.take_all();
+        for (user, amount) in shares {
+            self.get_player_mut(user)?.points.give(amount, "Points")?;
+            if amount > 0 {
+                self.transfers.push(Transfer {
+                    from: victim,
+                    to: user,
+                    kind: TransferKind::Loot,
+                    amount,
+                });
+            }
+        }
+        Ok(())
+    }
+    /// Who gets what of the points of `victim` when it is killed, according to `Settings::loot_mode`.
+    /// # Errors
+    /// If a player's points would overflow, before anything changes.
+    pub(crate) fn loot(&self, victim: i32, killer: Option<i32>) -> Result<Vec<(i32, u32)>, Error> {
+        let points = self.get_player(victim)?.points.get();
+        let shares = match self.settings.loot_mode {
+            LootMode::Killer => killer
+                .filter(|k| self.players.contains_key(k))
+                .map(|k| (k, points))
+                .into_iter()
+                .collect(),
+            LootMode::Split => {
+                let living = self
+                    .players
+                    .values()
+                    .filter(|p| p.user != victim && p.alive())
+                    .map(|p| p.user)
+                    .collect::<Vec<_>>();
+                let share = u32::try_from(living.len())
+                    .ok()
+                    .and_then(|n| points.checked_div(n))
+                    .unwrap_or_default();
+                living.into_iter().map(|user| (user, share)).collect()
+            }
+            LootMode::Destroy => Vec::new(),
+        };
+        for &(user, amount) in &shares {
+            let mut points = self.get_player(user)?.points;
+            points.give(amount, "Points")?;
+        }
+        Ok(shares)
+    }
+    /// The weight of a vote by `voter`, which grows (or shrinks) with `Settings::jury_weight` for every round they have been dead.
+    pub(crate) fn vote_weight(&self, voter: i32) -> u32 {
+        let dead_for = self
+            .players
+            .get(&voter)
+            .and_then(|p| p.died_round)
+            .map_or(0, |died| self.round - died);
+        let weight = i64::from(self.settings.jury_weight) * i64::from(dead_for) + 1;
+        u32::try_from(weight.max(0)).unwrap_or(u32::MAX)
+    }
+    /// Applies a poll `line` that passed `Game::check`.
+    /// # Errors
+    /// If the `line` is not a poll move.
+    fn handle_poll(&mut self, line: &MoveLine) -> Result<(), Error> {
+        match line.move_type {
+            MoveLineType::Poll => {
+                let text = line.text.as_ref().ok_or(Error::MalformedMove)?;
+                self.polls.push(Poll::from_text(line.authorizer, text)?);
+            }
+            MoveLineType::PollVote => {
+                let option = line.amount.ok_or(Error::MalformedMove)?;
+                let poll = self.get_poll_mut(line)?;
+                poll.votes.insert(line.authorizer, option);
+            }
+            MoveLineType::ClosePoll => self.get_poll_mut(line)?.open = false,
+            _ => return Err(Error::MalformedMove),
+        }
+        Ok(())
+    }
+    /// Applies a `line` that passed `Game::check`.
+    /// # Errors
+    /// If applying the line would overflow or underflow a resource, leaving the game as it was.
+    fn handle_unchecked(&mut self, line: MoveLine) -> Result<(), Error> {
+        match self.settings.rules_version {
+            1 => self.handle_v1(line),
+            2..=4 => {
+                let next = self.next_rng();
+                let rand = core::mem::replace(&mut self.rand, next);
+                let handled = self.handle_v1(line);
+                if handled.is_err() {
+                    self.rand = rand;
+                }
+                handled
             }
+            _ => Err(Error::OutOfRange(
+                "Rules version".into(),
+                format!("in {RULES_VERSIONS:?}"),
+            )),
+        }
+    }
+    /// Applies a line under the first rules.
+    fn handle_v1(&mut self, line: MoveLine) -> Result<(), Error> {
+        match line.move_type {
+            MoveLineType::Join | MoveLineType::JoinRandom => self.handle_join(&line),
+            MoveLineType::Drive => self.handle_drive(&line)?,
             MoveLineType::Shoot => {
-                let target = line.target.ok_or(Error::MalformedMove).unwrap();
-                let target = self.players.get_mut(&target).unwrap();
-                target.health -= 1;
-                let add = (target.health == 0)
-                    .then(|| std::mem::replace(&mut target.points, 0))
-                    .unwrap_or(0);
-                let player = self.players.get_mut(&line.authorizer).unwrap();
-                player.points -= 1;
-                player.points += add;
+                let target = line.target.ok_or(Error::MalformedMove)?;
+                let cost = self.settings.costs.shoot;
+                self.get_player_mut(line.authorizer)?
+                    .points
+                    .take(cost, "Points")?;
+                if let Err(e) = self.hit(target, line.authorizer) {
+                    // `hit` fails before changing anything, so giving the cost back undoes the shot.
+                    self.get_player_mut(line.authorizer)?
+                        .points
+                        .give(cost, "Points")?;
+                    return Err(e);
+                }
+            }
+            MoveLineType::Repair => self.handle_repair(&line)?,
+            MoveLineType::Lend => self.handle_lend(&line)?,
+            MoveLineType::Pause | MoveLineType::Resume => self.handle_pause(&line)?,
+            MoveLineType::Surrender => {
+                let player = self.get_player_mut(line.authorizer)?;
+                player.health.take_all();
+                let killer = player.last_hit_by;
+                self.kill(line.authorizer, killer)?;
             }
             MoveLineType::Gift => {
-                let target = line.target.unwrap();
-                let player = self.players.get_mut(&line.authorizer).unwrap();
-                player.points -= 1;
-                let target = self.players.get_mut(&target).unwrap();
-                target.points += 1;
+                let target = line.target.ok_or(Error::MalformedMove)?;
+                let amount = self.settings.costs.gift;
+                // Both sides are worked out before either changes, so a gift that would overflow changes nothing.
+                let mut left = self.get_player(line.authorizer)?.points;
+                left.take(amount, "Points")?;
+                let mut received = self.get_player(target)?.points;
+                received.give(amount, "Points")?;
+                if target != line.authorizer {
+                    self.get_player_mut(line.authorizer)?.points = left;
+                    self.get_player_mut(target)?.points = received;
+                }
+                self.transfers.push(Transfer {
+                    from: line.authorizer,
+                    to: target,
+                    kind: TransferKind::Gift,
+                    amount,
+                });
+                self.gifts.insert((line.authorizer, target), self.round);
             }
             MoveLineType::Vote => {
-                let target = line.target.unwrap();
+                let target = line.target.ok_or(Error::MalformedMove)?;
                 self.votes.insert(line.authorizer, target);
             }
             MoveLineType::HandleVotes => {
-                self.players.iter_mut().for_each(|(_, p)| p.points += 1);
-                let mut votes = HashMap::new();
-                std::mem::swap(&mut votes, &mut self.votes);
-                for player in votes
-                    .into_iter()
-                    .fold(HashMap::<i32, u32>::new(), |mut x, (_, y)| {
-                        *x.entry(y).or_default() += 1;
-                        x
-                    })
-                    .into_iter()
-                    .filter(|(_, v)| v >= &self.settings.vote_threshold)
-                    .map(|(x, _)| x)
-                {
-                    if let Some(player) = self
-                        .players
-                        .get_mut(&player)
-                        .and_then(|player| player.is_alive(true).is_ok().then_some(player))
-                    {
-                        player.points += 1;
-                    }
+                self.handle_votes()?;
+                if let Some(time) = line.time {
+                    self.last_vote = time;
                 }
             }
+            MoveLineType::Bounty => {
+                let x = line.x.ok_or(Error::MalformedMove)?;
+                let y = line.y.ok_or(Error::MalformedMove)?;
+                let amount = line.amount.ok_or(Error::MalformedMove)?;
+                let bounty = self
+                    .bounties
+                    .get(&(x, y))
+                    .copied()
+                    .unwrap_or_default()
+                    .checked_add(amount)
+                    .ok_or_else(|| {
+                        Error::OutOfRange("Bounty".into(), format!("<= {}", u32::MAX))
+                    })?;
+                self.get_player_mut(line.authorizer)?
+                    .points
+                    .take(amount, "Points")?;
+                self.bounties.insert((x, y), bounty);
+            }
             MoveLineType::Upgrade => {
-                self.players.get_mut(&line.authorizer).unwrap().level += 1;
+                let player = self.get_player_mut(line.authorizer)?;
+                player.level = player
+                    .level
+                    .checked_add(1)
+                    .ok_or_else(|| Error::OutOfRange("Level".into(), format!("< {}", u32::MAX)))?;
+                let level = player.level;
+                self.emit(GameEvent::PlayerUpgraded {
+                    user: line.authorizer,
+                    level,
+                });
+            }
+            MoveLineType::Poll | MoveLineType::PollVote | MoveLineType::ClosePoll => {
+                self.handle_poll(&line)?;
+            }
+            MoveLineType::BuyItem | MoveLineType::UseItem => self.handle_item(&line)?,
+        }
+        if line.move_type != MoveLineType::HandleVotes {
+            if let Some(player) = self.players.get_mut(&line.authorizer) {
+                player.last_action = self.lines.len();
             }
         }
         self.lines.push(line);
+        Ok(())
     }
 }
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Settings {
     pub seed: u64,
     pub width: u32,
@@ -479,8 +2152,380 @@ pub struct Settings {
     pub health: u32,
     pub max_level: i32,
     pub max_players: i32,
-    pub vote_threshold: u32,
+    /// The votes a tank needs for an extra point, given the size of the jury.
+    pub threshold_mode: ThresholdMode,
     pub range: LevelRangeMap,
+    pub point_decay: bool,
+    pub jury_weight: i32,
+    pub loot_mode: LootMode,
+    pub drive_length: u32,
+    pub rules_version: u32,
+    /// The mutators one is drawn from every round, using the game RNG.
+    pub mutators: Vec<Mutator>,
+    /// What moves cost in points.
+    pub costs: Costs,
+    /// How the game is won, see `Game::status`.
+    pub win_condition: WinCondition,
+    pub starting_points: u32,
+    pub starting_level: u32,
+    /// The max amount of moves that cost points a player may make between two `HandleVotes`, see `Game::actions_left`.
+    pub actions_per_round: Option<u32>,
+    /// The items tanks can buy, and that are dropped on the board when the votes are handled.
+    pub items: Vec<Item>,
+    /// Whether a tank shot to 0 health is disabled instead of destroyed. Other tanks in range can `Repair` it until
+    /// the votes of the next round are handled, after which it is destroyed and looted.
+    pub two_phase_kills: bool,
+    /// Whether tanks join at the free tile furthest from the other tanks instead of a random one, so nobody joins next
+    /// to a tank that can shoot them right away. Ties are broken by the seed.
+    pub symmetric_spawns: bool,
+    /// The rounds a player waits after a gift before gifting the same player again, so points can't be funneled to a
+    /// single tank every round. With 1 a player gifts another at most once a round, with 0 there is no wait.
+    pub gift_cooldown: u32,
+}
+/// What moves cost in points. Bounties cost the points put on the tile, upgrades are free but need a point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Costs {
+    /// The cost of every tile driven.
+    pub drive: u32,
+    pub shoot: u32,
+    /// The cost of a gift, which is also what the target gets.
+    pub gift: u32,
+    /// The cost of buying an item.
+    pub item: u32,
+    /// The cost of repairing a disabled tank.
+    pub repair: u32,
+}
+impl Default for Costs {
+    fn default() -> Self {
+        Costs {
+            drive: 1,
+            shoot: 1,
+            gift: 1,
+            item: 1,
+            repair: 1,
+        }
+    }
+}
+impl Costs {
+    /// The cost of every move type that costs points, in words.
+    #[must_use]
+    pub fn describe(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Drive", format!("{} per tile", self.drive)),
+            ("Shoot", self.shoot.to_string()),
+            ("Gift", self.gift.to_string()),
+            ("Item", self.item.to_string()),
+            ("Repair", self.repair.to_string()),
+            ("Bounty", "the bounty".into()),
+            ("Upgrade", "free, needs a point".into()),
+        ]
+    }
+}
+impl Settings {
+    #[must_use]
+    pub fn board(&self) -> Board {
+        Board {
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+/// Who gets the points of a player that dies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LootMode {
+    /// The killer takes all points. A surrendering player's points go to the last player that shot them.
+    Killer,
+    /// The points are split evenly among the other living players, the remainder is lost.
+    Split,
+    /// The points are lost.
+    Destroy,
+}
+impl LootMode {
+    /// Describes the mode for players.
+    #[must_use]
+    pub fn describe(&self) -> &'static str {
+        match self {
+            LootMode::Killer => "The killer takes the points of a destroyed tank.",
+            LootMode::Split => "The points of a destroyed tank are split among the living.",
+            LootMode::Destroy => "The points of a destroyed tank are lost.",
+        }
+    }
+}
+impl Display for LootMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LootMode::Killer => write!(f, "killer"),
+            LootMode::Split => write!(f, "split"),
+            LootMode::Destroy => write!(f, "destroy"),
+        }
+    }
+}
+impl core::str::FromStr for LootMode {
+    type Err = Error;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "killer" => Ok(LootMode::Killer),
+            "split" => Ok(LootMode::Split),
+            "destroy" => Ok(LootMode::Destroy),
+            _ => Err(Error::Other("Malformed LootMode.".into())),
+        }
+    }
+}
+/// A twist to the rules of a single round, see `Settings::mutators`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutator {
+    /// Handling the votes at the end of the round gives two points instead of one.
+    DoublePoints,
+    /// Every range is one shorter, but at least one.
+    Fog,
+    /// Every range is one longer.
+    ExtraRange,
+}
+impl Mutator {
+    pub const ALL: [Mutator; 3] = [Mutator::DoublePoints, Mutator::Fog, Mutator::ExtraRange];
+    /// Reads a comma separated list, an empty string is an empty list.
+    /// # Errors
+    /// If a mutator is unknown.
+    pub fn parse_list(text: &str) -> Result<Vec<Mutator>, Error> {
+        text.split(',')
+            .filter(|x| !x.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+    /// The announcement of the mutator.
+    #[must_use]
+    pub fn describe(self) -> &'static str {
+        match self {
+            Mutator::DoublePoints => "Double points: the end of this round gives two points.",
+            Mutator::Fog => "The fog thickens: every range is one shorter this round.",
+            Mutator::ExtraRange => "Clear skies: every range is one longer this round.",
+        }
+    }
+}
+impl Display for Mutator {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Mutator::DoublePoints => write!(f, "double_points"),
+            Mutator::Fog => write!(f, "fog"),
+            Mutator::ExtraRange => write!(f, "extra_range"),
+        }
+    }
+}
+impl core::str::FromStr for Mutator {
+    type Err = Error;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "double_points" => Ok(Mutator::DoublePoints),
+            "fog" => Ok(Mutator::Fog),
+            "extra_range" => Ok(Mutator::ExtraRange),
+            _ => Err(Error::Other("Malformed Mutator.".into())),
+        }
+    }
+}
+/// Named sets of rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// The rules of the original game, also used by `Settings::default`.
+    Classic,
+}
+impl Preset {
+    pub const ALL: [Preset; 1] = [Preset::Classic];
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Classic => "Classic",
+        }
+    }
+    #[must_use]
+    pub fn settings(self) -> Settings {
+        match self {
+            Preset::Classic => Settings::default(),
+        }
+    }
+}
+/// A rule of a game in words, see `Settings::describe`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub text: String,
+    /// Whether the rule differs from the Classic preset.
+    pub deviates: bool,
+}
+impl Settings {
+    /// The range of each level a tank can reach.
+    fn ranges(&self) -> Vec<u32> {
+        (0..=u32::try_from(self.max_level).unwrap_or_default())
+            .map_while(|level| match &self.range {
+                LevelRangeMap::Array(a) if a.len() <= level as usize => None,
+                range => Some(range.get_range(level)),
+            })
+            .collect()
+    }
+    /// Describes the rules of the game, marking the ones that differ from the Classic preset.
+    #[must_use]
+    pub fn describe(&self) -> Vec<Rule> {
+        let classic = Preset::Classic.settings();
+        let rule = |text: String, deviates: bool| Rule { text, deviates };
+        let mutators = self
+            .mutators
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let items = self
+            .items
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let start = match self.starting_points {
+            1 => "1 point".into(),
+            points => format!("{points} points"),
+        };
+        let mut rules = self.board_rules(&classic);
+        rules.extend(self.point_rules(&classic));
+        rules.extend([
+            rule(
+                if self.drive_length == 1 {
+                    "Tanks drive one tile per move.".into()
+                } else {
+                    format!(
+                        "A drive can cover up to {} tiles, costing a point per tile.",
+                        self.drive_length
+                    )
+                },
+                self.drive_length != classic.drive_length,
+            ),
+            rule(
+                if self.mutators.is_empty() {
+                    "Every round has the same rules.".into()
+                } else {
+                    format!("Every round one of these applies: {mutators}.")
+                },
+                self.mutators != classic.mutators,
+            ),
+            rule(
+                format!("{}.", self.win_condition.describe()),
+                self.win_condition != classic.win_condition,
+            ),
+            rule(
+                format!("Tanks join at level {} with {start}.", self.starting_level),
+                (self.starting_level, self.starting_points)
+                    != (classic.starting_level, classic.starting_points),
+            ),
+            rule(
+                match self.actions_per_round {
+                    None => "Tanks can spend their points on as many moves as they like.".into(),
+                    Some(1) => "Tanks make at most 1 move that costs points per round.".into(),
+                    Some(n) => format!("Tanks make at most {n} moves that cost points per round."),
+                },
+                self.actions_per_round != classic.actions_per_round,
+            ),
+            rule(
+                if self.items.is_empty() {
+                    "There are no items.".into()
+                } else {
+                    format!("Tanks can buy and pick up these items: {items}.")
+                },
+                self.items != classic.items,
+            ),
+        ]);
+        rules
+    }
+    /// The rules about the board and the tanks on it.
+    fn board_rules(&self, classic: &Settings) -> Vec<Rule> {
+        let rule = |text: String, deviates: bool| Rule { text, deviates };
+        let ranges = self
+            .ranges()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        vec![
+            rule(
+                format!("The board is {}x{} tiles.", self.width, self.height),
+                (self.width, self.height) != (classic.width, classic.height),
+            ),
+            rule(
+                format!("Up to {} players can join.", self.max_players),
+                self.max_players != classic.max_players,
+            ),
+            rule(
+                if self.symmetric_spawns {
+                    "Tanks join as far as possible from the other tanks.".into()
+                } else {
+                    "Tanks join at a random free tile.".into()
+                },
+                self.symmetric_spawns != classic.symmetric_spawns,
+            ),
+            rule(
+                if self.health == 1 {
+                    "Tanks have 1 health, a single shot kills.".into()
+                } else {
+                    format!("Tanks have {} health.", self.health)
+                },
+                self.health != classic.health,
+            ),
+            rule(
+                format!(
+                    "Tanks can upgrade to level {}, with a range of {ranges} per level.",
+                    self.max_level
+                ),
+                self.max_level != classic.max_level || self.ranges() != classic.ranges(),
+            ),
+        ]
+    }
+    /// The rules about how tanks get and lose points.
+    fn point_rules(&self, classic: &Settings) -> Vec<Rule> {
+        let rule = |text: String, deviates: bool| Rule { text, deviates };
+        vec![
+            rule(
+                format!(
+                    "A tank with {} or more votes gets an extra point each round.",
+                    self.threshold_mode.describe()
+                ),
+                self.threshold_mode != classic.threshold_mode,
+            ),
+            rule(
+                if self.point_decay {
+                    "Tanks that do nothing during a round lose a point.".into()
+                } else {
+                    "Points never decay.".into()
+                },
+                self.point_decay != classic.point_decay,
+            ),
+            rule(
+                if self.jury_weight == 0 {
+                    "All votes weigh the same.".into()
+                } else {
+                    format!(
+                        "Votes of dead tanks weigh {:+} for every round they have been dead.",
+                        self.jury_weight
+                    )
+                },
+                self.jury_weight != classic.jury_weight,
+            ),
+            rule(
+                if self.two_phase_kills {
+                    "A tank shot to 0 health is disabled for a round, in which others can repair it, before it is destroyed and looted.".into()
+                } else {
+                    "A tank shot to 0 health is destroyed.".into()
+                },
+                self.two_phase_kills != classic.two_phase_kills,
+            ),
+            rule(
+                match self.gift_cooldown {
+                    0 => "Tanks can gift points as often as they like.".into(),
+                    1 => "A tank can gift another at most once per round.".into(),
+                    n => format!("A tank can gift another at most once every {n} rounds."),
+                },
+                self.gift_cooldown != classic.gift_cooldown,
+            ),
+            rule(
+                self.loot_mode.describe().into(),
+                self.loot_mode != classic.loot_mode,
+            ),
+        ]
+    }
 }
 impl Default for Settings {
     fn default() -> Self {
@@ -492,81 +2537,418 @@ impl Default for Settings {
             range: LevelRangeMap::Linear,
             health: 3,
             max_players: 10,
-            vote_threshold: 3,
+            threshold_mode: ThresholdMode::Fixed(3),
+            point_decay: false,
+            jury_weight: 0,
+            loot_mode: LootMode::Killer,
+            drive_length: 1,
+            rules_version: RULES_VERSION,
+            mutators: Vec::new(),
+            costs: Costs::default(),
+            win_condition: WinCondition::LastAlive,
+            starting_points: 1,
+            starting_level: 0,
+            actions_per_round: None,
+            items: Vec::new(),
+            two_phase_kills: false,
+            symmetric_spawns: false,
+            gift_cooldown: 0,
         }
     }
 }
+/// The first move of a chain that doesn't verify, see `Game::verify_chain`.
+#[derive(Debug)]
+pub struct ChainBreak {
+    /// The position of the move in the chain.
+    pub index: usize,
+    pub error: Error,
+}
+impl Display for ChainBreak {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Move {}: {}", self.index, self.error)
+    }
+}
 #[derive(Debug)]
 pub enum Error {
     NotFound(String),           // the thing that wasn't found
     OutOfRange(String, String), // what (capitalized), range
     Unautherized(i32),          // the player
     MalformedMove,
+    TileOccupied(i32), // the player on the tile
     Other(String),
 }
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
 impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Error::NotFound(a) => write!(f, "Could not find {a}."),
             Error::OutOfRange(a, b) => write!(f, "{a} out of range: {b}."),
             Error::Unautherized(a) => write!(f, "Player ({a}) is unautherized."),
             Error::MalformedMove => write!(f, "Move was malformed."),
+            Error::TileOccupied(a) => write!(f, "Tile is occupied by player ({a})."),
             Error::Other(a) => write!(f, "{a}"),
         }
     }
 }
-#[derive(Clone)]
+/// How many votes a tank needs for an extra point, evaluated against the size of the jury when votes are handled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThresholdMode {
+    /// Always the same number of votes. (`N3`)
+    Fixed(u32),
+    /// A percentage of the dead players, rounded up and at least one. (`J50`)
+    FractionOfJury(u32),
+    /// The larger of two thresholds. (`X.N2.J50`)
+    Max(Box<ThresholdMode>, Box<ThresholdMode>),
+    /// The smaller of two thresholds. (`I.N5.J50`)
+    Min(Box<ThresholdMode>, Box<ThresholdMode>),
+}
+impl ThresholdMode {
+    /// The threshold for a jury of `jury` dead players.
+    #[must_use]
+    pub fn threshold(&self, jury: usize) -> u32 {
+        match self {
+            ThresholdMode::Fixed(n) => *n,
+            ThresholdMode::FractionOfJury(percent) => {
+                let jury = u64::try_from(jury).unwrap_or(u64::MAX);
+                let votes = jury.saturating_mul(u64::from(*percent)).div_ceil(100);
+                u32::try_from(votes).unwrap_or(u32::MAX).max(1)
+            }
+            ThresholdMode::Max(a, b) => a.threshold(jury).max(b.threshold(jury)),
+            ThresholdMode::Min(a, b) => a.threshold(jury).min(b.threshold(jury)),
+        }
+    }
+    /// Describes the threshold for players.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match self {
+            ThresholdMode::Fixed(n) => n.to_string(),
+            ThresholdMode::FractionOfJury(percent) => format!("{percent}% of the jury"),
+            ThresholdMode::Max(a, b) => format!("the most of ({}, {})", a.describe(), b.describe()),
+            ThresholdMode::Min(a, b) => {
+                format!("the least of ({}, {})", a.describe(), b.describe())
+            }
+        }
+    }
+    /// Parses a mode nested in `depth` others, refusing modes nested deeper than `MAX_EXPR_DEPTH`.
+    fn parse_parts<'a>(
+        parts: &mut impl Iterator<Item = &'a str>,
+        depth: u32,
+    ) -> Result<Self, Error> {
+        let part = parts.next().ok_or(Error::MalformedMove)?;
+        let number = || part[1..].parse::<u32>().map_err(|_| Error::MalformedMove);
+        match part.chars().next() {
+            Some('N') => Ok(ThresholdMode::Fixed(number()?)),
+            Some('J') => Ok(ThresholdMode::FractionOfJury(number()?)),
+            Some('X') if part.len() == 1 && depth < MAX_EXPR_DEPTH => Ok(ThresholdMode::Max(
+                Box::new(Self::parse_parts(parts, depth + 1)?),
+                Box::new(Self::parse_parts(parts, depth + 1)?),
+            )),
+            Some('I') if part.len() == 1 && depth < MAX_EXPR_DEPTH => Ok(ThresholdMode::Min(
+                Box::new(Self::parse_parts(parts, depth + 1)?),
+                Box::new(Self::parse_parts(parts, depth + 1)?),
+            )),
+            _ => Err(Error::NotFound("ThresholdMode".into())),
+        }
+    }
+}
+impl core::str::FromStr for ThresholdMode {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text.len() > MAX_SETTING_LENGTH {
+            return Err(Error::OutOfRange(
+                "Threshold mode".into(),
+                format!("at most {MAX_SETTING_LENGTH} characters"),
+            ));
+        }
+        let mut parts = text.split('.');
+        let mode = Self::parse_parts(&mut parts, 0)?;
+        match parts.next() {
+            None => Ok(mode),
+            Some(_) => Err(Error::MalformedMove),
+        }
+    }
+}
+impl Display for ThresholdMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ThresholdMode::Fixed(n) => write!(f, "N{n}"),
+            ThresholdMode::FractionOfJury(percent) => write!(f, "J{percent}"),
+            ThresholdMode::Max(a, b) => write!(f, "X.{a}.{b}"),
+            ThresholdMode::Min(a, b) => write!(f, "I.{a}.{b}"),
+        }
+    }
+}
+/// How a game is won, see `Game::status`. Whatever the condition, the game ends when at most one tank is left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinCondition {
+    /// The last tank alive wins. (`L`)
+    LastAlive,
+    /// The living tank with the most points wins once it has at least this many. (`P10`)
+    PointsAtLeast(u32),
+    /// Every tank alive after this many rounds wins. (`S5`)
+    SurviveRounds(u32),
+    /// The tank that ends this many rounds in a row on the flag, the center tile, wins. (`F3`)
+    FlagHeld(u32),
+}
+impl WinCondition {
+    /// Describes the condition for players.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match self {
+            WinCondition::LastAlive => "The last tank alive wins".into(),
+            WinCondition::PointsAtLeast(n) => format!("The first tank with {n} points wins"),
+            WinCondition::SurviveRounds(n) => format!("Every tank alive after {n} rounds wins"),
+            WinCondition::FlagHeld(n) => {
+                format!("The first tank to end {n} rounds in a row on the center tile wins")
+            }
+        }
+    }
+}
+impl core::str::FromStr for WinCondition {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let number = || text[1..].parse::<u32>().map_err(|_| Error::MalformedMove);
+        match text.chars().next() {
+            Some('L') if text.len() == 1 => Ok(WinCondition::LastAlive),
+            Some('P') => Ok(WinCondition::PointsAtLeast(number()?)),
+            Some('S') => Ok(WinCondition::SurviveRounds(number()?)),
+            Some('F') => Ok(WinCondition::FlagHeld(number()?)),
+            _ => Err(Error::NotFound("WinCondition".into())),
+        }
+    }
+}
+impl Display for WinCondition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            WinCondition::LastAlive => write!(f, "L"),
+            WinCondition::PointsAtLeast(n) => write!(f, "P{n}"),
+            WinCondition::SurviveRounds(n) => write!(f, "S{n}"),
+            WinCondition::FlagHeld(n) => write!(f, "F{n}"),
+        }
+    }
+}
+/// Whether a game is still being played, see `Game::status`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameStatus {
+    /// Fewer than two tanks joined, the game can't be won yet.
+    Waiting,
+    Running,
+    /// The game is over, won by these players.
+    Won(Vec<i32>),
+    /// The game is over without a winner, every tank died.
+    Draw,
+}
+impl GameStatus {
+    /// Whether the game is over, after which only votes are accepted.
+    #[must_use]
+    pub fn is_over(&self) -> bool {
+        matches!(self, GameStatus::Won(_) | GameStatus::Draw)
+    }
+}
+#[derive(Clone, PartialEq)]
 pub enum LevelRangeMap {
+    /// The range is the level plus one. (`L`)
     Linear,
+    /// The range is looked up by level. (`A1.2.4`)
     Array(Vec<u32>),
+    /// The range is `base + slope * level`. (`F1.2`)
+    Affine { base: u32, slope: u32 },
+    /// The range of another map limited to `min..=max`. (`C1.3.L`)
+    Clamped {
+        min: u32,
+        max: u32,
+        map: Box<LevelRangeMap>,
+    },
+    /// An arithmetic expression over `level` using `+`, `-`, `*`, `/` and parentheses. (`E(level+1)*2`)
+    Expr(String),
 }
-impl std::str::FromStr for LevelRangeMap {
+impl core::str::FromStr for LevelRangeMap {
     type Err = Error;
 
     fn from_str(text: &str) -> Result<Self, Self::Err> {
-        if text == "L" {
-            Ok(LevelRangeMap::Linear)
-        } else {
-            text.starts_with('A')
-                .then_some(())
-                .ok_or(Error::NotFound("LevelRangeMap".into()))?;
-            let mut parts = text.split('.');
-            let first = parts.next().map(|x| &x[1..]);
-            let parts = first
-                .into_iter()
-                .chain(parts)
-                .map(str::parse)
-                .try_fold(Vec::new(), |mut x, y| match y {
-                    Ok(y) => {
-                        x.push(y);
-                        Some(x)
-                    }
-                    _ => None,
+        if text.len() > MAX_SETTING_LENGTH {
+            return Err(Error::OutOfRange(
+                "Range".into(),
+                format!("at most {MAX_SETTING_LENGTH} characters"),
+            ));
+        }
+        Self::parse_nested(text, 0)
+    }
+}
+impl LevelRangeMap {
+    /// Parses a map nested in `depth` clamped maps, refusing maps nested deeper than `MAX_EXPR_DEPTH`.
+    fn parse_nested(text: &str, depth: u32) -> Result<Self, Error> {
+        let number = |x: &str| x.parse::<u32>().map_err(|_| Error::MalformedMove);
+        let mut chars = text.chars();
+        let rest = chars.as_str().get(1..).unwrap_or_default();
+        match chars.next() {
+            Some('L') if rest.is_empty() => Ok(LevelRangeMap::Linear),
+            Some('A') => Ok(LevelRangeMap::Array(
+                rest.split('.').map(number).collect::<Result<_, _>>()?,
+            )),
+            Some('F') => {
+                let (base, slope) = rest.split_once('.').ok_or(Error::MalformedMove)?;
+                Ok(LevelRangeMap::Affine {
+                    base: number(base)?,
+                    slope: number(slope)?,
+                })
+            }
+            Some('C') if depth < MAX_EXPR_DEPTH => {
+                let mut parts = rest.splitn(3, '.');
+                let min = number(parts.next().ok_or(Error::MalformedMove)?)?;
+                let max = number(parts.next().ok_or(Error::MalformedMove)?)?;
+                let map = Self::parse_nested(parts.next().ok_or(Error::MalformedMove)?, depth + 1)?;
+                if min > max {
+                    return Err(Error::OutOfRange("Minimum".into(), format!("<= {max}")));
+                }
+                Ok(LevelRangeMap::Clamped {
+                    min,
+                    max,
+                    map: Box::new(map),
                 })
-                .ok_or(Error::MalformedMove)?;
-            Ok(LevelRangeMap::Array(parts))
+            }
+            Some('E') => {
+                eval_expr(rest, 0).ok_or(Error::MalformedMove)?;
+                Ok(LevelRangeMap::Expr(rest.into()))
+            }
+            _ => Err(Error::NotFound("LevelRangeMap".into())),
         }
     }
 }
 impl Display for LevelRangeMap {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             LevelRangeMap::Linear => write!(f, "L"),
             LevelRangeMap::Array(a) => write!(
                 f,
-                "A{}|",
-                a.iter().map(u32::to_string).collect::<Vec<_>>().join(",")
+                "A{}",
+                a.iter().map(u32::to_string).collect::<Vec<_>>().join(".")
             ),
+            LevelRangeMap::Affine { base, slope } => write!(f, "F{base}.{slope}"),
+            LevelRangeMap::Clamped { min, max, map } => write!(f, "C{min}.{max}.{map}"),
+            LevelRangeMap::Expr(e) => write!(f, "E{e}"),
         }
     }
 }
+#[cfg(feature = "serde")]
+impl serde::Serialize for LevelRangeMap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for LevelRangeMap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <String as serde::Deserialize>::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
 impl LevelRangeMap {
     #[must_use]
     pub fn get_range(&self, level: u32) -> u32 {
         match self {
             LevelRangeMap::Linear => level + 1,
             LevelRangeMap::Array(a) => a[level as usize],
+            LevelRangeMap::Affine { base, slope } => {
+                base.saturating_add(slope.saturating_mul(level))
+            }
+            LevelRangeMap::Clamped { min, max, map } => map.get_range(level).clamp(*min, *max),
+            LevelRangeMap::Expr(e) => eval_expr(e, level).unwrap_or(0),
+        }
+    }
+}
+/// The most parentheses an expression of `LevelRangeMap::Expr` can be nested in, and the most clamped maps or threshold
+/// modes can be nested in each other, so parsing and evaluating them can't overflow the stack.
+const MAX_EXPR_DEPTH: u32 = 32;
+/// The longest text a `LevelRangeMap` or `ThresholdMode` is parsed from.
+const MAX_SETTING_LENGTH: usize = 1024;
+/// Evaluates an arithmetic expression over `level`.
+/// Uses saturating arithmetic, with division by zero giving zero.
+/// Returns `None` if the expression is malformed or nested deeper than `MAX_EXPR_DEPTH`.
+fn eval_expr(expr: &str, level: u32) -> Option<u32> {
+    struct Parser<'a> {
+        text: &'a [u8],
+        pos: usize,
+        level: u32,
+        /// The parentheses around the current position.
+        depth: u32,
+    }
+    impl Parser<'_> {
+        fn peek(&mut self) -> Option<u8> {
+            while self.text.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+                self.pos += 1;
+            }
+            self.text.get(self.pos).copied()
+        }
+        fn expr(&mut self) -> Option<u32> {
+            let mut value = self.term()?;
+            loop {
+                match self.peek() {
+                    Some(b'+') => {
+                        self.pos += 1;
+                        value = value.saturating_add(self.term()?);
+                    }
+                    Some(b'-') => {
+                        self.pos += 1;
+                        value = value.saturating_sub(self.term()?);
+                    }
+                    _ => return Some(value),
+                }
+            }
+        }
+        fn term(&mut self) -> Option<u32> {
+            let mut value = self.factor()?;
+            loop {
+                match self.peek() {
+                    Some(b'*') => {
+                        self.pos += 1;
+                        value = value.saturating_mul(self.factor()?);
+                    }
+                    Some(b'/') => {
+                        self.pos += 1;
+                        value = value.checked_div(self.factor()?).unwrap_or(0);
+                    }
+                    _ => return Some(value),
+                }
+            }
+        }
+        fn factor(&mut self) -> Option<u32> {
+            match self.peek()? {
+                b'(' if self.depth < MAX_EXPR_DEPTH => {
+                    self.pos += 1;
+                    self.depth += 1;
+                    let value = self.expr()?;
+                    (self.peek()? == b')').then(|| self.pos += 1)?;
+                    self.depth -= 1;
+                    Some(value)
+                }
+                b'0'..=b'9' => {
+                    let start = self.pos;
+                    while self.text.get(self.pos).is_some_and(u8::is_ascii_digit) {
+                        self.pos += 1;
+                    }
+                    core::str::from_utf8(&self.text[start..self.pos])
+                        .ok()?
+                        .parse()
+                        .ok()
+                }
+                _ if self.text[self.pos..].starts_with(b"level") => {
+                    self.pos += 5;
+                    Some(self.level)
+                }
+                _ => None,
+            }
         }
     }
+    let mut parser = Parser {
+        text: expr.as_bytes(),
+        pos: 0,
+        level,
+        depth: 0,
+    };
+    let value = parser.expr()?;
+    parser.peek().is_none().then_some(value)
 }
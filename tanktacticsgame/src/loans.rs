@@ -0,0 +1,106 @@
+//! Lending points, see `MoveLineType::Lend`.
+//!
+//! A tank lends points to a tank in range for a number of rounds, and the loan is paid back from the points of the
+//! borrower when the votes of its last round are handled. A borrower that can't pay it all back pays what it has and
+//! defaults on the rest, which is paid back from its points at every later handling of the votes until the loan is
+//! settled. Lending and paying back show in `Game::transfers`.
+
+use alloc::format;
+
+use crate::{Error, Game, MoveLine, Transfer, TransferKind};
+
+/// Points lent by one tank to another, see `Game::loans`.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loan {
+    pub lender: i32,
+    pub borrower: i32,
+    /// The points still to be paid back.
+    pub amount: u32,
+    /// The round the loan is paid back at the end of.
+    pub due_round: u32,
+    /// Whether the borrower failed to pay it all back when it was due.
+    pub defaulted: bool,
+}
+
+impl Game {
+    /// Check if a lend is valid.
+    /// # Errors
+    /// If the `line` is not valid.
+    pub(crate) fn check_lend(&self, line: &MoveLine) -> Result<(), Error> {
+        let target = line.target.ok_or(Error::MalformedMove)?;
+        let amount = line.amount.ok_or(Error::MalformedMove)?;
+        let rounds = line.x.ok_or(Error::MalformedMove)?;
+        if target == line.authorizer {
+            return Err(Error::Other("A tank can't lend to itself.".into()));
+        }
+        if amount == 0 {
+            return Err(Error::OutOfRange("Amount".into(), ">= 1".into()));
+        }
+        if rounds == 0 {
+            return Err(Error::OutOfRange("Rounds".into(), ">= 1".into()));
+        }
+        let t = self.get_player(target)?;
+        let p = self.get_player(line.authorizer)?;
+        t.is_alive(true)?;
+        p.is_alive(true)?;
+        p.can_afford(amount)?;
+        p.in_range(t.x, t.y, self.player_range(p))
+    }
+    /// Applies a lend `line` that passed `Game::check`.
+    /// # Errors
+    /// If the `line` is incomplete, or paying the points would overflow.
+    pub(crate) fn handle_lend(&mut self, line: &MoveLine) -> Result<(), Error> {
+        let target = line.target.ok_or(Error::MalformedMove)?;
+        let amount = line.amount.ok_or(Error::MalformedMove)?;
+        let rounds = line.x.ok_or(Error::MalformedMove)?;
+        let due_round = self
+            .round
+            .checked_add(rounds - 1)
+            .ok_or_else(|| Error::OutOfRange("Rounds".into(), format!("< {}", u32::MAX)))?;
+        self.get_player_mut(line.authorizer)?
+            .points
+            .take(amount, "Points")?;
+        self.get_player_mut(target)?.points.give(amount, "Points")?;
+        self.transfers.push(Transfer {
+            from: line.authorizer,
+            to: target,
+            kind: TransferKind::Loan,
+            amount,
+        });
+        self.loans.push(Loan {
+            lender: line.authorizer,
+            borrower: target,
+            amount,
+            due_round,
+            defaulted: false,
+        });
+        Ok(())
+    }
+    /// Pays back the loans due this round, as its votes are handled, defaulting on what the borrowers can't pay.
+    pub(crate) fn settle_loans(&mut self) -> Result<(), Error> {
+        let round = self.round;
+        let mut loans = core::mem::take(&mut self.loans);
+        for loan in loans.iter_mut().filter(|loan| loan.due_round <= round) {
+            let borrower = self.get_player_mut(loan.borrower)?;
+            let paid = loan.amount.min(borrower.points.get());
+            borrower.points.take(paid, "Points")?;
+            if let Some(lender) = self.players.get_mut(&loan.lender) {
+                lender.points.give(paid, "Points")?;
+            }
+            if paid > 0 {
+                self.transfers.push(Transfer {
+                    from: loan.borrower,
+                    to: loan.lender,
+                    kind: TransferKind::Repayment,
+                    amount: paid,
+                });
+            }
+            loan.amount -= paid;
+            loan.defaulted |= loan.amount > 0;
+        }
+        loans.retain(|loan| loan.amount > 0);
+        self.loans = loans;
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+//! Pausing a game, for holidays in long running games.
+//!
+//! A `MoveLineType::Pause` freezes the game from its `time` until a `MoveLineType::Resume`. While a game is paused no
+//! votes are handled, so no points are handed out, and only polls and the resume are accepted. Resuming moves the time
+//! of the last vote, if any, on by the length of the pause, so the round that was running continues where it was
+//! left. Who may pause a game is up to the host, as with handling the votes before the round is over.
+
+use alloc::format;
+
+use crate::{Error, Game, MoveLine, MoveLineType};
+
+impl Game {
+    /// Whether the game is paused, see `Game::paused`.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+    /// Check if a move may be made while the game is paused, and if a pause or resume is valid.
+    /// # Errors
+    /// If the `line` is not valid.
+    pub(crate) fn check_pause(&self, line: &MoveLine) -> Result<(), Error> {
+        match (&line.move_type, self.paused) {
+            (MoveLineType::Pause, None) => {
+                let time = line.time.ok_or(Error::MalformedMove)?;
+                if time < self.last_vote {
+                    return Err(Error::OutOfRange(
+                        "Time".into(),
+                        format!(">= {}", self.last_vote),
+                    ));
+                }
+                Ok(())
+            }
+            (MoveLineType::Resume, Some(since)) => {
+                let time = line.time.ok_or(Error::MalformedMove)?;
+                if time < since {
+                    return Err(Error::OutOfRange("Time".into(), format!(">= {since}")));
+                }
+                Ok(())
+            }
+            (MoveLineType::Pause, Some(_)) => {
+                Err(Error::Other("The game is already paused.".into()))
+            }
+            (MoveLineType::Resume, None) => Err(Error::Other("The game is not paused.".into())),
+            (MoveLineType::Poll | MoveLineType::PollVote | MoveLineType::ClosePoll, _)
+            | (_, None) => Ok(()),
+            (_, Some(_)) => Err(Error::Other("The game is paused.".into())),
+        }
+    }
+    /// Applies a pause or resume `line` that passed `Game::check`.
+    /// # Errors
+    /// If the `line` has no time.
+    pub(crate) fn handle_pause(&mut self, line: &MoveLine) -> Result<(), Error> {
+        let time = line.time.ok_or(Error::MalformedMove)?;
+        if line.move_type == MoveLineType::Pause {
+            self.paused = Some(time);
+        } else if let Some(since) = self.paused.take().filter(|_| self.last_vote > 0) {
+            self.last_vote = self.last_vote.saturating_add(time - since);
+        }
+        Ok(())
+    }
+}
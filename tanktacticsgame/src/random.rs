@@ -0,0 +1,42 @@
+//! Where the random numbers of a game come from.
+//!
+//! Every move of a game played with rules version 2 or later draws from its own stream of the seed of the game, the
+//! stream numbered by the index of the move. So what a move draws only depends on the seed and where the move is in the
+//! chain, and a game continued from a snapshot draws the same as the full replay, whichever moves drew before. Games
+//! of the first rules version draw from a single stream shared by every move, so the position in that stream is part
+//! of a snapshot. (see `Game::pack_board`)
+
+use alloc::vec::Vec;
+use rand_chacha::{rand_core::SeedableRng, ChaCha12Rng};
+
+use crate::{read_varint, write_varint, Error, Game};
+
+impl Game {
+    /// The random numbers the next move draws from, see the `random` module.
+    pub(crate) fn next_rng(&self) -> ChaCha12Rng {
+        if self.settings.rules_version < 2 {
+            return self.rand.clone();
+        }
+        let mut rand = ChaCha12Rng::seed_from_u64(self.settings.seed);
+        rand.set_stream(self.lines.len() as u64);
+        rand
+    }
+    /// Writes the position of the random numbers of a game of the first rules version, later games have none to keep.
+    pub(crate) fn write_rng(&self, out: &mut Vec<u8>) {
+        if self.settings.rules_version < 2 {
+            write_varint(out, self.rand.get_stream());
+            // 2^64 words are far more than a game ever draws.
+            #[allow(clippy::cast_possible_truncation)]
+            write_varint(out, self.rand.get_word_pos() as u64);
+        }
+    }
+    /// Reads the position written by `Game::write_rng`.
+    pub(crate) fn read_rng(&self, data: &mut &[u8]) -> Result<ChaCha12Rng, Error> {
+        let mut rand = ChaCha12Rng::seed_from_u64(self.settings.seed);
+        if self.settings.rules_version < 2 {
+            rand.set_stream(read_varint(data)?);
+            rand.set_word_pos(u128::from(read_varint(data)?));
+        }
+        Ok(rand)
+    }
+}
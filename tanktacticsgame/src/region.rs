@@ -0,0 +1,137 @@
+//! Following part of the board, for community games too large to send every move of to every client.
+//!
+//! A client that only shows a viewport of the board needs the moves that change a tile inside it, and a coarse
+//! `Minimap` of the rest. `Game::affected_tiles` tells which tiles a move will change before it is applied, so a host
+//! can leave out the moves that change nothing in view, and `Game::view` gives the tanks in view afterwards.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{Game, LootMode, MoveLine, MoveLineType};
+
+/// A rectangle of tiles, from `x`,`y` up to but not including `x + width`,`y + height`.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+impl Region {
+    #[must_use]
+    pub fn contains(&self, (x, y): (u32, u32)) -> bool {
+        x.checked_sub(self.x).is_some_and(|dx| dx < self.width)
+            && y.checked_sub(self.y).is_some_and(|dy| dy < self.height)
+    }
+}
+/// A tank as a viewport shows it.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewTank {
+    pub user: i32,
+    pub x: u32,
+    pub y: u32,
+    pub level: u32,
+    pub health: u32,
+    pub points: u32,
+    /// Whether the tank is alive, a dead or disabled tank stays on its tile as a wreck.
+    pub alive: bool,
+}
+/// The living tanks of the whole board, counted per square of `cell` by `cell` tiles, row by row.
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Minimap {
+    pub cell: u32,
+    /// The number of squares in a row.
+    pub width: u32,
+    /// The number of rows.
+    pub height: u32,
+    pub tanks: Vec<u32>,
+}
+
+impl Game {
+    /// The tiles `line` will change when it is applied: the tanks it moves, damages or pays, and the tiles it drives
+    /// over or places a bounty on. `None` when it changes the whole board, like handing out the votes or a kill
+    /// that splits its loot between every tank. Polls and pauses have no tile, so they count as the whole board too.
+    /// Tiles can repeat, and a move that fails to apply may list any tiles.
+    #[must_use]
+    pub fn affected_tiles(&self, line: &MoveLine) -> Option<Vec<(u32, u32)>> {
+        let tile = |user: Option<i32>| {
+            user.and_then(|user| self.players.get(&user))
+                .map(|p| (p.x, p.y))
+        };
+        let own = tile(Some(line.authorizer));
+        let mut tiles = match line.move_type {
+            MoveLineType::Shoot | MoveLineType::Surrender
+                if self.settings.loot_mode == LootMode::Split =>
+            {
+                return None
+            }
+            MoveLineType::Join | MoveLineType::JoinRandom => vec![self.get_pos()],
+            MoveLineType::Drive => Self::drive_steps(line).unwrap_or_default(),
+            MoveLineType::Shoot
+            | MoveLineType::Gift
+            | MoveLineType::Repair
+            | MoveLineType::Lend => tile(line.target).into_iter().collect(),
+            MoveLineType::Bounty => line.x.zip(line.y).into_iter().collect(),
+            MoveLineType::Vote
+            | MoveLineType::Upgrade
+            | MoveLineType::Surrender
+            | MoveLineType::BuyItem
+            | MoveLineType::UseItem => Vec::new(),
+            MoveLineType::HandleVotes
+            | MoveLineType::Poll
+            | MoveLineType::PollVote
+            | MoveLineType::ClosePoll
+            | MoveLineType::Pause
+            | MoveLineType::Resume => return None,
+        };
+        tiles.extend(own);
+        Some(tiles)
+    }
+    /// The tanks on the tiles of `region`, row by row.
+    #[must_use]
+    pub fn view(&self, region: Region) -> Vec<ViewTank> {
+        let mut tanks = self
+            .board
+            .iter()
+            .filter(|(tile, _)| region.contains(**tile))
+            .filter_map(|(_, user)| self.players.get(user))
+            .map(|p| ViewTank {
+                user: p.user,
+                x: p.x,
+                y: p.y,
+                level: p.level,
+                health: p.health.get(),
+                points: p.points.get(),
+                alive: p.alive(),
+            })
+            .collect::<Vec<_>>();
+        tanks.sort_unstable_by_key(|tank| (tank.y, tank.x));
+        tanks
+    }
+    /// The `Minimap` of the board with at most `size` squares on a side.
+    #[must_use]
+    pub fn minimap(&self, size: u32) -> Minimap {
+        let longest = self.settings.width.max(self.settings.height);
+        let cell = longest.div_ceil(size.max(1)).max(1);
+        let width = self.settings.width.div_ceil(cell);
+        let height = self.settings.height.div_ceil(cell);
+        let mut tanks = vec![0; (width * height) as usize];
+        for player in self.players.values() {
+            if player.alive() {
+                if let Some(count) =
+                    tanks.get_mut((player.y / cell * width + player.x / cell) as usize)
+                {
+                    *count += 1;
+                }
+            }
+        }
+        Minimap {
+            cell,
+            width,
+            height,
+            tanks,
+        }
+    }
+}
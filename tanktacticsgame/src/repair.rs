@@ -0,0 +1,115 @@
+//! Disabled tanks, see `Settings::two_phase_kills`.
+//!
+//! A tank shot to 0 health is disabled instead of destroyed: it can't act, vote or be shot, and it keeps its points.
+//! Another tank in range can `MoveLineType::Repair` it back to 1 health. A tank that is still disabled when the votes
+//! of the round after the one it was disabled in are handled is destroyed, and looted by whoever shot it last.
+
+use alloc::vec::Vec;
+
+use crate::{read_u32, read_varint, write_varint, Error, Game, GameEvent, MoveLine, Player};
+
+impl Game {
+    /// Takes a health of `target` for a shot by `shooter`, disabling or destroying the tank at 0 health.
+    pub(crate) fn hit(&mut self, target: i32, shooter: i32) -> Result<(), Error> {
+        let (round, disable) = (self.round, self.settings.two_phase_kills);
+        // A hit that would overflow the points of a looter fails before the tank is hit.
+        let mut health = self.get_player(target)?.health;
+        health.take(1, "Health")?;
+        if health.is_empty() && !disable {
+            self.loot(target, Some(shooter))?;
+        }
+        let victim = self.get_player_mut(target)?;
+        victim.health.take(1, "Health")?;
+        victim.last_hit_by = Some(shooter);
+        let empty = victim.health.is_empty();
+        self.emit(GameEvent::PlayerShot {
+            user: target,
+            shooter,
+        });
+        if !empty {
+            return Ok(());
+        }
+        if disable {
+            self.get_player_mut(target)?.disabled_round = Some(round);
+            self.emit(GameEvent::PlayerDisabled {
+                user: target,
+                shooter,
+            });
+            Ok(())
+        } else {
+            self.kill(target, Some(shooter))
+        }
+    }
+    /// Check if a repair is valid.
+    /// # Errors
+    /// If the `line` is not valid.
+    pub(crate) fn check_repair(&self, line: &MoveLine) -> Result<(), Error> {
+        let target = line.target.ok_or(Error::MalformedMove)?;
+        let t = self.get_player(target)?;
+        let p = self.get_player(line.authorizer)?;
+        if t.disabled_round.is_none() {
+            return Err(Error::Other("The target is not disabled.".into()));
+        }
+        p.is_alive(true)?;
+        p.can_afford(self.settings.costs.repair)?;
+        p.in_range(t.x, t.y, self.player_range(p))
+    }
+    /// Applies a repair `line` that passed `Game::check`.
+    /// # Errors
+    /// If the `line` has no target.
+    pub(crate) fn handle_repair(&mut self, line: &MoveLine) -> Result<(), Error> {
+        let target = line.target.ok_or(Error::MalformedMove)?;
+        let cost = self.settings.costs.repair;
+        self.get_player_mut(line.authorizer)?
+            .points
+            .take(cost, "Points")?;
+        let tank = self.get_player_mut(target)?;
+        tank.health.give(1, "Health")?;
+        tank.disabled_round = None;
+        self.emit(GameEvent::PlayerRepaired {
+            user: target,
+            by: line.authorizer,
+        });
+        Ok(())
+    }
+    /// Destroys the tanks that were disabled before this round and weren't repaired, crediting who shot them last.
+    pub(crate) fn destroy_disabled(&mut self) -> Result<(), Error> {
+        let round = self.round;
+        let mut disabled = self
+            .players
+            .values()
+            .filter(|p| p.disabled_round.is_some_and(|r| r < round))
+            .map(|p| (p.user, p.last_hit_by))
+            .collect::<Vec<_>>();
+        disabled.sort_unstable();
+        for (victim, killer) in disabled {
+            self.kill(victim, killer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the round each disabled tank of `players` was disabled in, by its position in `players`.
+pub(crate) fn write_disabled(out: &mut Vec<u8>, players: &[&Player]) {
+    let disabled = players
+        .iter()
+        .zip(0u64..)
+        .filter_map(|(p, index)| p.disabled_round.map(|round| (index, round)))
+        .collect::<Vec<_>>();
+    write_varint(out, disabled.len() as u64);
+    for (index, round) in disabled {
+        write_varint(out, index);
+        write_varint(out, u64::from(round));
+    }
+}
+/// Reads the disabled tanks written by `write_disabled` into `players`.
+pub(crate) fn read_disabled(data: &mut &[u8], players: &mut [Player]) -> Result<(), Error> {
+    for _ in 0..read_varint(data)? {
+        let player = usize::try_from(read_varint(data)?)
+            .ok()
+            .and_then(|i| players.get_mut(i))
+            .ok_or(Error::MalformedMove)?;
+        player.disabled_round = Some(read_u32(data)?);
+    }
+    Ok(())
+}
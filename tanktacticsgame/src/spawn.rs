@@ -0,0 +1,108 @@
+//! Where a tank joins, see `Game::get_pos`.
+//!
+//! Tanks join at a random free tile. With `Settings::symmetric_spawns` they join at the free tile furthest from the
+//! tanks on the board instead, by `Board::chebyshev`, with ties broken by the random state of the game so every
+//! client finds the same tile.
+//!
+//! A `MoveLineType::Join` names the tile it joins at, a `MoveLineType::JoinRandom` takes whatever tile is picked when
+//! it is appended.
+
+use alloc::{format, vec::Vec};
+use rand_chacha::{rand_core::RngCore, ChaCha12Rng};
+
+use crate::{Error, Game, GameEvent, MoveLine, MoveLineType, Player, Step, Topology};
+
+impl Game {
+    /// Check if a join is valid.
+    /// # Errors
+    /// If the `line` is not valid.
+    pub(crate) fn check_join(&self, line: &MoveLine) -> Result<(), Error> {
+        self.can_join(line.authorizer)?;
+        if line.move_type == MoveLineType::JoinRandom {
+            return match (line.x, line.y, &line.text) {
+                (None, None, Some(_)) => Ok(()),
+                _ => Err(Error::MalformedMove),
+            };
+        }
+        let pos = self.get_pos();
+        if line.x.ok_or(Error::MalformedMove)? != pos.0
+            || line.y.ok_or(Error::MalformedMove)? != pos.1
+        {
+            Err(Error::OutOfRange(
+                "Position".into(),
+                format!("({}, {})", pos.0, pos.1),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+    /// Places the tank of a join `line` that passed `Game::check`.
+    pub(crate) fn handle_join(&mut self, line: &MoveLine) {
+        let (x, y) = self.get_pos_mut();
+        self.players.insert(
+            line.authorizer,
+            Player {
+                user: line.authorizer,
+                health: self.settings.health.into(),
+                level: self.settings.starting_level,
+                points: self.settings.starting_points.into(),
+                x,
+                y,
+                last_action: self.lines.len(),
+                died_round: None,
+                last_hit_by: None,
+                items: Vec::new(),
+                effects: Vec::new(),
+                disabled_round: None,
+            },
+        );
+        self.board.insert((x, y), line.authorizer);
+        self.emit(GameEvent::PlayerJoined {
+            user: line.authorizer,
+            at: Step { x, y },
+        });
+    }
+    /// The tile the next tank joins at, drawing from `rand`.
+    pub(crate) fn spawn(&self, rand: &mut ChaCha12Rng) -> (u32, u32) {
+        if self.settings.symmetric_spawns {
+            return self.furthest_free(rand);
+        }
+        let mut x = 0;
+        let mut y = 0;
+        while self.board.contains_key(&(x, y)) {
+            let random = rand.next_u64();
+            #[allow(clippy::cast_possible_truncation)]
+            let low: u32 = random as u32;
+            #[allow(clippy::cast_possible_truncation)]
+            let high: u32 = (random >> 32) as u32;
+            x = low % self.settings.width;
+            y = high % self.settings.height;
+        }
+        (x, y)
+    }
+    /// The free tile furthest from its nearest tank, one drawn from `rand` if several are as far.
+    fn furthest_free(&self, rand: &mut ChaCha12Rng) -> (u32, u32) {
+        let board = self.settings.board();
+        let mut best = Vec::new();
+        let mut furthest = 0;
+        for tile in board.tiles().filter(|tile| !self.board.contains_key(tile)) {
+            let distance = self
+                .board
+                .keys()
+                .map(|&tank| board.chebyshev(tile, tank, Topology::Flat))
+                .min()
+                .unwrap_or(u32::MAX);
+            if distance > furthest {
+                furthest = distance;
+                best.clear();
+            }
+            if distance == furthest {
+                best.push(tile);
+            }
+        }
+        // `can_join` refuses a full board, so there is always a free tile.
+        let count = u64::try_from(best.len()).unwrap_or(u64::MAX).max(1);
+        let index = usize::try_from(rand.next_u64() % count).unwrap_or_default();
+        best.get(index).copied().unwrap_or_default()
+    }
+}
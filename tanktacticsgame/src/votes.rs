@@ -0,0 +1,119 @@
+//! How the votes of a round are counted, see `Game::tally`.
+//!
+//! Every tank that got votes is a candidate. The weight of a voter grows with the rounds they have been dead for
+//! (see `Settings::jury_weight`). A candidate with at least the threshold of weight gets a point, if it is still alive
+//! when the votes are handled.
+
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use hashbrown::HashMap;
+#[cfg(feature = "openapi")]
+use poem_openapi::Object;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use crate::{Error, Game, MoveLine, MoveLineType, Player};
+
+/// The votes for a single tank in a round.
+#[cfg_attr(feature = "openapi", derive(Object))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub target: i32,
+    /// Who voted for the tank, in order of user id.
+    pub voters: Vec<i32>,
+    /// The summed weight of `voters`.
+    pub weight: u32,
+    /// Whether `weight` reached the threshold.
+    pub reached: bool,
+    /// Whether the tank got the point, which it only does while alive.
+    pub elected: bool,
+}
+/// The count of the votes of a round, see `Game::tally`.
+#[cfg_attr(feature = "openapi", derive(Object))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoteTally {
+    pub round: u32,
+    /// The size of the jury the threshold was based on.
+    pub jury: u32,
+    /// The weight a candidate needed for a point.
+    pub threshold: u32,
+    /// The tanks that got votes, most weight first.
+    pub candidates: Vec<Candidate>,
+}
+
+impl Game {
+    /// Counts the votes cast so far this round, as they would be if the votes were handled now.
+    #[must_use]
+    pub fn tally(&self) -> VoteTally {
+        let jury = self
+            .players
+            .values()
+            .filter(|p| p.is_alive(false).is_ok())
+            .count();
+        let threshold = self.settings.threshold_mode.threshold(jury);
+        let mut voters = HashMap::<i32, Vec<i32>>::new();
+        for (&voter, &target) in &self.votes {
+            voters.entry(target).or_default().push(voter);
+        }
+        let mut candidates = voters
+            .into_iter()
+            .map(|(target, mut voters)| {
+                voters.sort_unstable();
+                let weight = voters
+                    .iter()
+                    .fold(0u32, |x, &voter| x.saturating_add(self.vote_weight(voter)));
+                let reached = weight >= threshold;
+                let elected = reached && self.players.get(&target).is_some_and(Player::alive);
+                Candidate {
+                    target,
+                    voters,
+                    weight,
+                    reached,
+                    elected,
+                }
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|c| (core::cmp::Reverse(c.weight), c.target));
+        VoteTally {
+            round: self.round,
+            jury: u32::try_from(jury).unwrap_or(u32::MAX),
+            threshold,
+            candidates,
+        }
+    }
+    /// The jurors that haven't voted yet this round, in order of user id.
+    #[must_use]
+    pub fn missing_voters(&self) -> Vec<i32> {
+        let mut missing = self
+            .players
+            .values()
+            .filter(|p| p.is_alive(false).is_ok() && !self.votes.contains_key(&p.user))
+            .map(|p| p.user)
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+        missing
+    }
+    /// Applies `lines` to this game, which should have none yet, up to the handling of the votes of `round`. Returns
+    /// the tally as it was counted then, or the tally so far if `round` is the current round.
+    /// # Errors
+    /// If a line is not valid, or the game never reaches `round`.
+    pub fn tally_round(
+        mut self,
+        lines: impl IntoIterator<Item = MoveLine>,
+        round: u32,
+    ) -> Result<VoteTally, Error> {
+        for line in lines {
+            if line.move_type == MoveLineType::HandleVotes && self.round == round {
+                return Ok(self.tally());
+            }
+            self.apply(line)?;
+        }
+        if self.round == round {
+            Ok(self.tally())
+        } else {
+            Err(Error::NotFound(alloc::format!("round ({round})")))
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Checks that `Settings::actions_per_round` limits the moves that cost points.
+
+use tanktacticsgame::{MoveLine, MoveLineType, Topology, RULES_VERSION};
+
+mod common;
+
+#[test]
+fn spending_moves_are_limited_per_round() {
+    // Every player joins, then player 1 drives once.
+    let mut chain = common::chain(9);
+    chain.game.starting_points = 5;
+    let unlimited = chain
+        .game
+        .clone()
+        .as_game(chain.moves.clone(), &chain.users)
+        .unwrap();
+    assert_eq!(unlimited.actions_left(1), None);
+
+    chain.game.actions_per_round = 1;
+    let limited = chain.game.as_game(chain.moves, &chain.users).unwrap();
+    assert_eq!(limited.actions_left(1), Some(0));
+    assert_eq!(limited.actions_left(2), Some(1));
+
+    let player = &limited.players[&1];
+    let board = limited.settings.board();
+    let drive = board
+        .neighbors(player.x, player.y, 1, Topology::Flat)
+        .map(|(x, y)| MoveLine {
+            move_type: MoveLineType::Drive,
+            x: Some(x),
+            y: Some(y),
+            target: None,
+            amount: None,
+            text: None,
+            time: None,
+            clock: None,
+            path: None,
+            rules_version: Some(RULES_VERSION),
+            authorizer: 1,
+            signature: String::new(),
+        })
+        .find(|drive| unlimited.check(drive).is_ok())
+        .unwrap();
+    assert!(limited.check(&drive).is_err());
+}
@@ -0,0 +1,102 @@
+//! Checks the distances and neighborhoods of `Board` on every topology, and that drives stay on the board.
+
+use tanktacticsgame::{Board, MoveLine, MoveLineType, Step, Topology, RULES_VERSION};
+
+mod common;
+
+const BOARD: Board = Board {
+    width: 5,
+    height: 4,
+};
+
+#[test]
+fn distances_follow_the_topology() {
+    assert_eq!(BOARD.chebyshev((0, 0), (4, 1), Topology::Flat), 4);
+    assert_eq!(BOARD.manhattan((0, 0), (4, 1), Topology::Flat), 5);
+    assert_eq!(BOARD.chebyshev((0, 0), (4, 1), Topology::Torus), 1);
+    assert_eq!(BOARD.manhattan((0, 0), (4, 3), Topology::Torus), 2);
+    // (1, 1) sits in a shifted row, so both (1, 0) and (2, 0) touch it.
+    assert_eq!(BOARD.chebyshev((1, 1), (1, 0), Topology::Hex), 1);
+    assert_eq!(BOARD.chebyshev((1, 1), (2, 0), Topology::Hex), 1);
+    assert_eq!(BOARD.chebyshev((1, 1), (0, 0), Topology::Hex), 2);
+}
+
+#[test]
+fn neighbors_stay_on_the_board() {
+    let flat = BOARD.neighbors(0, 0, 1, Topology::Flat).collect::<Vec<_>>();
+    assert_eq!(flat, vec![(1, 0), (0, 1), (1, 1)]);
+    assert_eq!(BOARD.neighbors(0, 0, 1, Topology::Torus).count(), 8);
+    assert_eq!(BOARD.neighbors(2, 2, 1, Topology::Hex).count(), 6);
+    assert_eq!(BOARD.neighbors(2, 2, 9, Topology::Torus).count(), 19);
+    assert!(BOARD
+        .neighbors(2, 1, 2, Topology::Flat)
+        .all(|(x, y)| BOARD.contains(x, y)));
+}
+
+#[test]
+fn shortest_paths_go_around_blocked_tiles() {
+    let open = |_| false;
+    assert_eq!(
+        BOARD.shortest_path((0, 0), (2, 2), 5, open, Topology::Flat),
+        Some(vec![(1, 1), (2, 2)])
+    );
+    assert_eq!(
+        BOARD.shortest_path((0, 0), (0, 0), 0, open, Topology::Flat),
+        Some(Vec::new())
+    );
+    // A wall along x = 1 with a gap at the bottom.
+    let wall = |(x, y): (u32, u32)| x == 1 && y < 3;
+    let path = BOARD
+        .shortest_path((0, 0), (2, 0), 9, wall, Topology::Flat)
+        .unwrap();
+    assert_eq!(path.len(), 6);
+    assert_eq!(path.last(), Some(&(2, 0)));
+    assert!(path.iter().all(|&tile| !wall(tile)));
+    assert_eq!(
+        BOARD.shortest_path((0, 0), (2, 0), 5, wall, Topology::Flat),
+        None
+    );
+    assert_eq!(
+        BOARD.shortest_path((0, 0), (1, 0), 9, wall, Topology::Flat),
+        None
+    );
+    assert_eq!(
+        BOARD.shortest_path((0, 0), (9, 0), 9, open, Topology::Flat),
+        None
+    );
+}
+
+#[test]
+fn drives_stay_on_the_board() {
+    let mut chain = common::chain(0);
+    chain.game.width = 1;
+    chain.game.height = 4;
+    chain.game.drive_length = 2;
+    chain.game.starting_points = 5;
+    let mut game = chain.game.as_game(Vec::new(), &chain.users).unwrap();
+    let (x, y) = game.get_pos();
+    let line = |move_type, (x, y)| MoveLine {
+        move_type,
+        x: Some(x),
+        y: Some(y),
+        target: None,
+        amount: None,
+        text: None,
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: Some(RULES_VERSION),
+        authorizer: 1,
+        signature: String::new(),
+    };
+    game.apply(line(MoveLineType::Join, (x, y))).unwrap();
+
+    let end = (0, if y == 0 { 1 } else { y - 1 });
+    assert!(game.check(&line(MoveLineType::Drive, end)).is_ok());
+    // Through the tile right of the board, which is as close to both ends.
+    let outside = MoveLine {
+        path: Some(vec![Step { x: 1, y }]),
+        ..line(MoveLineType::Drive, end)
+    };
+    assert!(game.check(&outside).is_err());
+}
@@ -0,0 +1,35 @@
+//! Checks that the moves of a `Hunter` are accepted until the game is over.
+
+use tanktacticsgame::{Game, Hunter, MoveLine, MoveLineType, Settings, Strategy};
+
+#[test]
+fn hunters_play_a_game_to_the_end() {
+    let mut game = Game::new(
+        1,
+        Settings {
+            width: 6,
+            height: 6,
+            ..Settings::default()
+        },
+    );
+    for user in 1..=3 {
+        let (x, y) = game.get_pos();
+        game.apply(MoveLine {
+            move_type: MoveLineType::Join,
+            x: Some(x),
+            y: Some(y),
+            ..Hunter.next_move(&game, user)
+        })
+        .unwrap();
+    }
+    for turn in 0..1_000 {
+        if game.status().is_over() {
+            return;
+        }
+        let bot = turn % 3 + 1;
+        if game.players[&bot].alive() {
+            game.apply(Hunter.next_move(&game, bot)).unwrap();
+        }
+    }
+    panic!("the hunters never finished the game");
+}
@@ -0,0 +1,45 @@
+//! Checks that the canonical JSON of a move has sorted keys, no unset fields and minimal escapes, and reads back.
+
+use tanktacticsgame::{MoveLine, MoveLineType, Step};
+
+fn line() -> MoveLine {
+    MoveLine {
+        move_type: MoveLineType::Drive,
+        x: Some(2),
+        y: Some(3),
+        target: None,
+        amount: None,
+        text: None,
+        time: None,
+        clock: None,
+        path: Some(vec![Step { x: 1, y: 2 }]),
+        rules_version: Some(1),
+        authorizer: -4,
+        signature: "sig".into(),
+    }
+}
+
+#[test]
+fn canonical_json_is_stable() {
+    assert_eq!(
+        line().to_canonical_json(),
+        r#"{"authorizer":-4,"move_type":"Drive","path":[{"x":1,"y":2}],"rules_version":1,"signature":"sig","x":2,"y":3}"#
+    );
+    let poll = MoveLine {
+        move_type: MoveLineType::Poll,
+        x: None,
+        y: None,
+        path: None,
+        text: Some("Truce?\n\"yes\"\\no\u{1}é".into()),
+        ..line()
+    };
+    let json = poll.to_canonical_json();
+    assert_eq!(
+        json,
+        "{\"authorizer\":-4,\"move_type\":\"Poll\",\"rules_version\":1,\"signature\":\"sig\",\"text\":\"Truce?\\n\\\"yes\\\"\\\\no\\u0001é\"}"
+    );
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["text"], "Truce?\n\"yes\"\\no\u{1}é");
+    // Sorted keys and no whitespace, so writing the parsed value again gives the same text.
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+}
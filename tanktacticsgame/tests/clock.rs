@@ -0,0 +1,42 @@
+//! Checks that votes handled with a time from the server clock keep the signature through the compact format.
+
+use tanktacticsgame::{
+    clock_data, decode_tokens, encode_tokens, get_key, get_random_keys, sign_data, MoveLine,
+    MoveLineType,
+};
+
+fn handle_votes(time: u64, clock: Option<String>) -> MoveLine {
+    MoveLine {
+        move_type: MoveLineType::HandleVotes,
+        x: None,
+        y: None,
+        target: None,
+        amount: None,
+        text: None,
+        time: Some(time),
+        clock,
+        path: None,
+        rules_version: None,
+        authorizer: 1,
+        signature: "c2lnbmF0dXJl".into(),
+    }
+}
+
+#[test]
+fn clock_checks_the_signed_time() {
+    let (private, public) = get_random_keys();
+    let server = get_key(public).unwrap();
+    let clock = sign_data(&clock_data(1_700_000_000), private).unwrap();
+
+    let line = handle_votes(1_700_000_000, Some(clock.clone()));
+    assert!(line.check_clock(&server).is_ok());
+    let lines = vec![line];
+    assert_eq!(decode_tokens(&encode_tokens(&lines)).unwrap(), lines);
+
+    assert!(handle_votes(1_700_000_001, Some(clock))
+        .check_clock(&server)
+        .is_err());
+    assert!(handle_votes(1_700_000_000, None)
+        .check_clock(&server)
+        .is_err());
+}
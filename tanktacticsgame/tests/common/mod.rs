@@ -0,0 +1,142 @@
+//! Builds moves for the tests, and long valid chains for the replay benchmarks and budget test.
+// Not every test uses every helper.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use tanktacticsgame::{
+    get_key, get_random_keys, DataBaseGame, Game, MoveLine, MoveLineType, PublicKey, Topology,
+    RULES_VERSION,
+};
+
+const PLAYERS: i32 = 8;
+
+pub struct Chain {
+    pub game: DataBaseGame,
+    pub users: HashMap<i32, PublicKey>,
+    pub moves: Vec<MoveLine>,
+}
+
+fn database_game() -> DataBaseGame {
+    DataBaseGame {
+        id: 1,
+        seed: 0,
+        last_vote: 0,
+        width: 16,
+        height: 16,
+        health: 3,
+        max_level: 2,
+        max_players: PLAYERS,
+        vote_threshold: 3,
+        range: "L".into(),
+        point_decay: false,
+        jury_weight: 0,
+        loot_mode: "killer".into(),
+        drive_length: 1,
+        rules_version: RULES_VERSION,
+        mutators: String::new(),
+        threshold_mode: String::new(),
+        win_condition: String::new(),
+        starting_points: 1,
+        starting_level: 0,
+        actions_per_round: 0,
+        items: String::new(),
+        two_phase_kills: false,
+        symmetric_spawns: false,
+        gift_cooldown: 0,
+        vote_interval: 0,
+        locale: String::new(),
+        description: String::new(),
+    }
+}
+
+/// An unsigned move without any of its optional fields.
+pub fn line(authorizer: i32, move_type: MoveLineType) -> MoveLine {
+    MoveLine {
+        move_type,
+        x: None,
+        y: None,
+        target: None,
+        amount: None,
+        text: None,
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: None,
+        authorizer,
+        signature: String::new(),
+    }
+}
+
+/// `line` aimed at the player `target`.
+pub fn targeted(authorizer: i32, move_type: MoveLineType, target: i32) -> MoveLine {
+    MoveLine {
+        target: Some(target),
+        ..line(authorizer, move_type)
+    }
+}
+
+/// `line` made at the unix time `time`.
+pub fn timed(authorizer: i32, move_type: MoveLineType, time: u64) -> MoveLine {
+    MoveLine {
+        time: Some(time),
+        ..line(authorizer, move_type)
+    }
+}
+
+/// `line` at `pos`, with the current rules version like the moves of a real chain.
+fn chain_line(move_type: MoveLineType, authorizer: i32, pos: Option<(u32, u32)>) -> MoveLine {
+    MoveLine {
+        x: pos.map(|p| p.0),
+        y: pos.map(|p| p.1),
+        rules_version: Some(RULES_VERSION),
+        ..line(authorizer, move_type)
+    }
+}
+
+/// The next move: players join first, then take turns driving to a free neighbouring tile,
+/// with votes being handled whenever the player on turn is out of points.
+fn next_move(game: &Game, index: usize) -> MoveLine {
+    let joined = i32::try_from(game.players.len()).unwrap();
+    if joined < PLAYERS {
+        return chain_line(MoveLineType::Join, joined + 1, Some(game.get_pos()));
+    }
+    let id = i32::try_from(index).unwrap() % PLAYERS + 1;
+    let player = &game.players[&id];
+    let board = game.settings.board();
+    board
+        .neighbors(player.x, player.y, 1, Topology::Flat)
+        .filter(|&pos| board.manhattan((player.x, player.y), pos, Topology::Flat) == 1)
+        .map(|pos| chain_line(MoveLineType::Drive, id, Some(pos)))
+        .find(|drive| game.check(drive).is_ok())
+        .unwrap_or_else(|| MoveLine {
+            time: Some(index as u64),
+            ..chain_line(MoveLineType::HandleVotes, id, None)
+        })
+}
+
+/// Builds a valid, signed chain of `len` moves.
+pub fn chain(len: usize) -> Chain {
+    let keys = (0..PLAYERS).map(|_| get_random_keys()).collect::<Vec<_>>();
+    let users = keys
+        .iter()
+        .zip(1..)
+        .map(|((_, public), id)| (id, get_key(public.clone()).unwrap()))
+        .collect::<HashMap<_, _>>();
+    let game = database_game();
+    let mut state = game.clone().as_game(Vec::new(), &users).unwrap();
+    let mut moves = Vec::with_capacity(len);
+    while moves.len() < len {
+        let mut line = next_move(&state, moves.len());
+        let private_key = keys[usize::try_from(line.authorizer - 1).unwrap()]
+            .0
+            .clone();
+        line.sign(
+            moves.last().map(|l: &MoveLine| l.signature.as_str()),
+            private_key,
+        )
+        .unwrap();
+        state.load(line.clone(), &users).unwrap();
+        moves.push(line);
+    }
+    Chain { game, users, moves }
+}
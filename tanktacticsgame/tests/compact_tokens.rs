@@ -0,0 +1,95 @@
+//! Checks that the compact wire format of `/tokens` round-trips and stays below 60% of the JSON size,
+//! and that packed boards and state hashes follow the game.
+
+use tanktacticsgame::{
+    decode_tokens, encode_tokens, Game, MoveLine, MoveLineType, Step, RULES_VERSION,
+};
+
+mod common;
+
+#[test]
+fn compact_tokens_round_trip() {
+    let chain = common::chain(500);
+    let compact = encode_tokens(&chain.moves);
+    let decoded = decode_tokens(&compact).unwrap();
+    assert_eq!(decoded, chain.moves);
+    chain.game.as_game(decoded, &chain.users).unwrap();
+
+    let json = serde_json::to_string(&chain.moves).unwrap();
+    assert!(
+        compact.len() * 5 < json.len() * 3,
+        "compact tokens are {} bytes, json is {} bytes",
+        compact.len(),
+        json.len()
+    );
+}
+
+#[test]
+fn compact_tokens_escape_poll_text() {
+    let poll = vec![MoveLine {
+        move_type: MoveLineType::Poll,
+        x: None,
+        y: None,
+        target: None,
+        amount: None,
+        text: Some("Who next? | \\n\nRed\nBlue".into()),
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: None,
+        authorizer: 1,
+        signature: "c2lnbmF0dXJl".into(),
+    }];
+    assert_eq!(decode_tokens(&encode_tokens(&poll)).unwrap(), poll);
+}
+
+#[test]
+fn compact_tokens_keep_drive_path_and_rules_version() {
+    let drive = vec![MoveLine {
+        move_type: MoveLineType::Drive,
+        x: Some(3),
+        y: Some(1),
+        target: None,
+        amount: None,
+        text: None,
+        time: None,
+        clock: None,
+        path: Some(vec![Step { x: 1, y: 1 }, Step { x: 2, y: 1 }]),
+        rules_version: Some(RULES_VERSION),
+        authorizer: 1,
+        signature: "c2lnbmF0dXJl".into(),
+    }];
+    assert_eq!(decode_tokens(&encode_tokens(&drive)).unwrap(), drive);
+}
+
+#[test]
+fn packed_board_round_trips() {
+    let chain = common::chain(500);
+    let game = chain.game.as_game(chain.moves, &chain.users).unwrap();
+    let packed = game.pack_board();
+    let mut unpacked = Game::new(game.id, game.settings.clone());
+    unpacked.unpack_board(&packed).unwrap();
+    assert_eq!(unpacked.players, game.players);
+    assert_eq!(unpacked.board, game.board);
+    assert!(
+        packed.len() < 8 * game.players.len() + 8,
+        "{} bytes",
+        packed.len()
+    );
+    assert!(unpacked.unpack_board(&packed[..packed.len() - 1]).is_err());
+}
+
+#[test]
+fn state_hash_follows_the_game() {
+    let chain = common::chain(200);
+    let replay = |moves: &[MoveLine]| {
+        chain
+            .game
+            .clone()
+            .as_game(moves.to_vec(), &chain.users)
+            .unwrap()
+            .state_hash()
+    };
+    assert_eq!(replay(&chain.moves), replay(&chain.moves));
+    assert_ne!(replay(&chain.moves), replay(&chain.moves[..199]));
+}
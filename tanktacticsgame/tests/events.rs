@@ -0,0 +1,58 @@
+//! Checks the events moves queue, and that draining empties the queue.
+
+use tanktacticsgame::{Game, GameEvent, MoveLineType, Settings, Step};
+
+mod common;
+use common::line;
+
+#[test]
+fn moves_queue_events() {
+    let mut game = Game::new(
+        1,
+        Settings {
+            width: 3,
+            height: 1,
+            health: 1,
+            ..Settings::default()
+        },
+    );
+    for user in 1..=3 {
+        let (x, y) = game.get_pos();
+        let mut join = line(user, MoveLineType::Join);
+        join.x = Some(x);
+        join.y = Some(y);
+        game.apply(join).unwrap();
+        assert_eq!(
+            game.drain_events(),
+            vec![GameEvent::PlayerJoined {
+                user,
+                at: Step { x, y }
+            }]
+        );
+    }
+    assert!(game.drain_events().is_empty());
+
+    let mut shoot = line(1, MoveLineType::Shoot);
+    shoot.target = Some(2);
+    game.apply(shoot).unwrap();
+    assert_eq!(
+        game.drain_events(),
+        vec![
+            GameEvent::PlayerShot {
+                user: 2,
+                shooter: 1
+            },
+            GameEvent::PlayerKilled {
+                user: 2,
+                killer: Some(1)
+            },
+        ]
+    );
+
+    let mut handle = line(1, MoveLineType::HandleVotes);
+    handle.time = Some(1_000);
+    game.apply(handle).unwrap();
+    assert!(game
+        .drain_events()
+        .contains(&GameEvent::RoundStarted { round: game.round }));
+}
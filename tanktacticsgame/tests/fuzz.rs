@@ -0,0 +1,80 @@
+//! Feeds randomly generated (but correctly signed) moves into a game, checking that the engine never panics.
+
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha8Rng,
+};
+use std::collections::HashMap;
+use tanktacticsgame::{get_key, get_random_keys, Game, MoveLine, MoveLineType, Settings, Step};
+
+const TYPES: [MoveLineType; 12] = [
+    MoveLineType::Join,
+    MoveLineType::Drive,
+    MoveLineType::Shoot,
+    MoveLineType::Gift,
+    MoveLineType::Vote,
+    MoveLineType::HandleVotes,
+    MoveLineType::Upgrade,
+    MoveLineType::Bounty,
+    MoveLineType::Poll,
+    MoveLineType::PollVote,
+    MoveLineType::ClosePoll,
+    MoveLineType::Surrender,
+];
+
+#[test]
+fn random_moves_never_panic() {
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+    let keys = (0..4).map(|_| get_random_keys()).collect::<Vec<_>>();
+    let users = keys
+        .iter()
+        .zip(1..)
+        .map(|((_, public), id)| (id, get_key(public.clone()).unwrap()))
+        .collect::<HashMap<_, _>>();
+    let mut game = Game::new(
+        1,
+        Settings {
+            health: 1,
+            drive_length: 3,
+            ..Settings::default()
+        },
+    );
+    let mut small = |max: u32| rng.next_u32() % max;
+    for _ in 0..500 {
+        let authorizer = small(5).cast_signed();
+        let mut line = MoveLine {
+            move_type: TYPES[small(12) as usize].clone(),
+            x: Some(small(7)),
+            y: Some(small(7)),
+            target: Some(small(5).cast_signed()),
+            amount: Some(small(3)),
+            text: Some("Who next?\nRed\nBlue".into()),
+            time: Some(u64::from(small(100))),
+            clock: None,
+            path: (small(2) == 0).then(|| {
+                vec![Step {
+                    x: small(7),
+                    y: small(7),
+                }]
+            }),
+            rules_version: None,
+            authorizer,
+            signature: String::new(),
+        };
+        if line.move_type == MoveLineType::Join {
+            let (x, y) = game.get_pos();
+            line.x = Some(x);
+            line.y = Some(y);
+        }
+        let Some((private_key, _)) = keys.get((authorizer as usize).wrapping_sub(1)) else {
+            continue;
+        };
+        line.sign(
+            game.lines.last().map(|l| l.signature.as_str()),
+            private_key.clone(),
+        )
+        .unwrap();
+        let _ = game.load(line, &users);
+    }
+    assert!(game.players.values().all(|p| p.health.get() <= 1));
+}
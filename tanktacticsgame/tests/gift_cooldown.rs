@@ -0,0 +1,37 @@
+//! Checks that `Settings::gift_cooldown` makes a player wait before gifting the same player again.
+
+use tanktacticsgame::{Game, MoveLineType, Settings};
+
+mod common;
+use common::{line, targeted};
+
+#[test]
+fn gifts_to_the_same_player_wait_for_the_cooldown() {
+    let mut game = Game::new(
+        1,
+        Settings {
+            width: 2,
+            height: 1,
+            starting_points: 5,
+            gift_cooldown: 2,
+            ..Settings::default()
+        },
+    );
+    for user in 1..=2 {
+        let (x, y) = game.get_pos();
+        let mut join = line(user, MoveLineType::Join);
+        join.x = Some(x);
+        join.y = Some(y);
+        game.apply(join).unwrap();
+    }
+    let gift = targeted(1, MoveLineType::Gift, 2);
+    game.apply(gift.clone()).unwrap();
+    assert!(game.check(&gift).is_err());
+    // Gifts the other way have a cooldown of their own.
+    game.apply(targeted(2, MoveLineType::Gift, 1)).unwrap();
+
+    game.apply(line(1, MoveLineType::HandleVotes)).unwrap();
+    assert!(game.check(&gift).is_err());
+    game.apply(line(1, MoveLineType::HandleVotes)).unwrap();
+    game.apply(gift).unwrap();
+}
@@ -0,0 +1,66 @@
+//! Checks buying and using items, and that used items end with the round.
+
+use tanktacticsgame::{Error, Game, Item, MoveLine, MoveLineType, Settings};
+
+mod common;
+use common::line;
+
+fn item(authorizer: i32, move_type: MoveLineType, item: Item) -> MoveLine {
+    let mut line = line(authorizer, move_type);
+    line.text = Some(item.to_string());
+    line
+}
+
+#[test]
+fn items_are_bought_used_and_end_with_the_round() {
+    let mut game = Game::new(
+        1,
+        Settings {
+            starting_points: 5,
+            items: vec![Item::Smoke, Item::Radar],
+            ..Settings::default()
+        },
+    );
+    for user in [1, 2] {
+        let (x, y) = game.get_pos();
+        let mut join = line(user, MoveLineType::Join);
+        join.x = Some(x);
+        join.y = Some(y);
+        game.apply(join).unwrap();
+    }
+    assert!(game
+        .apply(item(2, MoveLineType::BuyItem, Item::ExtraMove))
+        .is_err());
+    assert!(game
+        .apply(item(2, MoveLineType::UseItem, Item::Smoke))
+        .is_err());
+
+    game.apply(item(2, MoveLineType::BuyItem, Item::Smoke))
+        .unwrap();
+    assert_eq!(game.players[&2].points.get(), 4);
+    game.apply(item(2, MoveLineType::UseItem, Item::Smoke))
+        .unwrap();
+    assert_eq!(game.players[&2].effects, vec![Item::Smoke]);
+
+    let mut shoot = line(1, MoveLineType::Shoot);
+    shoot.target = Some(2);
+    assert!(
+        matches!(game.check(&shoot), Err(Error::Other(e)) if e.contains("smoke")),
+        "smoke should hide the tank"
+    );
+
+    let mut packed = Game::new(1, game.settings.clone());
+    packed.unpack_board(&game.pack_board()).unwrap();
+    assert_eq!(packed.players, game.players);
+
+    game.apply(item(1, MoveLineType::BuyItem, Item::Radar))
+        .unwrap();
+    game.apply(item(1, MoveLineType::UseItem, Item::Radar))
+        .unwrap();
+    let player = &game.players[&1];
+    assert_eq!(game.player_range(player), game.range(player.level) + 1);
+
+    game.apply(line(1, MoveLineType::HandleVotes)).unwrap();
+    assert!(game.players.values().all(|p| p.effects.is_empty()));
+    assert!(!matches!(game.check(&shoot), Err(Error::Other(e)) if e.contains("smoke")));
+}
@@ -0,0 +1,54 @@
+//! Checks that a `JoinRandom` move signed before a game existed can be appended to it, at the tile the game picks.
+
+use std::collections::HashMap;
+use tanktacticsgame::{get_key, get_random_keys, Game, MoveLine, MoveLineType, Settings};
+
+fn join_random(authorizer: i32, text: Option<&str>) -> MoveLine {
+    MoveLine {
+        move_type: MoveLineType::JoinRandom,
+        x: None,
+        y: None,
+        target: None,
+        amount: None,
+        text: text.map(Into::into),
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: None,
+        authorizer,
+        signature: String::new(),
+    }
+}
+
+#[test]
+fn presigned_joins_land_on_the_picked_tile() {
+    let (first_private, first_public) = get_random_keys();
+    let (second_private, second_public) = get_random_keys();
+    let users = HashMap::from([
+        (1, get_key(first_public).unwrap()),
+        (2, get_key(second_public).unwrap()),
+    ]);
+    let mut first = join_random(1, Some("queue|Classic|0"));
+    first.sign(None, first_private).unwrap();
+    let mut second = join_random(2, Some("queue|Classic|0"));
+    second.sign(None, second_private).unwrap();
+
+    let mut game = Game::new(1, Settings::default());
+    for line in [first, second] {
+        let user = line.authorizer;
+        let pos = game.get_pos();
+        game.load(line.clone(), &users).unwrap();
+        assert_eq!((game.players[&user].x, game.players[&user].y), pos);
+        // The text form reads back as the same move.
+        assert_eq!(line.to_string().parse::<MoveLine>().unwrap(), line);
+    }
+
+    let mut game = Game::new(1, Settings::default());
+    assert!(game.apply(join_random(1, None)).is_err());
+    let placed = MoveLine {
+        x: Some(0),
+        y: Some(0),
+        ..join_random(1, Some("queue|Classic|0"))
+    };
+    assert!(game.apply(placed).is_err());
+}
@@ -0,0 +1,93 @@
+//! Checks that randomly generated level range maps round-trip, that expressions evaluate like a reference evaluator,
+//! and that deeply nested expressions are refused instead of overflowing the stack.
+
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha8Rng,
+};
+use tanktacticsgame::LevelRangeMap;
+
+/// A random expression, fully parenthesized, with its value at `level`.
+fn expr(rng: &mut ChaCha8Rng, depth: u32, level: u32) -> (String, u32) {
+    if depth == 0 || rng.next_u32().is_multiple_of(3) {
+        return if rng.next_u32().is_multiple_of(2) {
+            ("level".into(), level)
+        } else {
+            let n = rng.next_u32() % 100;
+            (n.to_string(), n)
+        };
+    }
+    let (a, x) = expr(rng, depth - 1, level);
+    let (b, y) = expr(rng, depth - 1, level);
+    match rng.next_u32() % 4 {
+        0 => (format!("({a} + {b})"), x.saturating_add(y)),
+        1 => (format!("({a}-{b})"), x.saturating_sub(y)),
+        2 => (format!("({a} * {b})"), x.saturating_mul(y)),
+        _ => (format!("({a}/{b})"), x.checked_div(y).unwrap_or(0)),
+    }
+}
+
+/// A random level range map, in its text form.
+fn map(rng: &mut ChaCha8Rng, depth: u32) -> String {
+    match rng.next_u32() % if depth == 0 { 4 } else { 5 } {
+        0 => "L".into(),
+        1 => format!(
+            "A{}",
+            (0..=rng.next_u32() % 4)
+                .map(|_| (rng.next_u32() % 10).to_string())
+                .collect::<Vec<_>>()
+                .join(".")
+        ),
+        2 => format!("F{}.{}", rng.next_u32() % 10, rng.next_u32() % 10),
+        3 => format!("E{}", expr(rng, 4, 0).0),
+        _ => {
+            let min = rng.next_u32() % 10;
+            let max = min + rng.next_u32() % 10;
+            format!("C{min}.{max}.{}", map(rng, depth - 1))
+        }
+    }
+}
+
+#[test]
+fn maps_round_trip() {
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+    for _ in 0..500 {
+        let text = map(&mut rng, 3);
+        let parsed = text.parse::<LevelRangeMap>().unwrap();
+        assert_eq!(parsed.to_string(), text);
+        assert!(parsed.to_string().parse::<LevelRangeMap>().unwrap() == parsed);
+    }
+}
+
+#[test]
+fn expressions_evaluate_like_the_reference() {
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+    for _ in 0..500 {
+        let level = rng.next_u32() % 5;
+        let (text, value) = expr(&mut rng, 6, level);
+        let map = format!("E{text}").parse::<LevelRangeMap>().unwrap();
+        assert_eq!(map.get_range(level), value, "{text} at level {level}");
+    }
+    // Without parentheses, products go before sums and operators of the same kind go from left to right.
+    let map = "E1 + 2 * level - 6 / 2".parse::<LevelRangeMap>().unwrap();
+    assert_eq!(map.get_range(3), 4);
+}
+
+#[test]
+fn deep_expressions_are_refused() {
+    let nested = |depth: usize| format!("E{}level{}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(nested(32).parse::<LevelRangeMap>().unwrap().get_range(2), 2);
+    assert!(nested(33).parse::<LevelRangeMap>().is_err());
+    assert!(nested(100_000).parse::<LevelRangeMap>().is_err());
+}
+
+#[test]
+fn deep_clamped_maps_are_refused() {
+    let nested = |depth: usize| format!("{}L", "C0.9.".repeat(depth));
+    assert!(nested(32).parse::<LevelRangeMap>().is_ok());
+    assert!(nested(33).parse::<LevelRangeMap>().is_err());
+    assert!(nested(200_000).parse::<LevelRangeMap>().is_err());
+    assert!(format!("A{}", ".1".repeat(1000))
+        .parse::<LevelRangeMap>()
+        .is_err());
+}
@@ -0,0 +1,70 @@
+//! Checks that loans are paid back when they are due, and that a borrower that can't pay defaults.
+
+use tanktacticsgame::{Game, MoveLine, MoveLineType, Settings, TransferKind};
+
+mod common;
+use common::line;
+
+fn lend(lender: i32, borrower: i32, amount: u32, rounds: u32) -> MoveLine {
+    MoveLine {
+        target: Some(borrower),
+        amount: Some(amount),
+        x: Some(rounds),
+        ..line(lender, MoveLineType::Lend)
+    }
+}
+
+#[test]
+fn loans_are_paid_back_or_defaulted() {
+    let mut game = Game::new(
+        1,
+        Settings {
+            width: 3,
+            height: 3,
+            starting_points: 4,
+            ..Settings::default()
+        },
+    );
+    for user in 1..=3 {
+        let (x, y) = game.get_pos();
+        let mut join = line(user, MoveLineType::Join);
+        join.x = Some(x);
+        join.y = Some(y);
+        game.apply(join).unwrap();
+    }
+    assert!(game.apply(lend(1, 1, 1, 1)).is_err());
+    assert!(game.apply(lend(1, 2, 0, 1)).is_err());
+    assert!(game.apply(lend(1, 2, 1, 0)).is_err());
+    assert!(game.apply(lend(1, 2, 5, 1)).is_err());
+    game.apply(lend(1, 2, 2, 2)).unwrap();
+    game.apply(lend(3, 2, 4, 1)).unwrap();
+    assert_eq!(game.players[&2].points.get(), 10);
+    // Tank 2 spends everything but the point it gets when the votes are handled.
+    game.apply(MoveLine {
+        target: Some(1),
+        amount: Some(10),
+        x: Some(5),
+        ..line(2, MoveLineType::Lend)
+    })
+    .unwrap();
+
+    game.apply(line(1, MoveLineType::HandleVotes)).unwrap();
+    // Tank 3 was due and only got back the point tank 2 had, tank 1 isn't due yet.
+    assert_eq!(game.players[&3].points.get(), 2);
+    assert_eq!(game.loans.len(), 3);
+    assert!(game
+        .loans
+        .iter()
+        .any(|l| l.lender == 3 && l.amount == 3 && l.defaulted));
+    assert!(game
+        .loans
+        .iter()
+        .any(|l| l.lender == 1 && l.amount == 2 && !l.defaulted));
+    assert!(game
+        .transfers
+        .iter()
+        .any(|t| t.kind == TransferKind::Repayment && t.from == 2 && t.to == 3));
+
+    let text = lend(1, 2, 2, 2).to_string();
+    assert_eq!(text.parse::<MoveLine>().unwrap(), lend(1, 2, 2, 2));
+}
@@ -0,0 +1,50 @@
+//! Checks that a move that would overflow the points of a tank is refused without changing the game.
+
+use tanktacticsgame::{Game, MoveLine, MoveLineType, Settings};
+
+mod common;
+use common::line;
+
+/// Two tanks next to each other with all the points there are.
+fn rich() -> Game {
+    let mut game = Game::new(
+        1,
+        Settings {
+            width: 2,
+            height: 1,
+            health: 1,
+            starting_points: u32::MAX,
+            ..Settings::default()
+        },
+    );
+    for user in 1..=2 {
+        let (x, y) = game.get_pos();
+        let mut join = line(user, MoveLineType::Join);
+        join.x = Some(x);
+        join.y = Some(y);
+        game.apply(join).unwrap();
+    }
+    game.drain_events();
+    game
+}
+
+#[test]
+fn overflowing_moves_change_nothing() {
+    for (move_type, target) in [
+        (MoveLineType::Gift, Some(2)),
+        (MoveLineType::Shoot, Some(2)),
+        (MoveLineType::HandleVotes, None),
+    ] {
+        let mut game = rich();
+        let before = game.state_hash();
+        assert!(game
+            .apply(MoveLine {
+                target,
+                ..line(1, move_type)
+            })
+            .is_err());
+        assert_eq!(game.state_hash(), before);
+        assert!(game.drain_events().is_empty());
+        assert!(game.transfers.is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+//! Checks that moves signed through `WebAuthn` assertions of a passkey are accepted, and only for the site of the
+//! passkey.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use p256::{
+    ecdsa::{signature::Signer, DerSignature, SigningKey},
+    pkcs8::EncodePublicKey,
+};
+use rand_chacha::rand_core::OsRng;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tanktacticsgame::{
+    get_key, passkey_challenge, passkey_signature, verify_data, verify_passkey_registration, Game,
+    MoveLine, MoveLineType, PublicKey, Settings, BASE64,
+};
+
+/// Signs `data` the way an authenticator answers `navigator.credentials.get` on `origin`, for the site `rp_id`.
+fn assert_on(key: &SigningKey, data: &str, origin: &str, rp_id: &str) -> String {
+    let client_data = format!(
+        r#"{{"type":"webauthn.get","challenge":"{}","origin":"{origin}"}}"#,
+        URL_SAFE_NO_PAD.encode(passkey_challenge(data))
+    );
+    let mut authenticator_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+    authenticator_data.extend([1, 0, 0, 0, 1]);
+    let mut message = authenticator_data.clone();
+    message.extend(Sha256::digest(client_data.as_bytes()));
+    let signature: DerSignature = key.sign(&message);
+    passkey_signature(
+        &BASE64.encode(authenticator_data),
+        &BASE64.encode(client_data),
+        &BASE64.encode(signature.as_bytes()),
+    )
+}
+
+fn assert(key: &SigningKey, data: &str) -> String {
+    assert_on(key, data, "http://localhost:8080", "localhost")
+}
+
+/// The authenticator data of a registration of `key` as `credential_id` for the site `rp_id`, with the key in COSE.
+fn registration(key: &SigningKey, credential_id: &[u8], rp_id: &str) -> Vec<u8> {
+    let point = key.verifying_key().to_encoded_point(false);
+    let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+    data.extend([0x41, 0, 0, 0, 0]);
+    data.extend([0; 16]);
+    data.extend(u16::try_from(credential_id.len()).unwrap().to_be_bytes());
+    data.extend(credential_id);
+    data.extend([0xa5, 0x01, 0x02, 0x03, 0x26, 0x20, 0x01, 0x21, 0x58, 0x20]);
+    data.extend(point.x().unwrap());
+    data.extend([0x22, 0x58, 0x20]);
+    data.extend(point.y().unwrap());
+    data
+}
+
+#[test]
+fn passkey_signed_moves_verify() {
+    let key = SigningKey::random(&mut OsRng);
+    let der = key.verifying_key().to_public_key_der().unwrap();
+    let stored = PublicKey::passkey_from_der(der.as_bytes(), "localhost").unwrap();
+    let public_key = get_key(stored).unwrap();
+    assert!(matches!(public_key, PublicKey::P256 { .. }));
+
+    let mut game = Game::new(1, Settings::default());
+    let (x, y) = game.get_pos();
+    let mut line = MoveLine {
+        move_type: MoveLineType::Join,
+        x: Some(x),
+        y: Some(y),
+        target: None,
+        amount: None,
+        text: None,
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: None,
+        authorizer: 1,
+        signature: String::new(),
+    };
+    let data = line.signing_data(None);
+    line.signature = assert(&key, &data);
+
+    assert!(verify_data(&public_key, &data, &line.signature));
+    assert!(!verify_data(&public_key, "1H|", &line.signature));
+    game.load(line, &HashMap::from([(1, public_key)])).unwrap();
+}
+
+#[test]
+fn assertions_for_other_sites_are_refused() {
+    let key = SigningKey::random(&mut OsRng);
+    let der = key.verifying_key().to_public_key_der().unwrap();
+    let public_key =
+        get_key(PublicKey::passkey_from_der(der.as_bytes(), "tanktactics.example").unwrap())
+            .unwrap();
+    let data = "1H|";
+    let signed = |origin, rp_id| assert_on(&key, data, origin, rp_id);
+
+    assert!(verify_data(
+        &public_key,
+        data,
+        &signed("https://tanktactics.example", "tanktactics.example")
+    ));
+    assert!(!verify_data(
+        &public_key,
+        data,
+        &signed("https://evil.example", "evil.example")
+    ));
+    assert!(!verify_data(
+        &public_key,
+        data,
+        &signed("https://evil.example", "tanktactics.example")
+    ));
+    assert!(!verify_data(
+        &public_key,
+        data,
+        &signed("http://tanktactics.example", "tanktactics.example")
+    ));
+
+    // Passkeys stored before the site was kept still verify, so their games replay.
+    let legacy = PublicKey::passkey_from_der(der.as_bytes(), "localhost")
+        .unwrap()
+        .replace("P256:localhost:", "P256:");
+    let legacy = get_key(legacy).unwrap();
+    assert!(matches!(legacy, PublicKey::P256 { rp_id: None, .. }));
+    assert!(verify_data(
+        &legacy,
+        data,
+        &signed("https://tanktactics.example", "tanktactics.example")
+    ));
+}
+
+#[test]
+fn registrations_attest_their_key() {
+    let key = SigningKey::random(&mut OsRng);
+    let der = key.verifying_key().to_public_key_der().unwrap();
+    let data = registration(&key, b"credential", "localhost");
+    assert!(verify_passkey_registration(
+        &data,
+        "localhost",
+        b"credential",
+        der.as_bytes()
+    ));
+    assert!(!verify_passkey_registration(
+        &data,
+        "evil.example",
+        b"credential",
+        der.as_bytes()
+    ));
+    assert!(!verify_passkey_registration(
+        &data,
+        "localhost",
+        b"other",
+        der.as_bytes()
+    ));
+    let other = SigningKey::random(&mut OsRng);
+    let other = other.verifying_key().to_public_key_der().unwrap();
+    assert!(!verify_passkey_registration(
+        &data,
+        "localhost",
+        b"credential",
+        other.as_bytes()
+    ));
+    assert!(!verify_passkey_registration(
+        &data[..40],
+        "localhost",
+        b"credential",
+        der.as_bytes()
+    ));
+}
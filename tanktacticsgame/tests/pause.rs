@@ -0,0 +1,42 @@
+//! Checks that a paused game hands out no points and continues its round where it was left once resumed.
+
+use tanktacticsgame::{Game, MoveLine, MoveLineType, Settings};
+
+mod common;
+use common::{line, timed};
+
+#[test]
+fn paused_games_hand_out_nothing() {
+    let mut game = Game::new(1, Settings::default());
+    for user in 1..=2 {
+        let (x, y) = game.get_pos();
+        let mut join = line(user, MoveLineType::Join);
+        join.x = Some(x);
+        join.y = Some(y);
+        game.apply(join).unwrap();
+    }
+    game.apply(timed(1, MoveLineType::HandleVotes, 1_000))
+        .unwrap();
+    let points = game.players[&1].points.get();
+
+    assert!(game.apply(timed(1, MoveLineType::Resume, 1_100)).is_err());
+    assert!(game.apply(timed(1, MoveLineType::Pause, 999)).is_err());
+    game.apply(timed(1, MoveLineType::Pause, 1_100)).unwrap();
+    assert!(game.is_paused());
+    assert!(game.apply(timed(1, MoveLineType::Pause, 1_200)).is_err());
+    assert!(game
+        .apply(timed(1, MoveLineType::HandleVotes, 1_200))
+        .is_err());
+    assert!(game.apply(line(2, MoveLineType::Upgrade)).is_err());
+    assert_eq!(game.players[&1].points.get(), points);
+
+    game.apply(timed(2, MoveLineType::Resume, 1_500)).unwrap();
+    assert!(!game.is_paused());
+    // The 400 seconds of the pause don't count towards the round.
+    assert_eq!(game.last_vote, 1_400);
+    game.apply(timed(2, MoveLineType::HandleVotes, 1_600))
+        .unwrap();
+
+    let pause = timed(1, MoveLineType::Pause, 1_700);
+    assert_eq!(pause.to_string().parse::<MoveLine>().unwrap(), pause);
+}
@@ -0,0 +1,63 @@
+//! Checks that moves draw from their own stream of the seed, and that a snapshot keeps the position of the random
+//! numbers of older games.
+
+use tanktacticsgame::{Game, Item, MoveLineType, Settings};
+
+mod common;
+use common::line;
+
+fn join(game: &mut Game, user: i32) -> (u32, u32) {
+    let (x, y) = game.get_pos();
+    let mut join = line(user, MoveLineType::Join);
+    join.x = Some(x);
+    join.y = Some(y);
+    game.apply(join).unwrap();
+    (x, y)
+}
+
+/// Where the third tank joins after a round was handled, with supplies dropped or not.
+fn third_spawn(rules_version: u32, items: Vec<Item>) -> (u32, u32) {
+    let mut game = Game::new(
+        1,
+        Settings {
+            rules_version,
+            items,
+            ..Settings::default()
+        },
+    );
+    join(&mut game, 1);
+    join(&mut game, 2);
+    let mut handle = line(1, MoveLineType::HandleVotes);
+    handle.time = Some(1_000);
+    game.apply(handle).unwrap();
+    join(&mut game, 3)
+}
+
+#[test]
+fn moves_draw_from_their_own_stream() {
+    // Dropping supplies draws random numbers, which doesn't move where later tanks join.
+    assert_eq!(
+        third_spawn(2, Item::ALL.to_vec()),
+        third_spawn(2, Vec::new())
+    );
+    assert_ne!(
+        third_spawn(1, Item::ALL.to_vec()),
+        third_spawn(1, Vec::new())
+    );
+}
+
+#[test]
+fn snapshots_keep_the_random_position() {
+    let settings = Settings {
+        rules_version: 1,
+        ..Settings::default()
+    };
+    let mut game = Game::new(1, settings.clone());
+    for user in 1..=3 {
+        join(&mut game, user);
+    }
+    let mut unpacked = Game::new(1, settings);
+    unpacked.unpack_board(&game.pack_board()).unwrap();
+    assert_eq!(unpacked.rand.get_word_pos(), game.rand.get_word_pos());
+    assert_eq!(unpacked.get_pos(), game.get_pos());
+}
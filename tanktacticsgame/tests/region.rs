@@ -0,0 +1,60 @@
+//! Checks the tiles moves affect, the tanks in a viewport and the minimap of a large board.
+
+use tanktacticsgame::{Game, MoveLineType, Region, Settings};
+
+mod common;
+use common::line;
+
+#[test]
+fn moves_affect_their_tiles() {
+    let mut game = Game::new(
+        1,
+        Settings {
+            width: 200,
+            height: 200,
+            ..Settings::default()
+        },
+    );
+    for user in 1..=2 {
+        let (x, y) = game.get_pos();
+        let mut join = line(user, MoveLineType::Join);
+        join.x = Some(x);
+        join.y = Some(y);
+        assert_eq!(game.affected_tiles(&join), Some(vec![(x, y)]));
+        game.apply(join).unwrap();
+    }
+    let tank = game.players[&1].clone();
+    let other = (game.players[&2].x, game.players[&2].y);
+
+    let mut drive = line(1, MoveLineType::Drive);
+    let to = if tank.x > 0 { tank.x - 1 } else { tank.x + 1 };
+    drive.x = Some(to);
+    drive.y = Some(tank.y);
+    let tiles = game.affected_tiles(&drive).unwrap();
+    assert!(tiles.contains(&(tank.x, tank.y)) && tiles.contains(&(to, tank.y)));
+
+    let mut gift = line(1, MoveLineType::Gift);
+    gift.target = Some(2);
+    assert_eq!(
+        game.affected_tiles(&gift),
+        Some(vec![other, (tank.x, tank.y)])
+    );
+    assert_eq!(
+        game.affected_tiles(&line(1, MoveLineType::HandleVotes)),
+        None
+    );
+
+    let view = Region {
+        x: tank.x.saturating_sub(2),
+        y: tank.y.saturating_sub(2),
+        width: 5,
+        height: 5,
+    };
+    assert!(game.view(view).iter().any(|t| t.user == 1 && t.alive));
+    assert!(game.view(view).iter().all(|t| view.contains((t.x, t.y))));
+
+    let minimap = game.minimap(50);
+    assert_eq!((minimap.cell, minimap.width, minimap.height), (4, 50, 50));
+    assert_eq!(minimap.tanks.iter().sum::<u32>(), 2);
+    assert!(minimap.tanks[(tank.y / 4 * 50 + tank.x / 4) as usize] > 0);
+}
@@ -0,0 +1,49 @@
+//! Fails if a cold replay takes longer than `TANKTACTICS_REPLAY_BUDGET_MS` (default 20 seconds), and checks that
+//! replays skipping verified signatures still refuse moves that were changed.
+
+use std::time::{Duration, Instant};
+use tanktacticsgame::VerifiedCache;
+
+mod common;
+
+#[test]
+fn cold_replay_within_budget() {
+    let budget = std::env::var("TANKTACTICS_REPLAY_BUDGET_MS")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .map_or(Duration::from_secs(20), Duration::from_millis);
+    let chain = common::chain(1_000);
+
+    let start = Instant::now();
+    chain.game.as_game(chain.moves, &chain.users).unwrap();
+    let elapsed = start.elapsed();
+
+    assert!(
+        elapsed <= budget,
+        "replaying 1000 moves took {elapsed:?}, budget is {budget:?}"
+    );
+}
+
+#[test]
+fn verified_replays_refuse_changed_moves() {
+    let chain = common::chain(50);
+    let verified = VerifiedCache::new(100);
+    let replay = |moves: Vec<_>| {
+        chain
+            .game
+            .clone()
+            .as_game_verified(moves, &chain.users, &verified)
+    };
+    let game = replay(chain.moves.clone()).unwrap();
+    assert_eq!(
+        replay(chain.moves.clone()).unwrap().state_hash(),
+        game.state_hash()
+    );
+
+    let mut changed = chain.moves.clone();
+    changed[20].x = changed[20].x.map(|x| x + 1);
+    assert!(replay(changed).is_err());
+    let mut changed = chain.moves.clone();
+    changed[30].authorizer = changed[30].authorizer % 8 + 1;
+    assert!(replay(changed).is_err());
+}
@@ -0,0 +1,38 @@
+//! Checks that sealed private keys are read back as written, and malformed ones are refused.
+
+use tanktacticsgame::{SealedKey, BASE64};
+
+use base64::Engine;
+
+#[test]
+fn sealed_keys_round_trip() {
+    let sealed = SealedKey {
+        kdf: SealedKey::KDF.into(),
+        iterations: SealedKey::ITERATIONS,
+        salt: BASE64.encode([1; 16]),
+        nonce: BASE64.encode([2; 12]),
+        ciphertext: BASE64.encode([3; 48]),
+    };
+    let stored = sealed.to_string();
+    assert!(SealedKey::is_sealed(&stored));
+    assert_eq!(SealedKey::parse(&stored), Some(sealed.clone()));
+
+    assert!(!SealedKey::is_sealed("passkey:abc"));
+    let short_nonce = SealedKey {
+        nonce: BASE64.encode([2; 8]),
+        ..sealed.clone()
+    };
+    assert_eq!(SealedKey::parse(&short_nonce.to_string()), None);
+    let unknown_kdf = SealedKey {
+        kdf: "md5".into(),
+        ..sealed.clone()
+    };
+    assert_eq!(SealedKey::parse(&unknown_kdf.to_string()), None);
+    let pbkdf2 = SealedKey {
+        kdf: SealedKey::PBKDF2.into(),
+        iterations: 600_000,
+        ..sealed.clone()
+    };
+    assert_eq!(SealedKey::parse(&pbkdf2.to_string()), Some(pbkdf2));
+    assert_eq!(SealedKey::parse(&format!("{stored}:extra")), None);
+}
@@ -0,0 +1,91 @@
+//! Checks that with `Settings::symmetric_spawns` tanks join as far as possible from the other tanks, the same way for
+//! every game with the same seed.
+
+use tanktacticsgame::{Game, MoveLine, MoveLineType, Settings, Topology};
+
+fn join(authorizer: i32, (x, y): (u32, u32)) -> MoveLine {
+    MoveLine {
+        move_type: MoveLineType::Join,
+        x: Some(x),
+        y: Some(y),
+        target: None,
+        amount: None,
+        text: None,
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: None,
+        authorizer,
+        signature: String::new(),
+    }
+}
+
+fn joined(seed: u64) -> Vec<(u32, u32)> {
+    let settings = Settings {
+        seed,
+        width: 9,
+        height: 9,
+        symmetric_spawns: true,
+        ..Settings::default()
+    };
+    let board = settings.board();
+    let mut game = Game::new(1, settings);
+    let mut positions = Vec::new();
+    for user in 1..=6 {
+        let pos = game.get_pos();
+        let nearest = |tile| {
+            game.board
+                .keys()
+                .map(|&tank| board.chebyshev(tile, tank, Topology::Flat))
+                .min()
+        };
+        let furthest = board
+            .tiles()
+            .filter(|tile| !game.board.contains_key(tile))
+            .map(nearest)
+            .max()
+            .unwrap();
+        assert_eq!(
+            nearest(pos),
+            furthest,
+            "tank {user} joined closer than needed"
+        );
+        game.apply(join(user, pos)).unwrap();
+        assert_eq!(game.players[&user].x, pos.0);
+        assert_eq!(game.players[&user].y, pos.1);
+        positions.push(pos);
+    }
+    positions
+}
+
+#[test]
+fn tanks_join_furthest_from_others() {
+    let positions = joined(7);
+    assert_eq!(positions, joined(7));
+    // The second tank goes to a corner opposite the first.
+    let ((ax, ay), (bx, by)) = (positions[0], positions[1]);
+    assert!(ax.abs_diff(bx).max(ay.abs_diff(by)) >= 4);
+}
+
+/// Whether a third tank can join a game of two players under `rules_version`.
+fn third_joins(rules_version: u32) -> bool {
+    let mut game = Game::new(
+        1,
+        Settings {
+            max_players: 2,
+            rules_version,
+            ..Settings::default()
+        },
+    );
+    for user in 1..=2 {
+        game.apply(join(user, game.get_pos())).unwrap();
+    }
+    game.check(&join(3, game.get_pos())).is_ok()
+}
+
+#[test]
+fn only_newer_rules_refuse_full_games() {
+    assert!(third_joins(1));
+    assert!(third_joins(2));
+    assert!(!third_joins(3));
+}
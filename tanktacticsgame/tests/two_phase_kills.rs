@@ -0,0 +1,64 @@
+//! Checks that with `Settings::two_phase_kills` a tank shot to 0 health is disabled, can be repaired, and is
+//! destroyed when the next round ends.
+
+use tanktacticsgame::{Game, GameStatus, MoveLineType, Settings};
+
+mod common;
+use common::{line, targeted};
+
+#[test]
+fn disabled_tanks_are_repaired_or_destroyed() {
+    let mut game = Game::new(
+        1,
+        Settings {
+            width: 3,
+            height: 1,
+            health: 1,
+            starting_points: 5,
+            two_phase_kills: true,
+            ..Settings::default()
+        },
+    );
+    for user in [1, 2, 3] {
+        let mut join = line(user, MoveLineType::Join);
+        join.x = Some(u32::try_from(user - 1).unwrap());
+        join.y = Some(0);
+        game.apply(join).unwrap();
+    }
+    assert!(game.apply(targeted(3, MoveLineType::Repair, 2)).is_err());
+
+    game.apply(targeted(1, MoveLineType::Shoot, 2)).unwrap();
+    let tank = &game.players[&2];
+    assert_eq!((tank.disabled_round, tank.died_round), (Some(0), None));
+    assert!(game.check(&line(2, MoveLineType::Upgrade)).is_err());
+    assert!(game.check(&targeted(2, MoveLineType::Vote, 1)).is_err());
+    assert!(game.check(&targeted(1, MoveLineType::Shoot, 2)).is_err());
+
+    game.apply(targeted(3, MoveLineType::Repair, 2)).unwrap();
+    let tank = &game.players[&2];
+    assert_eq!((tank.disabled_round, tank.health.get()), (None, 1));
+
+    game.apply(targeted(1, MoveLineType::Shoot, 2)).unwrap();
+    let points = game.players[&2].points.get();
+    game.apply(line(1, MoveLineType::HandleVotes)).unwrap();
+    assert_eq!(
+        game.players[&2].disabled_round,
+        Some(0),
+        "disabled for a full round"
+    );
+
+    let mut packed = Game::new(1, game.settings.clone());
+    packed.unpack_board(&game.pack_board()).unwrap();
+    assert_eq!(packed.players, game.players);
+
+    let before = game.players[&1].points.get();
+    game.apply(line(1, MoveLineType::HandleVotes)).unwrap();
+    let tank = &game.players[&2];
+    assert_eq!((tank.disabled_round, tank.died_round), (None, Some(1)));
+    assert_eq!(game.rounds[1].kills, vec![2]);
+    assert!(
+        game.players[&1].points.get() >= before + points,
+        "the shooter loots the tank"
+    );
+    assert_eq!(game.status(), GameStatus::Running);
+}
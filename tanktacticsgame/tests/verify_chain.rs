@@ -0,0 +1,28 @@
+//! Checks that `Game::verify_chain` finds the first broken move without changing the game.
+
+mod common;
+
+#[test]
+fn verify_chain_finds_the_first_broken_move() {
+    let chain = common::chain(50);
+    let game = chain
+        .game
+        .clone()
+        .as_game(Vec::new(), &chain.users)
+        .unwrap();
+    assert!(game.verify_chain(&chain.moves, &chain.users).is_ok());
+
+    let mut moves = chain.moves.clone();
+    moves[20].signature = moves[19].signature.clone();
+    let broken = game.verify_chain(&moves, &chain.users).unwrap_err();
+    assert_eq!(broken.index, 20);
+    assert_eq!(broken.to_string(), "Move 20: Invalid signature.");
+
+    moves.remove(20);
+    assert_eq!(
+        game.verify_chain(&moves, &chain.users).unwrap_err().index,
+        20,
+        "the move after a missing one is signed on top of the wrong move"
+    );
+    assert!(game.lines.is_empty());
+}
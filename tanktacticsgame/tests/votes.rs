@@ -0,0 +1,62 @@
+//! Checks that the tally of a round replayed from the moves matches what the round handed out.
+
+use tanktacticsgame::{Candidate, Game, MoveLineType, Settings, ThresholdMode};
+
+mod common;
+use common::{line, targeted};
+
+#[test]
+fn tallies_match_the_rounds() {
+    let settings = Settings {
+        threshold_mode: ThresholdMode::Fixed(2),
+        ..Settings::default()
+    };
+    let mut game = Game::new(1, settings.clone());
+    let mut lines = Vec::new();
+    for user in 1..=5 {
+        let (x, y) = game.get_pos();
+        let mut join = line(user, MoveLineType::Join);
+        join.x = Some(x);
+        join.y = Some(y);
+        lines.push(join);
+        game.apply(lines.last().unwrap().clone()).unwrap();
+    }
+    for user in [3, 4, 5] {
+        lines.push(line(user, MoveLineType::Surrender));
+    }
+    lines.push(targeted(3, MoveLineType::Vote, 1));
+    lines.push(targeted(4, MoveLineType::Vote, 1));
+    lines.push(targeted(5, MoveLineType::Vote, 2));
+    lines.push(line(1, MoveLineType::HandleVotes));
+    lines.push(targeted(5, MoveLineType::Vote, 1));
+    for line in lines.iter().skip(5) {
+        game.apply(line.clone()).unwrap();
+    }
+
+    let tally = Game::new(1, settings.clone())
+        .tally_round(lines.clone(), 0)
+        .unwrap();
+    assert_eq!((tally.round, tally.jury, tally.threshold), (0, 3, 2));
+    let summary = |c: &Candidate| (c.target, c.voters.clone(), c.reached, c.elected);
+    assert_eq!(
+        tally.candidates.iter().map(summary).collect::<Vec<_>>(),
+        vec![(1, vec![3, 4], true, true), (2, vec![5], false, false)]
+    );
+    assert_eq!(game.rounds[0].elected, vec![1]);
+
+    let current = Game::new(1, settings.clone())
+        .tally_round(lines.clone(), 1)
+        .unwrap();
+    assert_eq!(current, game.tally());
+    assert_eq!(current.candidates[0].voters, vec![5]);
+    assert_eq!(game.missing_voters(), vec![3, 4]);
+    assert!(Game::new(1, settings).tally_round(lines, 2).is_err());
+}
+
+#[test]
+fn deep_threshold_modes_are_refused() {
+    let nested = |depth: usize| format!("{}N1{}", "X.".repeat(depth), ".N2".repeat(depth));
+    assert!(nested(32).parse::<ThresholdMode>().is_ok());
+    assert!(nested(33).parse::<ThresholdMode>().is_err());
+    assert!("I.".repeat(200_000).parse::<ThresholdMode>().is_err());
+}
@@ -0,0 +1,137 @@
+//! Checks that win conditions round-trip and end the game.
+
+use tanktacticsgame::{Game, GameStatus, MoveLine, MoveLineType, Settings, WinCondition};
+
+mod common;
+use common::{line, targeted};
+
+#[test]
+fn win_conditions_round_trip() {
+    for text in ["L", "P10", "S5", "F3"] {
+        assert_eq!(text.parse::<WinCondition>().unwrap().to_string(), text);
+    }
+    for text in ["", "L1", "P", "Sx", "Q3"] {
+        assert!(text.parse::<WinCondition>().is_err());
+    }
+}
+
+#[test]
+fn surviving_the_rounds_wins() {
+    let mut chain = common::chain(300);
+    chain.game.win_condition = "S1".into();
+    let mut game = chain
+        .game
+        .clone()
+        .as_game(Vec::new(), &chain.users)
+        .unwrap();
+    // The chain goes on past the win, which the finished game refuses.
+    for line in chain.moves.clone() {
+        if game.status().is_over() {
+            break;
+        }
+        game.load(line, &chain.users).unwrap();
+    }
+    assert!(game.round >= 1);
+    let mut alive = game
+        .players
+        .values()
+        .filter(|p| p.is_alive(true).is_ok())
+        .map(|p| p.user)
+        .collect::<Vec<_>>();
+    alive.sort_unstable();
+    assert_eq!(game.status(), GameStatus::Won(alive));
+
+    chain.game.win_condition = String::new();
+    let game = chain.game.as_game(chain.moves, &chain.users).unwrap();
+    assert_eq!(game.status(), GameStatus::Running);
+}
+
+/// A game of `rules_version` on a board of three tiles, that the first of its two tanks won.
+fn won(rules_version: u32) -> Game {
+    let mut game = Game::new(
+        1,
+        Settings {
+            width: 3,
+            height: 1,
+            health: 1,
+            rules_version,
+            ..Settings::default()
+        },
+    );
+    assert_eq!(game.status(), GameStatus::Waiting);
+    for user in 1..=2 {
+        game.apply(join(&game, user)).unwrap();
+    }
+    assert_eq!(game.status(), GameStatus::Running);
+
+    game.apply(targeted(1, MoveLineType::Shoot, 2)).unwrap();
+    assert_eq!(game.status(), GameStatus::Won(vec![1]));
+    assert!(game.status().is_over());
+    game
+}
+
+fn join(game: &Game, user: i32) -> MoveLine {
+    let (x, y) = game.get_pos();
+    let mut join = line(user, MoveLineType::Join);
+    join.x = Some(x);
+    join.y = Some(y);
+    join
+}
+
+#[test]
+fn finished_games_only_take_votes() {
+    let mut game = won(4);
+    assert!(game.check(&join(&game, 3)).is_err());
+    game.apply(targeted(2, MoveLineType::Vote, 1)).unwrap();
+    game.apply(line(1, MoveLineType::HandleVotes)).unwrap();
+
+    // Older games went on after the win, so their chains still replay.
+    let mut game = won(3);
+    game.apply(join(&game, 3)).unwrap();
+}
+
+#[test]
+fn finished_games_rank_their_players() {
+    let settings = Settings {
+        width: 3,
+        height: 1,
+        health: 1,
+        ..Settings::default()
+    };
+    let mut game = Game::new(1, settings.clone());
+    for user in 1..=3 {
+        let (x, y) = game.get_pos();
+        let mut join = line(user, MoveLineType::Join);
+        join.x = Some(x);
+        join.y = Some(y);
+        game.apply(join).unwrap();
+    }
+    let shot = [(1, 2), (2, 1), (2, 3), (3, 2), (1, 3), (3, 1)]
+        .into_iter()
+        .map(|(shooter, target)| targeted(shooter, MoveLineType::Shoot, target))
+        .find(|shot| game.check(shot).is_ok())
+        .unwrap();
+    let target = shot.target.unwrap();
+    game.apply(shot).unwrap();
+    let standings = game.standings();
+    assert_eq!(
+        standings.iter().filter(|&&(_, place)| place == 1).count(),
+        2
+    );
+    assert_eq!(standings.last(), Some(&(target, 3)));
+
+    let data = game.result_data(7);
+    assert!(data.starts_with(&format!(
+        "result|7|{}|{}|",
+        game.settings_hash(),
+        game.chain_hash()
+    )));
+    let other = Game::new(
+        1,
+        Settings {
+            seed: 2,
+            ..settings
+        },
+    );
+    assert_ne!(other.settings_hash(), game.settings_hash());
+}
@@ -0,0 +1,322 @@
+//! Runs the server binary against a MySQL container and drives it over HTTP, like the frontend does.
+//! Needs a running Docker daemon, so the tests are ignored by default. Run them with
+//! `cargo test --test api -- --ignored`.
+
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+use std::{net::TcpListener, process::Stdio, time::Duration};
+use tanktacticsgame::{get_random_keys, DataBaseGame, MoveLine, MoveLineType, RULES_VERSION};
+use testcontainers_modules::{
+    mysql::Mysql,
+    testcontainers::{runners::AsyncRunner, ContainerAsync},
+};
+use tokio::process::{Child, Command};
+
+/// A migrated server with its own database, stopped when dropped.
+struct Server {
+    url: String,
+    client: Client,
+    _server: Child,
+    _database: ContainerAsync<Mysql>,
+    _secret: SecretFile,
+}
+
+/// The server signing key, removed when dropped.
+struct SecretFile(std::path::PathBuf);
+impl Drop for SecretFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// A registered user.
+struct Player {
+    id: i32,
+    private_key: String,
+}
+
+async fn start() -> Server {
+    let database = Mysql::default().start().await.unwrap();
+    let database_url = format!(
+        "mysql://root@{}:{}/test",
+        database.get_host().await.unwrap(),
+        database.get_host_port_ipv4(3306).await.unwrap()
+    );
+    let status = Command::new(env!("CARGO_BIN_EXE_tankgameagain"))
+        .args(["--database-url", &database_url, "migrate"])
+        .status()
+        .await
+        .unwrap();
+    assert!(status.success(), "migrations failed");
+
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let secret = SecretFile(std::env::temp_dir().join(format!("tanktactics-secret-{port}.txt")));
+    let (private, public) = get_random_keys();
+    std::fs::write(&secret.0, format!("{private}\n{public}\n")).unwrap();
+    let bind = format!("127.0.0.1:{port}");
+    let server = Command::new(env!("CARGO_BIN_EXE_tankgameagain"))
+        .arg("--database-url")
+        .arg(&database_url)
+        .arg("--bind")
+        .arg(&bind)
+        .arg("--secret-file")
+        .arg(&secret.0)
+        .stdout(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+
+    let url = format!("http://{bind}");
+    let client = Client::new();
+    for _ in 0..100 {
+        if client.get(format!("{url}/games")).send().await.is_ok() {
+            return Server {
+                url,
+                client,
+                _server: server,
+                _database: database,
+                _secret: secret,
+            };
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the server did not start");
+}
+
+impl Server {
+    async fn register(&self) -> Player {
+        let (private_key, public_key) = get_random_keys();
+        let response = self
+            .client
+            .post(format!("{}/regester", self.url))
+            .json(&public_key)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        Player {
+            id: response.json().await.unwrap(),
+            private_key,
+        }
+    }
+    async fn make_game(&self) -> i32 {
+        let game = DataBaseGame {
+            id: 0,
+            seed: 1,
+            last_vote: 0,
+            width: 8,
+            height: 8,
+            health: 3,
+            max_level: 2,
+            max_players: 4,
+            vote_threshold: 3,
+            range: "L".into(),
+            point_decay: false,
+            jury_weight: 0,
+            loot_mode: "killer".into(),
+            drive_length: 1,
+            rules_version: RULES_VERSION,
+            mutators: String::new(),
+            threshold_mode: String::new(),
+            win_condition: String::new(),
+            starting_points: 1,
+            starting_level: 0,
+            actions_per_round: 0,
+            items: String::new(),
+            two_phase_kills: false,
+            symmetric_spawns: false,
+            gift_cooldown: 0,
+            vote_interval: 0,
+            locale: String::new(),
+            description: String::new(),
+        };
+        let response = self
+            .client
+            .post(format!("{}/make_game", self.url))
+            .json(&game)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.json().await.unwrap()
+    }
+    async fn tokens(&self, game: i32) -> Vec<MoveLine> {
+        let page: serde_json::Value = self
+            .client
+            .get(format!("{}/tokens?game={game}", self.url))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(page["index"], 0);
+        serde_json::from_value(page["moves"].clone()).unwrap()
+    }
+    async fn join_position(&self, game: i32, player: &Player) -> (u32, u32) {
+        let position: serde_json::Value = self
+            .client
+            .get(format!(
+                "{}/join_position?game={game}&user={}",
+                self.url, player.id
+            ))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        (
+            position["x"].as_u64().unwrap().try_into().unwrap(),
+            position["y"].as_u64().unwrap().try_into().unwrap(),
+        )
+    }
+    /// Signs `line` with `key` on top of `previous` and posts it, returning the status.
+    async fn send(
+        &self,
+        game: i32,
+        mut line: MoveLine,
+        previous: Option<&str>,
+        key: &str,
+    ) -> (StatusCode, MoveLine) {
+        line.sign(previous, key.into()).unwrap();
+        let status = self
+            .client
+            .post(format!("{}/move?game={game}", self.url))
+            .json(&line)
+            .send()
+            .await
+            .unwrap()
+            .status();
+        (status, line)
+    }
+}
+
+fn line(move_type: MoveLineType, authorizer: i32, (x, y): (u32, u32)) -> MoveLine {
+    MoveLine {
+        move_type,
+        x: Some(x),
+        y: Some(y),
+        target: None,
+        amount: None,
+        text: None,
+        time: None,
+        clock: None,
+        path: None,
+        rules_version: Some(RULES_VERSION),
+        authorizer,
+        signature: String::new(),
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a running Docker daemon"]
+async fn join_and_drive_end_up_in_the_tokens() {
+    let server = start().await;
+    let player = server.register().await;
+    let game = server.make_game().await;
+
+    let position = server.join_position(game, &player).await;
+    let join = line(MoveLineType::Join, player.id, position);
+    let (status, join) = server.send(game, join, None, &player.private_key).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let target = if position.0 == 0 {
+        (1, position.1)
+    } else {
+        (position.0 - 1, position.1)
+    };
+    let drive = line(MoveLineType::Drive, player.id, target);
+    let (status, drive) = server
+        .send(game, drive, Some(&join.signature), &player.private_key)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(server.tokens(game).await, vec![join, drive.clone()]);
+    let page = server
+        .client
+        .get(format!(
+            "{}/tokens?game={game}&after_index=0&limit=5",
+            server.url
+        ))
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(page["index"], 1);
+    assert_eq!(
+        serde_json::from_value::<Vec<MoveLine>>(page["moves"].clone()).unwrap(),
+        vec![drive.clone()]
+    );
+    let head: String = server
+        .client
+        .get(format!("{}/head?game={game}", server.url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(head, drive.signature);
+    let status = server
+        .client
+        .get(format!(
+            "{}/join_position?game={game}&user={}",
+            server.url, player.id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .status();
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+#[ignore = "needs a running Docker daemon"]
+async fn malformed_keys_and_signatures_are_refused() {
+    let server = start().await;
+    let status = server
+        .client
+        .post(format!("{}/regester", server.url))
+        .json(&json!("not a key"))
+        .send()
+        .await
+        .unwrap()
+        .status();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let player = server.register().await;
+    let other = server.register().await;
+    let game = server.make_game().await;
+    let position = server.join_position(game, &player).await;
+    let join = line(MoveLineType::Join, player.id, position);
+    let (status, _) = server.send(game, join, None, &other.private_key).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(server.tokens(game).await.is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs a running Docker daemon"]
+async fn moves_signed_on_an_old_head_are_refused() {
+    let server = start().await;
+    let first = server.register().await;
+    let second = server.register().await;
+    let game = server.make_game().await;
+
+    let position = server.join_position(game, &first).await;
+    let join = line(MoveLineType::Join, first.id, position);
+    let (status, join) = server.send(game, join, None, &first.private_key).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let position = server.join_position(game, &second).await;
+    let stale = line(MoveLineType::Join, second.id, position);
+    let (status, _) = server.send(game, stale, None, &second.private_key).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(server.tokens(game).await, vec![join]);
+}